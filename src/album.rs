@@ -0,0 +1,156 @@
+//! Единообразие альбома: если кракозябры в треках одного каталога получились из разных
+//! источников, после исправления название альбома и исполнитель альбома могут разойтись
+//! в мелочах — регистре, «ё», пробелах, знаках препинания или одной-двух буквах. Такие
+//! варианты сводятся к значению, которое встречается у большинства треков
+
+use lofty::prelude::ItemKey;
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+/// Поля, которые должны быть одинаковыми у всех треков альбома
+pub const KEYS: [ItemKey; 2] = [ItemKey::AlbumTitle, ItemKey::AlbumArtist];
+/// Поля трека, которые сверяются с треклистом релиза
+pub const TRACK_KEYS: [ItemKey; 2] = [ItemKey::TrackTitle, ItemKey::TrackArtist];
+
+/// Значения полей альбома и трека у одного трека
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub path: PathBuf,
+    pub values: Vec<(ItemKey, String)>,
+}
+
+impl Track {
+    /// Значение поля `key`
+    pub fn get(&self, key: &ItemKey) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Поле, которое у треков альбома пишется по-разному
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub key: ItemKey,
+    /// Значение большинства треков
+    pub value: String,
+    /// Все варианты написания с числом треков, от частых к редким
+    pub variants: Vec<(String, usize)>,
+    /// Треки с другим написанием и их значения
+    pub tracks: Vec<(PathBuf, String)>,
+}
+
+/// Поля, которые у треков `tracks` одного каталога почти совпадают, но не равны.
+/// Совсем разные значения (сборник нескольких альбомов в одном каталоге) не сводятся
+pub fn mismatches(tracks: &[Track]) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for key in KEYS {
+        let values: Vec<(&PathBuf, &str)> = tracks
+            .iter()
+            .filter_map(|track| Some((&track.path, track.get(&key)?)))
+            .collect();
+
+        // Варианты в порядке первого появления, затем — от частых к редким
+        let mut variants: Vec<(String, usize)> = Vec::new();
+        for (_, value) in &values {
+            match variants.iter_mut().find(|(v, _)| v == value) {
+                Some((_, count)) => *count += 1,
+                None => variants.push((value.to_string(), 1)),
+            }
+        }
+        variants.sort_by_key(|(_, count)| Reverse(*count));
+
+        // Каждый вариант присоединяется к самому частому похожему
+        let mut groups: Vec<Vec<(String, usize)>> = Vec::new();
+        for variant in variants {
+            match groups
+                .iter_mut()
+                .find(|group| similar(&group[0].0, &variant.0))
+            {
+                Some(group) => group.push(variant),
+                None => groups.push(vec![variant]),
+            }
+        }
+        for group in groups.into_iter().filter(|group| group.len() > 1) {
+            let value = group[0].0.clone();
+            let tracks = values
+                .iter()
+                .filter(|(_, v)| *v != value && group.iter().any(|(g, _)| g == v))
+                .map(|(path, v)| ((*path).clone(), v.to_string()))
+                .collect();
+            mismatches.push(Mismatch {
+                key: key.clone(),
+                value,
+                variants: group,
+                tracks,
+            });
+        }
+    }
+    mismatches
+}
+
+/// Одно ли это значение, записанное по-разному: совпадает без учёта регистра, «ё», пробелов
+/// и знаков препинания или отличается на одну букву (на две — у значений длиннее 15 букв).
+/// Цифры должны совпадать: `CD1` и `CD2` — разные диски
+fn similar(a: &str, b: &str) -> bool {
+    let (a, b) = (fold(a), fold(b));
+    if a == b {
+        return true;
+    }
+    let digits = |value: &[char]| -> Vec<char> {
+        value.iter().copied().filter(char::is_ascii_digit).collect()
+    };
+    if digits(&a) != digits(&b) {
+        return false;
+    }
+    let limit = if a.len().min(b.len()) > 15 { 2 } else { 1 };
+    a.len().min(b.len()) >= 4 && distance(&a, &b) <= limit
+}
+
+/// Похоже ли название трека `title` на название `listed` из треклиста: одно содержит другое
+/// (`Кукушка (Live)` и `Кукушка`) или они отличаются не больше чем на четверть букв
+pub fn resembles(title: &str, listed: &str) -> bool {
+    let (a, b) = (fold(title), fold(listed));
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if short.is_empty() {
+        return false;
+    }
+    (short.len() >= 3
+        && long
+            .windows(short.len())
+            .any(|window| window == short.as_slice()))
+        || distance(&a, &b) * 4 <= long.len()
+}
+
+/// Буквы и цифры значения в нижнем регистре, «ё» — как «е»: для сравнения написаний
+pub fn fold(value: &str) -> Vec<char> {
+    value
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == 'ё' { 'е' } else { c })
+        .collect()
+}
+
+/// Расстояние Левенштейна: сколько букв вставить, удалить или заменить
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,716 @@
+//! Текстовые теги WMA/ASF, которые не поддерживает lofty
+//!
+//! Читаются и переписываются только строки объектов Content Description и
+//! Extended Content Description заголовка; остальные объекты, нетекстовые атрибуты
+//! и данные после заголовка сохраняются байт в байт.
+
+use crate::atomic;
+use crate::backup::BackupManager;
+use crate::detect::{Detector, Mojibake};
+use crate::error::{Error, Result};
+use crate::fields::{FieldFilter, asf_item_key};
+use crate::locale::tr;
+use crate::normalize::trim_whitespace;
+use crate::plan::PlanChange;
+use crate::translit::{self, Scheme};
+use crate::yo::Yo;
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+type Guid = [u8; 16];
+
+const HEADER_OBJECT: Guid = guid(
+    0x75B22630,
+    0x668E,
+    0x11CF,
+    [0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C],
+);
+const CONTENT_DESCRIPTION: Guid = guid(
+    0x75B22633,
+    0x668E,
+    0x11CF,
+    [0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C],
+);
+const EXTENDED_CONTENT_DESCRIPTION: Guid = guid(
+    0xD2D0A440,
+    0xE307,
+    0x11D2,
+    [0x97, 0xF0, 0x00, 0xA0, 0xC9, 0x5E, 0xA8, 0x50],
+);
+const FILE_PROPERTIES: Guid = guid(
+    0x8CABDCA1,
+    0xA947,
+    0x11CF,
+    [0x8E, 0xE4, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65],
+);
+
+/// Размер заголовка объекта: GUID + u64 размер
+const OBJECT_HEADER_LEN: usize = 24;
+/// Имена полей Content Description в порядке хранения
+const CONTENT_FIELDS: [&str; 5] = ["Title", "Author", "Copyright", "Description", "Rating"];
+/// Тип значения «строка UTF-16LE» в Extended Content Description
+const UNICODE_STRING: u16 = 0;
+
+/// Начинается ли файл с заголовка ASF (WMA, WMV)
+pub fn is_asf(header: &[u8]) -> bool {
+    header.starts_with(&HEADER_OBJECT)
+}
+
+/// GUID в порядке байт ASF: первые три поля little-endian
+const fn guid(a: u32, b: u16, c: u16, d: [u8; 8]) -> Guid {
+    let a = a.to_le_bytes();
+    let b = b.to_le_bytes();
+    let c = c.to_le_bytes();
+    [
+        a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6],
+        d[7],
+    ]
+}
+
+/// Атрибут Extended Content Description; значение хранится как есть
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Descriptor {
+    name: Vec<u8>,
+    kind: u16,
+    value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Object {
+    /// Строки UTF-16LE в порядке `CONTENT_FIELDS`
+    ContentDescription([Vec<u8>; 5]),
+    ExtendedContentDescription(Vec<Descriptor>),
+    Other {
+        guid: Guid,
+        data: Vec<u8>,
+    },
+}
+
+/// Заголовок ASF файла и всё, что после него
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsfFile {
+    /// Два зарезервированных байта заголовка
+    reserved: [u8; 2],
+    objects: Vec<Object>,
+    rest: Vec<u8>,
+}
+
+impl AsfFile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&bytes).map_err(|message| Error::Read {
+            path: path.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidData, message),
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> std::result::Result<Self, &'static str> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(16)? != HEADER_OBJECT {
+            return Err(tr("не ASF файл", "not an ASF file"));
+        }
+        let header_len = usize::try_from(reader.u64()?)
+            .map_err(|_| tr("слишком большой заголовок", "header too large"))?;
+        let count = reader.u32()?;
+        let reserved = [reader.u8()?, reader.u8()?];
+        if header_len > bytes.len() {
+            return Err(tr("заголовок ASF обрезан", "ASF header truncated"));
+        }
+
+        let mut objects = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let guid: Guid = reader.take(16)?.try_into().unwrap();
+            let len = usize::try_from(reader.u64()?)
+                .map_err(|_| tr("слишком большой объект", "object too large"))?;
+            let data = reader.take(
+                len.checked_sub(OBJECT_HEADER_LEN)
+                    .ok_or(tr("неверный размер объекта", "invalid object size"))?,
+            )?;
+            objects.push(match guid {
+                CONTENT_DESCRIPTION => Object::ContentDescription(parse_content(data)?),
+                EXTENDED_CONTENT_DESCRIPTION => {
+                    Object::ExtendedContentDescription(parse_extended(data)?)
+                }
+                guid => Object::Other {
+                    guid,
+                    data: data.to_vec(),
+                },
+            });
+        }
+        if reader.pos != header_len {
+            return Err(tr(
+                "размер заголовка не совпадает с объектами",
+                "header size does not match its objects",
+            ));
+        }
+
+        Ok(Self {
+            reserved,
+            objects,
+            rest: bytes[header_len..].to_vec(),
+        })
+    }
+
+    /// Текстовые поля: (имя, значение)
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        for object in &self.objects {
+            match object {
+                Object::ContentDescription(values) => {
+                    for (name, value) in CONTENT_FIELDS.iter().zip(values) {
+                        let value = decode_utf16(value);
+                        if !value.is_empty() {
+                            fields.push((name.to_string(), value));
+                        }
+                    }
+                }
+                Object::ExtendedContentDescription(descriptors) => {
+                    for d in descriptors.iter().filter(|d| d.kind == UNICODE_STRING) {
+                        fields.push((decode_utf16(&d.name), decode_utf16(&d.value)));
+                    }
+                }
+                Object::Other { .. } => {}
+            }
+        }
+        fields
+    }
+
+    /// Замена значения `original` текстового поля на `value`. У атрибута может быть
+    /// несколько значений (`WM/Genre`, `WM/AlbumArtist`): остальные не меняются.
+    /// `false`, если такого значения нет
+    pub fn set(&mut self, name: &str, original: &str, value: &str) -> bool {
+        let mut found = false;
+        for object in &mut self.objects {
+            match object {
+                Object::ContentDescription(values) => {
+                    if let Some(i) = CONTENT_FIELDS.iter().position(|&n| n == name)
+                        && decode_utf16(&values[i]) == original
+                    {
+                        values[i] = encode_utf16(value);
+                        found = true;
+                    }
+                }
+                Object::ExtendedContentDescription(descriptors) => {
+                    for d in descriptors.iter_mut().filter(|d| {
+                        d.kind == UNICODE_STRING
+                            && decode_utf16(&d.name) == name
+                            && decode_utf16(&d.value) == original
+                    }) {
+                        d.value = encode_utf16(value);
+                        found = true;
+                    }
+                }
+                Object::Other { .. } => {}
+            }
+        }
+        found
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut objects: Vec<(Guid, Vec<u8>)> = self
+            .objects
+            .iter()
+            .map(|object| match object {
+                Object::ContentDescription(values) => (CONTENT_DESCRIPTION, write_content(values)),
+                Object::ExtendedContentDescription(descriptors) => {
+                    (EXTENDED_CONTENT_DESCRIPTION, write_extended(descriptors))
+                }
+                Object::Other { guid, data } => (*guid, data.clone()),
+            })
+            .collect();
+
+        let header_len = 30
+            + objects
+                .iter()
+                .map(|(_, data)| OBJECT_HEADER_LEN + data.len())
+                .sum::<usize>();
+        // Размер файла в File Properties: после GUID файла (16 байт)
+        let file_len = (header_len + self.rest.len()) as u64;
+        for (guid, data) in &mut objects {
+            if *guid == FILE_PROPERTIES && data.len() >= 24 {
+                data[16..24].copy_from_slice(&file_len.to_le_bytes());
+            }
+        }
+
+        let mut out = Vec::with_capacity(header_len + self.rest.len());
+        out.extend_from_slice(&HEADER_OBJECT);
+        out.extend_from_slice(&(header_len as u64).to_le_bytes());
+        out.extend_from_slice(&(objects.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.reserved);
+        for (guid, data) in objects {
+            out.extend_from_slice(&guid);
+            out.extend_from_slice(&((OBJECT_HEADER_LEN + data.len()) as u64).to_le_bytes());
+            out.extend_from_slice(&data);
+        }
+        out.extend_from_slice(&self.rest);
+        out
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        atomic::write(path, &self.to_bytes())
+    }
+}
+
+/// Исправление одного текстового поля ASF
+#[derive(Debug, Clone)]
+pub struct AsfFieldFix {
+    pub name: String,
+    pub original: String,
+    pub fixed: String,
+    /// `None` — поле только транслитерировано
+    pub mojibake: Option<Mojibake>,
+    /// Уверенность в исправлении, 0–100
+    pub confidence: u8,
+    pub yo: Option<Yo>,
+    pub transliteration: Option<Scheme>,
+}
+
+/// Найденные исправления тегов WMA/ASF файла
+pub struct AsfFix {
+    file: AsfFile,
+    pub fixes: Vec<AsfFieldFix>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AsfFixer {
+    pub detector: Detector,
+    /// Какие поля исправлять; атрибуты сопоставляются с полями lofty по имени
+    pub fields: FieldFilter,
+    /// Заменять или восстанавливать `ё` (--yo)
+    pub yo: Option<Yo>,
+    /// Записывать атрибуты латиницей (--transliterate)
+    pub transliterate: Option<Scheme>,
+    /// Убирать лишние пробелы в атрибутах переписываемого заголовка (--trim-tags)
+    pub trim: bool,
+}
+
+impl AsfFixer {
+    pub fn new(detector: Detector) -> Self {
+        Self {
+            detector,
+            fields: FieldFilter::default(),
+            yo: None,
+            transliterate: None,
+            trim: false,
+        }
+    }
+
+    /// Исправлять только поля, разрешённые фильтром
+    pub fn with_fields(mut self, fields: FieldFilter) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Приводить `ё` в атрибутах к одному виду, см. [`crate::yo`]
+    pub fn with_yo(mut self, yo: Option<Yo>) -> Self {
+        self.yo = yo;
+        self
+    }
+
+    /// Записывать исправленные атрибуты латиницей в этой системе транслитерации
+    pub fn with_transliterate(mut self, scheme: Option<Scheme>) -> Self {
+        self.transliterate = scheme;
+        self
+    }
+
+    /// Убирать пробелы по краям, повторные пробелы и нулевые байты во всех атрибутах, если
+    /// заголовок всё равно переписывается
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Чтение заголовка и поиск исправлений; `None`, если исправлять нечего
+    pub fn scan(&self, path: &Path) -> Result<Option<AsfFix>> {
+        Ok(self.find_fixes(AsfFile::read(path)?))
+    }
+
+    /// Поиск исправлений в файле, уже прочитанном в память; `path` — для сообщений об ошибках
+    pub fn scan_bytes(&self, path: &Path, bytes: &[u8]) -> Result<Option<AsfFix>> {
+        let file = AsfFile::parse(bytes).map_err(|message| Error::Read {
+            path: path.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidData, message),
+        })?;
+        Ok(self.find_fixes(file))
+    }
+
+    fn find_fixes(&self, file: AsfFile) -> Option<AsfFix> {
+        // Атрибуты, где нужно только убрать пробелы, исправляются вместе с остальными
+        let (fixes, trimmed): (Vec<_>, Vec<_>) = file
+            .fields()
+            .into_iter()
+            .filter(|(name, _)| self.fields.allows(&asf_item_key(name)))
+            .filter_map(|(name, original)| {
+                let detection = self
+                    .detector
+                    .for_field(&asf_item_key(&name))
+                    .detect(&original);
+                let value = detection.as_ref().map_or(&original, |d| &d.text);
+                let (yo, value) = match self.yo.map(|yo| (yo, yo.apply(value))) {
+                    Some((yo, Cow::Owned(value))) => (Some(yo), value),
+                    _ => (None, value.clone()),
+                };
+                let transliteration = self
+                    .transliterate
+                    .filter(|_| translit::has_cyrillic(&value));
+                let mut fixed = match transliteration {
+                    Some(scheme) => translit::transliterate(&value, scheme),
+                    None => value,
+                };
+                if self.trim {
+                    fixed = trim_whitespace(&fixed).into_owned();
+                }
+                (fixed != original).then(|| AsfFieldFix {
+                    fixed,
+                    name,
+                    original,
+                    confidence: detection.as_ref().map_or(100, |d| d.confidence),
+                    mojibake: detection.map(|d| d.mojibake),
+                    yo,
+                    transliteration,
+                })
+            })
+            .partition(|fix| {
+                fix.mojibake.is_some() || fix.yo.is_some() || fix.transliteration.is_some()
+            });
+
+        if fixes.is_empty() {
+            return None;
+        }
+        let fixes = fixes.into_iter().chain(trimmed).collect();
+        Some(AsfFix { file, fixes })
+    }
+
+    /// Исправления из плана: исходные значения сверяются с текущими атрибутами файла
+    pub fn planned(&self, path: &Path, changes: &[PlanChange]) -> Result<AsfFix> {
+        let file = AsfFile::read(path)?;
+        let fields = file.fields();
+        let fixes = changes
+            .iter()
+            .map(|change| {
+                let error = |reason| Error::PlanApply {
+                    path: path.to_path_buf(),
+                    field: change.field.clone(),
+                    reason,
+                };
+                let name = change.key.as_ref().unwrap_or(&change.field);
+                if !fields
+                    .iter()
+                    .any(|(n, value)| n == name && *value == change.before)
+                {
+                    return Err(error(tr(
+                        "значение изменилось после составления плана",
+                        "value changed since the plan was made",
+                    )));
+                }
+                Ok(AsfFieldFix {
+                    name: name.clone(),
+                    original: change.before.clone(),
+                    fixed: change.after.clone(),
+                    mojibake: change.mojibake(),
+                    confidence: change.confidence.unwrap_or(100),
+                    yo: None,
+                    transliteration: None,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(AsfFix { file, fixes })
+    }
+
+    /// Применение исправлений с предварительным бэкапом. Манифест не умеет хранить
+    /// атрибуты ASF, поэтому, как и для .cue, всегда делается копия файла
+    pub fn apply(&self, path: &Path, fix: AsfFix, backup_manager: &BackupManager) -> Result<()> {
+        let AsfFix { mut file, fixes } = fix;
+        for fix in &fixes {
+            file.set(&fix.name, &fix.original, &fix.fixed);
+        }
+
+        backup_manager.backup_file(path)?;
+        file.save(path)
+    }
+
+    /// Содержимое файла с исправлениями, без бэкапа: его делает владелец данных
+    pub fn apply_bytes(&self, fix: AsfFix) -> Vec<u8> {
+        let AsfFix { mut file, fixes } = fix;
+        for fix in &fixes {
+            file.set(&fix.name, &fix.original, &fix.fixed);
+        }
+        file.to_bytes()
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], &'static str> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(tr("заголовок ASF обрезан", "ASF header truncated"))?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(tr("заголовок ASF обрезан", "ASF header truncated"))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> std::result::Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> std::result::Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, &'static str> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+fn parse_content(data: &[u8]) -> std::result::Result<[Vec<u8>; 5], &'static str> {
+    let mut reader = Reader {
+        bytes: data,
+        pos: 0,
+    };
+    let mut lens = [0usize; 5];
+    for len in &mut lens {
+        *len = reader.u16()? as usize;
+    }
+    let mut values: [Vec<u8>; 5] = Default::default();
+    for (value, len) in values.iter_mut().zip(lens) {
+        *value = reader.take(len)?.to_vec();
+    }
+    Ok(values)
+}
+
+fn write_content(values: &[Vec<u8>; 5]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for value in values {
+        out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    }
+    for value in values {
+        out.extend_from_slice(value);
+    }
+    out
+}
+
+fn parse_extended(data: &[u8]) -> std::result::Result<Vec<Descriptor>, &'static str> {
+    let mut reader = Reader {
+        bytes: data,
+        pos: 0,
+    };
+    let count = reader.u16()?;
+    let mut descriptors = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name_len = reader.u16()? as usize;
+        let name = reader.take(name_len)?.to_vec();
+        let kind = reader.u16()?;
+        let value_len = reader.u16()? as usize;
+        let value = reader.take(value_len)?.to_vec();
+        descriptors.push(Descriptor { name, kind, value });
+    }
+    Ok(descriptors)
+}
+
+fn write_extended(descriptors: &[Descriptor]) -> Vec<u8> {
+    let mut out = (descriptors.len() as u16).to_le_bytes().to_vec();
+    for d in descriptors {
+        out.extend_from_slice(&(d.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&d.name);
+        out.extend_from_slice(&d.kind.to_le_bytes());
+        out.extend_from_slice(&(d.value.len() as u16).to_le_bytes());
+        out.extend_from_slice(&d.value);
+    }
+    out
+}
+
+/// UTF-16LE без завершающего нуля
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// UTF-16LE с завершающим нулём, как пишут ASF кодировщики
+fn encode_utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_EXTENSION: Guid = guid(
+        0x5FBF03B5,
+        0xA92E,
+        0x11CF,
+        [0x8E, 0xE3, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65],
+    );
+    const METADATA_LIBRARY: Guid = guid(
+        0x44231C94,
+        0x9498,
+        0x49D1,
+        [0xA1, 0x41, 0x1D, 0x13, 0x4E, 0x45, 0x70, 0x54],
+    );
+
+    fn object(guid: Guid, data: &[u8]) -> Vec<u8> {
+        let mut out = guid.to_vec();
+        out.extend(((OBJECT_HEADER_LEN + data.len()) as u64).to_le_bytes());
+        out.extend(data);
+        out
+    }
+
+    fn descriptor(name: &str, kind: u16, value: &[u8]) -> Vec<u8> {
+        let name = encode_utf16(name);
+        let mut out = (name.len() as u16).to_le_bytes().to_vec();
+        out.extend(name);
+        out.extend(kind.to_le_bytes());
+        out.extend((value.len() as u16).to_le_bytes());
+        out.extend(value);
+        out
+    }
+
+    /// Объекты заголовка в файле: (GUID, размер из заголовка объекта, содержимое)
+    fn objects(bytes: &[u8]) -> Vec<(Guid, u64, Vec<u8>)> {
+        let count = u32::from_le_bytes(bytes[24..28].try_into().unwrap());
+        let mut pos = 30;
+        (0..count)
+            .map(|_| {
+                let guid: Guid = bytes[pos..pos + 16].try_into().unwrap();
+                let size = u64::from_le_bytes(bytes[pos + 16..pos + 24].try_into().unwrap());
+                let data = bytes[pos + 24..pos + size as usize].to_vec();
+                pos += size as usize;
+                (guid, size, data)
+            })
+            .collect()
+    }
+
+    /// Заголовок с кракозябрами в Title и WM/AlbumTitle и атрибутами всех нетекстовых
+    /// типов: BYTE ARRAY, BOOL, DWORD, QWORD, WORD в Extended Content Description и GUID
+    /// в Metadata Library внутри Header Extension
+    fn sample() -> Vec<u8> {
+        let mojibake = |text: &str| {
+            let (bytes, _, _) = encoding_rs::WINDOWS_1251.encode(text);
+            encode_utf16(&bytes.iter().map(|&b| char::from(b)).collect::<String>())
+        };
+        let title = mojibake("Группа крови");
+        let mut content = Vec::new();
+        for len in [title.len(), 0, 0, 0, 0] {
+            content.extend((len as u16).to_le_bytes());
+        }
+        content.extend(&title);
+
+        let descriptors = [
+            descriptor("WM/AlbumTitle", UNICODE_STRING, &mojibake("Кино")),
+            descriptor("WM/Picture", 1, &[0x89, b'P', b'N', b'G', 0, 0xff]),
+            descriptor("IsVBR", 2, &1u32.to_le_bytes()),
+            descriptor("WM/TrackNumber", 3, &4u32.to_le_bytes()),
+            descriptor("WM/Duration", 4, &123_456_789u64.to_le_bytes()),
+            descriptor("WM/Stereo", 5, &2u16.to_le_bytes()),
+        ];
+        let mut extended = (descriptors.len() as u16).to_le_bytes().to_vec();
+        extended.extend(descriptors.concat());
+
+        let name = encode_utf16("WM/MediaClassPrimaryID");
+        let mut library = 1u16.to_le_bytes().to_vec();
+        library.extend([0, 0, 0, 0]);
+        library.extend((name.len() as u16).to_le_bytes());
+        library.extend(6u16.to_le_bytes());
+        library.extend(16u32.to_le_bytes());
+        library.extend(&name);
+        library.extend(METADATA_LIBRARY);
+        let library = object(METADATA_LIBRARY, &library);
+        let mut extension = [0x11; 16].to_vec();
+        extension.extend(6u16.to_le_bytes());
+        extension.extend((library.len() as u32).to_le_bytes());
+        extension.extend(library);
+
+        let objects = [
+            object(FILE_PROPERTIES, &[0x22; 80]),
+            object(CONTENT_DESCRIPTION, &content),
+            object(EXTENDED_CONTENT_DESCRIPTION, &extended),
+            object(HEADER_EXTENSION, &extension),
+        ];
+        let header_len = 30 + objects.iter().map(Vec::len).sum::<usize>();
+        let mut out = HEADER_OBJECT.to_vec();
+        out.extend((header_len as u64).to_le_bytes());
+        out.extend((objects.len() as u32).to_le_bytes());
+        out.extend([0x01, 0x02]);
+        out.extend(objects.concat());
+        out.extend(b"data object bytes");
+        out
+    }
+
+    #[test]
+    fn rewrite_keeps_non_text_attributes() {
+        let original = sample();
+        let fixer = AsfFixer::new(Detector::default());
+        let fix = fixer
+            .scan_bytes(Path::new("a.wma"), &original)
+            .unwrap()
+            .expect("кракозябры в Title и WM/AlbumTitle");
+        let fixed = fixer.apply_bytes(fix);
+
+        let file = AsfFile::parse(&fixed).unwrap();
+        let fields = file.fields();
+        assert!(fields.contains(&("Title".into(), "Группа крови".into())));
+        assert!(fields.contains(&("WM/AlbumTitle".into(), "Кино".into())));
+
+        let (before, after) = (objects(&original), objects(&fixed));
+        assert_eq!(before.len(), after.len());
+        for ((guid, size, data), (new_guid, new_size, new_data)) in before.iter().zip(&after) {
+            assert_eq!(guid, new_guid);
+            assert_eq!(*new_size as usize, OBJECT_HEADER_LEN + new_data.len());
+            match *guid {
+                // размер файла поменялся вместе с заголовком
+                FILE_PROPERTIES => {
+                    assert_eq!(data[..16], new_data[..16]);
+                    let file_len = u64::from_le_bytes(new_data[16..24].try_into().unwrap());
+                    assert_eq!(file_len, fixed.len() as u64);
+                    assert_eq!(data[24..], new_data[24..]);
+                }
+                CONTENT_DESCRIPTION => {}
+                EXTENDED_CONTENT_DESCRIPTION => {
+                    let non_text = |data: &[u8]| -> Vec<Descriptor> {
+                        parse_extended(data)
+                            .unwrap()
+                            .into_iter()
+                            .filter(|d| d.kind != UNICODE_STRING)
+                            .collect()
+                    };
+                    assert_eq!(non_text(data).len(), 5);
+                    assert_eq!(non_text(data), non_text(new_data));
+                }
+                _ => assert_eq!((size, data), (new_size, new_data)),
+            }
+        }
+        let header_len = u64::from_le_bytes(fixed[16..24].try_into().unwrap()) as usize;
+        assert_eq!(
+            header_len,
+            30 + after.iter().map(|o| o.1 as usize).sum::<usize>()
+        );
+        assert_eq!(fixed[28..30], original[28..30]);
+        assert!(fixed.ends_with(b"data object bytes"));
+    }
+}
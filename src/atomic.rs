@@ -0,0 +1,113 @@
+//! Атомарная запись файлов: результат пишется во временный файл в том же каталоге
+//! и переименовывается поверх оригинала. При сбое посреди записи (или отключении питания)
+//! на месте остаётся либо старый файл целиком, либо новый
+
+use crate::error::{Error, Result};
+use crate::locale::tr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Суффикс временных файлов; такие файлы не обрабатываются, даже если остались после сбоя
+pub const TEMP_SUFFIX: &str = ".cyrtag-tmp";
+
+/// Временный файл рядом с `path`: `каталог/.имя.cyrtag-tmp`
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}{TEMP_SUFFIX}"))
+}
+
+/// Записать `content` в `path` через временный файл.
+/// Права доступа существующего файла сохраняются
+pub fn write(path: &Path, content: &[u8]) -> Result<()> {
+    let path = resolve(path);
+    replace(&path, |temp| {
+        let mut file = File::create(temp)?;
+        file.write_all(content)?;
+        file.sync_all()
+    })
+    .map_err(|source| Error::Write { path, source })
+}
+
+/// Записать в `path` всё содержимое `reader` через временный файл, не читая его в память
+/// целиком; ошибка — без пути, его добавляет вызывающий
+pub fn copy_from(path: &Path, reader: &mut impl Read) -> io::Result<()> {
+    replace(&resolve(path), |temp| {
+        let mut file = File::create(temp)?;
+        io::copy(reader, &mut file)?;
+        file.sync_all()
+    })
+}
+
+/// Изменить файл на месте через временную копию: `modify` получает копию, открытую
+/// на чтение и запись, после успешного изменения копия заменяет оригинал
+pub fn modify(path: &Path, modify: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let path = resolve(path);
+    modify_with(&path, |temp| fs::copy(&path, temp).map(drop), modify)
+}
+
+/// Как [`modify`], но копию пишет `copy` из оригинала, открытого на чтение: так файл можно
+/// переписать потоком, не читая его в память целиком
+pub fn modify_copy(
+    path: &Path,
+    copy: impl FnOnce(&mut File, &mut File) -> io::Result<()>,
+    modify: impl FnOnce(&mut File) -> Result<()>,
+) -> Result<()> {
+    let path = resolve(path);
+    modify_with(
+        &path,
+        |temp| copy(&mut File::open(&path)?, &mut File::create(temp)?),
+        modify,
+    )
+}
+
+/// Подготовить временную копию `path` через `copy`, изменить её через `modify` и заменить
+/// ею оригинал
+fn modify_with(
+    path: &Path,
+    copy: impl FnOnce(&Path) -> io::Result<()>,
+    modify: impl FnOnce(&mut File) -> Result<()>,
+) -> Result<()> {
+    let mut result = Ok(());
+    let replaced = replace(path, |temp| {
+        copy(temp)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(temp)?;
+        result = modify(&mut file);
+        if result.is_err() {
+            return Err(io::Error::other(tr("файл не изменён", "file not modified")));
+        }
+        file.sync_all()
+    });
+    result?;
+    replaced.map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Подготовить временный файл через `prepare` и переименовать его в `path`;
+/// при ошибке временный файл удаляется
+fn replace(path: &Path, prepare: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = prepare(&temp)
+        .and_then(|()| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Символическая ссылка заменяется файлом, на который она указывает, а не самой ссылкой
+fn resolve(path: &Path) -> PathBuf {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,1290 @@
+//! Исправление тегов аудио-файлов через lofty
+
+use crate::album;
+use crate::asf;
+use crate::atomic;
+use crate::backup::BackupManager;
+use crate::detect::{Detector, Mojibake};
+use crate::error::{Error, Result};
+use crate::fields::{CHAPTER_FIELD, FieldFilter, PICTURE_FIELD, chapter_key, picture_key};
+use crate::id3v1::{self, Id3v1Policy};
+use crate::locale::tr;
+use crate::manifest::{FileRecord, parse_tag_type};
+use crate::mp4chap;
+use crate::normalize::trim_whitespace;
+use crate::plan::PlanChange;
+use crate::riff;
+use crate::text;
+use crate::translit::{self, Scheme};
+use crate::yo::Yo;
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::error::LoftyError;
+use lofty::file::{FileType, TaggedFile};
+use lofty::id3::v2::Id3v2Tag;
+use lofty::io::{FileLike, Length, Truncate};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagExt, TagItem, TagType};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Исправление одного текстового поля тега
+#[derive(Debug, Clone)]
+pub struct FieldFix {
+    /// Тег, в котором найдено поле
+    pub tag_type: TagType,
+    pub key: ItemKey,
+    pub original: String,
+    pub fixed: String,
+    /// Как появились кракозябры; `None` — поле только транслитерировано
+    pub mojibake: Option<Mojibake>,
+    /// Уверенность в исправлении, 0–100
+    pub confidence: u8,
+    /// В исправленном значении заменена или восстановлена `ё` (--yo)
+    pub yo: Option<Yo>,
+    /// Исправленное значение записано латиницей (--transliterate)
+    pub transliteration: Option<Scheme>,
+}
+
+/// Найденные исправления тегов аудио-файла
+pub struct AudioFix {
+    /// Теги, в которых есть что исправлять
+    tags: Vec<Tag>,
+    /// Исправления всех тегов файла
+    pub fixes: Vec<FieldFix>,
+    /// ID3v2 переписывается целиком в UTF-8 или UTF-16 из-за фреймов в Latin-1
+    /// (--reencode-frames), даже если исправлять в нём нечего
+    pub reencode: bool,
+    /// Что сделать с ID3v1 (--id3v1)
+    pub id3v1: Option<Id3v1Fix>,
+    /// Теги, которые удаляются после переноса их полей в основной тег (--strip-other-tags)
+    pub strip: Vec<TagType>,
+}
+
+impl AudioFix {
+    /// Значение поля после исправления: исправленное, а если поле не исправляется — из тега
+    pub fn value(&self, key: &ItemKey) -> Option<String> {
+        self.fixes
+            .iter()
+            .find(|f| f.key == *key)
+            .map(|f| f.fixed.clone())
+            .or_else(|| {
+                self.tags
+                    .iter()
+                    .find_map(|tag| tag.get_string(key))
+                    .map(str::to_string)
+            })
+    }
+
+    /// Есть ли исправления названий глав MP4, см. [`crate::mp4chap`]
+    pub fn has_chapters(&self) -> bool {
+        has_chapters(&self.fixes)
+    }
+}
+
+/// Изменение ID3v1
+#[derive(Clone)]
+pub enum Id3v1Fix {
+    /// Пересобрать из этого тега (ID3v2 или APE) с исправленными значениями
+    Rebuild(Tag),
+    /// Удалить
+    Strip,
+}
+
+/// Версия ID3v2 в сохранённых тегах
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Id3Version {
+    /// Как в исходном файле; ID3v2.2, который lofty не записывает, сохраняется как v2.3
+    #[default]
+    Keep,
+    /// ID3v2.3: его читают старые магнитолы и плееры
+    V23,
+    /// ID3v2.4
+    V24,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AudioFixer {
+    pub detector: Detector,
+    /// Какие поля исправлять
+    pub fields: FieldFilter,
+    /// Определять формат файла по содержимому, а не по расширению
+    pub sniff: bool,
+    /// В какой версии записывать ID3v2
+    pub id3: Id3Version,
+    /// Переписывать ID3v2 с не-ASCII фреймами в Latin-1, даже если кракозябр нет
+    pub reencode_frames: bool,
+    /// Что делать с ID3v1
+    pub id3v1: Id3v1Policy,
+    /// Заменять или восстанавливать `ё` в текстовых полях
+    pub yo: Option<Yo>,
+    /// Записывать текстовые поля латиницей в этой системе транслитерации
+    pub transliterate: Option<Scheme>,
+    /// Убирать лишние пробелы во всех текстовых полях переписываемых тегов
+    pub trim: bool,
+    /// Оставлять в переписываемых файлах только основной тег формата
+    pub strip_others: bool,
+}
+
+impl AudioFixer {
+    pub fn new(detector: Detector) -> Self {
+        Self {
+            detector,
+            fields: FieldFilter::default(),
+            sniff: false,
+            id3: Id3Version::Keep,
+            reencode_frames: false,
+            id3v1: Id3v1Policy::Keep,
+            yo: None,
+            transliterate: None,
+            trim: false,
+            strip_others: false,
+        }
+    }
+
+    /// Определять формат по содержимому: для файлов без расширения или с чужим расширением
+    pub fn with_sniff(mut self, sniff: bool) -> Self {
+        self.sniff = sniff;
+        self
+    }
+
+    /// Исправлять только поля, разрешённые фильтром
+    pub fn with_fields(mut self, fields: FieldFilter) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Записывать ID3v2 в этой версии
+    pub fn with_id3(mut self, id3: Id3Version) -> Self {
+        self.id3 = id3;
+        self
+    }
+
+    /// Переписывать фреймы ID3v2 в Latin-1 в UTF-8 или UTF-16
+    pub fn with_reencode_frames(mut self, reencode_frames: bool) -> Self {
+        self.reencode_frames = reencode_frames;
+        self
+    }
+
+    /// Пересобирать или удалять ID3v1
+    pub fn with_id3v1(mut self, id3v1: Id3v1Policy) -> Self {
+        self.id3v1 = id3v1;
+        self
+    }
+
+    /// Приводить `ё` к одному виду по всей библиотеке: заменять на `е` или возвращать
+    /// по словарю, см. [`crate::yo`]. Кракозябры сначала исправляются
+    pub fn with_yo(mut self, yo: Option<Yo>) -> Self {
+        self.yo = yo;
+        self
+    }
+
+    /// Записывать текстовые поля латиницей: для магнитол, которые не показывают кириллицу.
+    /// Кракозябры сначала исправляются, затем результат транслитерируется
+    pub fn with_transliterate(mut self, scheme: Option<Scheme>) -> Self {
+        self.transliterate = scheme;
+        self
+    }
+
+    /// Убирать пробелы по краям, повторные пробелы и нулевые байты в текстовых полях
+    /// тегов, которые всё равно переписываются; теги без исправлений не трогаются
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Переносить в основной тег формата (ID3v2 в MP3, Vorbis Comments во FLAC) поля
+    /// и обложки других тегов, которых в нём нет, и удалять остальные теги, включая ID3v1,
+    /// если он не пересобирается. Касается только файлов, которые всё равно переписываются
+    pub fn with_strip_others(mut self, strip_others: bool) -> Self {
+        self.strip_others = strip_others;
+        self
+    }
+
+    /// Поиск исправлений в текстовых полях тега и описаниях встроенных картинок
+    /// (без изменения файла)
+    pub fn detect_tag_fixes(&self, tag: &Tag) -> Vec<FieldFix> {
+        let picture_key = picture_key();
+        let texts = tag
+            .items()
+            .filter_map(|item| Some((item.key(), item.value().text()?)))
+            .chain(
+                tag.pictures()
+                    .iter()
+                    .filter_map(|picture| Some((&picture_key, picture.description()?))),
+            );
+        self.detect_fixes(tag.tag_type(), texts)
+    }
+
+    /// Поиск исправлений в значениях `texts` (поле, текст), найденных в теге `tag_type`
+    fn detect_fixes<'a>(
+        &self,
+        tag_type: TagType,
+        texts: impl Iterator<Item = (&'a ItemKey, &'a str)>,
+    ) -> Vec<FieldFix> {
+        let mut fixes = Vec::new();
+        // Поля, где нужно только убрать пробелы: они исправляются вместе с остальными
+        let mut trimmed = Vec::new();
+        for (key, text) in texts {
+            if !self.fields.allows(key) {
+                continue;
+            }
+            let detector = self.detector.for_field(key);
+            let cuesheet = is_cuesheet(key);
+            let detection = if cuesheet {
+                text::fix_cue_text(&detector, text)
+            } else {
+                detector.detect(text)
+            };
+            let value = detection.as_ref().map_or(text, |d| d.text.as_str());
+            // В .cue ё и транслит сломали бы имена файлов в строках FILE
+            let (yo, value) = match self
+                .yo
+                .filter(|_| !cuesheet)
+                .map(|yo| (yo, yo.apply(value)))
+            {
+                Some((yo, Cow::Owned(value))) => (Some(yo), value),
+                _ => (None, value.to_string()),
+            };
+            let scheme = self.transliterate.filter(|_| !cuesheet);
+            let transliteration = scheme.filter(|_| translit::has_cyrillic(&value));
+            let mut fixed = match transliteration {
+                Some(scheme) => translit::transliterate(&value, scheme),
+                None => value,
+            };
+            if self.trim && !cuesheet {
+                fixed = trim_whitespace(&fixed).into_owned();
+            }
+            if fixed == text {
+                continue;
+            }
+            let fix = FieldFix {
+                tag_type,
+                key: key.clone(),
+                original: text.to_string(),
+                fixed,
+                confidence: detection.as_ref().map_or(100, |d| d.confidence),
+                yo,
+                transliteration,
+                mojibake: detection.map(|d| d.mojibake),
+            };
+            if fix.mojibake.is_none() && fix.yo.is_none() && fix.transliteration.is_none() {
+                trimmed.push(fix);
+            } else {
+                fixes.push(fix);
+            }
+        }
+
+        if !fixes.is_empty() {
+            fixes.extend(trimmed);
+        }
+        fixes
+    }
+
+    /// Чтение всех тегов файла и поиск исправлений; `None`, если исправлять нечего.
+    ///
+    /// ID3v1 пропускается: он хранит только Latin-1, и «кракозябры» в нём —
+    /// это как раз исходные байты cp1251, которые плееры читают правильно. С --id3v1 он
+    /// пересобирается из исправленного основного тега или удаляется
+    pub fn scan(&self, path: &Path) -> Result<Option<AudioFix>> {
+        let tagged_file = read_tags(path, self.sniff)?;
+        let mut file = self
+            .reads_file(&tagged_file)
+            .then(|| File::open(path).ok().map(BufReader::new))
+            .flatten();
+        Ok(self.find_fixes(&tagged_file, file.as_mut()))
+    }
+
+    /// Поиск исправлений в файле, уже прочитанном в память (например, из ZIP-архива):
+    /// формат определяется по содержимому, `path` нужен только для сообщений об ошибках
+    pub fn scan_bytes(&self, path: &Path, bytes: &[u8]) -> Result<Option<AudioFix>> {
+        let tagged_file = Probe::new(Cursor::new(bytes))
+            .options(parse_options())
+            .guess_file_type()
+            .map_err(LoftyError::from)
+            .and_then(|probe| probe.read())
+            .map_err(|source| Error::ReadTags {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Ok(self.find_fixes(&tagged_file, Some(&mut Cursor::new(bytes))))
+    }
+
+    /// Нужны ли для исправлений байты файла: включён --reencode-frames и есть ID3v2,
+    /// включён --id3v1 и есть ID3v1, это MP4, в котором могут быть главы, или WAV,
+    /// в котором может быть RIFF INFO не в UTF-8
+    fn reads_file(&self, tagged_file: &TaggedFile) -> bool {
+        (self.reencode_frames && tagged_file.tag(TagType::Id3v2).is_some())
+            || (self.id3v1 != Id3v1Policy::Keep && tagged_file.tag(TagType::Id3v1).is_some())
+            || self.reads_chapters(tagged_file)
+            || tagged_file.file_type() == FileType::Wav
+    }
+
+    fn reads_chapters(&self, tagged_file: &TaggedFile) -> bool {
+        tagged_file.file_type() == FileType::Mp4 && self.fields.allows(&chapter_key())
+    }
+
+    fn find_fixes<R: Read + Seek>(
+        &self,
+        tagged_file: &TaggedFile,
+        file: Option<&mut R>,
+    ) -> Option<AudioFix> {
+        let mut tags = Vec::new();
+        let mut fixes = Vec::new();
+        let mut file = file;
+        let info = file
+            .as_mut()
+            .filter(|_| tagged_file.file_type() == FileType::Wav)
+            .and_then(riff_info_tag);
+        for tag in file_tags(tagged_file, info.as_ref()) {
+            // тег, который lofty не записывает (ID3v2 во FLAC), можно только удалить
+            // с --strip-other-tags, перенеся его поля в основной
+            if tag.tag_type() == TagType::Id3v1
+                || !(self.strip_others || writable(tagged_file, tag.tag_type()))
+            {
+                continue;
+            }
+            let tag_fixes = self.detect_tag_fixes(tag);
+            if !tag_fixes.is_empty() {
+                fixes.extend(tag_fixes);
+                tags.push(tag.to_owned());
+            }
+        }
+
+        // названия глав лежат вне ilst, и сам тег ради них не переписывается
+        if let Some(file) = file.as_mut().filter(|_| self.reads_chapters(tagged_file)) {
+            let key = chapter_key();
+            let titles = mp4chap::read_titles(file);
+            let texts = titles.iter().map(|title| (&key, title.as_str()));
+            fixes.extend(self.detect_fixes(TagType::Mp4Ilst, texts));
+        }
+
+        let mut fix = AudioFix {
+            tags,
+            fixes,
+            reencode: false,
+            id3v1: None,
+            strip: Vec::new(),
+        };
+        self.add_file_fixes(&mut fix, tagged_file, file);
+        if fix.tags.is_empty() && fix.id3v1.is_none() && !fix.has_chapters() {
+            return None;
+        }
+        self.add_strip(&mut fix, tagged_file, info.as_ref());
+        Some(fix)
+    }
+
+    /// С --strip-other-tags: все теги файла, кроме основного, в `fix.strip`, а в `fix.tags`
+    /// — все теги, чтобы перенести их поля в основной; основного тега может и не быть
+    fn add_strip(&self, fix: &mut AudioFix, tagged_file: &TaggedFile, info: Option<&Tag>) {
+        if !self.strip_others {
+            return;
+        }
+        let primary = tagged_file.primary_tag_type();
+        // пересобираемый или удаляемый по --id3v1 ID3v1 обрабатывается отдельно
+        fix.strip = file_tags(tagged_file, info)
+            .map(Tag::tag_type)
+            .filter(|&t| t != primary && (t != TagType::Id3v1 || self.id3v1 == Id3v1Policy::Keep))
+            .collect();
+        if fix.strip.is_empty() {
+            return;
+        }
+        fix.tags = file_tags(tagged_file, info)
+            .filter(|tag| tag.tag_type() != TagType::Id3v1)
+            .cloned()
+            .collect();
+        if tagged_file.tag(primary).is_none() {
+            fix.tags.insert(0, Tag::new(primary));
+        }
+    }
+
+    /// Дополнить исправления теми, для которых нужны байты файла: перезапись фреймов ID3v2
+    /// в Latin-1 (--reencode-frames) и пересборка или удаление ID3v1 (--id3v1)
+    fn add_file_fixes<R: Read + Seek>(
+        &self,
+        fix: &mut AudioFix,
+        tagged_file: &TaggedFile,
+        file: Option<&mut R>,
+    ) {
+        let Some(file) = file.filter(|_| self.reads_file(tagged_file)) else {
+            return;
+        };
+        if let Some(tag) = tagged_file
+            .tag(TagType::Id3v2)
+            .filter(|_| self.reencode_frames)
+            && has_latin1_frames(file)
+        {
+            fix.reencode = true;
+            if !fix.tags.iter().any(|t| t.tag_type() == TagType::Id3v2) {
+                fix.tags.push(tag.to_owned());
+            }
+        }
+        if self.id3v1 == Id3v1Policy::Keep {
+            return;
+        }
+        let Some(old) = read_id3v1(file) else {
+            return;
+        };
+        fix.id3v1 = match self.id3v1 {
+            Id3v1Policy::Strip => Some(Id3v1Fix::Strip),
+            policy => main_tag(tagged_file)
+                .filter(|tag| id3v1::rebuild(&old, &id3v1_fields(tag, &fix.fixes), policy) != old)
+                .map(|tag| Id3v1Fix::Rebuild(tag.to_owned())),
+        };
+    }
+
+    /// Исправления из плана: исходные значения сверяются с текущими тегами файла
+    pub fn planned(&self, path: &Path, changes: &[PlanChange]) -> Result<AudioFix> {
+        let tagged_file = read_tags(path, self.sniff)?;
+        let error = |change: &PlanChange, reason| Error::PlanApply {
+            path: path.to_path_buf(),
+            field: change.field.clone(),
+            reason,
+        };
+
+        let info = File::open(path)
+            .ok()
+            .filter(|_| tagged_file.file_type() == FileType::Wav)
+            .and_then(|file| riff_info_tag(&mut BufReader::new(file)));
+
+        let mut tags: Vec<Tag> = Vec::new();
+        let mut fixes = Vec::new();
+        for change in changes {
+            let (Some(tag_type), Some(key)) = (
+                change.tag.as_deref().and_then(parse_tag_type),
+                change.key.as_deref(),
+            ) else {
+                return Err(error(
+                    change,
+                    tr("неполная запись плана", "incomplete plan record"),
+                ));
+            };
+            if key == CHAPTER_FIELD {
+                let titles = File::open(path)
+                    .map(|file| mp4chap::read_titles(&mut BufReader::new(file)))
+                    .unwrap_or_default();
+                if !titles.contains(&change.before) {
+                    return Err(error(
+                        change,
+                        tr(
+                            "значение изменилось после составления плана",
+                            "value changed since the plan was made",
+                        ),
+                    ));
+                }
+                fixes.push(FieldFix {
+                    tag_type,
+                    key: chapter_key(),
+                    original: change.before.clone(),
+                    fixed: change.after.clone(),
+                    mojibake: change.mojibake(),
+                    confidence: change.confidence.unwrap_or(100),
+                    yo: None,
+                    transliteration: None,
+                });
+                continue;
+            }
+            let key = match key {
+                PICTURE_FIELD => picture_key(),
+                key if tag_type == TagType::RiffInfo => riff_key(key),
+                key => ItemKey::from_key(tag_type, key),
+            };
+            let Some(tag) = file_tags(&tagged_file, info.as_ref())
+                .find(|tag| tag.tag_type() == tag_type)
+                .filter(|tag| tag_values(tag, &key).contains(&change.before.as_str()))
+            else {
+                return Err(error(
+                    change,
+                    tr(
+                        "значение изменилось после составления плана",
+                        "value changed since the plan was made",
+                    ),
+                ));
+            };
+
+            if !tags.iter().any(|t| t.tag_type() == tag_type) {
+                tags.push(tag.to_owned());
+            }
+            fixes.push(FieldFix {
+                tag_type,
+                key,
+                original: change.before.clone(),
+                fixed: change.after.clone(),
+                mojibake: change.mojibake(),
+                confidence: change.confidence.unwrap_or(100),
+                yo: None,
+                transliteration: None,
+            });
+        }
+
+        let mut fix = AudioFix {
+            tags,
+            fixes,
+            reencode: false,
+            id3v1: None,
+            strip: Vec::new(),
+        };
+        let mut file = self
+            .reads_file(&tagged_file)
+            .then(|| File::open(path).ok().map(BufReader::new))
+            .flatten();
+        self.add_file_fixes(&mut fix, &tagged_file, file.as_mut());
+        Ok(fix)
+    }
+
+    /// Применение исправлений ко всем тегам и сохранение файла с предварительным бэкапом
+    pub fn apply(&self, path: &Path, fix: AudioFix, backup_manager: &BackupManager) -> Result<()> {
+        let AudioFix {
+            mut tags,
+            fixes,
+            reencode,
+            id3v1,
+            strip,
+        } = fix;
+        // после отказа от части исправлений в некоторых тегах может не остаться изменений;
+        // при удалении других тегов нужны все: их поля переносятся в основной
+        tags.retain(|tag| {
+            !strip.is_empty()
+                || (reencode && tag.tag_type() == TagType::Id3v2)
+                || fixes.iter().any(|f| f.tag_type == tag.tag_type())
+        });
+        if tags.is_empty() && id3v1.is_none() && !has_chapters(&fixes) {
+            return Ok(());
+        }
+        let records: Vec<_> = tags
+            .iter()
+            .map(|tag| {
+                let keys = fixes
+                    .iter()
+                    .filter(|f| f.tag_type == tag.tag_type())
+                    .map(|f| &f.key);
+                FileRecord::from_tag(path, tag, keys)
+            })
+            .collect();
+
+        // бэкап один раз, до сохранения первого тега; ID3v1, удалённые теги, главы и байты
+        // RIFF INFO не в UTF-8 манифест не восстанавливает, поэтому для них нужна полная копия
+        if id3v1.is_some() || !strip.is_empty() || has_chapters(&fixes) || has_riff_info(&tags) {
+            backup_manager.backup_file(path)?;
+        } else {
+            backup_manager.backup_tags(path, &records)?;
+        }
+
+        // все теги сохраняются во временную копию, которая затем заменяет оригинал; названия
+        // глав MP4 исправляются ещё при копировании
+        if has_chapters(&fixes) {
+            return atomic::modify_copy(
+                path,
+                |original, copy| write_chapters(original, copy, &fixes),
+                |file| self.save_tags(path, file, tags, &fixes, id3v1.as_ref(), &strip),
+            );
+        }
+        atomic::modify(path, |file| {
+            self.save_tags(path, file, tags, &fixes, id3v1.as_ref(), &strip)
+        })
+    }
+
+    /// Применение исправлений к файлу в памяти, без бэкапа: его делает владелец данных
+    /// (для файла из архива — бэкап всего архива)
+    pub fn apply_bytes(&self, path: &Path, bytes: &mut Vec<u8>, fix: AudioFix) -> Result<()> {
+        let AudioFix {
+            tags,
+            fixes,
+            id3v1,
+            strip,
+            ..
+        } = fix;
+        if has_chapters(&fixes) {
+            let mut copy = Vec::new();
+            write_chapters(&mut Cursor::new(bytes.as_slice()), &mut copy, &fixes).map_err(
+                |source| Error::Write {
+                    path: path.to_path_buf(),
+                    source,
+                },
+            )?;
+            *bytes = copy;
+        }
+        let mut file = Cursor::new(std::mem::take(bytes));
+        let saved = self.save_tags(path, &mut file, tags, &fixes, id3v1.as_ref(), &strip);
+        *bytes = file.into_inner();
+        saved
+    }
+
+    /// Значения полей альбома и трека (album::KEYS, album::TRACK_KEYS) из основного тега файла
+    pub fn album_values(&self, path: &Path) -> Result<Vec<(ItemKey, String)>> {
+        let tagged_file = read_tags(path, self.sniff)?;
+        let Some(tag) = main_tag(&tagged_file) else {
+            return Ok(Vec::new());
+        };
+        Ok(album::KEYS
+            .iter()
+            .chain(&album::TRACK_KEYS)
+            .filter_map(|key| Some((key.clone(), tag.get_string(key)?.to_string())))
+            .collect())
+    }
+
+    /// Записать новые значения полей `changes` (поле, прежнее значение, новое) во все теги
+    /// файла, где у поля прежнее значение, с бэкапом, как перед исправлением. ID3v1 не
+    /// меняется
+    pub fn set_fields(
+        &self,
+        path: &Path,
+        changes: &[(ItemKey, String, String)],
+        backup_manager: &BackupManager,
+    ) -> Result<()> {
+        let tagged_file = read_tags(path, self.sniff)?;
+        let info = File::open(path)
+            .ok()
+            .filter(|_| tagged_file.file_type() == FileType::Wav)
+            .and_then(|file| riff_info_tag(&mut BufReader::new(file)));
+        let mut tags = Vec::new();
+        let mut records = Vec::new();
+        for tag in file_tags(&tagged_file, info.as_ref()) {
+            let matching: Vec<_> = changes
+                .iter()
+                .filter(|(key, before, _)| tag.get_strings(key).any(|value| value == before))
+                .collect();
+            if tag.tag_type() == TagType::Id3v1
+                || !writable(&tagged_file, tag.tag_type())
+                || matching.is_empty()
+            {
+                continue;
+            }
+            records.push(FileRecord::from_tag(
+                path,
+                tag,
+                matching.iter().map(|(key, ..)| key),
+            ));
+            let mut tag = tag.to_owned();
+            for (key, before, after) in matching {
+                replace_text(&mut tag, key, before, after);
+            }
+            tags.push(tag);
+        }
+        if tags.is_empty() {
+            return Ok(());
+        }
+        if has_riff_info(&tags) {
+            backup_manager.backup_file(path)?;
+        } else {
+            backup_manager.backup_tags(path, &records)?;
+        }
+        atomic::modify(path, |file| {
+            self.save_tags(path, file, tags, &[], None, &[])
+        })
+    }
+
+    /// Записать исправленные значения во все теги `tags` и сохранить их в `file`,
+    /// затем пересобрать или удалить ID3v1. Если `strip` не пуст, сохраняется только
+    /// основной тег — с полями из остальных, — а теги `strip` удаляются. Остальные теги
+    /// файла, а также заполнение ID3v2 и фреймы, которые общий Tag не читает (CHAP, PRIV),
+    /// остаются как были. Если после записи пропала хоть одна встроенная картинка или её
+    /// байты изменились, возвращается ошибка: `file` — временная копия, и оригинал не меняется
+    fn save_tags<F>(
+        &self,
+        path: &Path,
+        file: &mut F,
+        mut tags: Vec<Tag>,
+        fixes: &[FieldFix],
+        id3v1: Option<&Id3v1Fix>,
+        strip: &[TagType],
+    ) -> Result<()>
+    where
+        F: FileLike,
+        LoftyError: From<<F as Truncate>::Error> + From<<F as Length>::Error>,
+    {
+        let pictures = picture_data(file);
+        for tag in &mut tags {
+            let tag_type = tag.tag_type();
+            for fix in fixes.iter().filter(|f| f.tag_type == tag_type) {
+                replace_text(tag, &fix.key, &fix.original, &fix.fixed);
+            }
+        }
+        if !strip.is_empty() {
+            let (others, main): (Vec<_>, Vec<_>) = tags
+                .into_iter()
+                .partition(|tag| strip.contains(&tag.tag_type()));
+            tags = main;
+            if let Some(main) = tags.first_mut() {
+                for other in &others {
+                    merge_tag(main, other);
+                }
+            }
+        }
+
+        // FLAC с ID3v2 в начале lofty не записывает: теги сохраняются в копию без ID3v2,
+        // а он затем возвращается на место, если его не удаляют
+        let Some(id3v2_len) = flac_id3v2_len(file) else {
+            self.write_tags(path, file, tags, fixes, id3v1, strip)?;
+            return check_pictures(path, file, &pictures);
+        };
+        let write_error = |source| Error::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut bytes = Vec::new();
+        file.rewind()
+            .and_then(|()| file.read_to_end(&mut bytes))
+            .map_err(write_error)?;
+        let mut flac = Cursor::new(bytes.split_off(id3v2_len));
+        if strip.contains(&TagType::Id3v2) {
+            bytes.clear();
+        }
+        let strip: Vec<TagType> = strip
+            .iter()
+            .copied()
+            .filter(|&tag_type| tag_type != TagType::Id3v2)
+            .collect();
+        self.write_tags(path, &mut flac, tags, fixes, id3v1, &strip)?;
+        bytes.extend(flac.into_inner());
+        file.rewind()
+            .and_then(|()| file.write_all(&bytes))
+            .map_err(write_error)?;
+        file.truncate(bytes.len() as u64)
+            .map_err(|e| Error::SaveTags {
+                path: path.to_path_buf(),
+                source: e.into(),
+            })?;
+        check_pictures(path, file, &pictures)
+    }
+
+    /// Сохранить теги `tags` с уже применёнными исправлениями в `file`, удалить теги `strip`,
+    /// затем пересобрать или удалить ID3v1
+    fn write_tags<F>(
+        &self,
+        path: &Path,
+        file: &mut F,
+        tags: Vec<Tag>,
+        fixes: &[FieldFix],
+        id3v1: Option<&Id3v1Fix>,
+        strip: &[TagType],
+    ) -> Result<()>
+    where
+        F: FileLike,
+        LoftyError: From<<F as Truncate>::Error> + From<<F as Length>::Error>,
+    {
+        let write_error = |source| Error::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+        let save_error = |source| Error::SaveTags {
+            path: path.to_path_buf(),
+            source,
+        };
+        let has_id3v2 = tags.iter().any(|tag| tag.tag_type() == TagType::Id3v2);
+        let options = write_options(file, self.id3, has_id3v2);
+        for tag in tags {
+            save_tag(file, tag, options).map_err(save_error)?;
+        }
+        for tag_type in strip {
+            file.rewind().map_err(write_error)?;
+            tag_type.remove_from(file).map_err(save_error)?;
+        }
+
+        let Some(id3v1) = id3v1 else {
+            return Ok(());
+        };
+        let Some(old) = read_id3v1(file) else {
+            return Ok(());
+        };
+        match id3v1 {
+            Id3v1Fix::Strip => {
+                let len = file.len().map_err(|e| save_error(e.into()))?;
+                file.truncate(len - id3v1::TAG_LEN as u64)
+                    .map_err(|e| save_error(e.into()))?;
+            }
+            Id3v1Fix::Rebuild(tag) => {
+                let tag = id3v1::rebuild(&old, &id3v1_fields(tag, fixes), self.id3v1);
+                if tag != old {
+                    file.seek(SeekFrom::End(-(id3v1::TAG_LEN as i64)))
+                        .and_then(|_| file.write_all(&tag))
+                        .map_err(write_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Заменить значение `original` поля `key` на `fixed`. У поля может быть несколько значений
+/// (исполнители, комментарии и слова песни на разных языках): меняются только совпадающие
+/// с исходным, порядок значений, язык и описание у них сохраняются.
+///
+/// Значения возвращаются без проверки ключа: `Tag::push` отбрасывает пользовательские поля
+/// (`ItemKey::Unknown` — TXXX, свои поля Vorbis), хотя при сохранении они записываются.
+/// Для [`picture_key`] меняются описания картинок
+fn replace_text(tag: &mut Tag, key: &ItemKey, original: &str, fixed: &str) {
+    if *key == picture_key() {
+        for (i, picture) in tag.pictures().to_vec().into_iter().enumerate() {
+            if picture.description() == Some(original) {
+                let mut picture = picture;
+                picture.set_description(Some(fixed.to_string()));
+                tag.set_picture(i, picture);
+            }
+        }
+        return;
+    }
+    let items: Vec<TagItem> = tag.take(key).collect();
+    for item in items {
+        if item.value().text() != Some(original) {
+            tag.push_unchecked(item);
+            continue;
+        }
+        let mut fixed = TagItem::new(key.clone(), ItemValue::Text(fixed.to_string()));
+        fixed.set_lang(*item.lang());
+        fixed.set_description(item.description().to_string());
+        tag.push_unchecked(fixed);
+    }
+}
+
+fn has_chapters(fixes: &[FieldFix]) -> bool {
+    let key = chapter_key();
+    fixes.iter().any(|f| f.key == key)
+}
+
+/// Скопировать MP4 `file` в `out` с исправленными названиями глав; теги lofty сохраняет
+/// уже в копию
+fn write_chapters<R: Read + Seek, W: Write>(
+    file: &mut R,
+    out: &mut W,
+    fixes: &[FieldFix],
+) -> std::io::Result<()> {
+    let key = chapter_key();
+    let rewrite = mp4chap::replace_titles(file, |title| {
+        fixes
+            .iter()
+            .find(|f| f.key == key && f.original == title)
+            .map(|f| f.fixed.clone())
+    })
+    .map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidData, message))?;
+    match rewrite {
+        Some(rewrite) => rewrite.write(file, out),
+        None => file
+            .rewind()
+            .and_then(|()| std::io::copy(file, out))
+            .map(drop),
+    }
+}
+
+/// Значения поля `key` в `tag`; для [`picture_key`] — описания картинок по порядку
+/// (пустые у картинок без описания)
+pub(crate) fn tag_values<'a>(tag: &'a Tag, key: &'a ItemKey) -> Vec<&'a str> {
+    if *key == picture_key() {
+        return tag
+            .pictures()
+            .iter()
+            .map(|picture| picture.description().unwrap_or_default())
+            .collect();
+    }
+    tag.get_strings(key).collect()
+}
+
+/// Встроенный .cue: поле CUESHEET у Vorbis и APE (и TXXX:CUESHEET в ID3v2).
+/// Блок CUESHEET самого FLAC хранит только номер каталога, ISRC и индексы в ASCII —
+/// исправлять в нём нечего
+fn is_cuesheet(key: &ItemKey) -> bool {
+    matches!(key, ItemKey::Unknown(name) if name.eq_ignore_ascii_case("CUESHEET"))
+}
+
+/// Тег ID3v1 в последних 128 байтах файла
+fn read_id3v1<R: Read + Seek>(file: &mut R) -> Option<[u8; id3v1::TAG_LEN]> {
+    let mut tail = [0; id3v1::TAG_LEN];
+    file.seek(SeekFrom::End(-(id3v1::TAG_LEN as i64))).ok()?;
+    file.read_exact(&mut tail).ok()?;
+    id3v1::parse(&tail)
+}
+
+/// Теги файла; RIFF INFO — из `info`, если он прочитан из байтов файла
+fn file_tags<'a>(
+    tagged_file: &'a TaggedFile,
+    info: Option<&'a Tag>,
+) -> impl Iterator<Item = &'a Tag> {
+    tagged_file
+        .tags()
+        .iter()
+        .filter(move |tag| info.is_none() || tag.tag_type() != TagType::RiffInfo)
+        .chain(info)
+}
+
+/// Переписывается ли RIFF INFO: все его значения сохраняются в UTF-8
+fn has_riff_info(tags: &[Tag]) -> bool {
+    tags.iter().any(|tag| tag.tag_type() == TagType::RiffInfo)
+}
+
+/// RIFF INFO со всеми полями, в том числе не в UTF-8, которые lofty пропускает (см.
+/// [`crate::riff`]); `None`, если списка INFO нет
+fn riff_info_tag<R: Read + Seek>(file: &mut R) -> Option<Tag> {
+    let fields = riff::read_info(file);
+    if fields.is_empty() {
+        return None;
+    }
+    let mut tag = Tag::new(TagType::RiffInfo);
+    for (id, value) in fields {
+        tag.push_unchecked(TagItem::new(riff_key(&id), ItemValue::Text(value)));
+    }
+    Some(tag)
+}
+
+/// Ключ поля RIFF INFO. Код, который lofty записал бы под другим (`ITRK` → `IPRT`),
+/// остаётся своим
+fn riff_key(id: &str) -> ItemKey {
+    let key = ItemKey::from_key(TagType::RiffInfo, id);
+    if key.map_key(TagType::RiffInfo, false) == Some(id) {
+        key
+    } else {
+        ItemKey::Unknown(id.to_string())
+    }
+}
+
+/// Тег, из которого пересобирается ID3v1: ID3v2, а если его нет — любой другой
+fn main_tag(tagged_file: &TaggedFile) -> Option<&Tag> {
+    tagged_file.tag(TagType::Id3v2).or_else(|| {
+        tagged_file
+            .tags()
+            .iter()
+            .find(|tag| tag.tag_type() != TagType::Id3v1)
+    })
+}
+
+/// Может ли lofty записать тег типа `tag_type` в этот файл: ID3v2 во FLAC, APE и Musepack
+/// он читает, но не записывает
+fn writable(tagged_file: &TaggedFile, tag_type: TagType) -> bool {
+    tag_type != TagType::Id3v2
+        || !matches!(
+            tagged_file.file_type(),
+            FileType::Flac | FileType::Ape | FileType::Mpc
+        )
+}
+
+/// Перенести в `main` поля тега `other`, которых в нём нет, со всеми значениями, и картинки,
+/// которых в нём нет. Поля, которых в формате `main` не бывает, теряются
+fn merge_tag(main: &mut Tag, other: &Tag) {
+    let missing: Vec<TagItem> = other
+        .items()
+        .filter(|item| main.get(item.key()).is_none())
+        .cloned()
+        .collect();
+    for item in missing {
+        main.push(item);
+    }
+    for picture in other.pictures() {
+        if !main.pictures().iter().any(|p| p.data() == picture.data()) {
+            main.push_picture(picture.clone());
+        }
+    }
+}
+
+/// Данные всех встроенных картинок файла во всех тегах, без повторов; `file` остаётся
+/// в начале
+pub(crate) fn picture_data<R: Read + Seek>(file: &mut R) -> Vec<Vec<u8>> {
+    let tagged_file = file
+        .rewind()
+        .and_then(|()| {
+            Probe::new(&mut *file)
+                .options(parse_options())
+                .guess_file_type()
+        })
+        .map_err(LoftyError::from)
+        .and_then(|probe| probe.read());
+    // запись тегов, которая идёт следом, ждёт файл с начала
+    let rewound = file.rewind();
+    let (Ok(tagged_file), Ok(())) = (tagged_file, rewound) else {
+        return Vec::new();
+    };
+    let mut data: Vec<Vec<u8>> = tagged_file
+        .tags()
+        .iter()
+        .flat_map(Tag::pictures)
+        .map(|picture| picture.data().to_vec())
+        .collect();
+    data.sort_unstable();
+    data.dedup();
+    data
+}
+
+/// Ошибка, если каких-то картинок из `before` (см. [`picture_data`]) в `file` больше нет
+/// байт в байт: lofty может пересобрать фрейм картинки или не записать её вовсе
+pub(crate) fn check_pictures<R: Read + Seek>(
+    path: &Path,
+    file: &mut R,
+    before: &[Vec<u8>],
+) -> Result<()> {
+    if before.is_empty() {
+        return Ok(());
+    }
+    let after = picture_data(file);
+    let count = before
+        .iter()
+        .filter(|data| after.binary_search(data).is_err())
+        .count();
+    if count == 0 {
+        return Ok(());
+    }
+    Err(Error::PicturesLost {
+        path: path.to_path_buf(),
+        count,
+    })
+}
+
+/// Поля ID3v1 из тега `tag` с исправлениями `fixes`
+fn id3v1_fields(tag: &Tag, fixes: &[FieldFix]) -> id3v1::Fields {
+    let value = |key: ItemKey| {
+        fixes
+            .iter()
+            .find(|f| f.tag_type == tag.tag_type() && f.key == key)
+            .map(|f| f.fixed.clone())
+            .or_else(|| tag.get_string(&key).map(str::to_string))
+    };
+    id3v1::Fields {
+        title: value(ItemKey::TrackTitle),
+        artist: value(ItemKey::TrackArtist),
+        album: value(ItemKey::AlbumTitle),
+        comment: value(ItemKey::Comment),
+    }
+}
+
+/// Основная версия ID3v2 в файле; `None` — тега нет
+fn id3v2_major<R: Read + Seek>(file: &mut R) -> Option<u8> {
+    id3v2_header(file).map(|header| header[3])
+}
+
+/// Настройки записи тегов в `file`: с [`Id3Version::Keep`] ID3v2 (если он записывается,
+/// `has_id3v2`) остаётся версии 2.3, если был ею или 2.2
+pub(crate) fn write_options<R: Read + Seek>(
+    file: &mut R,
+    id3: Id3Version,
+    has_id3v2: bool,
+) -> WriteOptions {
+    let use_id3v23 = match id3 {
+        Id3Version::V23 => true,
+        Id3Version::V24 => false,
+        Id3Version::Keep => has_id3v2 && id3v2_major(file).is_some_and(|major| major < 4),
+    };
+    WriteOptions::new().use_id3v23(use_id3v23)
+}
+
+/// Сохранить `tag` в `file` с настройками `options`. Общий Tag записывает COMM и USLT без
+/// языка и описания, а преобразование в Id3v2Tag их сохраняет и собирает значения в один
+/// фрейм через `\0`. Заполнение ID3v2 остаётся прежнего размера: иначе lofty подставил бы
+/// своё
+pub(crate) fn save_tag<F>(
+    file: &mut F,
+    tag: Tag,
+    options: WriteOptions,
+) -> std::result::Result<(), LoftyError>
+where
+    F: FileLike,
+    LoftyError: From<<F as Truncate>::Error> + From<<F as Length>::Error>,
+{
+    file.rewind()?;
+    if tag.tag_type() != TagType::Id3v2 {
+        return tag.save_to(file, options);
+    }
+    let options = match id3v2_padding(file) {
+        Some(padding) => options.preferred_padding(padding),
+        None => options,
+    };
+    file.rewind()?;
+    Id3v2Tag::from(tag).save_to(file, options)
+}
+
+/// Заголовок тега ID3v2: в начале файла (MP3, AAC) или в чанке `id3 ` у WAV и AIFF.
+/// Файл остаётся на начале фреймов
+fn id3v2_header<R: Read + Seek>(file: &mut R) -> Option<[u8; 10]> {
+    let mut header = [0; 12];
+    file.rewind().ok()?;
+    file.read_exact(&mut header).ok()?;
+    if header.starts_with(b"ID3") {
+        file.seek(SeekFrom::Start(10)).ok()?;
+        return header[..10].try_into().ok();
+    }
+    let big_endian = match &header[..4] {
+        b"RIFF" => false,
+        b"FORM" => true,
+        _ => return None,
+    };
+    loop {
+        let mut chunk = [0; 8];
+        file.read_exact(&mut chunk).ok()?;
+        let size: [u8; 4] = chunk[4..].try_into().ok()?;
+        let size = if big_endian {
+            u32::from_be_bytes(size)
+        } else {
+            u32::from_le_bytes(size)
+        };
+        if chunk[..4].eq_ignore_ascii_case(b"id3 ") {
+            let mut tag = [0; 10];
+            file.read_exact(&mut tag).ok()?;
+            return tag.starts_with(b"ID3").then_some(tag);
+        }
+        // Чанки выровнены по двум байтам
+        file.seek(SeekFrom::Current(i64::from(size) + i64::from(size & 1)))
+            .ok()?;
+    }
+}
+
+/// Есть ли в ID3v2 текстовые фреймы (T***, COMM, USLT), объявленные как Latin-1, с не-ASCII
+/// текстом: такой текст плееры показывают по-разному — в Latin-1 или в кодировке системы.
+/// Сжатые и зашифрованные фреймы не проверяются
+fn has_latin1_frames<R: Read + Seek>(file: &mut R) -> bool {
+    let mut found = false;
+    walk_id3v2_frames(file, |id, body| {
+        let text = match id {
+            [b'T', ..] => body.get(1..),
+            b"COMM" | b"USLT" | b"COM" | b"ULT" => body.get(4..),
+            _ => None,
+        };
+        found = body.first() == Some(&0) && text.is_some_and(|text| !text.is_ascii());
+        !found
+    });
+    found
+}
+
+/// Размер заполнения (нулевых байтов после фреймов) в ID3v2; `None` — тега нет
+pub(crate) fn id3v2_padding<R: Read + Seek>(file: &mut R) -> Option<u32> {
+    walk_id3v2_frames(file, |_, _| true)
+}
+
+/// Перебор фреймов ID3v2 файла: `f` получает идентификатор и содержимое фрейма и возвращает
+/// `false`, чтобы остановиться. Сжатые и зашифрованные фреймы пропускаются.
+/// Результат — размер заполнения после фреймов; `None` — тега нет или он не читается
+fn walk_id3v2_frames<R: Read + Seek>(
+    file: &mut R,
+    mut f: impl FnMut(&[u8], &[u8]) -> bool,
+) -> Option<u32> {
+    let header = id3v2_header(file)?;
+    let major = header[3];
+    let mut tag = vec![0; synchsafe(&header[6..10]) as usize];
+    file.read_exact(&mut tag).ok()?;
+    // Флаг unsynchronisation: после каждого 0xFF вставлен 0x00
+    if header[5] & 0x80 != 0 {
+        let mut previous = 0;
+        tag.retain(|&byte| {
+            let keep = !(previous == 0xFF && byte == 0);
+            previous = byte;
+            keep
+        });
+    }
+    let mut pos = 0;
+    // Расширенный заголовок: в v2.3 размер без самого поля размера, в v2.4 — с ним
+    if header[5] & 0x40 != 0 && major >= 3 {
+        let size = tag.get(..4)?;
+        pos = match major {
+            3 => u32::from_be_bytes(size.try_into().unwrap()) as usize + 4,
+            _ => synchsafe(size) as usize,
+        };
+    }
+
+    let (id_len, header_len) = if major == 2 { (3, 6) } else { (4, 10) };
+    while let Some(frame) = tag.get(pos..pos + header_len) {
+        let (id, size) = frame.split_at(id_len);
+        if id[0] == 0 {
+            // Началось заполнение
+            break;
+        }
+        let size = match major {
+            2 => u32::from_be_bytes([0, size[0], size[1], size[2]]),
+            3 => u32::from_be_bytes(size[..4].try_into().unwrap()),
+            _ => synchsafe(&size[..4]),
+        } as usize;
+        let Some(mut body) = tag.get(pos + header_len..pos + header_len + size) else {
+            break;
+        };
+        pos += header_len + size;
+
+        let format_flags = if major == 2 { 0 } else { frame[9] };
+        let (packed, has_length) = match major {
+            2 => (false, false),
+            3 => (format_flags & 0xC0 != 0, false),
+            _ => (format_flags & 0x0C != 0, format_flags & 0x01 != 0),
+        };
+        if packed {
+            continue;
+        }
+        if has_length {
+            body = body.get(4..).unwrap_or_default();
+        }
+        if !f(id, body) {
+            break;
+        }
+    }
+    Some(tag.len().saturating_sub(pos) as u32)
+}
+
+/// Длина ID3v2 в начале FLAC (вместе с заголовком и футером); `None` — его нет или файл
+/// не FLAC
+fn flac_id3v2_len<R: Read + Seek>(file: &mut R) -> Option<usize> {
+    let header = id3v2_header(file)?;
+    if file.stream_position().ok()? != 10 {
+        // ID3v2 в чанке RIFF или AIFF
+        return None;
+    }
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    let len = 10 + synchsafe(&header[6..10]) as u64 + footer;
+    let mut marker = [0; 4];
+    file.seek(SeekFrom::Start(len)).ok()?;
+    file.read_exact(&mut marker).ok()?;
+    (&marker == b"fLaC").then_some(len as usize)
+}
+
+/// Число из 7-битных байт (размеры в заголовках ID3v2.4)
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |size, &byte| (size << 7) | u32::from(byte & 0x7F))
+}
+
+/// Расширение, соответствующее содержимому аудио-файла (по сигнатуре в начале файла);
+/// `None` — формат не распознан или не поддерживается
+pub fn sniff_extension(path: &Path) -> Option<&'static str> {
+    let mut file = File::open(path).ok()?;
+    let mut header = [0; 16];
+    if file.read_exact(&mut header).is_ok() && asf::is_asf(&header) {
+        return Some("wma");
+    }
+    file.rewind().ok()?;
+    let probe = Probe::new(BufReader::new(file))
+        .options(parse_options())
+        .guess_file_type()
+        .ok()?;
+    match probe.file_type()? {
+        FileType::Flac => Some("flac"),
+        FileType::Mpeg => Some("mp3"),
+        FileType::Mp4 => Some("m4a"),
+        FileType::Vorbis => Some("ogg"),
+        FileType::Opus => Some("opus"),
+        FileType::Speex => Some("spx"),
+        FileType::Wav => Some("wav"),
+        FileType::Ape => Some("ape"),
+        FileType::WavPack => Some("wv"),
+        FileType::Mpc => Some("mpc"),
+        _ => None,
+    }
+}
+
+/// Читаются только теги: свойства потока (длительность, битрейт) для исправления не нужны,
+/// а их разбор у каждого FLAC заметно замедляет обход сетевых дисков
+fn parse_options() -> ParseOptions {
+    ParseOptions::new().read_properties(false)
+}
+
+/// Прочитать теги файла; с `sniff` формат определяется по содержимому, а расширение
+/// используется, только если содержимое не распознано
+pub(crate) fn read_tags(path: &Path, sniff: bool) -> Result<TaggedFile> {
+    Probe::open(path)
+        .and_then(|probe| {
+            let probe = probe.options(parse_options());
+            if sniff {
+                probe.guess_file_type()?.read()
+            } else {
+                probe.read()
+            }
+        })
+        .map_err(|source| Error::ReadTags {
+            path: path.to_path_buf(),
+            source,
+        })
+}
@@ -0,0 +1,464 @@
+//! Создание .bak файлов перед изменением оригиналов
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::locale::tr;
+use crate::manifest::{FileRecord, Manifest};
+use crate::time;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Расширение файлов бэкапа
+pub const BACKUP_EXTENSION: &str = "bak";
+
+/// Расширение сжатых бэкапов (`track.flac.bak.gz`)
+pub const COMPRESSED_EXTENSION: &str = "gz";
+
+/// Журнал созданных бэкапов относительно корня обработки
+pub const JOURNAL_FILE: &str = ".cyrtag-fix/backups.tsv";
+
+/// Отдельный каталог для бэкапов с повторением структуры исходного дерева
+#[derive(Debug, Clone)]
+pub struct BackupDir {
+    /// Куда складываются бэкапы
+    pub dir: PathBuf,
+    /// Корень исходного дерева, относительно которого строятся пути
+    pub root: PathBuf,
+}
+
+impl BackupDir {
+    pub fn new(dir: impl Into<PathBuf>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            root: root.into(),
+        }
+    }
+
+    /// Путь бэкапа для `path` (`root/a/b.mp3` -> `dir/a/b.mp3`)
+    pub fn backup_path(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(self.dir.join(relative))
+    }
+
+    /// Путь оригинала для бэкапа из каталога (`dir/a/b.mp3`, `dir/a/b.mp3.1` -> `root/a/b.mp3`)
+    pub fn original_path(&self, backup: &Path) -> Option<PathBuf> {
+        let (backup, _) = split_number(backup);
+        let relative = backup.strip_prefix(&self.dir).ok()?;
+        Some(self.root.join(relative))
+    }
+
+    /// Лежит ли файл внутри каталога бэкапов
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.dir)
+    }
+}
+
+/// Журнал созданных бэкапов (`время<TAB>путь бэкапа` на строку): по нему clean-backups
+/// удаляет только копии, сделанные самой программой, а не `.bak` пользователя
+#[derive(Debug, Clone)]
+pub struct BackupJournal {
+    path: PathBuf,
+}
+
+impl BackupJournal {
+    /// Журнал в корне обработки `root`
+    pub fn in_dir(root: &Path) -> Self {
+        Self {
+            path: root.join(JOURNAL_FILE),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn error(&self, source: io::Error) -> Error {
+        Error::BackupJournal {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Дописать созданный бэкап; строка пишется одним вызовом, чтобы записи из разных
+    /// потоков не перемешивались
+    pub fn record(&self, backup: &Path) -> Result<()> {
+        let backup = std::path::absolute(backup).map_err(|e| self.error(e))?;
+        let line = format!("{}\t{}\n", time::now_secs(), backup.display());
+        self.path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+            })
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| self.error(e))
+    }
+
+    /// Бэкапы из журнала со временем последнего создания, от старых к новым; отсутствующий
+    /// журнал — пустой список
+    pub fn entries(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(self.error(e)),
+        };
+
+        // бэкап файла, исправленного повторно, перезаписывается: важна последняя запись
+        let mut entries: Vec<(u64, PathBuf)> = Vec::new();
+        for (time, path) in content
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .filter_map(|(time, path)| Some((time.parse().ok()?, PathBuf::from(path))))
+        {
+            entries.retain(|(_, p)| *p != path);
+            entries.push((time, path));
+        }
+        entries.sort_by_key(|&(time, _)| time);
+        Ok(entries)
+    }
+
+    /// Перезаписать журнал оставшимися записями; пустой журнал удаляется
+    pub fn replace(&self, entries: &[(u64, PathBuf)]) -> Result<()> {
+        if entries.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(self.error(e)),
+                _ => Ok(()),
+            };
+        }
+
+        let content: String = entries
+            .iter()
+            .map(|(time, path)| format!("{time}\t{}\n", path.display()))
+            .collect();
+        fs::write(&self.path, content).map_err(|e| self.error(e))
+    }
+}
+
+pub struct BackupManager {
+    no_backup: bool,
+    manifest: Option<Manifest>,
+    backup_dir: Option<BackupDir>,
+    journal: Option<BackupJournal>,
+    compress: bool,
+}
+
+impl BackupManager {
+    pub fn new(no_backup: bool) -> Self {
+        Self {
+            no_backup,
+            manifest: None,
+            backup_dir: None,
+            journal: None,
+            compress: false,
+        }
+    }
+
+    /// Бэкап тегов аудио-файлов в манифест вместо полных копий
+    pub fn with_manifest(mut self, manifest: Manifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Бэкапы в отдельный каталог вместо .bak рядом с файлом
+    pub fn with_backup_dir(mut self, backup_dir: BackupDir) -> Self {
+        self.backup_dir = Some(backup_dir);
+        self
+    }
+
+    /// Записывать созданные бэкапы в журнал для clean-backups
+    pub fn with_journal(mut self, journal: BackupJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Сжимать копии файлов gzip
+    pub fn with_compression(mut self) -> Self {
+        self.compress = true;
+        self
+    }
+
+    pub fn backup_dir(&self) -> Option<&BackupDir> {
+        self.backup_dir.as_ref()
+    }
+
+    /// Файл сам является бэкапом и не должен обрабатываться
+    pub fn is_backup(&self, path: &Path) -> bool {
+        self.backup_dir.as_ref().is_some_and(|b| b.contains(path))
+    }
+
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Бэкап перед изменением тегов: записи в манифест (по одной на тег), если он есть,
+    /// иначе копия файла
+    pub fn backup_tags(&self, path: &Path, records: &[FileRecord]) -> Result<()> {
+        match &self.manifest {
+            Some(manifest) => records
+                .iter()
+                .try_for_each(|record| manifest.record(record)),
+            None => self.backup_file(path),
+        }
+    }
+
+    /// Копия `path` под первым свободным именем, чтобы повторный запуск не затёр бэкап
+    /// настоящего оригинала уже исправленным файлом; возвращается путь копии
+    fn create_backup(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let base = match &self.backup_dir {
+            Some(backup_dir) => backup_dir.backup_path(path),
+            None => backup_path(path),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                tr("Не удалось получить имя файла", "cannot get file name"),
+            )
+        })?;
+
+        let backup_path = free_path(&base, self.compress);
+        if let Some(parent) = backup_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if self.compress {
+            compress(path, &backup_path)?;
+        } else {
+            fs::copy(path, &backup_path)?;
+        }
+        if let Err(e) = verify_copy(path, &backup_path) {
+            // неполная копия хуже отсутствующей: restore вернул бы её вместо оригинала
+            let _ = fs::remove_file(&backup_path);
+            return Err(e);
+        }
+        Ok(backup_path)
+    }
+
+    pub fn backup_file(&self, path: &Path) -> Result<()> {
+        if self.no_backup {
+            return Ok(());
+        }
+        let backup = self.create_backup(path).map_err(|source| Error::Backup {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        match &self.journal {
+            Some(journal) => journal.record(&backup),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Сжатая gzip копия `path`
+fn compress(path: &Path, backup: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(backup)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+/// Содержимое бэкапа, сжатого или нет
+fn open_backup(backup: &Path) -> io::Result<Box<dyn Read>> {
+    let file = File::open(backup)?;
+    Ok(if is_compressed(backup) {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    })
+}
+
+/// Проверка, что копия совпадает с оригиналом по размеру и CRC32 (сжатая — после
+/// распаковки): при переполненном диске или обрыве сетевой папки копия может оказаться
+/// неполной без ошибки
+fn verify_copy(original: &Path, copy: &Path) -> io::Result<()> {
+    if checksum(File::open(original)?)? == checksum(open_backup(copy)?)? {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        tr(
+            "бэкап не совпадает с оригиналом",
+            "backup does not match the original",
+        ),
+    ))
+}
+
+/// Размер и CRC32 содержимого
+fn checksum(mut file: impl Read) -> io::Result<(u64, u32)> {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 1 << 16];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok((size, hasher.finalize()));
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+}
+
+/// Путь к бэкапу рядом с оригиналом (`track.mp3` -> `track.mp3.bak`)
+pub fn backup_path(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?;
+    Some(path.with_file_name(format!(
+        "{}.{BACKUP_EXTENSION}",
+        file_name.to_string_lossy()
+    )))
+}
+
+/// Имя `n`-го бэкапа на месте `base`: сам `base` (`track.mp3.bak`), затем `track.mp3.bak.1`,
+/// `track.mp3.bak.2`…
+fn numbered_path(base: &Path, n: u32) -> PathBuf {
+    if n == 0 {
+        return base.to_path_buf();
+    }
+    let mut name = base.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Сжатый бэкап на месте `path` (`track.mp3.bak` -> `track.mp3.bak.gz`)
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{COMPRESSED_EXTENSION}"));
+    PathBuf::from(name)
+}
+
+fn is_compressed(backup: &Path) -> bool {
+    backup
+        .extension()
+        .is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+}
+
+/// Имя бэкапа с номером `n`, если такой бэкап есть, сжатый или нет
+fn existing_path(base: &Path, n: u32) -> Option<PathBuf> {
+    let path = numbered_path(base, n);
+    let compressed = compressed_path(&path);
+    [path, compressed].into_iter().find(|path| path.exists())
+}
+
+/// Первое незанятое имя бэкапа на месте `base`; номер занят и несжатым, и сжатым бэкапом
+fn free_path(base: &Path, compress: bool) -> PathBuf {
+    let n = (0..)
+        .find(|&n| existing_path(base, n).is_none())
+        .unwrap_or(0);
+    let path = numbered_path(base, n);
+    if compress {
+        compressed_path(&path)
+    } else {
+        path
+    }
+}
+
+/// Пары (бэкап, новое имя) для переноса всех бэкапов с места `from` на место `to`
+/// с теми же номерами и сжатием
+pub fn moved_backups(from: &Path, to: &Path) -> Vec<(PathBuf, PathBuf)> {
+    (0..)
+        .map_while(|n| existing_path(from, n).map(|path| (n, path)))
+        .map(|(n, path)| {
+            let target = numbered_path(to, n);
+            let target = if is_compressed(&path) {
+                compressed_path(&target)
+            } else {
+                target
+            };
+            (path, target)
+        })
+        .collect()
+}
+
+/// Путь бэкапа без номера и сжатия и номер (`track.mp3.bak.2.gz` -> `track.mp3.bak`, 2);
+/// у первого бэкапа номер 0
+pub fn split_number(backup: &Path) -> (PathBuf, u32) {
+    let uncompressed;
+    let backup = if is_compressed(backup) {
+        uncompressed = backup.with_extension("");
+        uncompressed.as_path()
+    } else {
+        backup
+    };
+    let number = backup
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|ext| ext.parse().ok());
+    match number {
+        Some(n) if n > 0 => (backup.with_extension(""), n),
+        _ => (backup.to_path_buf(), 0),
+    }
+}
+
+/// Путь к оригиналу для файла бэкапа (`track.mp3.bak`, `track.mp3.bak.1` -> `track.mp3`)
+pub fn original_path(backup: &Path) -> Option<PathBuf> {
+    let (backup, _) = split_number(backup);
+    if backup.extension()? != BACKUP_EXTENSION {
+        return None;
+    }
+    Some(backup.with_extension(""))
+}
+
+/// Восстановление оригинала из бэкапа; при `keep_backup` бэкап остаётся на месте
+pub fn restore_backup(backup: &Path, keep_backup: bool) -> Result<PathBuf> {
+    let original = original_path(backup).ok_or_else(|| Error::Restore {
+        path: backup.to_path_buf(),
+        source: std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            tr("файл не является бэкапом", "not a backup file"),
+        ),
+    })?;
+
+    restore_to(backup, &original, keep_backup)?;
+    Ok(original)
+}
+
+/// Восстановление оригинала из каталога бэкапов
+pub fn restore_from_dir(
+    backup: &Path,
+    backup_dir: &BackupDir,
+    keep_backup: bool,
+) -> Result<PathBuf> {
+    let original = backup_dir
+        .original_path(backup)
+        .ok_or_else(|| Error::Restore {
+            path: backup.to_path_buf(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                tr(
+                    "файл лежит вне каталога бэкапов",
+                    "file is outside the backup directory",
+                ),
+            ),
+        })?;
+
+    restore_to(backup, &original, keep_backup)?;
+    Ok(original)
+}
+
+fn restore_to(backup: &Path, original: &Path, keep_backup: bool) -> Result<()> {
+    let restored = if is_compressed(backup) {
+        open_backup(backup)
+            .and_then(|mut content| atomic::copy_from(original, &mut content))
+            .and_then(|()| {
+                if keep_backup {
+                    Ok(())
+                } else {
+                    fs::remove_file(backup)
+                }
+            })
+    } else if keep_backup {
+        fs::copy(backup, original).map(|_| ())
+    } else {
+        // Каталог бэкапов может быть на другом диске — тогда rename не сработает
+        fs::rename(backup, original)
+            .or_else(|_| fs::copy(backup, original).and_then(|_| fs::remove_file(backup)))
+    };
+    restored.map_err(|source| Error::Restore {
+        path: backup.to_path_buf(),
+        source,
+    })
+}
@@ -0,0 +1,434 @@
+//! Скрипты автодополнения (команда completions) для bash, zsh, fish и PowerShell.
+//! Строятся по описанию команд clap, поэтому новые параметры и подкоманды попадают в них сами
+
+use clap::builder::StyledStr;
+use clap::{Arg, Command, ValueEnum, ValueHint};
+use std::fmt::Write;
+
+/// Оболочка, для которой печатается скрипт
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Скрипт автодополнения для `command` (со всеми подкомандами)
+pub fn generate(shell: Shell, mut command: Command) -> String {
+    // Глобальные параметры и --help попадают в подкоманды только при сборке
+    command.build();
+    match shell {
+        Shell::Bash => bash(&command),
+        Shell::Zsh => zsh(&command),
+        Shell::Fish => fish(&command),
+        Shell::Powershell => powershell(&command),
+    }
+}
+
+fn subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command.get_subcommands().filter(|sub| !sub.is_hide_set())
+}
+
+fn options(command: &Command) -> impl Iterator<Item = &Arg> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+}
+
+fn positionals(command: &Command) -> impl Iterator<Item = &Arg> {
+    command.get_arguments().filter(|arg| arg.is_positional())
+}
+
+/// Слова, которые предлагаются вместо параметров: подкоманды (у корневой команды)
+/// и допустимые значения позиционных аргументов
+fn words(command: &Command, top: bool) -> Vec<String> {
+    let mut words = Vec::new();
+    if top {
+        words.extend(subcommands(command).map(|sub| sub.get_name().to_string()));
+    }
+    words.extend(positionals(command).flat_map(values));
+    words
+}
+
+/// `-q`, `--quiet`
+fn flags(arg: &Arg) -> Vec<String> {
+    let short = arg.get_short().map(|short| format!("-{short}"));
+    let long = arg.get_long().map(|long| format!("--{long}"));
+    short.into_iter().chain(long).collect()
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+/// Имена допустимых значений (у параметров ValueEnum)
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn is_path(arg: &Arg) -> bool {
+    matches!(
+        arg.get_value_hint(),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath
+    )
+}
+
+/// Описание в одну строку
+fn about(text: Option<&StyledStr>) -> String {
+    let text = text.map(|text| text.to_string()).unwrap_or_default();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn bash(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut script = String::new();
+    let _ = write!(
+        script,
+        "{function}() {{\n    \
+             local cur prev cmd i\n    \
+             COMPREPLY=()\n    \
+             cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    \
+             prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n    \
+             cmd=\"\"\n    \
+             for ((i = 1; i < COMP_CWORD; i++)); do\n        \
+                 case \"${{COMP_WORDS[i]}}\" in\n"
+    );
+    for sub in subcommands(command) {
+        let names: Vec<&str> = std::iter::once(sub.get_name())
+            .chain(sub.get_all_aliases())
+            .collect();
+        let _ = writeln!(
+            script,
+            "            {}) cmd=\"{}\"; break ;;",
+            names.join("|"),
+            sub.get_name()
+        );
+    }
+    script.push_str("        esac\n    done\n\n    case \"$cmd\" in\n");
+    bash_case(&mut script, "\"\"", command, true);
+    for sub in subcommands(command) {
+        bash_case(&mut script, sub.get_name(), sub, false);
+    }
+    let _ = write!(
+        script,
+        "    esac\n}}\n\ncomplete -F {function} -o bashdefault -o default {name}\n"
+    );
+    script
+}
+
+fn bash_case(script: &mut String, pattern: &str, command: &Command, top: bool) {
+    let _ = writeln!(script, "        {pattern})\n            case \"$prev\" in");
+    let mut free = Vec::new();
+    for arg in options(command).filter(|arg| takes_value(arg)) {
+        let values = values(arg);
+        if values.is_empty() {
+            free.extend(flags(arg));
+        } else {
+            let _ = writeln!(
+                script,
+                "                {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                flags(arg).join("|"),
+                values.join(" ")
+            );
+        }
+    }
+    // Пути и числа: пустой ответ, bash сам предложит имена файлов (-o default)
+    if !free.is_empty() {
+        let _ = writeln!(script, "                {}) return ;;", free.join("|"));
+    }
+    let options: Vec<String> = options(command).flat_map(flags).collect();
+    let _ = writeln!(
+        script,
+        "            esac\n            \
+             if [[ \"$cur\" == -* ]]; then\n                \
+                 COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        options.join(" ")
+    );
+    let words = words(command, top);
+    if !words.is_empty() {
+        let _ = writeln!(
+            script,
+            "            else\n                \
+                 COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+            words.join(" ")
+        );
+    }
+    script.push_str("            fi\n            ;;\n");
+}
+
+fn zsh(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut script = format!("#compdef {name}\n\n{function}() {{\n    local line state\n");
+    script.push_str("    _arguments -C \\\n");
+    for spec in zsh_specs(command) {
+        let _ = writeln!(script, "        {spec} \\");
+    }
+    let _ = write!(
+        script,
+        "        '1: :{function}_commands' \\\n        \
+             '*:: :->args'\n\n    \
+             case $state in\n        \
+             args)\n            \
+             case $line[1] in\n"
+    );
+    for sub in subcommands(command) {
+        let names: Vec<&str> = std::iter::once(sub.get_name())
+            .chain(sub.get_all_aliases())
+            .collect();
+        let _ = writeln!(script, "                {})", names.join("|"));
+        script.push_str("                    _arguments");
+        for spec in zsh_specs(sub) {
+            let _ = write!(script, " \\\n                        {spec}");
+        }
+        script.push_str("\n                    ;;\n");
+    }
+    let _ = write!(
+        script,
+        "            esac\n            ;;\n    esac\n}}\n\n\
+         {function}_commands() {{\n    local commands=(\n"
+    );
+    for sub in subcommands(command) {
+        let _ = writeln!(
+            script,
+            "        '{}:{}'",
+            sub.get_name(),
+            zsh_quote(&about(sub.get_about()))
+        );
+    }
+    let _ = write!(
+        script,
+        "    )\n    _describe 'command' commands\n}}\n\n{function} \"$@\"\n"
+    );
+    script
+}
+
+/// Описания параметров для `_arguments`
+fn zsh_specs(command: &Command) -> Vec<String> {
+    let mut specs = Vec::new();
+    for arg in options(command) {
+        let repeat = if matches!(
+            arg.get_action(),
+            clap::ArgAction::Append | clap::ArgAction::Count
+        ) {
+            "*"
+        } else {
+            ""
+        };
+        let help = zsh_escape(&about(arg.get_help()));
+        let value = if takes_value(arg) {
+            let values = values(arg);
+            let action = if !values.is_empty() {
+                format!("({})", values.join(" "))
+            } else if is_path(arg) {
+                "_files".to_string()
+            } else {
+                " ".to_string()
+            };
+            let value_name = arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| arg.get_id().to_string());
+            format!(":{value_name}:{action}")
+        } else {
+            String::new()
+        };
+        for flag in flags(arg) {
+            let equals = if takes_value(arg) && flag.starts_with("--") {
+                "="
+            } else {
+                ""
+            };
+            specs.push(format!("'{repeat}{flag}{equals}[{help}]{value}'"));
+        }
+    }
+    for arg in positionals(command) {
+        let optional = if arg.is_required_set() { "" } else { ":" };
+        let values = values(arg);
+        let action = if !values.is_empty() {
+            format!("({})", values.join(" "))
+        } else if is_path(arg) {
+            "_files".to_string()
+        } else {
+            " ".to_string()
+        };
+        specs.push(format!("':{optional}{}:{action}'", arg.get_id()));
+    }
+    specs
+}
+
+/// Текст внутри `[...]` в одинарных кавычках
+fn zsh_escape(text: &str) -> String {
+    zsh_quote(text)
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_quote(text: &str) -> String {
+    text.replace('\'', "'\\''")
+}
+
+fn fish(command: &Command) -> String {
+    let name = command.get_name();
+    let mut script = String::new();
+    let top = "__fish_use_subcommand";
+    for arg in options(command) {
+        fish_option(&mut script, name, top, arg);
+    }
+    for sub in subcommands(command) {
+        let _ = writeln!(
+            script,
+            "complete -c {name} -n {top} -f -a {} -d '{}'",
+            sub.get_name(),
+            fish_quote(&about(sub.get_about()))
+        );
+    }
+    for sub in subcommands(command) {
+        let names: Vec<&str> = std::iter::once(sub.get_name())
+            .chain(sub.get_all_aliases())
+            .collect();
+        let condition = format!("'__fish_seen_subcommand_from {}'", names.join(" "));
+        for arg in options(sub) {
+            fish_option(&mut script, name, &condition, arg);
+        }
+        let words = words(sub, false);
+        if !words.is_empty() {
+            let _ = writeln!(
+                script,
+                "complete -c {name} -n {condition} -f -a '{}'",
+                words.join(" ")
+            );
+        }
+    }
+    script
+}
+
+fn fish_option(script: &mut String, name: &str, condition: &str, arg: &Arg) {
+    let _ = write!(script, "complete -c {name} -n {condition}");
+    if let Some(short) = arg.get_short() {
+        let _ = write!(script, " -s {short}");
+    }
+    if let Some(long) = arg.get_long() {
+        let _ = write!(script, " -l {long}");
+    }
+    if takes_value(arg) {
+        let values = values(arg);
+        if !values.is_empty() {
+            let _ = write!(script, " -x -a '{}'", values.join(" "));
+        } else if is_path(arg) {
+            script.push_str(" -r -F");
+        } else {
+            script.push_str(" -x");
+        }
+    }
+    let _ = writeln!(script, " -d '{}'", fish_quote(&about(arg.get_help())));
+}
+
+fn fish_quote(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn powershell(command: &Command) -> String {
+    let name = command.get_name();
+    let mut script = format!(
+        "using namespace System.Management.Automation\n\n\
+         Register-ArgumentCompleter -Native -CommandName '{name}' -ScriptBlock {{\n    \
+             param($wordToComplete, $commandAst, $cursorPosition)\n\n    \
+             $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})\n    \
+             $previous = if ($wordToComplete) {{ $words[-2] }} else {{ $words[-1] }}\n    \
+             $command = ''\n    \
+             foreach ($word in $words | Select-Object -Skip 1) {{\n        \
+                 if ($word -eq $wordToComplete) {{ break }}\n        \
+                 switch ($word) {{\n"
+    );
+    for sub in subcommands(command) {
+        for alias in std::iter::once(sub.get_name()).chain(sub.get_all_aliases()) {
+            let _ = writeln!(
+                script,
+                "            '{alias}' {{ $command = '{}' }}",
+                sub.get_name()
+            );
+        }
+    }
+    script.push_str(
+        "        }\n        if ($command) { break }\n    }\n\n    \
+         $values = switch (\"$command;$previous\") {\n",
+    );
+    for (prefix, command) in
+        std::iter::once(("", command)).chain(subcommands(command).map(|sub| (sub.get_name(), sub)))
+    {
+        for arg in options(command) {
+            let values = values(arg);
+            if values.is_empty() {
+                continue;
+            }
+            let values: Vec<String> = values.iter().map(|value| format!("'{value}'")).collect();
+            for flag in flags(arg) {
+                let _ = writeln!(
+                    script,
+                    "        '{prefix};{flag}' {{ {} }}",
+                    values.join(", ")
+                );
+            }
+        }
+    }
+    script.push_str(
+        "    }\n    \
+         if ($values) {\n        \
+             $values | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n            \
+                 [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)\n        \
+             }\n        \
+             return\n    \
+         }\n\n    \
+         $completions = switch ($command) {\n",
+    );
+    for (pattern, command, top) in std::iter::once(("", command, true))
+        .chain(subcommands(command).map(|sub| (sub.get_name(), sub, false)))
+    {
+        let _ = writeln!(script, "        '{pattern}' {{");
+        if top {
+            for sub in subcommands(command) {
+                powershell_result(
+                    &mut script,
+                    sub.get_name(),
+                    "ParameterValue",
+                    &about(sub.get_about()),
+                );
+            }
+        }
+        for value in positionals(command).flat_map(values) {
+            powershell_result(&mut script, &value, "ParameterValue", "");
+        }
+        for arg in options(command) {
+            let help = about(arg.get_help());
+            for flag in flags(arg) {
+                powershell_result(&mut script, &flag, "ParameterName", &help);
+            }
+        }
+        script.push_str("        }\n");
+    }
+    script.push_str(
+        "    }\n    \
+         $completions | Where-Object { $_.CompletionText -like \"$wordToComplete*\" }\n\
+         }\n",
+    );
+    script
+}
+
+fn powershell_result(script: &mut String, text: &str, kind: &str, help: &str) {
+    // Пустая подсказка в CompletionResult недопустима
+    let help = if help.is_empty() { text } else { help };
+    let _ = writeln!(
+        script,
+        "            [CompletionResult]::new('{text}', '{text}', [CompletionResultType]::{kind}, '{}')",
+        help.replace('\'', "''")
+    );
+}
@@ -0,0 +1,931 @@
+//! Настройки по умолчанию из `~/.config/cyrtag-fixer/config.toml`
+//!
+//! Поддерживается подмножество TOML: пары `ключ = значение` верхнего уровня со строками,
+//! числами, `true`/`false` и однострочными массивами. Параметры командной строки
+//! всегда важнее настроек из файла.
+
+use crate::hook::Hook;
+use crate::output::OutputFormat;
+use crate::server::{MediaServer, PathMap};
+use crate::{
+    BackupArgs, BackupCompress, BackupMode, BomMode, Command, DetectArgs, FieldArgs, FilterArgs,
+    HardlinkMode, Id3Mode, Id3v1Mode, LineEndingMode, NotifyArgs, ReviewArgs, TransliterateMode,
+    WriteArgs, YoMode,
+};
+use clap::ArgMatches;
+use clap::ValueEnum;
+use clap::parser::ValueSource;
+use cyrtag_fix::Language;
+use cyrtag_fix::glob::Pattern;
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::tr_format;
+use encoding_rs::Encoding;
+use lofty::prelude::ItemKey;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Каталог настроек внутри `$XDG_CONFIG_HOME` (или `~/.config`, `%APPDATA%`)
+const CONFIG_DIR: &str = "cyrtag-fixer";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Значения по умолчанию для параметров команд; `None` — не задано в файле
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub cyr_threshold: Option<f64>,
+    pub field_thresholds: Option<Vec<(ItemKey, f64)>>,
+    pub lang: Option<Vec<Language>>,
+    pub from_encoding: Option<&'static Encoding>,
+    pub to_encoding: Option<&'static Encoding>,
+    pub dictionary: Option<PathBuf>,
+    pub no_normalize: Option<bool>,
+    pub no_backup: Option<bool>,
+    pub backup_mode: Option<BackupMode>,
+    pub backup_dir: Option<PathBuf>,
+    pub backup_compress: Option<BackupCompress>,
+    pub force_cp1251_cue: Option<bool>,
+    pub bom: Option<BomMode>,
+    pub preserve_mtime: Option<bool>,
+    pub fail_fast: Option<bool>,
+    pub hardlinks: Option<HardlinkMode>,
+    pub seed_dirs: Option<Vec<PathBuf>>,
+    pub exec: Option<Vec<Hook>>,
+    pub notify_server: Option<MediaServer>,
+    pub notify_url: Option<String>,
+    pub notify_token: Option<String>,
+    pub notify_user: Option<String>,
+    pub notify_path_map: Option<PathMap>,
+    pub mpd: Option<String>,
+    pub mpd_music_dir: Option<PathBuf>,
+    pub line_endings: Option<LineEndingMode>,
+    pub id3: Option<Id3Mode>,
+    pub id3v1: Option<Id3v1Mode>,
+    pub transliterate: Option<TransliterateMode>,
+    pub yo: Option<YoMode>,
+    pub reencode_frames: Option<bool>,
+    pub trim_tags: Option<bool>,
+    pub strip_other_tags: Option<bool>,
+    pub update_playlist_paths: Option<bool>,
+    pub threads: Option<usize>,
+    pub min_confidence: Option<u8>,
+    pub by_album: Option<bool>,
+    pub online_validate: Option<bool>,
+    pub musicbrainz_url: Option<String>,
+    pub discogs_token: Option<String>,
+    pub discogs_url: Option<String>,
+    pub normalize_album: Option<bool>,
+    pub output: Option<OutputFormat>,
+    pub exclude: Option<Vec<Pattern>>,
+    pub include: Option<Vec<Pattern>>,
+    pub extensions: Option<Vec<String>>,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: Option<bool>,
+    pub hidden: Option<bool>,
+    pub sniff: Option<bool>,
+    pub zip: Option<bool>,
+    pub zip_names: Option<bool>,
+    pub incremental: Option<bool>,
+    pub mark: Option<bool>,
+    pub fields: Option<Vec<ItemKey>>,
+    pub skip_fields: Option<Vec<ItemKey>>,
+    pub no_custom_fields: Option<bool>,
+}
+
+/// Ошибка чтения или разбора файла настроек
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    /// Номер строки, начиная с 1; `None` — ошибка чтения файла
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{} {}, {} {line}: {}",
+                tr("настроек", "in config"),
+                self.path.display(),
+                tr("строка", "line"),
+                self.message
+            ),
+            None => write!(
+                f,
+                "{} {}: {}",
+                tr("настроек", "in config"),
+                self.path.display(),
+                self.message
+            ),
+        }
+    }
+}
+
+/// Значение TOML
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => tr("строка", "string"),
+            Value::Integer(_) => tr("целое число", "integer"),
+            Value::Float(_) => tr("число", "number"),
+            Value::Bool(_) => "true/false",
+            Value::Array(_) => tr("массив", "array"),
+        }
+    }
+}
+
+impl Config {
+    /// Путь к файлу настроек по умолчанию
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(base.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// Чтение настроек: явно указанный файл обязан существовать,
+    /// а отсутствие файла по умолчанию — не ошибка
+    pub fn load(explicit: Option<&Path>) -> Result<Self, ConfigError> {
+        let Some(path) = explicit.map(Path::to_path_buf).or_else(Self::default_path) else {
+            return Ok(Self::default());
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if explicit.is_none() && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(ConfigError {
+                    path,
+                    line: None,
+                    message: e.to_string(),
+                });
+            }
+        };
+
+        Self::parse(&text).map_err(|(line, message)| ConfigError {
+            path,
+            line: Some(line),
+            message,
+        })
+    }
+
+    fn parse(text: &str) -> Result<Self, (usize, String)> {
+        let mut config = Self::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err((
+                    line_no,
+                    tr("таблицы не поддерживаются", "tables are not supported").to_string(),
+                ));
+            }
+
+            let (key, rest) = line.split_once('=').ok_or_else(|| {
+                (
+                    line_no,
+                    tr("ожидалось ключ = значение", "expected key = value").to_string(),
+                )
+            })?;
+            let key = key.trim().trim_matches('"').replace('-', "_");
+            let mut parser = ValueParser { rest: rest.trim() };
+            let value = parser.value().map_err(|e| (line_no, e))?;
+            parser.skip_ws();
+            if !parser.rest.is_empty() && !parser.rest.starts_with('#') {
+                let message = tr("лишние символы", "trailing characters");
+                return Err((line_no, format!("{message}: {}", parser.rest)));
+            }
+
+            config.set(&key, value).map_err(|e| (line_no, e))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let mismatch = |expected: &str, value: &Value| {
+            tr_format!(
+                "{key}: ожидалось {expected}, получено {}",
+                "{key}: expected {expected}, got {}",
+                value.type_name()
+            )
+        };
+
+        match key {
+            "cyr_threshold" => {
+                self.cyr_threshold = Some(match value {
+                    Value::Float(v) => v,
+                    Value::Integer(v) => v as f64,
+                    other => return Err(mismatch(tr("число", "number"), &other)),
+                })
+            }
+            "threads" | "max_depth" => match value {
+                Value::Integer(v) if v >= 0 => match key {
+                    "threads" => self.threads = Some(v as usize),
+                    _ => self.max_depth = Some(v as usize),
+                },
+                other => {
+                    return Err(mismatch(
+                        tr("неотрицательное целое число", "a non-negative integer"),
+                        &other,
+                    ));
+                }
+            },
+            "min_confidence" => match value {
+                Value::Integer(v @ 0..=100) => self.min_confidence = Some(v as u8),
+                other => {
+                    return Err(mismatch(
+                        tr("целое число от 0 до 100", "an integer from 0 to 100"),
+                        &other,
+                    ));
+                }
+            },
+            "no_backup"
+            | "force_cp1251_cue"
+            | "update_playlist_paths"
+            | "preserve_mtime"
+            | "fail_fast"
+            | "follow_symlinks"
+            | "hidden"
+            | "sniff"
+            | "zip"
+            | "zip_names"
+            | "reencode_frames"
+            | "trim_tags"
+            | "strip_other_tags"
+            | "no_custom_fields"
+            | "by_album"
+            | "normalize_album"
+            | "online_validate"
+            | "no_normalize"
+            | "incremental"
+            | "mark" => {
+                let Value::Bool(v) = value else {
+                    return Err(mismatch("true/false", &value));
+                };
+                match key {
+                    "no_backup" => self.no_backup = Some(v),
+                    "force_cp1251_cue" => self.force_cp1251_cue = Some(v),
+                    "preserve_mtime" => self.preserve_mtime = Some(v),
+                    "fail_fast" => self.fail_fast = Some(v),
+                    "follow_symlinks" => self.follow_symlinks = Some(v),
+                    "hidden" => self.hidden = Some(v),
+                    "sniff" => self.sniff = Some(v),
+                    "zip" => self.zip = Some(v),
+                    "zip_names" => self.zip_names = Some(v),
+                    "reencode_frames" => self.reencode_frames = Some(v),
+                    "trim_tags" => self.trim_tags = Some(v),
+                    "strip_other_tags" => self.strip_other_tags = Some(v),
+                    "no_custom_fields" => self.no_custom_fields = Some(v),
+                    "by_album" => self.by_album = Some(v),
+                    "normalize_album" => self.normalize_album = Some(v),
+                    "online_validate" => self.online_validate = Some(v),
+                    "incremental" => self.incremental = Some(v),
+                    "mark" => self.mark = Some(v),
+                    "no_normalize" => self.no_normalize = Some(v),
+                    _ => self.update_playlist_paths = Some(v),
+                }
+            }
+            "backup_dir" => match value {
+                Value::String(v) => self.backup_dir = Some(expand_home(&v)),
+                other => return Err(mismatch(tr("строка", "string"), &other)),
+            },
+            "exclude" => self.exclude = Some(patterns(key, value)?),
+            "include" => self.include = Some(patterns(key, value)?),
+            "extensions" => {
+                self.extensions = Some(
+                    strings(key, value)?
+                        .iter()
+                        .map(|ext| crate::parse_extension(ext).map_err(|e| format!("{key}: {e}")))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "lang" => {
+                self.lang = Some(
+                    strings(key, value)?
+                        .iter()
+                        .map(|code| crate::parse_language(code).map_err(|e| format!("{key}: {e}")))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "from_encoding" | "to_encoding" => {
+                let Value::String(name) = value else {
+                    return Err(mismatch(tr("строка", "string"), &value));
+                };
+                let encoding = crate::parse_encoding(&name).map_err(|e| format!("{key}: {e}"))?;
+                match key {
+                    "from_encoding" => self.from_encoding = Some(encoding),
+                    _ => self.to_encoding = Some(encoding),
+                }
+            }
+            "field_thresholds" => {
+                self.field_thresholds = Some(
+                    strings(key, value)?
+                        .iter()
+                        .map(|v| crate::parse_field_threshold(v).map_err(|e| format!("{key}: {e}")))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "fields" => self.fields = Some(field_keys(key, value)?),
+            "skip_fields" => self.skip_fields = Some(field_keys(key, value)?),
+            "backup_mode" => self.backup_mode = Some(enum_value(key, value)?),
+            "backup_compress" => self.backup_compress = Some(enum_value(key, value)?),
+            "bom" => self.bom = Some(enum_value(key, value)?),
+            "line_endings" => self.line_endings = Some(enum_value(key, value)?),
+            "id3" => self.id3 = Some(enum_value(key, value)?),
+            "id3v1" => self.id3v1 = Some(enum_value(key, value)?),
+            "transliterate" => self.transliterate = Some(enum_value(key, value)?),
+            "yo" => self.yo = Some(enum_value(key, value)?),
+            "hardlinks" => self.hardlinks = Some(enum_value(key, value)?),
+            "seed_dirs" => {
+                self.seed_dirs = Some(
+                    strings(key, value)?
+                        .iter()
+                        .map(|d| expand_home(d))
+                        .collect(),
+                )
+            }
+            "exec" => {
+                self.exec = Some(
+                    strings(key, value)?
+                        .iter()
+                        .map(|cmd| cmd.parse().map_err(|e| format!("{key}: {e}")))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "output" => self.output = Some(enum_value(key, value)?),
+            "notify_server" => self.notify_server = Some(enum_value(key, value)?),
+            "notify_url" | "notify_token" | "notify_user" | "musicbrainz_url" | "discogs_token"
+            | "discogs_url" => {
+                let Value::String(v) = value else {
+                    return Err(mismatch(tr("строка", "string"), &value));
+                };
+                match key {
+                    "notify_url" => self.notify_url = Some(v),
+                    "notify_token" => self.notify_token = Some(v),
+                    "musicbrainz_url" => self.musicbrainz_url = Some(v),
+                    "discogs_token" => self.discogs_token = Some(v),
+                    "discogs_url" => self.discogs_url = Some(v),
+                    _ => self.notify_user = Some(v),
+                }
+            }
+            "mpd" => match value {
+                Value::String(v) => self.mpd = Some(v),
+                // mpd = true — адрес из MPD_HOST и MPD_PORT
+                Value::Bool(v) => self.mpd = v.then(String::new),
+                other => {
+                    return Err(mismatch(
+                        tr("строка или true/false", "a string or true/false"),
+                        &other,
+                    ));
+                }
+            },
+            "mpd_music_dir" => match value {
+                Value::String(v) => self.mpd_music_dir = Some(expand_home(&v)),
+                other => return Err(mismatch(tr("строка", "string"), &other)),
+            },
+            "dictionary" => match value {
+                Value::String(v) => self.dictionary = Some(expand_home(&v)),
+                other => return Err(mismatch(tr("строка", "string"), &other)),
+            },
+            "notify_path_map" => {
+                let Value::String(v) = value else {
+                    return Err(mismatch(tr("строка", "string"), &value));
+                };
+                let v = match v.split_once('=') {
+                    Some((local, remote)) => {
+                        format!("{}={remote}", expand_home(local).display())
+                    }
+                    None => v,
+                };
+                self.notify_path_map = Some(v.parse().map_err(|e| format!("{key}: {e}"))?);
+            }
+            _ => {
+                return Err(format!(
+                    "{} {key}",
+                    tr("неизвестный параметр", "unknown key")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Подстановка настроек в команду там, где параметр не указан в командной строке
+    pub fn apply(&self, command: &mut Command, matches: &ArgMatches) {
+        let Some((_, sub)) = matches.subcommand() else {
+            return;
+        };
+        let unset = |id: &str| sub.value_source(id) != Some(ValueSource::CommandLine);
+
+        match command {
+            Command::Fix(args) => {
+                self.apply_backup(&mut args.backup, sub);
+                self.apply_write(&mut args.write, sub);
+                self.apply_notify(&mut args.notify, sub);
+                if unset("force_cp1251_cue") {
+                    set(&mut args.text.force_cp1251_cue, self.force_cp1251_cue);
+                }
+                if unset("update_playlist_paths") {
+                    set(
+                        &mut args.text.update_playlist_paths,
+                        self.update_playlist_paths,
+                    );
+                }
+                self.apply_detect(&mut args.detect, sub);
+                self.apply_fields(&mut args.fields, sub);
+                self.apply_review(&mut args.review, sub);
+                if unset("normalize_album") {
+                    set(&mut args.normalize_album, self.normalize_album);
+                }
+                self.apply_filter(&mut args.filter, sub);
+                if unset("incremental") {
+                    set(&mut args.incremental.incremental, self.incremental);
+                }
+                if unset("mark") {
+                    set(&mut args.incremental.mark, self.mark);
+                }
+                // Интерактивный режим работает в один поток и только с обычным выводом
+                if !args.interactive {
+                    if unset("threads") {
+                        set(&mut args.threads.threads, self.threads);
+                    }
+                    if unset("output") {
+                        set(&mut args.output.output, self.output);
+                    }
+                }
+            }
+            Command::Scan(args) => {
+                if unset("force_cp1251_cue") {
+                    set(&mut args.text.force_cp1251_cue, self.force_cp1251_cue);
+                }
+                if unset("update_playlist_paths") {
+                    set(
+                        &mut args.text.update_playlist_paths,
+                        self.update_playlist_paths,
+                    );
+                }
+                self.apply_detect(&mut args.detect, sub);
+                self.apply_fields(&mut args.fields, sub);
+                self.apply_review(&mut args.review, sub);
+                self.apply_filter(&mut args.filter, sub);
+                if unset("incremental") {
+                    set(&mut args.incremental.incremental, self.incremental);
+                }
+                if unset("mark") {
+                    set(&mut args.incremental.mark, self.mark);
+                }
+                if unset("threads") {
+                    set(&mut args.threads.threads, self.threads);
+                }
+                if unset("output") {
+                    set(&mut args.output.output, self.output);
+                }
+            }
+            Command::Apply(args) => {
+                self.apply_backup(&mut args.backup, sub);
+                self.apply_write(&mut args.write, sub);
+                self.apply_notify(&mut args.notify, sub);
+                if unset("output") {
+                    set(&mut args.output.output, self.output);
+                }
+            }
+            Command::Restore(args) => {
+                if unset("backup_dir") && self.backup_dir.is_some() {
+                    args.backup_dir = self.backup_dir.clone();
+                }
+            }
+            Command::Rename(args) => {
+                self.apply_detect(&mut args.detect, sub);
+                self.apply_filter(&mut args.filter, sub);
+            }
+            Command::FixString(args) => self.apply_detect(&mut args.detect, sub),
+            Command::Undo(_) | Command::CleanBackups(_) | Command::Completions(_) => {}
+        }
+    }
+}
+
+impl Config {
+    fn apply_backup(&self, backup: &mut BackupArgs, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        // Явный способ бэкапа в командной строке важнее no_backup из файла
+        if unset("no_backup")
+            && unset("backup_mode")
+            && unset("backup_dir")
+            && unset("backup_compress")
+        {
+            set(&mut backup.no_backup, self.no_backup);
+        }
+        if !backup.no_backup {
+            if unset("backup_mode") {
+                set(&mut backup.backup_mode, self.backup_mode);
+            }
+            if unset("backup_dir") && self.backup_dir.is_some() {
+                backup.backup_dir = self.backup_dir.clone();
+            }
+            if unset("backup_compress") && self.backup_compress.is_some() {
+                backup.backup_compress = self.backup_compress;
+            }
+        }
+    }
+
+    fn apply_write(&self, write: &mut WriteArgs, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        if unset("bom") {
+            set(&mut write.bom, self.bom);
+        }
+        if unset("line_endings") {
+            set(&mut write.line_endings, self.line_endings);
+        }
+        if unset("id3") {
+            set(&mut write.id3, self.id3);
+        }
+        if unset("id3v1") {
+            set(&mut write.id3v1, self.id3v1);
+        }
+        if unset("yo") {
+            set(&mut write.yo, self.yo);
+        }
+        if unset("transliterate") && self.transliterate.is_some() {
+            write.transliterate = self.transliterate;
+        }
+        if unset("reencode_frames") {
+            set(&mut write.reencode_frames, self.reencode_frames);
+        }
+        if unset("trim_tags") {
+            set(&mut write.trim_tags, self.trim_tags);
+        }
+        if unset("strip_other_tags") {
+            set(&mut write.strip_other_tags, self.strip_other_tags);
+        }
+        if unset("preserve_mtime") {
+            set(&mut write.preserve_mtime, self.preserve_mtime);
+        }
+        if unset("fail_fast") {
+            set(&mut write.fail_fast, self.fail_fast);
+        }
+        if unset("hardlinks") {
+            set(&mut write.hardlinks, self.hardlinks);
+        }
+        if unset("seed_dir")
+            && let Some(dirs) = &self.seed_dirs
+        {
+            write.seed_dir = dirs.clone();
+        }
+        if unset("exec")
+            && let Some(exec) = &self.exec
+        {
+            write.exec = exec.clone();
+        }
+    }
+
+    fn apply_notify(&self, notify: &mut NotifyArgs, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        if unset("notify_server") && self.notify_server.is_some() {
+            notify.notify_server = self.notify_server;
+        }
+        if unset("notify_url") && self.notify_url.is_some() {
+            notify.notify_url = self.notify_url.clone();
+        }
+        if unset("notify_token") && self.notify_token.is_some() {
+            notify.notify_token = self.notify_token.clone();
+        }
+        if unset("notify_user") && self.notify_user.is_some() {
+            notify.notify_user = self.notify_user.clone();
+        }
+        if unset("notify_path_map") && self.notify_path_map.is_some() {
+            notify.notify_path_map = self.notify_path_map.clone();
+        }
+        if unset("mpd") && self.mpd.is_some() {
+            notify.mpd = self.mpd.clone();
+        }
+        if unset("mpd_music_dir") && self.mpd_music_dir.is_some() {
+            notify.mpd_music_dir = self.mpd_music_dir.clone();
+        }
+    }
+
+    fn apply_detect(&self, detect: &mut DetectArgs, matches: &ArgMatches) {
+        if matches.value_source("cyr_threshold") != Some(ValueSource::CommandLine) {
+            set(&mut detect.cyr_threshold, self.cyr_threshold);
+        }
+        if matches.value_source("field_threshold") != Some(ValueSource::CommandLine)
+            && let Some(thresholds) = &self.field_thresholds
+        {
+            detect.field_threshold = thresholds.clone();
+        }
+        if matches.value_source("lang") != Some(ValueSource::CommandLine)
+            && let Some(lang) = &self.lang
+        {
+            detect.lang = lang.clone();
+        }
+        if matches.value_source("from_encoding") != Some(ValueSource::CommandLine)
+            && self.from_encoding.is_some()
+        {
+            detect.from_encoding = self.from_encoding;
+        }
+        if matches.value_source("to_encoding") != Some(ValueSource::CommandLine)
+            && self.to_encoding.is_some()
+        {
+            detect.to_encoding = self.to_encoding;
+        }
+        if matches.value_source("dictionary") != Some(ValueSource::CommandLine)
+            && self.dictionary.is_some()
+        {
+            detect.dictionary = self.dictionary.clone();
+        }
+        if matches.value_source("no_normalize") != Some(ValueSource::CommandLine) {
+            set(&mut detect.no_normalize, self.no_normalize);
+        }
+    }
+
+    fn apply_fields(&self, fields: &mut FieldArgs, matches: &ArgMatches) {
+        if matches.value_source("fields") != Some(ValueSource::CommandLine)
+            && let Some(only) = &self.fields
+        {
+            fields.fields = only.clone();
+        }
+        if matches.value_source("skip_fields") != Some(ValueSource::CommandLine)
+            && let Some(skip) = &self.skip_fields
+        {
+            fields.skip_fields = skip.clone();
+        }
+        if matches.value_source("no_custom_fields") != Some(ValueSource::CommandLine) {
+            set(&mut fields.no_custom_fields, self.no_custom_fields);
+        }
+    }
+
+    fn apply_review(&self, review: &mut ReviewArgs, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        if unset("min_confidence") {
+            set(&mut review.min_confidence, self.min_confidence);
+        }
+        if unset("by_album") {
+            set(&mut review.by_album, self.by_album);
+        }
+        if unset("online_validate") {
+            set(&mut review.online_validate, self.online_validate);
+        }
+        if unset("musicbrainz_url") && self.musicbrainz_url.is_some() {
+            review.musicbrainz_url = self.musicbrainz_url.clone();
+        }
+        if unset("discogs_token") && self.discogs_token.is_some() {
+            review.discogs_token = self.discogs_token.clone();
+        }
+        if unset("discogs_url") && self.discogs_url.is_some() {
+            review.discogs_url = self.discogs_url.clone();
+        }
+    }
+
+    fn apply_filter(&self, filter: &mut FilterArgs, matches: &ArgMatches) {
+        if matches.value_source("exclude") != Some(ValueSource::CommandLine)
+            && let Some(exclude) = &self.exclude
+        {
+            filter.exclude = exclude.clone();
+        }
+        if matches.value_source("include") != Some(ValueSource::CommandLine)
+            && let Some(include) = &self.include
+        {
+            filter.include = include.clone();
+        }
+        if matches.value_source("extensions") != Some(ValueSource::CommandLine)
+            && let Some(extensions) = &self.extensions
+        {
+            filter.extensions = extensions.clone();
+        }
+        if matches.value_source("max_depth") != Some(ValueSource::CommandLine)
+            && let Some(max_depth) = self.max_depth
+        {
+            filter.max_depth = Some(max_depth);
+        }
+        if matches.value_source("follow_symlinks") != Some(ValueSource::CommandLine) {
+            set(&mut filter.follow_symlinks, self.follow_symlinks);
+        }
+        if matches.value_source("hidden") != Some(ValueSource::CommandLine) {
+            set(&mut filter.hidden, self.hidden);
+        }
+        if matches.value_source("sniff") != Some(ValueSource::CommandLine) {
+            set(&mut filter.sniff, self.sniff);
+        }
+        if matches.value_source("zip") != Some(ValueSource::CommandLine) {
+            set(&mut filter.zip, self.zip);
+        }
+        if matches.value_source("zip_names") != Some(ValueSource::CommandLine) {
+            set(&mut filter.zip_names, self.zip_names);
+        }
+    }
+}
+
+fn set<T: Copy>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+fn enum_value<T: ValueEnum>(key: &str, value: Value) -> Result<T, String> {
+    let Value::String(name) = value else {
+        return Err(tr_format!(
+            "{key}: ожидалась строка, получено {}",
+            "{key}: expected a string, got {}",
+            value.type_name()
+        ));
+    };
+    T::from_str(&name, true).map_err(|_| {
+        let possible: Vec<_> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        tr_format!(
+            "{key}: неизвестное значение {name:?}, допустимы: {}",
+            "{key}: unknown value {name:?}, possible values: {}",
+            possible.join(", ")
+        )
+    })
+}
+
+/// Массив строк с glob-шаблонами
+fn patterns(key: &str, value: Value) -> Result<Vec<Pattern>, String> {
+    strings(key, value)?
+        .iter()
+        .map(|s| Pattern::new(s).map_err(|e| format!("{key}: {e}")))
+        .collect()
+}
+
+/// Массив имён полей тегов
+fn field_keys(key: &str, value: Value) -> Result<Vec<ItemKey>, String> {
+    strings(key, value)?
+        .iter()
+        .map(|name| crate::parse_field(name).map_err(|e| format!("{key}: {e}")))
+        .collect()
+}
+
+fn strings(key: &str, value: Value) -> Result<Vec<String>, String> {
+    let Value::Array(items) = value else {
+        return Err(tr_format!(
+            "{key}: ожидался массив строк, получено {}",
+            "{key}: expected an array of strings, got {}",
+            value.type_name()
+        ));
+    };
+    items
+        .into_iter()
+        .map(|item| match item {
+            Value::String(s) => Ok(s),
+            other => Err(tr_format!(
+                "{key}: ожидался массив строк, в нём {}",
+                "{key}: expected an array of strings, it contains {}",
+                other.type_name()
+            )),
+        })
+        .collect()
+}
+
+/// `~/путь` -> `$HOME/путь`
+fn expand_home(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/")
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        return Path::new(&home).join(rest);
+    }
+    PathBuf::from(path)
+}
+
+/// Разбор одного значения TOML из оставшейся части строки
+struct ValueParser<'a> {
+    rest: &'a str,
+}
+
+impl ValueParser<'_> {
+    fn skip_ws(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        match self.rest.chars().next() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some(_) => self.scalar(),
+            None => Err(tr("пропущено значение", "missing value").to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        let mut chars = self.rest[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 2..];
+                    return Ok(out);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some(u @ ('u' | 'U')) => {
+                            let len = if u == 'u' { 4 } else { 8 };
+                            let hex: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| {
+                                    let message = tr(
+                                        "неверная escape-последовательность",
+                                        "invalid escape sequence",
+                                    );
+                                    format!("{message} \\{u}{hex}")
+                                })?
+                        }
+                        other => {
+                            return Err(format!(
+                                "{} \\{}",
+                                tr(
+                                    "неверная escape-последовательность",
+                                    "invalid escape sequence"
+                                ),
+                                other.unwrap_or(' ')
+                            ));
+                        }
+                    };
+                    out.push(escaped);
+                }
+                c => out.push(c),
+            }
+        }
+        Err(tr("незакрытая строка", "unterminated string").to_string())
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        let end = self.rest[1..]
+            .find('\'')
+            .ok_or_else(|| tr("незакрытая строка", "unterminated string").to_string())?;
+        let out = self.rest[1..=end].to_string();
+        self.rest = &self.rest[end + 2..];
+        Ok(out)
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.rest = &self.rest[1..];
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            if let Some(rest) = self.rest.strip_prefix(']') {
+                self.rest = rest;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_ws();
+            if let Some(rest) = self.rest.strip_prefix(',') {
+                self.rest = rest;
+            } else if !self.rest.starts_with(']') {
+                return Err(
+                    tr("ожидалась , или ] в массиве", "expected , or ] in array").to_string(),
+                );
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Value, String> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '#'))
+            .unwrap_or(self.rest.len());
+        let token = &self.rest[..end];
+        self.rest = &self.rest[end..];
+
+        let number = token.replace('_', "");
+        if let Ok(v) = token.parse() {
+            Ok(Value::Bool(v))
+        } else if let Ok(v) = number.parse() {
+            Ok(Value::Integer(v))
+        } else if let Ok(v) = number.parse() {
+            Ok(Value::Float(v))
+        } else {
+            Err(format!(
+                "{} {token}",
+                tr("неизвестное значение", "unknown value")
+            ))
+        }
+    }
+}
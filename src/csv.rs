@@ -0,0 +1,70 @@
+//! Выгрузка исправлений в CSV (--csv) для просмотра или импорта в Mp3tag и foobar2000:
+//! UTF-8 с BOM, разделитель `;`, как в текстовых выгрузках Mp3tag
+
+use crate::output::{FileReport, ReportFix, Status};
+use cyrtag_fix::fields::{asf_item_key, mp3tag_name, parse_field};
+use std::fs;
+use std::path::Path;
+
+const HEADER: [&str; 5] = ["Path", "Field", "Old", "New", "Encoding"];
+
+/// Сохранить применённые (или найденные в пробном запуске) исправления в `path`.
+/// Отложенные для проверки исправления и пропущенные файлы в выгрузку не попадают
+pub fn save(path: &Path, reports: &[FileReport]) -> cyrtag_fix::Result<()> {
+    let mut csv = String::from("\u{feff}");
+    push_row(&mut csv, HEADER.map(String::from));
+    for report in reports {
+        if !matches!(report.status, Status::Fixed | Status::WouldFix) {
+            continue;
+        }
+        for fix in report.fixes.iter().filter(|fix| !fix.review) {
+            push_row(
+                &mut csv,
+                [
+                    match &fix.member {
+                        Some(member) => report.path.join(member).display().to_string(),
+                        None => report.path.display().to_string(),
+                    },
+                    field_name(fix),
+                    fix.before.trim().to_string(),
+                    fix.after.trim().to_string(),
+                    fix.encoding.clone().unwrap_or_default(),
+                ],
+            );
+        }
+    }
+    fs::write(path, csv).map_err(|source| cyrtag_fix::Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Имя поля в терминах Mp3tag (`TITLE`, `ALBUMARTIST`); если соответствия нет —
+/// родной ключ тега, у строк текстовых файлов — `line N`
+fn field_name(fix: &ReportFix) -> String {
+    let key = match fix.tag.as_deref() {
+        None => None,
+        Some("asf") => Some(asf_item_key(&fix.field)),
+        Some(_) => parse_field(&fix.field),
+    };
+    key.as_ref()
+        .and_then(mp3tag_name)
+        .map(str::to_string)
+        .or_else(|| fix.key.clone())
+        .unwrap_or_else(|| fix.field.clone())
+}
+
+fn push_row(csv: &mut String, row: [String; 5]) {
+    let row: Vec<String> = row.iter().map(|value| quote(value)).collect();
+    csv.push_str(&row.join(";"));
+    csv.push_str("\r\n");
+}
+
+/// Значение в кавычках, если в нём есть разделитель, кавычки или перевод строки
+fn quote(value: &str) -> String {
+    if value.contains([';', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,774 @@
+//! Определение кракозябр и восстановление исходного текста
+
+use crate::dictionary::Dictionary;
+use crate::lang::Languages;
+use crate::locale::tr;
+use crate::normalize::normalize;
+use encoding_rs::{Encoding, IBM866, KOI8_R, UTF_8, WINDOWS_1251, WINDOWS_1252};
+use lofty::prelude::ItemKey;
+use phf::{Set, phf_set};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Типографские знаки препинания: как и ASCII, не считаются в длине строки при оценке
+static PUNCTUATION: Set<char> = phf_set! {'–', '—', '«', '»', '„', '“', '”', '‘', '’', '…'};
+
+static LATIN_DIACRITICS: Set<char> = phf_set! {
+'ä', 'ö', 'ü', 'ß', 'Ä', 'Ö', 'Ü', 'é', 'è', 'ê', 'ë', 'á', 'à', 'â', 'å', 'í', 'ì', 'î', 'ó',
+'ò', 'ô', 'ú', 'ù', 'û'};
+
+const WEIGHT_CYR: f64 = 1.0;
+const WEIGHT_DIACRITICS: f64 = 0.8;
+const WEIGHT_PSEUDOGRAPHICS: f64 = 0.5;
+/// Какую часть оценки может отнять непохожесть на слова языка
+const WEIGHT_WORDS: f64 = 0.5;
+/// Штраф за символы вне ASCII, которые не буквы и не типографские знаки (`√`, `†`,
+/// неразрывный пробел), при выборе кодировки файла
+const WEIGHT_ODD_SYMBOLS: f64 = 1.0;
+/// На сколько оценка кодировки должна превысить оценку первой подходящей из списка, чтобы
+/// победить её: близкие кодировки (cp1251 и x-mac-cyrillic) расходятся лишь в нескольких
+/// буквах, и случайный перевес не должен портить файл
+const ENCODING_MARGIN: f64 = 0.1;
+/// Похожесть на слова языка, начиная с которой текст считается вполне правдоподобным:
+/// в названиях и именах редкие сочетания букв всё же встречаются
+const TYPICAL_WORD_LIKENESS: f64 = 0.75;
+/// Сколько символов не из ASCII должно быть в строке: по одной букве («ß» → «Я»,
+/// «ñ» → «с») не понять, кракозябра это или обычная буква латиницы
+const MIN_MOJIBAKE_CHARS: usize = 2;
+
+/// Порог определения кириллицы по умолчанию
+pub const DEFAULT_CYR_THRESHOLD: f64 = 0.2;
+/// Насколько ниже порога может быть оценка варианта, чтобы он считался почти прошедшим,
+/// см. [`Trace::near_miss`]
+pub const NEAR_MISS_MARGIN: f64 = 0.1;
+
+/// Способ появления кракозябр: текст в кодировке `source` был прочитан как `misread`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mojibake {
+    pub source: &'static Encoding,
+    pub misread: &'static Encoding,
+}
+
+impl Mojibake {
+    pub const fn new(source: &'static Encoding, misread: &'static Encoding) -> Self {
+        Self { source, misread }
+    }
+
+    /// Обратное преобразование: кодируем как `misread`, декодируем как `source`
+    pub fn recover(&self, text: &str) -> Option<String> {
+        let (bytes, _, unmappable) = self.misread.encode(text);
+        if unmappable {
+            return None;
+        }
+        let (decoded, had_errors) = self.source.decode_without_bom_handling(&bytes);
+        if had_errors {
+            return None;
+        }
+        Some(decoded.into_owned())
+    }
+}
+
+impl fmt::Display for Mojibake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            encoding_name(self.source),
+            tr("как", "as"),
+            encoding_name(self.misread)
+        )
+    }
+}
+
+/// Короткое имя кодировки в том виде, как его обычно пишут пользователи
+pub fn encoding_name(encoding: &'static Encoding) -> String {
+    match encoding.name() {
+        "IBM866" => "cp866".to_string(),
+        name if name.starts_with("windows-") => format!("cp{}", &name["windows-".len()..]),
+        name => name.to_lowercase(),
+    }
+}
+
+/// Проверяемые варианты в порядке приоритета: при равной оценке побеждает первый
+pub const CANDIDATES: &[Mojibake] = &[
+    Mojibake::new(WINDOWS_1251, WINDOWS_1252),
+    Mojibake::new(KOI8_R, WINDOWS_1252),
+    Mojibake::new(KOI8_R, WINDOWS_1251),
+    Mojibake::new(IBM866, WINDOWS_1252),
+    Mojibake::new(IBM866, WINDOWS_1251),
+    Mojibake::new(WINDOWS_1251, IBM866),
+    // Дважды закодированный UTF-8: "Ð›ÑŒÐ²Ð¸Ñ†Ð°" / "Р›СЊРІРёС†Р°" -> "Львица"
+    Mojibake::new(UTF_8, WINDOWS_1252),
+    Mojibake::new(UTF_8, WINDOWS_1251),
+];
+
+/// Наибольшее число неверных перекодировок подряд, которое ищет [`Detector`]
+pub const MAX_CHAIN: usize = 3;
+
+/// Результат определения: восстановленный текст и победивший вариант
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub text: String,
+    /// Итог цепочки: исходная кодировка текста и та, в которой он виден сейчас
+    pub mojibake: Mojibake,
+    /// Шаги восстановления по порядку; у однократной ошибки — один шаг
+    pub chain: Vec<Mojibake>,
+    pub score: f64,
+    /// Уверенность в исправлении, 0–100: насколько результат похож на русский текст
+    /// (доля кириллицы среди букв с учётом правдоподобности регистра)
+    pub confidence: u8,
+}
+
+/// Разбор одного решения детектора: все проверенные варианты с оценками
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub text: String,
+    /// Оценка исходного текста: вариант должен превзойти и её, и порог
+    pub original_score: f64,
+    pub threshold: f64,
+    /// Проверенные варианты по убыванию оценки; при равной — в порядке перебора
+    pub candidates: Vec<TraceCandidate>,
+    /// Выбранное исправление, если оно есть
+    pub fixed: Option<String>,
+}
+
+impl Trace {
+    /// Лучший вариант, если строка не исправлена только из-за порога: вариант лучше
+    /// исходного текста, но его оценка не выше порога и ниже него не больше чем на
+    /// [`NEAR_MISS_MARGIN`]
+    pub fn near_miss(&self) -> Option<&TraceCandidate> {
+        if self.fixed.is_some() {
+            return None;
+        }
+        self.candidates.first().filter(|best| {
+            best.score > self.original_score
+                && best.score <= self.threshold
+                && best.score > self.threshold - NEAR_MISS_MARGIN
+        })
+    }
+}
+
+/// Вариант восстановления в [`Trace`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceCandidate {
+    pub chain: Vec<Mojibake>,
+    pub text: String,
+    pub score: f64,
+}
+
+thread_local! {
+    /// Разборы решений, собираемые [`traced`] в текущем потоке
+    static TRACES: RefCell<Option<Vec<Trace>>> = const { RefCell::new(None) };
+    /// Значения, которые нельзя восстановить, собираемые [`unrecoverable`] в текущем потоке
+    static UNRECOVERABLE: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Выполнить `f`, собрав разборы всех решений детектора в этом потоке — например,
+/// чтобы показать, почему поле не было исправлено.
+/// Записываются только строки, для которых нашёлся хотя бы один вариант восстановления
+pub fn traced<R>(f: impl FnOnce() -> R) -> (R, Vec<Trace>) {
+    let previous = TRACES.replace(Some(Vec::new()));
+    let result = f();
+    let traces = TRACES.replace(previous).unwrap_or_default();
+    (result, traces)
+}
+
+/// Выполнить `f`, собрав все проверенные детектором в этом потоке значения, исходные байты
+/// которых уже потеряны, см. [`is_unrecoverable`]
+pub fn unrecoverable<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    let previous = UNRECOVERABLE.replace(Some(Vec::new()));
+    let result = f();
+    let values = UNRECOVERABLE.replace(previous).unwrap_or_default();
+    (result, values)
+}
+
+/// Текст, который уже не восстановить: программа, записавшая тег, заменила непереводимые
+/// символы на U+FFFD или на `?` (`???? - ?????`). `?` в конце слова (`Кто ты?`) — знак
+/// препинания, поэтому считаются только остальные, и их должно быть хотя бы три
+pub fn is_unrecoverable(text: &str) -> bool {
+    if text.contains('\u{FFFD}') {
+        return true;
+    }
+    let lost: usize = text
+        .split_whitespace()
+        .map(|word| {
+            let body = word.trim_end_matches('?');
+            let trailing = word.len() - body.len();
+            // Вопросы после буквы или цифры в конце слова — препинание
+            let punctuation = body.chars().last().is_some_and(char::is_alphanumeric);
+            body.matches('?').count() + if punctuation { 0 } else { trailing }
+        })
+        .sum();
+    lost >= 3
+}
+
+/// Детектор кракозябр с настраиваемым порогом
+#[derive(Debug, Clone)]
+pub struct Detector {
+    pub cyr_threshold: f64,
+    /// Языки, на которых ожидается восстановленный текст
+    pub languages: Languages,
+    /// Проверять только этот вариант вместо [`CANDIDATES`]
+    pub pair: Option<Mojibake>,
+    /// Замены пользователя, которые важнее автоматического определения
+    pub dictionary: Option<Arc<Dictionary>>,
+    /// Свои пороги для отдельных полей тегов, см. [`Detector::for_field`]
+    pub field_thresholds: Vec<(ItemKey, f64)>,
+    /// Очищать исправленный текст, см. [`crate::normalize`]
+    pub normalize: bool,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::new(DEFAULT_CYR_THRESHOLD)
+    }
+}
+
+impl Detector {
+    pub fn new(cyr_threshold: f64) -> Self {
+        Self {
+            cyr_threshold,
+            languages: Languages::default(),
+            pair: None,
+            dictionary: None,
+            field_thresholds: Vec::new(),
+            normalize: true,
+        }
+    }
+
+    /// Искать только кракозябры, получившиеся из `pair` (cp1253 как cp1252 и т.п.)
+    pub fn with_pair(mut self, pair: Mojibake) -> Self {
+        self.pair = Some(pair);
+        self
+    }
+
+    /// Проверяемые варианты
+    pub fn candidates(&self) -> &[Mojibake] {
+        match &self.pair {
+            Some(pair) => std::slice::from_ref(pair),
+            None => CANDIDATES,
+        }
+    }
+
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// Сначала искать значение в словаре замен, см. [`Dictionary`]
+    pub fn with_dictionary(mut self, dictionary: Dictionary) -> Self {
+        self.dictionary = Some(Arc::new(dictionary));
+        self
+    }
+
+    /// Свои пороги для полей тегов: короткому исполнителю («ДДТ») может подойти один,
+    /// длинным названиям вперемешку с английским — другой
+    pub fn with_field_thresholds(mut self, thresholds: Vec<(ItemKey, f64)>) -> Self {
+        self.field_thresholds = thresholds;
+        self
+    }
+
+    /// Собирать исправленный текст в NFC и убирать из него управляющие символы и символы
+    /// нулевой ширины (по умолчанию включено)
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Детектор для поля тега `key`: с порогом этого поля, если он задан
+    pub fn for_field(&self, key: &ItemKey) -> Cow<'_, Self> {
+        match self.field_thresholds.iter().find(|(field, _)| field == key) {
+            Some(&(_, threshold)) if threshold != self.cyr_threshold => Cow::Owned(Self {
+                cyr_threshold: threshold,
+                ..self.clone()
+            }),
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Кодировка из `candidates`, в которой `bytes` больше всего похожи на текст на одном
+    /// из языков; первая подходящая в списке уступает другой, только если та оценена выше
+    /// на [`ENCODING_MARGIN`]. Кодировки, в которых байты не декодируются без ошибок,
+    /// пропускаются
+    pub fn detect_encoding(
+        &self,
+        bytes: &[u8],
+        candidates: &[&'static Encoding],
+    ) -> Option<&'static Encoding> {
+        if bytes.is_ascii() {
+            return candidates.first().copied();
+        }
+
+        let mut first: Option<(&'static Encoding, f64)> = None;
+        let mut best: Option<(&'static Encoding, f64)> = None;
+        for &encoding in candidates {
+            let (decoded, errors) = encoding.decode_without_bom_handling(bytes);
+            if errors {
+                continue;
+            }
+            // ASCII одинаков во всех кодировках и только разбавлял бы оценку
+            let sample = decoded
+                .split_whitespace()
+                .filter(|word| !word.is_ascii())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let score = score(&sample, &sample, self.languages)
+                - WEIGHT_ODD_SYMBOLS * odd_symbols_ratio(&decoded);
+            let margin = if first.is_some() {
+                ENCODING_MARGIN
+            } else {
+                0.0
+            };
+            if best.is_none_or(|(_, b)| score > b) && first.is_none_or(|(_, f)| score >= f + margin)
+            {
+                best = Some((encoding, score));
+            }
+            first = first.or(Some((encoding, score)));
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    /// Исправленный текст, если в `text` найдены кракозябры
+    pub fn fix(&self, text: &str) -> Option<String> {
+        self.detect(text).map(|d| d.text)
+    }
+
+    /// Поиск кракозябр во всей строке, а если в ней уже есть кириллица (буквы языка) —
+    /// в отдельных словах (`Кино - Ãðóïïà êðîâè` -> `Кино - Группа крови`).
+    /// Многострочный текст проверяется по строкам, см. [`Detector::detect_lines`]
+    pub fn detect(&self, text: &str) -> Option<Detection> {
+        if is_unrecoverable(text) {
+            UNRECOVERABLE.with_borrow_mut(|values| {
+                if let Some(values) = values
+                    && !values.iter().any(|value| value == text)
+                {
+                    values.push(text.to_string());
+                }
+            });
+        }
+        let detection = if text.contains('\n') {
+            self.detect_lines(text)
+        } else {
+            self.detect_line(text)
+        }?;
+        if !self.normalize {
+            return Some(detection);
+        }
+        let fixed = normalize(&detection.text).into_owned();
+        (fixed != text).then_some(Detection {
+            text: fixed,
+            ..detection
+        })
+    }
+
+    fn detect_line(&self, text: &str) -> Option<Detection> {
+        if let Some(dictionary) = &self.dictionary
+            && let Some(fixed) = dictionary.replace(text)
+        {
+            return Some(self.replaced(text, fixed));
+        }
+        // Чистый ASCII («01», «2019», английские названия) во всех проверяемых кодировках
+        // остаётся собой: перекодировать его незачем
+        if text.is_ascii() && self.ascii_compatible() {
+            return None;
+        }
+        self.detect_whole(text).or_else(|| {
+            text.chars()
+                .any(|c| self.is_native(c))
+                .then(|| self.detect_mixed(text))
+                .flatten()
+        })
+    }
+
+    /// Многострочный текст (слова песни, комментарий): каждая строка проверяется отдельно,
+    /// переводы строк и отступы сохраняются. По всему тексту сразу английский припев
+    /// разбавляет оценку, и она не дотягивает до порога.
+    /// Оценка и уверенность — худшие среди исправленных строк
+    fn detect_lines(&self, text: &str) -> Option<Detection> {
+        let mut fixed = String::with_capacity(text.len());
+        let mut result: Option<Detection> = None;
+        for line in text.split_inclusive('\n') {
+            let content = line.trim();
+            let Some(detection) = (!content.is_empty())
+                .then(|| self.detect_line(content))
+                .flatten()
+            else {
+                fixed.push_str(line);
+                continue;
+            };
+            let start = line.len() - line.trim_start().len();
+            fixed.push_str(&line[..start]);
+            fixed.push_str(&detection.text);
+            fixed.push_str(&line[start + content.len()..]);
+            result = Some(worst(result, detection));
+        }
+
+        result.map(|detection| Detection {
+            text: fixed,
+            ..detection
+        })
+    }
+
+    /// Замена из словаря: уверенность 100, а вариант кракозябр — тот, что восстанавливает
+    /// из исходного текста ту же замену (если такого нет — первый из проверяемых)
+    fn replaced(&self, text: &str, fixed: String) -> Detection {
+        let mojibake = self
+            .candidates()
+            .iter()
+            .copied()
+            .find(|m| m.recover(text.trim()).as_deref() == Some(fixed.trim()))
+            .unwrap_or(self.candidates()[0]);
+        Detection {
+            score: score(text, &fixed, self.languages),
+            text: fixed,
+            mojibake,
+            chain: vec![mojibake],
+            confidence: 100,
+        }
+    }
+
+    /// Все ли проверяемые кодировки совпадают с ASCII на ASCII-символах; не совпадают,
+    /// например, UTF-16 и ISO-2022-JP, заданные как пара кодировок
+    fn ascii_compatible(&self) -> bool {
+        self.candidates().iter().all(|m| {
+            m.source.is_ascii_compatible() && m.misread.output_encoding().is_ascii_compatible()
+        })
+    }
+
+    /// Не-ASCII буква одного из языков: признак уже нормального текста
+    fn is_native(&self, c: char) -> bool {
+        !c.is_ascii() && self.languages.is_letter(c)
+    }
+
+    /// Перебор вариантов из [`Detector::candidates`] с выбором лучшего по оценке.
+    /// Кандидат должен превзойти и порог, и оценку исходного текста —
+    /// иначе нормальная кириллица «исправлялась» бы в кракозябры.
+    ///
+    /// Кроме одной ошибки перебираются цепочки из нескольких неверных перекодировок
+    /// длиной до [`MAX_CHAIN`]: например, cp1251, прочитанная как cp1252, сохранённая
+    /// в UTF-8 и снова прочитанная как cp1252. Однократное «восстановление» такого текста
+    /// даёт псевдографику вперемешку с буквами, поэтому более длинная цепочка побеждает,
+    /// если её итог оценён строго выше; при равенстве остаётся более короткая
+    fn detect_whole(&self, text: &str) -> Option<Detection> {
+        let original_score = score(text, text, self.languages);
+        let mut seen = HashSet::from([text.to_string()]);
+        let mut level: Vec<(String, Vec<Mojibake>)> = vec![(text.to_string(), Vec::new())];
+        let mut best: Option<Detection> = None;
+        let tracing = TRACES.with_borrow(Option::is_some);
+        let mut candidates = Vec::new();
+
+        for _ in 0..MAX_CHAIN {
+            let mut next = Vec::new();
+
+            for (current, chain) in &level {
+                for &mojibake in self.candidates() {
+                    let Some(decoded) = mojibake.recover(current) else {
+                        continue;
+                    };
+                    if !seen.insert(decoded.clone()) {
+                        continue;
+                    }
+                    let mut chain = chain.clone();
+                    chain.push(mojibake);
+
+                    let fixed = decoded.trim();
+                    let mut score = score(text, fixed, self.languages);
+                    // Псевдографика в исходном тексте — почти верный признак cp1251, показанной как cp866
+                    if chain[0].misread == IBM866 {
+                        score += WEIGHT_PSEUDOGRAPHICS * pseudographics_ratio(text);
+                    }
+                    if tracing {
+                        candidates.push(TraceCandidate {
+                            chain: chain.clone(),
+                            text: fixed.to_string(),
+                            score,
+                        });
+                    }
+
+                    if score > self.cyr_threshold
+                        && score > original_score
+                        && best.as_ref().is_none_or(|b| score > b.score)
+                    {
+                        best = Some(Detection {
+                            text: fixed.to_string(),
+                            mojibake: Mojibake::new(mojibake.source, chain[0].misread),
+                            chain: chain.clone(),
+                            score,
+                            confidence: confidence(fixed, self.languages),
+                        });
+                    }
+                    next.push((decoded, chain));
+                }
+            }
+
+            level = next;
+        }
+
+        if !candidates.is_empty() {
+            candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+            let trace = Trace {
+                text: text.to_string(),
+                original_score,
+                threshold: self.cyr_threshold,
+                candidates,
+                fixed: best.as_ref().map(|b| b.text.clone()),
+            };
+            TRACES.with_borrow_mut(|traces| {
+                if let Some(traces) = traces {
+                    traces.push(trace);
+                }
+            });
+        }
+        best
+    }
+
+    /// Исправление строки, в которой нормальная кириллица соседствует с кракозябрами.
+    ///
+    /// Подряд идущие слова без кириллицы (вместе с разделителями между ними) проверяются
+    /// одним куском, чтобы вариант выбирался по всему фрагменту, а не по короткому слову;
+    /// слова без букв («-», «1998») фрагмент не прерывают. Слова с кириллицей проверяются
+    /// по одному: так находится дважды закодированный UTF-8 («Р›СЊРІРёС†Р°»).
+    /// Остальной текст не меняется; оценка и уверенность — худшие среди исправленных фрагментов
+    fn detect_mixed(&self, text: &str) -> Option<Detection> {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        let mut current: Option<Range<usize>> = None;
+        for word in words(text) {
+            let slice = &text[word.clone()];
+            if slice.chars().any(|c| self.is_native(c)) {
+                runs.extend(current.take());
+                runs.push(word);
+            } else if slice.chars().any(char::is_alphabetic) {
+                current = Some(current.map_or(word.clone(), |run| run.start..word.end));
+            }
+        }
+        runs.extend(current);
+
+        let mut fixed = String::with_capacity(text.len());
+        let mut result: Option<Detection> = None;
+        let mut pos = 0;
+        for run in runs {
+            let Some(detection) = self.detect_whole(&text[run.clone()]) else {
+                continue;
+            };
+            fixed.push_str(&text[pos..run.start]);
+            fixed.push_str(&detection.text);
+            pos = run.end;
+            result = Some(worst(result, detection));
+        }
+        fixed.push_str(&text[pos..]);
+
+        result.map(|detection| Detection {
+            text: fixed,
+            ..detection
+        })
+    }
+}
+
+/// Итог исправления по частям: вариант первой исправленной части, оценка и уверенность —
+/// худшие из всех
+pub(crate) fn worst(first: Option<Detection>, detection: Detection) -> Detection {
+    match first {
+        Some(first) => Detection {
+            score: first.score.min(detection.score),
+            confidence: first.confidence.min(detection.confidence),
+            ..first
+        },
+        None => detection,
+    }
+}
+
+/// Положения слов (участков без пробелов) в строке
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    words.extend(start.map(|s| s..text.len()));
+    words
+}
+
+// fn has_cyrillic(s: &str) -> bool {
+//     s.chars()
+//         .any(|c| matches!(c, 'а'.='я' | 'А'.='Я' | 'ё' | 'Ё'))
+// }
+
+pub fn is_cyrillic(c: &char) -> bool {
+    ('\u{0400}'..='\u{04FF}').contains(c)
+}
+
+/// Число букв алфавитов выбранных языков: остальная кириллица (`ђ`, `љ`, а для русского
+/// и `і`, `ў`) чаще получается из неверной кодировки, чем встречается в тегах
+fn letter_count(s: &str, languages: Languages) -> usize {
+    s.chars().filter(|&c| languages.is_letter(c)).count()
+}
+
+fn latin_diacritics_count(s: &str) -> usize {
+    s.chars().filter(|c| LATIN_DIACRITICS.contains(c)).count()
+}
+
+fn is_pseudographic(c: &char) -> bool {
+    ('\u{2500}'..='\u{25A0}').contains(c)
+}
+
+fn pseudographics_ratio(s: &str) -> f64 {
+    let len = s.chars().count() as f64;
+    s.chars().filter(is_pseudographic).count() as f64 / len
+}
+
+/// Доля букв языка с правдоподобным регистром: первая буква слова — любая,
+/// остальные строчные; слова целиком из заглавных тоже считаются нормальными.
+/// cp1251 и KOI8-R отличаются как раз перевёрнутым регистром («лЙОП» вместо «Кино»).
+fn case_ratio(s: &str, languages: Languages) -> f64 {
+    let mut total = 0usize;
+    let mut plausible = 0usize;
+
+    for word in s.split(|c: char| !c.is_alphabetic()) {
+        let letters: Vec<char> = word.chars().filter(|&c| languages.is_letter(c)).collect();
+        total += letters.len();
+
+        if letters.iter().all(|c| c.is_uppercase()) {
+            plausible += letters.len();
+        } else {
+            plausible += letters
+                .iter()
+                .enumerate()
+                .filter(|(i, c)| *i == 0 || c.is_lowercase())
+                .count();
+        }
+    }
+
+    if total == 0 {
+        1.0
+    } else {
+        plausible as f64 / total as f64
+    }
+}
+
+/// Насколько текст похож на слова выбранных языков, 0–1; см. [`Languages::word_likeness`]
+fn word_plausibility(s: &str, languages: Languages) -> f64 {
+    (languages.word_likeness(s) / TYPICAL_WORD_LIKENESS).min(1.0)
+}
+
+/// Часть строки, по которой она оценивается: слова не из ASCII. Английские слова, цифры
+/// и знаки препинания при восстановлении не меняются, и в длинном названии
+/// («Ãðóïïà êðîâè (Live at Wembley, Remastered 2010)») они разбавляли бы оценку ниже
+/// любого порога. Строка целиком из ASCII оценивается как есть
+fn significant(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+    let words: Vec<&str> = s
+        .split_whitespace()
+        .filter(|word| !word.is_ascii())
+        .collect();
+    Cow::Owned(words.join(" "))
+}
+
+/// Длина строки для долей в оценке: без пробелов, цифр и знаков препинания, чтобы
+/// «Трек 01 (2003 ремастер)» оценивался как «Трек ремастер». Прочие символы
+/// (псевдографика, `¤`, `§`) считаются: в неверном варианте они и отличают его от текста
+fn scored_len(s: &str) -> usize {
+    s.chars()
+        .filter(|&c| {
+            !(c.is_whitespace()
+                || c.is_numeric()
+                || c.is_ascii_punctuation()
+                || PUNCTUATION.contains(&c))
+        })
+        .count()
+}
+
+/// Доля символов вне ASCII в `decoded`, которые не буквы, не цифры и не типографские знаки
+fn odd_symbols_ratio(decoded: &str) -> f64 {
+    let (odd, total) = decoded
+        .chars()
+        .filter(|c| !c.is_ascii())
+        .fold((0, 0), |(odd, total), c| {
+            let plain = c.is_alphanumeric() || PUNCTUATION.contains(&c) || c == '№';
+            (odd + usize::from(!plain), total + 1)
+        });
+    odd as f64 / total.max(1) as f64
+}
+
+/// Уверенность 0–100 в том, что `decoded` — нормальный текст на одном из языков
+fn confidence(decoded: &str, languages: Languages) -> u8 {
+    let decoded = &*significant(decoded);
+    let letters = decoded.chars().filter(|c| c.is_alphabetic()).count();
+    if letters == 0 {
+        return 0;
+    }
+    let ratio = letter_count(decoded, languages) as f64 / letters as f64
+        * case_ratio(decoded, languages)
+        * word_plausibility(decoded, languages);
+    (ratio.clamp(0.0, 1.0) * 100.0).round() as u8
+}
+
+/// Оценка правдоподобия `decoded` как восстановленного варианта `text`
+fn score(text: &str, decoded: &str, languages: Languages) -> f64 {
+    let (text, decoded) = (&*significant(text), &*significant(decoded));
+    let len = scored_len(decoded).max(1) as f64;
+
+    let cyr_ratio = if text.chars().filter(|c| !c.is_ascii()).count() < MIN_MOJIBAKE_CHARS {
+        0.0
+    } else {
+        letter_count(decoded, languages) as f64 / len
+    };
+    let diacritics_ratio = latin_diacritics_count(text) as f64 / len;
+    let words = 1.0 - WEIGHT_WORDS + WEIGHT_WORDS * word_plausibility(decoded, languages);
+    WEIGHT_CYR * cyr_ratio * case_ratio(decoded, languages) * words
+        - WEIGHT_DIACRITICS * diacritics_ratio
+}
+
+/// "Ëüâèöà ðîêà" -> "Львица рока"
+pub fn fix_mojibake(text: &str, cyr_threshold: f64) -> Option<String> {
+    Detector::new(cyr_threshold).fix(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::LEGACY_ENCODINGS;
+    use encoding_rs::{IBM866, KOI8_R, WINDOWS_1251, X_MAC_CYRILLIC};
+
+    /// Плейлист в cp1251, который x-mac-cyrillic читал бы как ` ино - √руппа крови`
+    const PLAYLIST: &str = "#EXTINF:123,Кино - Группа крови\r\nКино - Группа крови.flac\r\n";
+
+    fn detect_encoding(text: &str, encoding: &'static Encoding) -> Option<&'static str> {
+        let (bytes, _, _) = encoding.encode(text);
+        Detector::default()
+            .detect_encoding(&bytes, LEGACY_ENCODINGS)
+            .map(Encoding::name)
+    }
+
+    #[test]
+    fn cp1251_playlist_is_not_mac_cyrillic() {
+        assert_eq!(
+            detect_encoding(PLAYLIST, WINDOWS_1251),
+            Some("windows-1251")
+        );
+    }
+
+    #[test]
+    fn other_encodings_are_still_detected() {
+        for encoding in [KOI8_R, IBM866, X_MAC_CYRILLIC] {
+            assert_eq!(
+                detect_encoding(PLAYLIST, encoding),
+                Some(encoding.name()),
+                "{}",
+                encoding.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Словарь замен (--dictionary): точные строки с кракозябрами или регулярные выражения
+//! и правильные значения для них. Словарь важнее автоматического определения: так
+//! исправляются названия групп, которые эвристика восстанавливает неверно.
+//!
+//! Файл в UTF-8, формат — по расширению:
+//!
+//! ```toml
+//! # dictionary.toml
+//! "Êèíî" = "Кино"
+//! '/^Ãð[àå]æäàíñêàÿ îáîðîíà$/' = "Гражданская оборона"
+//! ```
+//!
+//! ```text
+//! # dictionary.csv: разделитель `;` или `,`, кавычки — как в CSV
+//! Êèíî;Кино
+//! "/^ÄÄÒ( \(.*\))?$/";ДДТ$1
+//! ```
+//!
+//! Строка совпадает, если значение поля (или строка многострочного поля) равно ей целиком,
+//! без учёта пробелов по краям. Выражение в `/…/` ищется в любом месте значения, все
+//! совпадения заменяются, в замене можно ссылаться на группы (`$1`), см. [`crate::regex`].
+//! Сначала проверяются точные строки, затем выражения по порядку в файле
+
+use crate::regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Словарь замен
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    exact: HashMap<String, String>,
+    patterns: Vec<(Regex, String)>,
+}
+
+/// Ошибка загрузки словаря
+#[derive(Debug, Clone)]
+pub struct DictionaryError {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path.display(), self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for DictionaryError {}
+
+impl Dictionary {
+    /// Загрузить словарь: `.csv` — CSV, всё остальное — TOML
+    pub fn load(path: &Path) -> Result<Self, DictionaryError> {
+        let error = |line, message| DictionaryError {
+            path: path.to_path_buf(),
+            line,
+            message,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| error(None, e.to_string()))?;
+        let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+        let csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let mut dictionary = Self::default();
+        let delimiter = if csv { csv_delimiter(text) } else { ',' };
+        for (i, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let entry = if csv {
+                csv_entry(line, delimiter)
+            } else {
+                toml_entry(trimmed)
+            };
+            entry
+                .and_then(|(from, to)| dictionary.insert(&from, to))
+                .map_err(|message| error(Some(i + 1), message))?;
+        }
+        Ok(dictionary)
+    }
+
+    /// Добавить замену: `from` в `/…/` — регулярное выражение, иначе точная строка
+    pub fn insert(&mut self, from: &str, to: String) -> Result<(), String> {
+        match from
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            Some(pattern) => {
+                let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                self.patterns.push((regex, to));
+            }
+            None => {
+                let from = from.trim();
+                if from.is_empty() {
+                    return Err("пустая строка для замены".to_string());
+                }
+                self.exact.insert(from.to_string(), to);
+            }
+        }
+        Ok(())
+    }
+
+    /// Правильное значение для `text`, если словарь его меняет
+    pub fn replace(&self, text: &str) -> Option<String> {
+        if let Some(to) = self.exact.get(text.trim()) {
+            return (to != text).then(|| to.clone());
+        }
+        let mut result: Option<String> = None;
+        for (regex, to) in &self.patterns {
+            let current = result.as_deref().unwrap_or(text);
+            if let Some(replaced) = regex.replace_all(current, to) {
+                result = Some(replaced);
+            }
+        }
+        result.filter(|replaced| replaced != text)
+    }
+}
+
+/// Разделитель CSV: `;`, если он есть в первой непустой строке, иначе `,`
+fn csv_delimiter(text: &str) -> char {
+    let first = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'));
+    if first.is_some_and(|line| line.contains(';')) {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Первые два поля строки CSV; кавычки внутри поля в кавычках удваиваются
+fn csv_entry(line: &str, delimiter: char) -> Result<(String, String), String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("незакрытая кавычка".to_string());
+    }
+    fields.push(field);
+    match fields.as_slice() {
+        [from, to, ..] => Ok((from.clone(), to.clone())),
+        _ => Err(format!("ожидалось: строка{delimiter}замена")),
+    }
+}
+
+/// Пара `"строка" = "замена"`; строки — в двойных кавычках (с `\"`, `\\`, `\n`, `\t`,
+/// `\uXXXX`) или в одинарных, без экранирования: удобно для регулярных выражений.
+/// Строку из латиницы, цифр, `_` и `-` можно писать без кавычек
+fn toml_entry(line: &str) -> Result<(String, String), String> {
+    let bare = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(line.len());
+    let (from, rest) = match bare {
+        0 => toml_string(line)?,
+        _ => (line[..bare].to_string(), &line[bare..]),
+    };
+    let rest = rest
+        .trim_start()
+        .strip_prefix('=')
+        .ok_or("ожидалось \"строка\" = \"замена\"")?;
+    let (to, rest) = toml_string(rest.trim_start())?;
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("лишние символы: {rest}"));
+    }
+    Ok((from, to))
+}
+
+/// Строка TOML в начале `input` и остаток после неё
+fn toml_string(input: &str) -> Result<(String, &str), String> {
+    let mut chars = input.char_indices();
+    let quote = match chars.next() {
+        Some((_, quote @ ('"' | '\''))) => quote,
+        _ => return Err("ожидалась строка в кавычках".to_string()),
+    };
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((value, &input[i + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("неверный \\u{hex}"))?;
+                    value.push(c);
+                }
+                Some(c @ ('"' | '\\')) => value.push(c),
+                Some(c) => return Err(format!("неизвестное экранирование \\{c}")),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err("незакрытая кавычка".to_string())
+}
@@ -0,0 +1,202 @@
+//! Сверка альбомов с треклистом Discogs (--discogs-token): по исправленным исполнителю
+//! и названию альбома ищется релиз, и треки, исправленное название которых не похоже ни на
+//! один трек релиза, выводятся предупреждением. Русская эстрада и рок на Discogs описаны
+//! полнее, чем в MusicBrainz.
+//!
+//! Поиск Discogs доступен только с персональным токеном, запросы идут не чаще раза
+//! в секунду, а треклисты сохраняются в `.cyrtag-fix/discogs.tsv` в корне обработки. Как
+//! и у MusicBrainz, запросы идут по HTTP, а api.discogs.com отвечает только по https,
+//! поэтому адрес по умолчанию не задан: нужен прокси с TLS на этой же машине
+//! (--discogs-url). Токен на другие http-адреса не отправляется, чтобы он не ушёл в сеть
+//! открытым текстом
+
+use crate::server::{Url, encode, http_request};
+use cyrtag_fix::album::fold;
+use cyrtag_fix::json::Json;
+use cyrtag_fix::locale::tr;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Сохранённые треклисты относительно корня обработки
+pub const CACHE_FILE: &str = ".cyrtag-fix/discogs.tsv";
+/// С токеном Discogs разрешает 60 запросов в минуту
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Сколько найденных релизов просматривается в поиске
+const SEARCH_LIMIT: usize = 10;
+
+/// Названия треков релиза; `None` — релиз не найден
+type Tracklist = Option<Vec<String>>;
+
+/// Подключение к Discogs с ограничением частоты запросов и кэшем треклистов
+pub struct Discogs {
+    url: Url,
+    token: String,
+    /// Треклисты по исполнителю и альбому
+    cache: Mutex<HashMap<(String, String), Tracklist>>,
+    /// Файл, куда дописываются новые треклисты
+    cache_file: Option<Mutex<File>>,
+    /// Когда можно отправить следующий запрос
+    next_request: Mutex<Instant>,
+    /// После ошибки сети сверка отключается до конца запуска
+    disabled: AtomicBool,
+}
+
+impl Discogs {
+    /// `url` — из --discogs-url, `token` — персональный токен Discogs. Адрес должен вести
+    /// на эту же машину: по http к другому серверу токен ушёл бы открытым текстом
+    pub fn new(url: &str, token: &str) -> Result<Self, String> {
+        let url: Url = url.parse()?;
+        if !url.is_loopback() {
+            return Err(tr(
+                "токен Discogs отправляется только на локальный адрес (localhost, 127.0.0.1, ::1) прокси с TLS, а не по открытому HTTP в сеть",
+                "the Discogs token is only sent to a local address (localhost, 127.0.0.1, ::1) of a TLS proxy, not over plain HTTP across the network",
+            )
+            .to_string());
+        }
+        Ok(Self {
+            url,
+            token: token.to_string(),
+            cache: Mutex::default(),
+            cache_file: None,
+            next_request: Mutex::new(Instant::now()),
+            disabled: AtomicBool::new(false),
+        })
+    }
+
+    /// Загрузить сохранённые треклисты из `path` и дописывать туда новые. Строка файла —
+    /// исполнитель, альбом и названия треков через табуляцию; без треков — релиз не найден
+    pub fn open_cache(&mut self, path: &Path) -> io::Result<()> {
+        let cache = match fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .filter_map(|line| {
+                    let mut values = line.split('\t').map(str::to_string);
+                    let key = (values.next()?, values.next()?);
+                    let tracks: Vec<String> = values.collect();
+                    Some((key, (!tracks.is_empty()).then_some(tracks)))
+                })
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.cache = Mutex::new(cache);
+        self.cache_file = Some(Mutex::new(file));
+        Ok(())
+    }
+
+    /// Названия треков релиза `album` исполнителя `artist`. `Ok(None)` — релиз не найден
+    /// или сверка отключена после ошибки сети
+    pub fn tracklist(&self, artist: &str, album: &str) -> Result<Tracklist, String> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let key = (artist.to_string(), album.to_string());
+        if let Some(tracks) = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            return Ok(tracks.clone());
+        }
+
+        let tracks = match self.find(artist, album) {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                self.disabled.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        // Кэш только ускоряет повторные запуски: ошибка записи не мешает сверке
+        if let Some(file) = &self.cache_file {
+            let mut line = vec![artist, album];
+            line.extend(tracks.iter().flatten().map(String::as_str));
+            let line: Vec<String> = line.iter().map(|value| value.replace('\t', " ")).collect();
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = writeln!(file, "{}", line.join("\t"));
+        }
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, tracks.clone());
+        Ok(tracks)
+    }
+
+    /// Поиск релиза и загрузка его треклиста
+    fn find(&self, artist: &str, album: &str) -> Result<Tracklist, String> {
+        let search = self.get(&format!(
+            "/database/search?type=release&artist={}&release_title={}&per_page={SEARCH_LIMIT}",
+            encode(artist),
+            encode(album)
+        ))?;
+        // Название в поиске — «Исполнитель - Альбом»
+        let album = fold(album);
+        let Some(id) = search
+            .get("results")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter(|result| {
+                result
+                    .get("title")
+                    .and_then(Json::as_str)
+                    .is_some_and(|title| fold(title).ends_with(&album))
+            })
+            .find_map(|result| result.get("id").and_then(Json::as_f64))
+        else {
+            return Ok(None);
+        };
+
+        let release = self.get(&format!("/releases/{id}"))?;
+        let mut tracks = Vec::new();
+        for track in release
+            .get("tracklist")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+        {
+            // Заголовки частей — не треки; у составных треков названия есть и у частей
+            if track.get("type_").and_then(Json::as_str) == Some("heading") {
+                continue;
+            }
+            let sub_tracks = track.get("sub_tracks").and_then(Json::as_array);
+            for track in std::iter::once(track).chain(sub_tracks.unwrap_or_default()) {
+                if let Some(title) = track.get("title").and_then(Json::as_str) {
+                    tracks.push(title.to_string());
+                }
+            }
+        }
+        Ok((!tracks.is_empty()).then_some(tracks))
+    }
+
+    /// GET-запрос к API с соблюдением частоты запросов
+    fn get(&self, path: &str) -> Result<Json, String> {
+        let auth = format!("Discogs token={}", self.token);
+        let body = {
+            let mut next = self.next_request.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            if *next > now {
+                thread::sleep(*next - now);
+            }
+            let body = http_request(
+                &self.url,
+                "GET",
+                path,
+                &[("Accept", "application/json"), ("Authorization", &auth)],
+                None,
+            );
+            *next = Instant::now() + REQUEST_INTERVAL;
+            body?
+        };
+        Json::parse(&body).map_err(|e| format!("Discogs: {e}"))
+    }
+}
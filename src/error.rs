@@ -0,0 +1,171 @@
+//! Ошибки обработки файлов
+
+use crate::locale::tr;
+use crate::tr_format;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Не удалось прочитать файл
+    Read { path: PathBuf, source: io::Error },
+    /// Текстовый файл больше [`crate::text::MAX_TEXT_SIZE`]: скорее всего, это не .cue
+    /// и не плейлист, а что-то под чужим расширением
+    TooLarge { path: PathBuf, size: u64 },
+    /// Не удалось прочитать теги аудио-файла
+    ReadTags {
+        path: PathBuf,
+        source: lofty::error::LoftyError,
+    },
+    /// Не удалось создать бэкап
+    Backup { path: PathBuf, source: io::Error },
+    /// Не удалось прочитать или записать журнал бэкапов
+    BackupJournal { path: PathBuf, source: io::Error },
+    /// Не удалось записать файл
+    Write { path: PathBuf, source: io::Error },
+    /// Не удалось сохранить теги аудио-файла
+    SaveTags {
+        path: PathBuf,
+        source: lofty::error::LoftyError,
+    },
+    /// После сохранения тегов в файле не нашлось бы некоторых встроенных картинок
+    PicturesLost { path: PathBuf, count: usize },
+    /// Не удалось переименовать файл
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        source: io::Error,
+    },
+    /// Не удалось восстановить файл из бэкапа
+    Restore { path: PathBuf, source: io::Error },
+    /// Не удалось прочитать или записать журнал переименований
+    Journal { path: PathBuf, source: io::Error },
+    /// Не удалось прочитать или записать манифест запуска
+    Manifest { path: PathBuf, source: io::Error },
+    /// Не удалось прочитать или записать план исправлений
+    Plan { path: PathBuf, source: io::Error },
+    /// Не удалось прочитать или записать состояние запусков (--incremental, --resume)
+    State { path: PathBuf, source: io::Error },
+    /// Не удалось записать отметку о проверке в атрибут файла (--mark)
+    Mark { path: PathBuf, source: io::Error },
+    /// Запись плана нельзя применить к файлу
+    PlanApply {
+        path: PathBuf,
+        field: String,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read { path, source } => {
+                let what = tr("чтения", "reading");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::TooLarge { path, size } => {
+                let what = tr("чтения", "reading");
+                const MB: u64 = 1024 * 1024;
+                let reason = tr_format!(
+                    "слишком большой для текстового файла ({} МБ, предел {} МБ)",
+                    "too large for a text file ({} MB, the limit is {} MB)",
+                    size.div_ceil(MB),
+                    crate::text::MAX_TEXT_SIZE / MB
+                );
+                write!(f, "{what} {}: {reason}", path.display())
+            }
+            Error::ReadTags { path, source } => {
+                let what = tr("чтения тегов", "reading tags of");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Backup { path, source } => {
+                let what = tr("при создании бэкапа", "creating backup");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::BackupJournal { path, source } => {
+                let what = tr("журнала бэкапов", "with backup journal");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Write { path, source } => {
+                let what = tr("записи", "writing");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::SaveTags { path, source } => {
+                let what = tr("сохранения тегов", "saving tags of");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::PicturesLost { path, count } => write!(
+                f,
+                "{}",
+                tr_format!(
+                    "сохранения тегов {}: пропали бы встроенные картинки ({count}), файл не изменён",
+                    "saving tags of {}: {count} embedded pictures would be lost, file left unchanged",
+                    path.display()
+                )
+            ),
+            Error::Rename { from, to, source } => write!(
+                f,
+                "{} {} -> {}: {source}",
+                tr("переименования", "renaming"),
+                from.display(),
+                to.display()
+            ),
+            Error::Restore { path, source } => {
+                let what = tr("восстановления из бэкапа", "restoring from backup");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Journal { path, source } => {
+                let what = tr("журнала переименований", "with rename journal");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Manifest { path, source } => {
+                let what = tr("манифеста запуска", "with run manifest");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Plan { path, source } => {
+                let what = tr("плана исправлений", "with fix plan");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::State { path, source } => {
+                let what = tr("состояния запусков", "with run state");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::Mark { path, source } => {
+                let what = tr("отметки о проверке", "with check marker");
+                write!(f, "{what} {}: {source}", path.display())
+            }
+            Error::PlanApply {
+                path,
+                field,
+                reason,
+            } => write!(
+                f,
+                "{}",
+                tr_format!(
+                    "применения плана к {}, поле {field}: {reason}",
+                    "applying plan to {}, field {field}: {reason}",
+                    path.display()
+                )
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Read { source, .. } | Error::Backup { source, .. } => Some(source),
+            Error::Write { source, .. } | Error::Rename { source, .. } => Some(source),
+            Error::Restore { source, .. } | Error::Journal { source, .. } => Some(source),
+            Error::Manifest { source, .. } | Error::Plan { source, .. } => Some(source),
+            Error::State { source, .. } | Error::BackupJournal { source, .. } => Some(source),
+            Error::Mark { source, .. } => Some(source),
+            Error::ReadTags { source, .. } | Error::SaveTags { source, .. } => Some(source),
+            Error::PlanApply { .. } | Error::TooLarge { .. } => None,
+            Error::PicturesLost { .. } => None,
+        }
+    }
+}
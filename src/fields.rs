@@ -0,0 +1,180 @@
+//! Имена полей тегов для --fields/--skip-fields и их соответствие `ItemKey` lofty
+
+use lofty::prelude::ItemKey;
+use lofty::tag::TagType;
+use phf::{Map, phf_map};
+
+/// Понятные имена полей и имена `ItemKey` из вывода (в нижнем регистре, без `_`, `-` и пробелов)
+static FIELD_NAMES: Map<&'static str, ItemKey> = phf_map! {
+    "title" => ItemKey::TrackTitle,
+    "tracktitle" => ItemKey::TrackTitle,
+    "subtitle" => ItemKey::TrackSubtitle,
+    "tracksubtitle" => ItemKey::TrackSubtitle,
+    "artist" => ItemKey::TrackArtist,
+    "trackartist" => ItemKey::TrackArtist,
+    "artists" => ItemKey::TrackArtists,
+    "trackartists" => ItemKey::TrackArtists,
+    "album" => ItemKey::AlbumTitle,
+    "albumtitle" => ItemKey::AlbumTitle,
+    "albumartist" => ItemKey::AlbumArtist,
+    "setsubtitle" => ItemKey::SetSubtitle,
+    "contentgroup" => ItemKey::ContentGroup,
+    "originalalbum" => ItemKey::OriginalAlbumTitle,
+    "originalalbumtitle" => ItemKey::OriginalAlbumTitle,
+    "originalartist" => ItemKey::OriginalArtist,
+    "originallyricist" => ItemKey::OriginalLyricist,
+    "genre" => ItemKey::Genre,
+    "mood" => ItemKey::Mood,
+    "comment" => ItemKey::Comment,
+    "description" => ItemKey::Description,
+    "lyrics" => ItemKey::Lyrics,
+    "composer" => ItemKey::Composer,
+    "lyricist" => ItemKey::Lyricist,
+    "writer" => ItemKey::Writer,
+    "arranger" => ItemKey::Arranger,
+    "conductor" => ItemKey::Conductor,
+    "performer" => ItemKey::Performer,
+    "producer" => ItemKey::Producer,
+    "remixer" => ItemKey::Remixer,
+    "publisher" => ItemKey::Publisher,
+    "label" => ItemKey::Label,
+    "copyright" => ItemKey::CopyrightMessage,
+    "copyrightmessage" => ItemKey::CopyrightMessage,
+    "encodedby" => ItemKey::EncodedBy,
+    "work" => ItemKey::Work,
+    "movement" => ItemKey::Movement,
+};
+
+/// Имя «поля» описаний встроенных картинок (APIC в ID3v2, METADATA_BLOCK_PICTURE во FLAC
+/// и Vorbis): своего `ItemKey` у них нет
+pub const PICTURE_FIELD: &str = "PictureDescription";
+
+/// Ключ для описаний встроенных картинок, см. [`PICTURE_FIELD`]
+pub fn picture_key() -> ItemKey {
+    ItemKey::Unknown(PICTURE_FIELD.to_string())
+}
+
+/// Имя «поля» названий глав MP4/M4B (Nero и QuickTime, см. [`crate::mp4chap`]): они лежат
+/// вне тега ilst, и своего `ItemKey` у них нет
+pub const CHAPTER_FIELD: &str = "ChapterTitle";
+
+/// Ключ для названий глав, см. [`CHAPTER_FIELD`]
+pub fn chapter_key() -> ItemKey {
+    ItemKey::Unknown(CHAPTER_FIELD.to_string())
+}
+
+/// Теги, в именах полей которых ищется имя, не найденное среди понятных (`TIT2`, `ALBUMARTIST`)
+const NATIVE_TAG_TYPES: &[TagType] = &[
+    TagType::Id3v2,
+    TagType::VorbisComments,
+    TagType::Ape,
+    TagType::Mp4Ilst,
+    TagType::RiffInfo,
+];
+
+/// Поле по имени: понятное (`title`, `album-artist`), как в выводе (`TrackTitle`)
+/// или родное для формата (`TIT2`, `ALBUMARTIST`, `©nam`)
+pub fn parse_field(name: &str) -> Option<ItemKey> {
+    let normalized: String = name
+        .chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect();
+    if let Some(key) = FIELD_NAMES.get(normalized.as_str()) {
+        return Some(key.clone());
+    }
+    if matches!(normalized.as_str(), "picture" | "picturedescription") {
+        return Some(picture_key());
+    }
+    if matches!(normalized.as_str(), "chapter" | "chaptertitle") {
+        return Some(chapter_key());
+    }
+
+    let name = name.trim();
+    NATIVE_TAG_TYPES.iter().find_map(|&tag_type| {
+        [name.to_string(), name.to_uppercase()]
+            .iter()
+            .map(|name| ItemKey::from_key(tag_type, name))
+            .find(|key| !matches!(key, ItemKey::Unknown(_)))
+    })
+}
+
+/// Поле lofty, соответствующее атрибуту WMA/ASF; для остальных — `ItemKey::Unknown`
+pub fn asf_item_key(name: &str) -> ItemKey {
+    match name {
+        "Title" => ItemKey::TrackTitle,
+        "Author" => ItemKey::TrackArtist,
+        "Copyright" => ItemKey::CopyrightMessage,
+        "Description" => ItemKey::Comment,
+        "WM/AlbumTitle" => ItemKey::AlbumTitle,
+        "WM/AlbumArtist" => ItemKey::AlbumArtist,
+        "WM/SubTitle" => ItemKey::TrackSubtitle,
+        "WM/ContentGroupDescription" => ItemKey::ContentGroup,
+        "WM/OriginalAlbumTitle" => ItemKey::OriginalAlbumTitle,
+        "WM/OriginalArtist" => ItemKey::OriginalArtist,
+        "WM/Genre" => ItemKey::Genre,
+        "WM/Mood" => ItemKey::Mood,
+        "WM/Lyrics" => ItemKey::Lyrics,
+        "WM/Composer" => ItemKey::Composer,
+        "WM/Writer" => ItemKey::Lyricist,
+        "WM/Conductor" => ItemKey::Conductor,
+        "WM/Producer" => ItemKey::Producer,
+        "WM/Publisher" => ItemKey::Publisher,
+        "WM/EncodedBy" => ItemKey::EncodedBy,
+        name => ItemKey::Unknown(name.to_string()),
+    }
+}
+
+/// Имя поля, как его показывают Mp3tag и foobar2000 (`TITLE`, `ALBUMARTIST`)
+pub fn mp3tag_name(key: &ItemKey) -> Option<&str> {
+    key.map_key(TagType::VorbisComments, false)
+}
+
+/// Имя поля для вывода: как у `ItemKey` (`TrackTitle`), а у пользовательского поля — как
+/// в файле (`TXXX:ALBUM ARTIST`, `MYFIELD`, `----:com.apple.iTunes:LABEL`)
+pub fn field_name(key: &ItemKey, tag_type: TagType) -> String {
+    match key {
+        ItemKey::Unknown(name) if name == PICTURE_FIELD || name == CHAPTER_FIELD => name.clone(),
+        // lofty хранит TXXX под описанием, а коды неизвестных фреймов — как есть
+        ItemKey::Unknown(name) if tag_type == TagType::Id3v2 && name.len() != 4 => {
+            format!("TXXX:{name}")
+        }
+        ItemKey::Unknown(name) => name.clone(),
+        key => format!("{key:?}"),
+    }
+}
+
+/// Какие поля тегов исправлять: только перечисленные в `only` (если список не пуст),
+/// кроме перечисленных в `skip`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldFilter {
+    pub only: Vec<ItemKey>,
+    pub skip: Vec<ItemKey>,
+    /// Не трогать пользовательские поля — те, у которых нет общего `ItemKey`
+    /// (TXXX, свои поля Vorbis и APE, freeform-атомы MP4)
+    pub skip_custom: bool,
+}
+
+impl FieldFilter {
+    pub fn new(only: Vec<ItemKey>, skip: Vec<ItemKey>) -> Self {
+        Self {
+            only,
+            skip,
+            skip_custom: false,
+        }
+    }
+
+    pub fn with_skip_custom(mut self, skip_custom: bool) -> Self {
+        self.skip_custom = skip_custom;
+        self
+    }
+
+    pub fn allows(&self, key: &ItemKey) -> bool {
+        if self.skip_custom
+            && matches!(key, ItemKey::Unknown(name) if name != PICTURE_FIELD && name != CHAPTER_FIELD)
+        {
+            return false;
+        }
+        (self.only.is_empty() || self.only.contains(key)) && !self.skip.contains(key)
+    }
+}
@@ -0,0 +1,161 @@
+//! Glob-шаблоны путей для фильтрации обхода: `*`, `**`, `?`, `[abc]`, `[!a-z]`
+//!
+//! Путь сравнивается целиком, с разделителем `/`. `*` и `?` не переходят границу
+//! каталога, `**` — любое число каталогов, `**/` в том числе ни одного.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    One,
+    /// `*`
+    Any,
+    /// `**`
+    Recursive,
+    /// `**/`: пусто или любые каталоги
+    AnyDirs,
+    /// `[...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// Скомпилированный glob-шаблон
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+/// Ошибка разбора шаблона
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub pattern: String,
+    pub message: &'static str,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pattern, self.message)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        let error = |message| PatternError {
+            pattern: pattern.to_string(),
+            message,
+        };
+
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '?' => Token::One,
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        Token::AnyDirs
+                    } else {
+                        Token::Recursive
+                    }
+                }
+                '*' => Token::Any,
+                '[' => {
+                    let negated = matches!(chars.peek(), Some('!' | '^'));
+                    if negated {
+                        chars.next();
+                    }
+                    let mut ranges = Vec::new();
+                    let mut closed = false;
+                    while let Some(c) = chars.next() {
+                        if c == ']' && !ranges.is_empty() {
+                            closed = true;
+                            break;
+                        }
+                        let mut lookahead = chars.clone();
+                        if lookahead.next() == Some('-')
+                            && let Some(end) = lookahead.next()
+                            && end != ']'
+                        {
+                            chars.next();
+                            chars.next();
+                            ranges.push((c, end));
+                        } else {
+                            ranges.push((c, c));
+                        }
+                    }
+                    if !closed {
+                        return Err(error("незакрытая [ в шаблоне"));
+                    }
+                    Token::Class { negated, ranges }
+                }
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+        }
+
+        Ok(Self {
+            source: pattern.to_string(),
+            tokens,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Совпадает ли путь (с разделителем `/`) с шаблоном целиком
+    pub fn matches(&self, path: &str) -> bool {
+        let chars: Vec<char> = path.chars().collect();
+        match_tokens(&self.tokens, &chars)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Char(c) => text.first() == Some(c) && match_tokens(rest, &text[1..]),
+        Token::One => text.first().is_some_and(|&c| c != '/') && match_tokens(rest, &text[1..]),
+        Token::Class { negated, ranges } => {
+            text.first().is_some_and(|&c| {
+                let inside = ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c));
+                c != '/' && inside != *negated
+            }) && match_tokens(rest, &text[1..])
+        }
+        Token::Any => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| match_tokens(rest, &text[i..])),
+        Token::Recursive => (0..=text.len()).any(|i| match_tokens(rest, &text[i..])),
+        Token::AnyDirs => {
+            match_tokens(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| match_tokens(rest, &text[i + 1..]))
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Справка на английском (--locale en). clap берёт тексты справки из doc-комментариев,
+//! поэтому при английском языке они подменяются в уже собранной команде
+
+use crate::log::LogLevel;
+use crate::output::OutputFormat;
+use crate::server::MediaServer;
+use crate::{
+    BackupMode, BomMode, ColorMode, HardlinkMode, Id3Mode, Id3v1Mode, LineEndingMode, LocaleMode,
+    TransliterateMode, YoMode,
+};
+use clap::builder::{EnumValueParser, PossibleValue, TypedValueParser};
+use clap::{Arg, Command, ValueEnum};
+use phf::{Map, phf_map};
+use std::ffi::OsStr;
+use std::marker::PhantomData;
+
+/// Переводы текстов справки; ключ — текст doc-комментария
+static ENGLISH: Map<&'static str, &'static str> = phf_map! {
+    // Команды
+    "Утилита для исправления кириллических кракозябр кодировки cp1251 в тегах музыкальных файлов, .cue и плейлистах" =>
+        "Fix Cyrillic mojibake (cp1251) in audio file tags, .cue sheets and playlists",
+    "Исправить теги, .cue и плейлисты на месте" => "Fix tags, .cue sheets and playlists in place",
+    "Только найти кракозябры, ничего не изменяя. Код возврата: 0 — всё чисто, 1 — найдены кракозябры, 2 — были ошибки" =>
+        "Only find mojibake without changing anything. Exit code: 0 — all clean, 1 — mojibake found, 2 — there were errors",
+    "Применить план исправлений, сохранённый командой scan --plan" =>
+        "Apply a fix plan saved by scan --plan",
+    "Восстановить файлы из .bak бэкапов" => "Restore files from .bak backups",
+    "Удалить бэкапы, созданные fix, по журналу бэкапов" => "Delete backups created by fix, using the backup journal",
+    "Исправить кракозябры в именах файлов" => "Fix mojibake in file names",
+    "Откатить изменения тегов по манифесту запуска" => "Revert tag changes using a run manifest",
+    "Показать сохранённые запуски" => "List saved runs",
+    "Исправить кракозябры в строке из аргументов или stdin и показать цепочку кодировок" =>
+        "Fix mojibake in a string from the arguments or stdin and show the encoding chain",
+    "Текст с кракозябрами (несколько аргументов склеиваются через пробел); без него текст читается из stdin" =>
+        "Text with mojibake (several arguments are joined with spaces); read from stdin if omitted",
+    "Напечатать скрипт автодополнения для оболочки" => "Print a shell completion script",
+    "Оболочка" => "Shell",
+
+    // Общие параметры
+    "Файл настроек (по умолчанию ~/.config/cyrtag-fixer/config.toml)" =>
+        "Config file (default: ~/.config/cyrtag-fixer/config.toml)",
+    "Дописывать ошибки и предупреждения (с --log-level info — и обработанные файлы) в журнал" =>
+        "Append errors and warnings (with --log-level info, processed files too) to a log file",
+    "Подробность журнала --log-file" => "Verbosity of the --log-file log",
+    "Раскрашивать вывод" => "Colorize output",
+    "Язык сообщений программы (не путать с --lang — языком текста в тегах)" =>
+        "Language of program messages (not to be confused with --lang, the language of tag text)",
+
+    // Вывод
+    "Формат вывода результатов" => "Output format",
+    "Показывать только исправленные файлы и итог" => "Show only fixed files and the summary",
+    "Подробнее: -v — каждый проверенный файл, -vv — ещё и все решения детектора с оценками" =>
+        "More output: -v — every checked file, -vv — also every detector decision with scores",
+    "Сохранить HTML-отчёт со всеми исправлениями, сгруппированными по каталогам альбомов" =>
+        "Save an HTML report with all fixes grouped by album directory",
+    "Сохранить исправления в CSV (путь, поле, было, стало, кодировка) для Mp3tag и foobar2000" =>
+        "Save fixes to CSV (path, field, old, new, encoding) for Mp3tag and foobar2000",
+    "Показывать строки, не исправленные только из-за порога: вариант восстановления и его оценку чуть ниже --cyr-threshold" =>
+        "Show strings left unfixed only because of the threshold: the would-be decode and its score just below --cyr-threshold",
+
+    // Исправление
+    "Путь к папке с музыкой" => "Path to the music folder",
+    "Пути к папкам с музыкой или отдельным файлам" => "Paths to music folders or individual files",
+    "Взять пути из файла (`-` — из stdin): по одному в строке или через NUL (find -print0, fd -0)" =>
+        "Read paths from a file (`-` for stdin): one per line or NUL-separated (find -print0, fd -0)",
+    "Путь к папке с музыкой, на которой запускался fix" => "Path to the music folder fix was run on",
+    "Только показать найденные исправления, ничего не записывая" =>
+        "Only show the fixes found without writing anything",
+    "Не создавать .bak файлы (по умолчанию создаются)" => "Do not create .bak files (created by default)",
+    "Способ бэкапа аудио-файлов" => "How to back up audio files",
+    "Складывать бэкапы в отдельный каталог, повторяя структуру исходного дерева" =>
+        "Put backups in a separate directory mirroring the source tree",
+    "Сжимать копии файлов (.bak.gz); restore распаковывает их сам" =>
+        "Compress file copies (.bak.gz); restore decompresses them itself",
+    "Не менять оригиналы, а записывать исправленные копии в этот каталог, повторяя структуру исходного дерева (бэкапы при этом не нужны и не создаются)" =>
+        "Leave originals untouched and write fixed copies to this directory, mirroring the source tree (no backups are needed or created)",
+    "Спрашивать подтверждение перед изменением каждого файла" => "Ask for confirmation before changing each file",
+    "Сохранять исправленным файлам прежнее время изменения (mtime)" =>
+        "Keep the original modification time (mtime) of fixed files",
+    "Остановиться на первом файле, который не удалось прочитать или записать" =>
+        "Stop at the first file that could not be read or written",
+    "Исправлять только эти поля тегов (через запятую, например: title,artist,album)" =>
+        "Fix only these tag fields (comma-separated, e.g. title,artist,album)",
+    "Не трогать эти поля тегов (через запятую, например: comment,lyrics)" =>
+        "Leave these tag fields alone (comma-separated, e.g. comment,lyrics)",
+    "Не трогать пользовательские поля: TXXX, свои поля Vorbis и APE, freeform-атомы MP4" =>
+        "Leave custom fields alone: TXXX, custom Vorbis and APE fields, MP4 freeform atoms",
+    "Обрабатывать файлы только с этими расширениями вместо стандартного набора (через запятую, например: mp3,flac,cue)" =>
+        "Process only files with these extensions instead of the default set (comma-separated, e.g. mp3,flac,cue)",
+    "Обходить скрытые файлы и каталоги, а также служебные каталоги NAS и Windows (@eaDir, #recycle, $RECYCLE.BIN, System Volume Information …): по умолчанию они пропускаются" =>
+        "Walk hidden files and directories as well as NAS and Windows system directories (@eaDir, #recycle, $RECYCLE.BIN, System Volume Information …), which are skipped by default",
+    "Обрабатывать и ZIP-архивы: теги и тексты файлов внутри исправляются в памяти, имена файлов в cp866 сохраняются в UTF-8, архив переписывается целиком" =>
+        "Process ZIP archives too: tags and texts of files inside are fixed in memory, cp866 file names are saved as UTF-8, and the archive is rewritten as a whole",
+    "Исправлять в ZIP-архивах только имена файлов в cp866 и cp1251: они сохраняются в UTF-8, содержимое файлов не распаковывается и не меняется" =>
+        "Fix only cp866 and cp1251 file names in ZIP archives: they are saved as UTF-8, file contents are neither unpacked nor changed",
+    "Определять формат аудио-файлов по содержимому, а не по расширению: для файлов без расширения или с чужим (FLAC с именем .mp3); каждый файл открывается, обход медленнее" =>
+        "Detect the format of audio files by content rather than extension: for files with no extension or a wrong one (FLAC named .mp3); every file is opened, so walking is slower",
+    "Не проверять файлы, не изменившиеся с прошлого запуска с теми же настройками (состояние хранится в .cyrtag-fix/state в корне обработки)" =>
+        "Don't check files unchanged since the last run with the same settings (the state is kept in .cyrtag-fix/state in the processed root)",
+    "Отмечать проверенные файлы в расширенном атрибуте user.cyrtag-fix и не проверять отмеченные, не изменившиеся с тех пор (отметка переезжает вместе с файлом)" =>
+        "Mark checked files with the user.cyrtag-fix extended attribute and skip marked files unchanged since (the marker moves with the file)",
+    "Продолжить прерванный запуск: файлы, обработанные в нём, пропускаются" =>
+        "Continue an interrupted run: files it already processed are skipped",
+    "После обработки следить за каталогами и исправлять новые и изменённые файлы, как только их перестали записывать; остановить — Ctrl-C" =>
+        "After processing, keep watching the directories and fix new and changed files once they are no longer being written; press Ctrl-C to stop",
+    "Как часто перечитывать каталоги с --watch, в секундах" =>
+        "How often to rescan the directories with --watch, in seconds",
+    "Запустить команду для каждого исправленного файла: {} заменяется путём, а без {} путь добавляется в конец (например, 'beet update -p {}'); можно указать несколько раз" =>
+        "Run a command for each fixed file: {} is replaced with the path, without {} the path is appended (e.g. 'beet update -p {}'); may be given several times",
+    "После записи попросить медиасервер перечитать каталоги с исправленными файлами" =>
+        "After writing, ask a media server to rescan the directories with fixed files",
+    "Адрес медиасервера, только http:// (например, http://localhost:8096)" =>
+        "Media server address, http:// only (e.g. http://localhost:8096)",
+    "API-ключ Jellyfin, X-Plex-Token или пароль Navidrome" =>
+        "Jellyfin API key, X-Plex-Token or Navidrome password",
+    "Пользователь Navidrome" => "Navidrome user name",
+    "Каталог, который сервер видит под другим путём, например в Docker: ~/Music=/music" =>
+        "A directory the server sees under another path, e.g. in Docker: ~/Music=/music",
+    "После записи обновить базу MPD по каталогам с исправленными файлами; адрес — --mpd=host[:port] или путь к сокету (по умолчанию из MPD_HOST и MPD_PORT)" =>
+        "After writing, update the MPD database for the directories with fixed files; the address is --mpd=host[:port] or a socket path (MPD_HOST and MPD_PORT by default)",
+    "Каталог музыки MPD (music_directory) на этой машине; без него MPD спрашивается сам, что работает только через unix-сокет" =>
+        "The MPD music directory (music_directory) on this machine; without it MPD is asked directly, which only works over a unix socket",
+    "Наибольшая глубина обхода, как у find -maxdepth: 1 — только файлы в самом каталоге" =>
+        "Maximum traversal depth, as with find -maxdepth: 1 means only files in the directory itself",
+    "Заходить по символическим ссылкам на каталоги и файлы; циклы ссылок пропускаются" =>
+        "Follow symbolic links to directories and files; link loops are skipped",
+    "Обрабатывать только файлы, подходящие под glob-шаблон (например, '**/2003 - */**'); можно указать несколько раз" =>
+        "Process only files matching a glob pattern (e.g. '**/2003 - */**'); may be given several times",
+    "Пропускать файлы и каталоги по glob-шаблону пути внутри обрабатываемого каталога (например, '**/@eaDir/**'); можно указать несколько раз" =>
+        "Skip files and directories matching a glob pattern relative to the processed directory (e.g. '**/@eaDir/**'); may be given several times",
+    "Каталог раздач: файлы в нём не меняются; можно указать несколько раз" =>
+        "Seeding directory: files in it are never changed; may be given several times",
+    "Файлы с несколькими жёсткими ссылками: break — разорвать ссылку, skip — пропустить" =>
+        "Files with several hard links: break — break the link, skip — skip the file",
+    "Принудительно считать все .cue и плейлисты файлами в cp1251 (без попыток угадать)" =>
+        "Treat all .cue sheets and playlists as cp1251 (no guessing)",
+    "BOM UTF-8 в записываемых .cue, плейлистах и других текстовых файлах" =>
+        "UTF-8 BOM in written .cue sheets, playlists and other text files",
+    "Переводы строк в записываемых текстовых файлах" => "Line endings in written text files",
+    "Версия ID3v2 в исправленных тегах: keep — как в файле, v2.3 или v2.4 — перевести в неё" =>
+        "ID3v2 version of fixed tags: keep — as in the file, v2.3 or v2.4 — convert to it",
+    "ID3v2.3: для магнитол и старых плееров" => "ID3v2.3: for car stereos and older players",
+    "ID3v1 (его предпочитают некоторые плееры): keep — не трогать, cp1251 или translit — пересобрать из исправленного ID3v2, strip — удалить" =>
+        "ID3v1 (some players prefer it): keep — leave as is, cp1251 or translit — rebuild from the fixed ID3v2, strip — remove",
+    "Не трогать" => "Leave as is",
+    "Пересобрать из исправленного тега в cp1251" => "Rebuild from the fixed tag in cp1251",
+    "Пересобрать в Latin-1, кириллица — транслитом" => "Rebuild in Latin-1 with Cyrillic transliterated",
+    "Удалить" => "Remove",
+    "Буква ё в текстовых полях тегов: keep — не трогать, to-e — заменять на е, restore — возвращать по словарю; иначе плееры делят исполнителей и альбомы надвое" =>
+        "The letter ё in tag text fields: keep — leave as is, to-e — replace with е, restore — put back by dictionary; otherwise players split artists and albums in two",
+    "Заменять `ё` на `е`" => "Replace `ё` with `е`",
+    "Возвращать `ё` в словах, где она обязательна (`еще` → `ещё`)" =>
+        "Put `ё` back in words that require it (`еще` → `ещё`)",
+    "Записывать текстовые поля тегов латиницей (после исправления кракозябр) — для магнитол и плееров без кириллицы; отменяется через undo, как и исправления" =>
+        "Write tag text fields in Latin script (after fixing mojibake) for car stereos and players without Cyrillic; reverted with undo like the fixes",
+    "В переписываемых файлах оставлять только основной тег формата: поля, которых в нём нет, переносятся из APE, ID3v1 и других тегов, а те удаляются" =>
+        "In rewritten files keep only the format's main tag: fields it lacks are moved from APE, ID3v1 and other tags, which are then removed",
+    "В переписываемых тегах убирать пробелы по краям, повторные пробелы и нулевые байты во всех текстовых полях: ими дополняли значения старые программы" =>
+        "In tags being rewritten, trim surrounding spaces, duplicate spaces and null bytes in all text fields: old taggers padded values with them",
+    "ГОСТ 7.79-2000, система Б: только ASCII, однозначно обратима" =>
+        "GOST 7.79-2000, system B: ASCII only, unambiguously reversible",
+    "ISO 9: буква в букву, с диакритикой" => "ISO 9: letter for letter, with diacritics",
+    "Простая латиница без диакритики, как в загранпаспорте" =>
+        "Plain Latin without diacritics, as in passports",
+    "Переписывать в UTF-8 (UTF-16 в ID3v2.3) все фреймы ID3v2 в Latin-1 с не-ASCII текстом, даже если кракозябр в них нет: такие фреймы плееры показывают по-разному" =>
+        "Rewrite all Latin-1 ID3v2 frames with non-ASCII text as UTF-8 (UTF-16 in ID3v2.3), even without mojibake: players show such frames inconsistently",
+    "Отрегулировать порог определения кириллицы" => "Adjust the Cyrillic detection threshold",
+    "Свой порог для полей тегов (через запятую, например: artist=0.1,title=0.3)" =>
+        "Own threshold for tag fields (comma-separated, e.g.: artist=0.1,title=0.3)",
+    "Не применять исправления с уверенностью ниже этой (0–100), а показать их в конце списком для проверки" =>
+        "Do not apply fixes with confidence below this (0–100); list them at the end for review",
+    "Показывать исправления по альбомам (каталогам) одной таблицей на альбом, а с --interactive спрашивать подтверждение сразу для всего альбома" =>
+        "Show fixes grouped by album (directory) as one table per album; with --interactive, confirm the whole album at once",
+    "Проверять исправления исполнителя, альбома и названия с низкой уверенностью по MusicBrainz (нужен интернет): если такой релиз, запись или исполнитель там есть, исправлению можно доверять" =>
+        "Check low-confidence fixes of artist, album and title against MusicBrainz (needs internet): if such a release, recording or artist exists there, the fix can be trusted",
+    "Адрес http-зеркала MusicBrainz или локального прокси к нему для --online-validate, например http://localhost:5000/ws/2: musicbrainz.org отвечает только по https" =>
+        "Address of a plain http MusicBrainz mirror or a local proxy to it for --online-validate, e.g. http://localhost:5000/ws/2: musicbrainz.org only answers over https",
+    "Сверять названия треков в альбомах, где что-то исправлено, с треклистом релиза на Discogs (нужен интернет и персональный токен Discogs): о треке, название которого не похоже ни на один трек релиза, выводится предупреждение" =>
+        "Compare track titles in albums with fixes against the release tracklist on Discogs (needs internet and a Discogs personal token): warn about tracks whose title matches no track of the release",
+    "Адрес локального прокси с TLS к API Discogs для --discogs-token, например http://localhost:8080: api.discogs.com отвечает только по https" =>
+        "Address of a local TLS proxy to the Discogs API for --discogs-token, e.g. http://localhost:8080: api.discogs.com only answers over https",
+    "Приводить название и исполнителя альбома, которые после исправления у треков одного каталога пишутся чуть по-разному, к значению большинства треков" =>
+        "Normalize album title and album artist that end up spelled slightly differently across tracks of one directory after fixing to the majority value",
+    "Настоящая кодировка текста (например, cp1253 для греческого, cp1254 для турецкого): искать только такие кракозябры; в ней же читаются текстовые файлы не в UTF-8" =>
+        "Actual text encoding (e.g. cp1253 for Greek, cp1254 for Turkish): look only for such mojibake; non-UTF-8 text files are read in it too",
+    "Словарь замен (TOML или CSV): точные строки с кракозябрами или /регулярные выражения/ и правильные значения для них; важнее автоматического определения" =>
+        "Replacement dictionary (TOML or CSV): exact mojibake strings or /regular expressions/ with their correct values; takes precedence over automatic detection",
+    "Оставлять исправленный текст как есть: не собирать его в NFC, не убирать управляющие символы, BOM и символы нулевой ширины, не заменять неразрывные пробелы" =>
+        "Leave fixed text as is: do not compose it to NFC, strip control characters, BOMs and zero-width characters or replace non-breaking spaces",
+    "Кодировка, в которой сломанный текст прочитан сейчас (с --to-encoding)" =>
+        "Encoding the broken text is currently read as (with --to-encoding)",
+    "Языки восстановленного текста (через запятую): ru, uk, be, el, tr (по умолчанию ru или язык кодировки --to-encoding)" =>
+        "Languages of the recovered text (comma-separated): ru, uk, be, el, tr (default: ru or the language of --to-encoding)",
+    "Число потоков обработки (0 — по числу ядер)" => "Number of worker threads (0 — one per core)",
+    "Сохранить найденные исправления в план (JSON) для просмотра, правки и команды apply" =>
+        "Save the fixes found to a plan (JSON) for review, editing and the apply command",
+
+    // apply, restore, rename, undo
+    "Файл плана, сохранённый командой scan --plan" => "Plan file saved by scan --plan",
+    "Только показать исправления из плана, ничего не записывая" =>
+        "Only show the fixes from the plan without writing anything",
+    "Восстанавливать из отдельного каталога бэкапов (см. fix --backup-dir)" =>
+        "Restore from a separate backup directory (see fix --backup-dir)",
+    "Только показать, что будет восстановлено, ничего не изменяя" =>
+        "Only show what would be restored without changing anything",
+    "Не удалять .bak файлы после восстановления" => "Do not delete .bak files after restoring",
+    "Удалять только бэкапы старше этого срока: 30d, 12h, 2w (по умолчанию — все)" =>
+        "Only delete backups older than this: 30d, 12h, 2w (default: all)",
+    "Только показать, что будет удалено, ничего не изменяя" =>
+        "Only show what would be deleted, without changing anything",
+    "Только показать новые имена, ничего не переименовывая" =>
+        "Only show the new names without renaming anything",
+    "Также исправлять имена каталогов (начиная с самых глубоких)" =>
+        "Also fix directory names (deepest first)",
+    "Обновить в плейлистах пути к файлам, переименованным командой rename" =>
+        "Update paths in playlists to files renamed by the rename command",
+    "Откатить переименования по журналу предыдущего запуска" =>
+        "Revert renames using the journal of the previous run",
+    "Идентификатор запуска (по умолчанию — последний)" => "Run id (default: the latest)",
+    "Только показать, что будет откачено, ничего не изменяя" =>
+        "Only show what would be reverted without changing anything",
+
+    // Значения параметров
+    "Цветной текст для человека" => "Colored text for humans",
+    "Один JSON-объект на обработанный файл (JSON Lines)" => "One JSON object per processed file (JSON Lines)",
+    "Только ошибки чтения и записи" => "Only read and write errors",
+    "Ошибки и предупреждения" => "Errors and warnings",
+    "Всё, включая исправленные файлы и итоги запуска" => "Everything, including fixed files and run totals",
+    "Только если stdout и stderr — терминал и не задана переменная NO_COLOR" =>
+        "Only if stdout and stderr are terminals and NO_COLOR is not set",
+    "По переменным окружения LC_ALL, LC_MESSAGES, LANG" => "From the LC_ALL, LC_MESSAGES, LANG environment variables",
+    "Полная копия рядом с файлом (.bak)" => "Full copy next to the file (.bak)",
+    "Исходные значения тегов в манифест запуска; .cue по-прежнему копируются" =>
+        "Original tag values in the run manifest; .cue sheets are still copied",
+    "Добавить (нужен некоторым старым плеерам для Windows)" => "Add (needed by some old Windows players)",
+    "Убрать" => "Remove",
+    "Как в исходном файле" => "As in the original file",
+    "Оставить как в исходном файле" => "Keep as in the original file",
+    "\\r\\n (Windows; так ждут .cue многие программы записи дисков)" =>
+        "\\r\\n (Windows; many disc burning programs expect this in .cue sheets)",
+    "Записать исправленный файл отдельной копией: остальные ссылки сохранят прежнее содержимое" =>
+        "Write the fixed file as a separate copy: the other links keep the old contents",
+    "Не трогать файл" => "Leave the file alone",
+    "Jellyfin или Emby: обновляются только каталоги с исправленными файлами" =>
+        "Jellyfin or Emby: only the directories with fixed files are refreshed",
+    "Plex: частичное сканирование каталогов в библиотеках, куда они входят" =>
+        "Plex: partial scan of the directories in the libraries that contain them",
+    "Navidrome и другие серверы Subsonic API: сканирование всей библиотеки" =>
+        "Navidrome and other Subsonic API servers: full library scan",
+};
+
+/// Перевести справку команды и всех её подкоманд на английский
+pub fn english(command: Command) -> Command {
+    let mut command = command.mut_args(english_arg).mut_subcommands(english);
+    if let Some(about) = command
+        .get_about()
+        .and_then(|about| translate(&about.to_string()))
+    {
+        command = command.about(about);
+    }
+    if let Some(about) = command
+        .get_long_about()
+        .and_then(|about| translate(&about.to_string()))
+    {
+        command = command.long_about(about);
+    }
+    command
+}
+
+fn english_arg(mut arg: Arg) -> Arg {
+    if let Some(help) = arg.get_help().and_then(|help| translate(&help.to_string())) {
+        arg = arg.help(help);
+    }
+    if let Some(help) = arg
+        .get_long_help()
+        .and_then(|help| translate(&help.to_string()))
+    {
+        arg = arg.long_help(help);
+    }
+    // Описания значений берутся из ValueEnum, поэтому разбор заменяется обёрткой
+    // с переведёнными описаниями
+    match arg.get_id().as_str() {
+        "output" => arg.value_parser(EnglishValues::<OutputFormat>::new()),
+        "log_level" => arg.value_parser(EnglishValues::<LogLevel>::new()),
+        "color" => arg.value_parser(EnglishValues::<ColorMode>::new()),
+        "locale" => arg.value_parser(EnglishValues::<LocaleMode>::new()),
+        "backup_mode" => arg.value_parser(EnglishValues::<BackupMode>::new()),
+        "bom" => arg.value_parser(EnglishValues::<BomMode>::new()),
+        "line_endings" => arg.value_parser(EnglishValues::<LineEndingMode>::new()),
+        "id3" => arg.value_parser(EnglishValues::<Id3Mode>::new()),
+        "id3v1" => arg.value_parser(EnglishValues::<Id3v1Mode>::new()),
+        "yo" => arg.value_parser(EnglishValues::<YoMode>::new()),
+        "transliterate" => arg.value_parser(EnglishValues::<TransliterateMode>::new()),
+        "hardlinks" => arg.value_parser(EnglishValues::<HardlinkMode>::new()),
+        "notify_server" => arg.value_parser(EnglishValues::<MediaServer>::new()),
+        _ => arg,
+    }
+}
+
+fn translate(text: &str) -> Option<&'static str> {
+    ENGLISH.get(text).copied()
+}
+
+/// Разбор значений `E` как у clap, но с описаниями значений на английском
+#[derive(Clone)]
+struct EnglishValues<E>(PhantomData<E>);
+
+impl<E> EnglishValues<E> {
+    fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ValueEnum + Clone + Send + Sync + 'static> TypedValueParser for EnglishValues<E> {
+    type Value = E;
+
+    fn parse_ref(&self, cmd: &Command, arg: Option<&Arg>, value: &OsStr) -> Result<E, clap::Error> {
+        EnumValueParser::<E>::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let values = E::value_variants().iter().filter_map(|value| {
+            let value = value.to_possible_value()?;
+            Some(
+                match value
+                    .get_help()
+                    .and_then(|help| translate(&help.to_string()))
+                {
+                    Some(help) => value.help(help),
+                    None => value,
+                },
+            )
+        });
+        Some(Box::new(values))
+    }
+}
@@ -0,0 +1,102 @@
+//! Внешняя команда для каждого исправленного файла (--exec), как у `find -exec`: `{}`
+//! заменяется путём файла, а если `{}` нет, путь добавляется последним аргументом.
+//!
+//! Команда запускается без оболочки: строка делится на слова по пробелам, слова в кавычках
+//! ('…' или "…") не делятся. Для перенаправлений и конвейеров — `sh -c '…' _ {}`
+
+use cyrtag_fix::locale::tr;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::str::FromStr;
+
+/// Команда --exec, разбитая на слова
+#[derive(Debug, Clone)]
+pub struct Hook {
+    source: String,
+    words: Vec<String>,
+}
+
+impl Hook {
+    /// Запустить команду для файла `path` и дождаться её завершения
+    pub fn run(&self, path: &Path) -> io::Result<Output> {
+        let mut args: Vec<OsString> = self.words.iter().map(|w| substitute(w, path)).collect();
+        if !self.words.iter().any(|w| w.contains("{}")) {
+            args.push(path.into());
+        }
+        Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .output()
+    }
+}
+
+impl FromStr for Hook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = split_words(s)?;
+        if words.is_empty() {
+            return Err(tr("пустая команда", "empty command").to_string());
+        }
+        Ok(Self {
+            source: s.to_string(),
+            words,
+        })
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Слово команды с путём вместо `{}`; путь подставляется как есть, даже не в UTF-8
+fn substitute(word: &str, path: &Path) -> OsString {
+    let mut arg = OsString::new();
+    for (i, part) in word.split("{}").enumerate() {
+        if i > 0 {
+            arg.push(path);
+        }
+        arg.push(part);
+    }
+    arg
+}
+
+/// Слова команды: разделитель — пробельные символы, кавычки объединяют слово
+fn split_words(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(tr("незакрытая кавычка", "unclosed quote").to_string());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
@@ -0,0 +1,94 @@
+//! ID3v1 — 128 байт в конце файла: пересборка из исправленного основного тега и удаление.
+//!
+//! Кодировка у ID3v1 не указывается: по стандарту это Latin-1, но программы для Windows
+//! писали и читали кириллицу в cp1251. lofty записывает в ID3v1 байты строки в UTF-8,
+//! поэтому блок собирается здесь
+
+use crate::translit::{self, Scheme};
+
+/// Длина тега ID3v1
+pub const TAG_LEN: usize = 128;
+
+/// Длина текстовых полей: название, исполнитель, альбом, комментарий
+const FIELD_LEN: usize = 30;
+const TITLE: usize = 3;
+const ARTIST: usize = TITLE + FIELD_LEN;
+const ALBUM: usize = ARTIST + FIELD_LEN;
+const COMMENT: usize = ALBUM + FIELD_LEN + 4;
+/// В ID3v1.1 последние два байта комментария — ноль и номер трека
+const TRACK: usize = COMMENT + FIELD_LEN - 1;
+
+/// Что делать с ID3v1 у исправляемых файлов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Id3v1Policy {
+    /// Не трогать
+    #[default]
+    Keep,
+    /// Пересобрать из исправленного тега в cp1251: так кириллицу показывают старые плееры
+    Cp1251,
+    /// Пересобрать в Latin-1, кириллица — транслитом (`Кино` → `Kino`)
+    Translit,
+    /// Удалить
+    Strip,
+}
+
+/// Текстовые поля, которые переносятся в ID3v1 (остальные остаются как были)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// Тег ID3v1, если `tail` (последние 128 байт файла) им является
+pub fn parse(tail: &[u8]) -> Option<[u8; TAG_LEN]> {
+    let tag: [u8; TAG_LEN] = tail.try_into().ok()?;
+    tag.starts_with(b"TAG").then_some(tag)
+}
+
+/// Тег `old` с текстовыми полями из `fields`; год, трек и жанр сохраняются. Поле, которого
+/// нет в `fields`, остаётся прежним
+pub fn rebuild(old: &[u8; TAG_LEN], fields: &Fields, policy: Id3v1Policy) -> [u8; TAG_LEN] {
+    let mut tag = *old;
+    // ID3v1.1: номер трека занимает конец комментария
+    let has_track = old[TRACK - 1] == 0 && old[TRACK] != 0;
+    let comment_len = if has_track { FIELD_LEN - 2 } else { FIELD_LEN };
+    for (value, start, len) in [
+        (&fields.title, TITLE, FIELD_LEN),
+        (&fields.artist, ARTIST, FIELD_LEN),
+        (&fields.album, ALBUM, FIELD_LEN),
+        (&fields.comment, COMMENT, comment_len),
+    ] {
+        if let Some(value) = value {
+            let mut bytes = encode(value, policy);
+            bytes.resize(len, 0);
+            tag[start..start + len].copy_from_slice(&bytes);
+        }
+    }
+    tag
+}
+
+/// Текст в байтах ID3v1; символ, которого нет в кодировке, — по транслиту или `?`
+fn encode(text: &str, policy: Id3v1Policy) -> Vec<u8> {
+    let mut out = Vec::new();
+    for c in text.chars() {
+        let encoded = match policy {
+            Id3v1Policy::Cp1251 => {
+                let mut buf = [0; 4];
+                let (bytes, _, unmappable) =
+                    encoding_rs::WINDOWS_1251.encode(c.encode_utf8(&mut buf));
+                (!unmappable).then(|| bytes.into_owned())
+            }
+            _ => u8::try_from(u32::from(c)).ok().map(|byte| vec![byte]),
+        };
+        match encoded {
+            Some(bytes) => out.extend(bytes),
+            None => match translit::transliterate_char(c, Scheme::Simple) {
+                Some(latin) => out.extend_from_slice(latin.as_bytes()),
+                None => out.push(b'?'),
+            },
+        }
+    }
+    out
+}
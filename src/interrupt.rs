@@ -0,0 +1,60 @@
+//! Мягкая остановка по Ctrl-C и SIGTERM: файл, который сейчас записывается, дописывается
+//! до конца, новые файлы не берутся, а итог печатается по уже обработанным. Повторный
+//! Ctrl-C завершает программу сразу
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Код возврата после остановки по сигналу, как у оболочек: 128 + SIGINT
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Пришёл ли сигнал остановки
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+pub fn install() {
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+    unsafe extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+
+    extern "C" fn handle(signum: i32) {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            // SAFETY: _exit безопасна в обработчике сигнала
+            unsafe { _exit(128 + signum) }
+        }
+    }
+
+    // SAFETY: обработчик только меняет атомарный флаг или вызывает _exit
+    unsafe {
+        signal(SIGINT, handle);
+        signal(SIGTERM, handle);
+    }
+}
+
+#[cfg(windows)]
+pub fn install() {
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+    }
+
+    /// Ctrl-C, Ctrl-Break, закрытие окна; при повторном сигнале — обработчик по умолчанию,
+    /// то есть немедленное завершение
+    extern "system" fn handle(_ctrl_type: u32) -> i32 {
+        i32::from(!INTERRUPTED.swap(true, Ordering::Relaxed))
+    }
+
+    // SAFETY: обработчик только меняет атомарный флаг
+    unsafe {
+        SetConsoleCtrlHandler(handle, 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
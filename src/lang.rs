@@ -0,0 +1,235 @@
+//! Языковые модели для проверки восстановленного текста: насколько он похож на слова языка,
+//! а не на случайный набор кириллических букв
+
+use encoding_rs::{
+    Encoding, IBM866, ISO_8859_5, ISO_8859_7, KOI8_R, KOI8_U, WINDOWS_1251, WINDOWS_1253,
+    WINDOWS_1254, X_MAC_CYRILLIC,
+};
+use phf::{Set, phf_set};
+use std::fmt;
+
+/// Сочетания двух букв, встречающиеся в русских словах (в нижнем регистре).
+/// Неверно выбранная кодировка даёт кириллицу, но с редкими сочетаниями («бЕВЕПМХИ», «Тхїх№эшщ»)
+static RUSSIAN_BIGRAMS: Set<&'static str> = phf_set! {
+    "аб", "ав", "аг", "ад", "ае", "аж", "аз", "аи", "ай", "ак", "ал", "ам", "ан", "ап",
+    "ар", "ас", "ат", "ау", "аф", "ах", "ач", "аш", "ащ", "аэ", "аю", "ая", "ба", "бе",
+    "би", "бк", "бл", "бо", "бр", "бу", "бщ", "бъ", "бы", "бь", "бэ", "бю", "ва", "вг",
+    "вд", "ве", "ви", "вк", "вл", "вм", "вн", "во", "вр", "вс", "вт", "ву", "вч", "вы",
+    "вь", "вя", "вё", "га", "гв", "гд", "ге", "ги", "гк", "гл", "гн", "го", "гр", "гу",
+    "да", "дв", "дд", "де", "дж", "ди", "дк", "дл", "дм", "дн", "до", "дп", "др", "дс",
+    "дт", "ду", "дц", "дъ", "ды", "дь", "дя", "дё", "еб", "ев", "ег", "ед", "ее", "еж",
+    "ез", "ей", "ек", "ел", "ем", "ен", "ео", "еп", "ер", "ес", "ет", "еф", "ех", "ец",
+    "еч", "еш", "ещ", "ею", "ея", "жа", "жб", "жд", "же", "жж", "жи", "жн", "жо", "жу",
+    "жь", "жю", "жё", "за", "зв", "зг", "зд", "зе", "зж", "зи", "зк", "зл", "зм", "зн",
+    "зо", "зр", "зу", "зъ", "зы", "иа", "иб", "ив", "иг", "ид", "ие", "иж", "из", "ии",
+    "ий", "ик", "ил", "им", "ин", "ио", "ип", "ир", "ис", "ит", "иу", "иф", "их", "иц",
+    "ич", "иш", "ищ", "ию", "ия", "йг", "йд", "йе", "йк", "йл", "йн", "йп", "йс", "йт",
+    "йф", "ка", "кв", "ке", "ки", "кк", "кл", "кн", "ко", "кр", "кс", "кт", "ку", "ла",
+    "лд", "ле", "лж", "ли", "лк", "лл", "лн", "ло", "лс", "лт", "лу", "лы", "ль", "лю",
+    "ля", "лё", "ма", "мб", "мв", "ме", "ми", "мк", "мл", "мм", "мн", "мо", "мп", "мс",
+    "му", "мф", "мы", "мь", "мя", "мё", "на", "нб", "нв", "нг", "нд", "не", "нж", "ни",
+    "нк", "нн", "но", "нр", "нс", "нт", "ну", "нц", "нч", "нщ", "ны", "нь", "ню", "ня",
+    "нё", "об", "ов", "ог", "од", "ое", "ож", "оз", "ои", "ой", "ок", "ол", "ом", "он",
+    "оо", "оп", "ор", "ос", "от", "оф", "ох", "оц", "оч", "ош", "ощ", "оэ", "ою", "оя",
+    "оё", "па", "пе", "пи", "пк", "пл", "по", "пп", "пр", "пс", "пт", "пу", "пь", "пю",
+    "пя", "ра", "рб", "рв", "рг", "рд", "ре", "ри", "рк", "рл", "рм", "рн", "ро", "рс",
+    "рт", "ру", "рц", "рч", "рш", "ры", "рь", "ря", "рё", "са", "сб", "св", "се", "си",
+    "ск", "сл", "см", "сн", "со", "сп", "сс", "ст", "су", "сф", "сч", "съ", "сы", "сь",
+    "сю", "ся", "сё", "та", "тв", "те", "ти", "тк", "тл", "тн", "то", "тр", "тс", "ту",
+    "тц", "ты", "ть", "тю", "тя", "тё", "уа", "уб", "ув", "уг", "уд", "уж", "уз", "уй",
+    "ук", "ул", "ум", "ун", "уп", "ур", "ус", "ут", "уф", "ух", "уц", "уч", "уш", "уэ",
+    "ую", "фа", "фе", "фи", "фо", "фр", "фу", "фё", "ха", "хи", "хл", "хн", "хо", "хр",
+    "хс", "ца", "цв", "це", "ци", "цк", "цу", "цы", "ча", "че", "чи", "чк", "чн", "чт",
+    "чу", "чч", "чш", "чь", "чё", "ша", "ше", "ши", "шк", "шн", "шо", "шт", "шу", "шь",
+    "шю", "шё", "ща", "ще", "щи", "щн", "щу", "щё", "ъе", "ъю", "ъя", "ъё", "ыв", "ыг",
+    "ые", "ый", "ык", "ыл", "ым", "ыр", "ыс", "ыт", "ых", "ыч", "ыш", "ьб", "ьв", "ьг",
+    "ьд", "ье", "ьи", "ьк", "ьм", "ьн", "ьо", "ьп", "ьс", "ьт", "ьш", "ью", "ья", "ьё",
+    "эг", "эд", "эй", "эк", "эл", "эр", "эс", "эт", "эх", "эш", "юа", "юб", "юг", "юд",
+    "юж", "юз", "юи", "юк", "юл", "юм", "юн", "юр", "ют", "юч", "юш", "ющ", "яб", "яв",
+    "яд", "яз", "як", "ял", "ям", "ян", "яр", "ят", "яу", "ях", "яч", "ящ", "яя", "ёв",
+    "ёд", "ёж", "ёз", "ёл", "ём", "ён", "ёп", "ёр", "ёт", "ёх",
+};
+
+/// Сочетания украинских слов, которых нет среди русских: с `і`, `ї`, `є`, `ґ` и другие.
+/// Русские сочетания из букв украинского алфавита тоже считаются знакомыми
+static UKRAINIAN_BIGRAMS: Set<&'static str> = phf_set! {
+    "ац", "аї", "бд", "бз", "бі", "вб", "вж", "вц", "ві", "гі", "дз", "дю", "ді", "еа",
+    "жг", "жк", "жм", "жя", "зб", "зі", "иє", "иї", "йм", "кі", "лі", "мі", "ні", "ої",
+    "пі", "рп", "рх", "рщ", "рі", "сі", "тд", "тт", "ті", "фі", "хе", "хт", "ху", "ць",
+    "цю", "ця", "ці", "чо", "чі", "що", "ьз", "юв", "яс", "яц", "яє", "єв", "єд", "єм",
+    "єн", "єр", "єю", "ів", "іг", "ід", "іж", "ій", "ік", "іл", "ім", "ін", "іо", "іп",
+    "ір", "іс", "іт", "іх", "іч", "іш", "іщ", "ія", "іє", "ії", "їв", "їд", "їж", "їн",
+    "їт", "їх", "її", "ґа", "ґр", "ґу",
+};
+
+/// Сочетания белорусских слов, которых нет среди русских: с `і`, `ў` и другие.
+/// Русские сочетания из букв белорусского алфавита тоже считаются знакомыми
+static BELARUSIAN_BIGRAMS: Set<&'static str> = phf_set! {
+    "ац", "аё", "аі", "аў", "бс", "бя", "бё", "бі", "ві", "гі", "дз", "дч", "дю", "еў",
+    "жл", "жс", "жы", "зб", "зь", "зя", "зё", "зі", "йр", "кі", "лі", "мі", "ні", "оў",
+    "пш", "пі", "рэ", "сц", "сі", "тэ", "уў", "хт", "ху", "хі", "цц", "ць", "цэ", "ця",
+    "цё", "ці", "чо", "чы", "чэ", "шм", "шч", "шы", "ыж", "ыз", "ын", "ып", "ыц", "ыя",
+    "ыё", "ыі", "ьц", "эа", "эж", "эз", "эц", "юц", "яе", "яп", "яс", "яц", "яш", "яў",
+    "ёц", "ёў", "іб", "ів", "іг", "ід", "іж", "ік", "іл", "ім", "ін", "ір", "іс", "іт",
+    "іх", "іц", "іч", "іш", "ія", "іў", "ўд", "ўк", "ўл", "ўн", "ўс", "ўт", "ўц", "ўш",
+    "ўі",
+};
+
+/// Язык, на котором ожидается восстановленный текст
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Russian,
+    Ukrainian,
+    Belarusian,
+    Greek,
+    Turkish,
+}
+
+impl Language {
+    pub const ALL: [Language; 5] = [
+        Language::Russian,
+        Language::Ukrainian,
+        Language::Belarusian,
+        Language::Greek,
+        Language::Turkish,
+    ];
+
+    /// Язык по коду ISO 639-1: `ru`, `uk`, `be`, `el`, `tr`
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|lang| lang.code().eq_ignore_ascii_case(code.trim()))
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Russian => "ru",
+            Language::Ukrainian => "uk",
+            Language::Belarusian => "be",
+            Language::Greek => "el",
+            Language::Turkish => "tr",
+        }
+    }
+
+    /// Язык, текст на котором обычно хранят в этой кодировке
+    pub fn for_encoding(encoding: &'static Encoding) -> Option<Self> {
+        if [WINDOWS_1251, KOI8_R, IBM866, ISO_8859_5, X_MAC_CYRILLIC].contains(&encoding) {
+            Some(Language::Russian)
+        } else if encoding == KOI8_U {
+            Some(Language::Ukrainian)
+        } else if [WINDOWS_1253, ISO_8859_7].contains(&encoding) {
+            Some(Language::Greek)
+        } else if encoding == WINDOWS_1254 {
+            Some(Language::Turkish)
+        } else {
+            None
+        }
+    }
+
+    /// Строчная или заглавная буква алфавита языка
+    pub fn is_letter(self, c: char) -> bool {
+        let c = c.to_lowercase().next().unwrap_or(c);
+        match self {
+            Language::Russian => matches!(c, 'а'..='я' | 'ё'),
+            Language::Ukrainian => {
+                matches!(c, 'а'..='я' | 'є' | 'і' | 'ї' | 'ґ') && !matches!(c, 'ъ' | 'ы' | 'э')
+            }
+            Language::Belarusian => {
+                matches!(c, 'а'..='я' | 'ё' | 'і' | 'ў') && !matches!(c, 'и' | 'щ' | 'ъ')
+            }
+            Language::Greek => matches!(c, 'ά'..='ώ'),
+            Language::Turkish => {
+                c.is_ascii_lowercase()
+                    || matches!(c, 'ç' | 'ğ' | 'ı' | 'ö' | 'ş' | 'ü' | 'â' | 'î' | 'û')
+            }
+        }
+    }
+
+    /// Встречается ли пара строчных букв в словах языка. Для греческого и турецкого
+    /// модели нет: знакомой считается любая пара из букв алфавита
+    fn knows(self, pair: &str) -> bool {
+        let own = match self {
+            Language::Russian => return RUSSIAN_BIGRAMS.contains(pair),
+            Language::Ukrainian => &UKRAINIAN_BIGRAMS,
+            Language::Belarusian => &BELARUSIAN_BIGRAMS,
+            Language::Greek | Language::Turkish => return true,
+        };
+        own.contains(pair) || RUSSIAN_BIGRAMS.contains(pair)
+    }
+
+    /// Доля пар соседних букв в словах `text`, встречающихся в языке (0–1).
+    /// Учитываются пары, где есть хотя бы одна буква алфавита; смесь с латиницей
+    /// или чужими буквами внутри слова («Сoсo») считается незнакомой парой. Если таких пар нет — 1
+    pub fn word_likeness(self, text: &str) -> f64 {
+        let mut total = 0usize;
+        let mut known = 0usize;
+
+        for word in text.split(|c: char| !c.is_alphabetic()) {
+            let letters: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+            for pair in letters.windows(2) {
+                if pair.iter().any(|&c| self.is_letter(c)) {
+                    total += 1;
+                    if pair.iter().all(|&c| self.is_letter(c))
+                        && self.knows(String::from_iter(pair).as_str())
+                    {
+                        known += 1;
+                    }
+                }
+            }
+        }
+
+        if total == 0 {
+            1.0
+        } else {
+            known as f64 / total as f64
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Набор языков, на которых ожидается восстановленный текст; по умолчанию — только русский
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Languages(u8);
+
+impl Default for Languages {
+    fn default() -> Self {
+        Self::new([Language::Russian])
+    }
+}
+
+impl Languages {
+    pub fn new(languages: impl IntoIterator<Item = Language>) -> Self {
+        Self(
+            languages
+                .into_iter()
+                .fold(0, |mask, lang| mask | 1 << lang as u8),
+        )
+    }
+
+    pub fn contains(self, lang: Language) -> bool {
+        self.0 & 1 << lang as u8 != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Language> {
+        Language::ALL
+            .into_iter()
+            .filter(move |&lang| self.contains(lang))
+    }
+
+    /// Буква алфавита одного из языков
+    pub fn is_letter(self, c: char) -> bool {
+        self.iter().any(|lang| lang.is_letter(c))
+    }
+
+    /// Похожесть на слова самого подходящего из языков, 0–1; см. [`Language::word_likeness`]
+    pub fn word_likeness(self, text: &str) -> f64 {
+        self.iter()
+            .map(|lang| lang.word_likeness(text))
+            .fold(0.0, f64::max)
+    }
+}
@@ -0,0 +1,67 @@
+//! Библиотека для исправления кириллических кракозябр (cp1251, KOI8-R, CP866 -> UTF-8)
+//! в тегах музыкальных файлов, .cue и плейлистах.
+//!
+//! ```no_run
+//! use cyrtag_fix::{AudioFixer, BackupManager, Detector};
+//!
+//! let fixer = AudioFixer::new(Detector::default());
+//! if let Some(fix) = fixer.scan("track.mp3".as_ref()).unwrap() {
+//!     fixer.apply("track.mp3".as_ref(), fix, &BackupManager::new(false)).unwrap();
+//! }
+//! ```
+
+pub mod album;
+pub mod asf;
+pub mod atomic;
+pub mod audio;
+pub mod backup;
+pub mod detect;
+pub mod dictionary;
+pub mod error;
+pub mod fields;
+pub mod glob;
+pub mod id3v1;
+pub mod json;
+pub mod lang;
+pub mod locale;
+pub mod manifest;
+pub mod mp4chap;
+pub mod normalize;
+pub mod plan;
+pub mod progress;
+pub mod regex;
+pub mod rename;
+pub mod riff;
+pub mod state;
+pub mod text;
+pub mod time;
+pub mod translit;
+pub mod xattr;
+pub mod yo;
+pub mod zip;
+
+pub use asf::{AsfFix, AsfFixer};
+pub use audio::{AudioFix, AudioFixer, FieldFix, Id3Version, Id3v1Fix};
+pub use backup::BackupManager;
+pub use detect::{Detection, Detector, Mojibake, fix_mojibake};
+pub use dictionary::Dictionary;
+pub use error::{Error, Result};
+pub use fields::FieldFilter;
+pub use id3v1::Id3v1Policy;
+pub use lang::{Language, Languages};
+pub use manifest::Manifest;
+pub use plan::Plan;
+pub use rename::{RenameJournal, Renamer};
+pub use text::{BomPolicy, LineChange, LineEndings, TextFix, TextFixer, TextFormat};
+
+use phf::{Set, phf_set};
+
+/// Расширения аудио-файлов, теги которых обрабатываются по умолчанию
+pub static AUDIO_EXTENSIONS: Set<&'static str> = phf_set! {
+    "mp3", "flac", "m4a", "m4b", "mp4", "ogg", "opus", "spx", "wav", "ape", "wv", "mpc", "wma",
+};
+/// Расширения WMA/ASF: их теги читаются модулем `asf`, а не lofty
+pub static ASF_EXTENSIONS: Set<&'static str> = phf_set! {"wma", "asf"};
+/// Расширения текстовых файлов, которые перекодируются в UTF-8
+pub static TEXT_EXTENSIONS: Set<&'static str> =
+    phf_set! {"cue", "m3u", "m3u8", "log", "accurip", "lrc"};
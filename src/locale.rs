@@ -0,0 +1,64 @@
+//! Язык сообщений программы: русский (по умолчанию) или английский
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Язык сообщений
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+static ENGLISH: AtomicBool = AtomicBool::new(false);
+
+/// Выбрать язык сообщений для всего процесса
+pub fn set(locale: Locale) {
+    ENGLISH.store(locale == Locale::En, Ordering::Relaxed);
+}
+
+pub fn get() -> Locale {
+    if ENGLISH.load(Ordering::Relaxed) {
+        Locale::En
+    } else {
+        Locale::Ru
+    }
+}
+
+/// Язык из переменных окружения `LC_ALL`, `LC_MESSAGES`, `LANG`: русский для `ru_*`,
+/// а также если язык не задан или задан `C`/`POSIX` (как обычно в Windows, cron и
+/// контейнерах); для остальных языков — английский
+pub fn from_env() -> Locale {
+    let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let Some(value) = value else {
+        return Locale::Ru;
+    };
+    let language = value.split(['_', '.', '@', '-']).next().unwrap_or_default();
+    match language.to_ascii_lowercase().as_str() {
+        "ru" | "c" | "posix" | "" => Locale::Ru,
+        _ => Locale::En,
+    }
+}
+
+/// Вариант сообщения на текущем языке: `tr("Готово!", "Done!")`
+pub fn tr<T>(ru: T, en: T) -> T {
+    match get() {
+        Locale::Ru => ru,
+        Locale::En => en,
+    }
+}
+
+/// `format!` с вариантами строки на русском и английском; аргументы общие:
+/// `tr_format!("{n} файлов", "{n} files", n = 3)`
+#[macro_export]
+macro_rules! tr_format {
+    ($ru:literal, $en:literal $(, $($args:tt)*)?) => {
+        match $crate::locale::get() {
+            $crate::locale::Locale::Ru => format!($ru $(, $($args)*)?),
+            $crate::locale::Locale::En => format!($en $(, $($args)*)?),
+        }
+    };
+}
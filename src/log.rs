@@ -0,0 +1,89 @@
+//! Журнал работы в файле (--log-file): ошибки, предупреждения и обработанные файлы
+//! с меткой времени и уровнем, чтобы после долгого запуска найти проблемные файлы через grep
+
+use clap::ValueEnum;
+use cyrtag_fix::time::{format_iso8601, now_secs};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Уровень подробности журнала
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Только ошибки чтения и записи
+    Error,
+    /// Ошибки и предупреждения
+    #[default]
+    Warn,
+    /// Всё, включая исправленные файлы и итоги запуска
+    Info,
+}
+
+impl LogLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+        }
+    }
+}
+
+struct LogFile {
+    file: File,
+    level: LogLevel,
+}
+
+static LOG: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Открыть журнал: записи дописываются в конец файла
+pub fn open(path: &Path, level: LogLevel) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(LogFile { file, level });
+    Ok(())
+}
+
+/// Записать в журнал одну строку
+pub fn write(level: LogLevel, message: &str) {
+    write_all([(level, message)]);
+}
+
+/// Записать строки одним блоком, чтобы записи параллельных потоков не перемешивались
+pub fn write_all<'a>(entries: impl IntoIterator<Item = (LogLevel, &'a str)>) {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+    let time = format_iso8601(now_secs());
+    for (level, message) in entries {
+        if level <= log.level {
+            let _ = writeln!(
+                log.file,
+                "{time} {:<5} {}",
+                level.as_str(),
+                plain(message).trim()
+            );
+        }
+    }
+    let _ = log.file.flush();
+}
+
+/// Текст без escape-последовательностей цвета терминала
+fn plain(message: &str) -> String {
+    let mut plain = String::with_capacity(message.len());
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // `ESC [ параметры m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
@@ -1,294 +1,2995 @@
-use clap::Parser;
+mod completions;
+mod config;
+mod csv;
+mod discogs;
+mod help;
+mod hook;
+mod interrupt;
+mod log;
+mod mpd;
+mod musicbrainz;
+mod output;
+mod process;
+mod prompt;
+mod report;
+mod server;
+mod watch;
+
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::*;
-use encoding_rs::{WINDOWS_1251, WINDOWS_1252};
-use lofty::config::{ParseOptions, WriteOptions};
-use lofty::prelude::*;
-use lofty::probe::Probe;
-use lofty::tag::TagExt;
-use phf::{Set, phf_set};
+use completions::Shell;
+use config::Config;
+use cyrtag_fix::audio;
+use cyrtag_fix::backup::{self, BackupDir, BackupJournal};
+use cyrtag_fix::detect::{DEFAULT_CYR_THRESHOLD, encoding_name};
+use cyrtag_fix::fields;
+use cyrtag_fix::glob::Pattern;
+use cyrtag_fix::locale::{self, Locale, tr};
+use cyrtag_fix::manifest::{self, Manifest};
+use cyrtag_fix::progress::{self, Progress};
+use cyrtag_fix::state::{Marker, State};
+use cyrtag_fix::time;
+use cyrtag_fix::tr_format;
+use cyrtag_fix::translit::Scheme;
+use cyrtag_fix::yo::Yo;
+use cyrtag_fix::zip;
+use cyrtag_fix::{
+    AUDIO_EXTENSIONS, AsfFixer, AudioFixer, BackupManager, BomPolicy, Detector, Dictionary,
+    FieldFilter, Id3Version, Id3v1Policy, Language, Languages, LineEndings, Mojibake, Plan,
+    RenameJournal, Renamer, TEXT_EXTENSIONS, TextFixer,
+};
+use discogs::Discogs;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use hook::Hook;
+use lofty::prelude::ItemKey;
+use log::LogLevel;
+use mpd::Mpd;
+use musicbrainz::MusicBrainz;
+use output::{FileReport, OutputFormat, Verbosity};
+use process::{Processor, Summary};
+use prompt::Prompter;
+use server::{MediaServer, Notifier, PathMap};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Debug;
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::Mutex;
+use std::time::Duration;
+use walkdir::{DirEntry, WalkDir};
+
+/// Код возврата scan: найдены кракозябры
+const EXIT_FOUND: i32 = 1;
+/// Код возврата scan: были ошибки чтения или обхода
+const EXIT_ERRORS: i32 = 2;
 
-static AUDIO_EXTENSIONS: Set<&'static str> = phf_set! {"mp3", "flac", "m4a", "mp4", "ogg", "wav"};
-static TEXT_EXTENSIONS: Set<&'static str> = phf_set! {"cue"};
-static LATIN_DIACRITICS: Set<char> = phf_set! {
-'ä', 'ö', 'ü', 'ß', 'Ä', 'Ö', 'Ü', 'é', 'è', 'ê', 'ë', 'á', 'à', 'â', 'å', 'í', 'ì', 'î', 'ó',
-'ò', 'ô', 'ú', 'ù', 'û'};
+/// Ошибки обхода каталогов, ещё не учтённые в итогах прохода
+static WALK_ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
-const WEIGHT_CYR: f64 = 1.0;
-const WEIGHT_DIACRITICS: f64 = 0.8;
+/// Забрать накопленные ошибки обхода: в --watch каталоги обходятся снова и снова, и без
+/// этого список рос бы всё время наблюдения
+fn take_walk_errors() -> Vec<String> {
+    std::mem::take(&mut *WALK_ERRORS.lock().unwrap_or_else(|e| e.into_inner()))
+}
 
-/// Простая утилита для исправления кириллических кракозябр в тегах музыкальных и .cue файлов
+/// Простая утилита для исправления кириллических кракозябр в тегах музыкальных файлов, .cue и плейлистах
 #[derive(Parser, Debug)]
 #[command(
     version,
-    about = "Утилита для исправления кириллических кракозябр кодировки cp1251 в тегах музыкальных и .cue файлов",
+    about = "Утилита для исправления кириллических кракозябр кодировки cp1251 в тегах музыкальных файлов, .cue и плейлистах",
     arg_required_else_help = true
 )]
-struct Args {
-    /// Путь к папке с музыкой
-    path: PathBuf,
+struct Cli {
+    /// Файл настроек (по умолчанию ~/.config/cyrtag-fixer/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 
-    /// Не создавать .bak файлы (по умолчанию создаются)
-    #[arg(long)]
-    no_backup: bool,
+    /// Дописывать ошибки и предупреждения (с --log-level info — и обработанные файлы) в журнал
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
 
-    /// Принудительно считать все .cue файлами в cp1251 (без попыток угадать)
-    #[arg(long)]
-    force_cp1251_cue: bool,
+    /// Подробность журнала --log-file
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Warn)]
+    log_level: LogLevel,
 
-    /// Отрегулировать порог определения кириллицы
-    #[arg(long, default_value_t = 0.2)]
-    cyr_threshold: f64,
+    /// Раскрашивать вывод
+    #[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Язык сообщений программы (не путать с --lang — языком текста в тегах)
+    #[arg(long, global = true, value_enum, default_value_t = LocaleMode::Auto)]
+    locale: LocaleMode,
+
+    #[command(subcommand)]
+    command: Command,
 }
 
-struct BackupManager {
-    no_backup: bool,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Исправить теги, .cue и плейлисты на месте
+    Fix(Box<FixArgs>),
+    /// Только найти кракозябры, ничего не изменяя.
+    /// Код возврата: 0 — всё чисто, 1 — найдены кракозябры, 2 — были ошибки
+    #[command(alias = "check")]
+    Scan(ScanArgs),
+    /// Применить план исправлений, сохранённый командой scan --plan
+    Apply(ApplyArgs),
+    /// Восстановить файлы из .bak бэкапов
+    Restore(RestoreArgs),
+    /// Удалить бэкапы, созданные fix, по журналу бэкапов
+    CleanBackups(CleanBackupsArgs),
+    /// Исправить кракозябры в именах файлов
+    Rename(RenameArgs),
+    /// Откатить изменения тегов по манифесту запуска
+    Undo(UndoArgs),
+    /// Исправить кракозябры в строке из аргументов или stdin и показать цепочку кодировок
+    FixString(FixStringArgs),
+    /// Напечатать скрипт автодополнения для оболочки
+    Completions(CompletionsArgs),
 }
 
-fn main() {
-    let args = Args::parse();
+/// Способ сохранения оригиналов перед изменением
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupMode {
+    /// Полная копия рядом с файлом (.bak)
+    Copy,
+    /// Исходные значения тегов в манифест запуска; .cue по-прежнему копируются
+    Manifest,
+}
 
-    if !args.path.exists() {
-        eprintln!(
-            "{}: путь не найден: {}",
-            "Ошибка".red(),
-            args.path.display()
-        );
-        std::process::exit(1);
+/// Сжатие полных копий
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupCompress {
+    /// gzip (.gz)
+    Gzip,
+}
+
+/// BOM в начале записываемых текстовых файлов
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BomMode {
+    /// Добавить (нужен некоторым старым плеерам для Windows)
+    Add,
+    /// Убрать
+    Strip,
+    /// Оставить как в исходном файле
+    Keep,
+}
+
+impl BomMode {
+    fn policy(self) -> BomPolicy {
+        match self {
+            BomMode::Add => BomPolicy::Add,
+            BomMode::Strip => BomPolicy::Strip,
+            BomMode::Keep => BomPolicy::Keep,
+        }
     }
+}
 
-    println!(
-        "{} {}",
-        "Старт обработки каталога:".green().bold(),
-        args.path.display()
-    );
+/// Когда раскрашивать вывод
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorMode {
+    /// Только если stdout и stderr — терминал и не задана переменная NO_COLOR
+    Auto,
+    Always,
+    Never,
+}
 
-    let mut count_fixed = 0usize;
-    let bm = BackupManager {
-        no_backup: args.no_backup,
-    };
+impl ColorMode {
+    fn apply(self) {
+        match self {
+            // colored сам учитывает NO_COLOR, CLICOLOR и терминал ли stdout;
+            // строки для stderr раскрашиваются заранее, поэтому проверяется и он
+            ColorMode::Auto if !io::stderr().is_terminal() => colored::control::set_override(false),
+            ColorMode::Auto => {}
+            ColorMode::Always => colored::control::set_override(true),
+            ColorMode::Never => colored::control::set_override(false),
+        }
+    }
+}
 
-    for entry in WalkDir::new(&args.path).follow_links(true) {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(err) => {
-                eprintln!("{}: {}", "Ошибка обхода".red(), err);
-                continue;
-            }
-        };
+/// Язык сообщений программы
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum LocaleMode {
+    /// По переменным окружения LC_ALL, LC_MESSAGES, LANG
+    Auto,
+    Ru,
+    En,
+}
 
-        if !entry.file_type().is_file() {
-            continue;
+impl LocaleMode {
+    fn locale(self) -> Locale {
+        match self {
+            LocaleMode::Auto => locale::from_env(),
+            LocaleMode::Ru => Locale::Ru,
+            LocaleMode::En => Locale::En,
         }
+    }
+}
 
-        let path = entry.path();
-        let Some(ext) = path.extension().and_then(|s| s.to_str()) else {
-            continue;
+/// Язык, выбранный ещё до разбора аргументов: от него зависит язык справки `--help`
+fn early_locale() -> Locale {
+    let mut args = std::env::args().skip(1);
+    let mut mode = LocaleMode::Auto;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--locale") {
+            Some("") => args.next(),
+            Some(value) => value.strip_prefix('=').map(str::to_string),
+            None => None,
         };
+        if let Some(value) = value
+            && let Ok(value) = LocaleMode::from_str(&value, true)
+        {
+            mode = value;
+        }
+    }
+    mode.locale()
+}
 
-        let ext = ext.to_lowercase();
+/// Переключить консоль Windows в UTF-8: иначе кириллица в cmd.exe и старых версиях
+/// PowerShell выводится в кодовой странице OEM (866) и превращается в кракозябры
+#[cfg(windows)]
+fn enable_utf8_console() {
+    const CP_UTF8: u32 = 65001;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+        fn SetConsoleCP(code_page: u32) -> i32;
+    }
+    // SAFETY: функции принимают только номер кодовой страницы; ошибку (например, вывод
+    // перенаправлен в файл и консоли нет) можно игнорировать
+    unsafe {
+        SetConsoleOutputCP(CP_UTF8);
+        SetConsoleCP(CP_UTF8);
+    }
+    // Цвета через escape-последовательности ANSI
+    let _ = colored::control::set_virtual_terminal(true);
+}
 
-        if TEXT_EXTENSIONS.contains(ext.as_str()) && process_cue(path, &bm, args.force_cp1251_cue) {
-            println!("{:<6} {}", "[CUE]".magenta(), path.display());
-            count_fixed += 1;
-        } else if AUDIO_EXTENSIONS.contains(ext.as_str())
-            && process_audio(path, &bm, args.cyr_threshold)
-        {
-            println!(
-                "{:<6} {}",
-                format!("[{}]", ext.to_uppercase()).bright_blue(),
-                path.display()
-            );
-            count_fixed += 1;
+#[cfg(not(windows))]
+fn enable_utf8_console() {}
+
+/// Что делать с файлами, у которых есть другие жёсткие ссылки (например, в каталоге торрент-клиента)
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum HardlinkMode {
+    /// Записать исправленный файл отдельной копией: остальные ссылки сохранят прежнее содержимое
+    Break,
+    /// Не трогать файл
+    Skip,
+}
+
+/// Переводы строк в записываемых текстовых файлах
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum LineEndingMode {
+    /// \r\n (Windows; так ждут .cue многие программы записи дисков)
+    Crlf,
+    /// \n
+    Lf,
+    /// Как в исходном файле
+    Keep,
+}
+
+impl LineEndingMode {
+    fn policy(self) -> LineEndings {
+        match self {
+            LineEndingMode::Crlf => LineEndings::Crlf,
+            LineEndingMode::Lf => LineEndings::Lf,
+            LineEndingMode::Keep => LineEndings::Keep,
         }
     }
+}
 
-    println!(
-        "{} {} файлов было исправлено.",
-        "Готово!".green().bold(),
-        count_fixed.to_string().bold()
-    );
+/// Версия ID3v2 в исправленных тегах
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Id3Mode {
+    /// Как в исходном файле
+    Keep,
+    /// ID3v2.3: для магнитол и старых плееров
+    #[value(name = "v2.3")]
+    V23,
+    /// ID3v2.4
+    #[value(name = "v2.4")]
+    V24,
+}
+
+impl Id3Mode {
+    fn policy(self) -> Id3Version {
+        match self {
+            Id3Mode::Keep => Id3Version::Keep,
+            Id3Mode::V23 => Id3Version::V23,
+            Id3Mode::V24 => Id3Version::V24,
+        }
+    }
+}
+
+/// Что делать с ID3v1 исправляемых файлов
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Id3v1Mode {
+    /// Не трогать
+    Keep,
+    /// Пересобрать из исправленного тега в cp1251
+    Cp1251,
+    /// Пересобрать в Latin-1, кириллица — транслитом
+    Translit,
+    /// Удалить
+    Strip,
 }
 
-// fn has_cyrillic(s: &str) -> bool {
-//     s.chars()
-//         .any(|c| matches!(c, 'а'.='я' | 'А'.='Я' | 'ё' | 'Ё'))
-// }
+impl Id3v1Mode {
+    fn policy(self) -> Id3v1Policy {
+        match self {
+            Id3v1Mode::Keep => Id3v1Policy::Keep,
+            Id3v1Mode::Cp1251 => Id3v1Policy::Cp1251,
+            Id3v1Mode::Translit => Id3v1Policy::Translit,
+            Id3v1Mode::Strip => Id3v1Policy::Strip,
+        }
+    }
+}
 
-fn is_cyrillic(c: &char) -> bool {
-    ('\u{0400}'..='\u{04FF}').contains(c)
+/// Что делать с `ё` в текстовых полях тегов
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum YoMode {
+    /// Не трогать
+    Keep,
+    /// Заменять `ё` на `е`
+    ToE,
+    /// Возвращать `ё` в словах, где она обязательна (`еще` → `ещё`)
+    Restore,
 }
 
-fn cyrillic_count(s: &str) -> usize {
-    s.chars().filter(is_cyrillic).count()
+impl YoMode {
+    fn yo(self) -> Option<Yo> {
+        match self {
+            YoMode::Keep => None,
+            YoMode::ToE => Some(Yo::ToE),
+            YoMode::Restore => Some(Yo::Restore),
+        }
+    }
 }
 
-fn latin_diacritics_count(s: &str) -> usize {
-    s.chars().filter(|c| LATIN_DIACRITICS.contains(c)).count()
+/// Система транслитерации для --transliterate
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum TransliterateMode {
+    /// ГОСТ 7.79-2000, система Б: только ASCII, однозначно обратима
+    Gost,
+    /// ISO 9: буква в букву, с диакритикой
+    Iso9,
+    /// Простая латиница без диакритики, как в загранпаспорте
+    Simple,
 }
 
-/// "Ëüâèöà ðîêà" -> "Львица рока"
-fn fix_mojibake(text: &str, cyr_threshold: f64) -> Option<String> {
-    if cyrillic_count(text) > 0 {
-        return None;
+impl TransliterateMode {
+    fn scheme(self) -> Scheme {
+        match self {
+            TransliterateMode::Gost => Scheme::Gost,
+            TransliterateMode::Iso9 => Scheme::Iso9,
+            TransliterateMode::Simple => Scheme::Simple,
+        }
     }
+}
 
-    let (latin1_bytes, _, _) = WINDOWS_1252.encode(text);
-    let (decoded, _, _) = WINDOWS_1251.decode(&latin1_bytes);
-    let decoded_str = decoded.trim().to_string();
-    let len = decoded_str.chars().count() as f64;
+#[derive(Args, Debug)]
+struct DetectArgs {
+    /// Отрегулировать порог определения кириллицы
+    #[arg(long, default_value_t = DEFAULT_CYR_THRESHOLD)]
+    cyr_threshold: f64,
 
-    let cyr_ratio = cyrillic_count(&decoded_str) as f64 / len;
-    let diacritics_ratio = latin_diacritics_count(text) as f64 / len;
-    let score = WEIGHT_CYR * cyr_ratio - WEIGHT_DIACRITICS * diacritics_ratio;
+    /// Свой порог для полей тегов (через запятую, например: artist=0.1,title=0.3)
+    #[arg(long, value_delimiter = ',', value_name = "FIELD=N", value_parser = parse_field_threshold)]
+    field_threshold: Vec<(ItemKey, f64)>,
 
-    if score > cyr_threshold {
-        Some(decoded_str)
-    } else {
-        None
+    /// Языки восстановленного текста (через запятую): ru, uk, be, el, tr
+    /// (по умолчанию ru или язык кодировки --to-encoding)
+    #[arg(long, value_delimiter = ',', value_name = "LANG", value_parser = parse_language)]
+    lang: Vec<Language>,
+
+    /// Кодировка, в которой сломанный текст прочитан сейчас (с --to-encoding)
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding, requires = "to_encoding")]
+    from_encoding: Option<&'static Encoding>,
+
+    /// Настоящая кодировка текста (например, cp1253 для греческого, cp1254 для турецкого):
+    /// искать только такие кракозябры; в ней же читаются текстовые файлы не в UTF-8
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding)]
+    to_encoding: Option<&'static Encoding>,
+
+    /// Словарь замен (TOML или CSV): точные строки с кракозябрами или /регулярные выражения/
+    /// и правильные значения для них; важнее автоматического определения
+    #[arg(long, value_name = "FILE")]
+    dictionary: Option<PathBuf>,
+
+    /// Оставлять исправленный текст как есть: не собирать его в NFC, не убирать управляющие
+    /// символы, BOM и символы нулевой ширины, не заменять неразрывные пробелы
+    #[arg(long)]
+    no_normalize: bool,
+}
+
+impl DetectArgs {
+    /// Явно заданный вариант кракозябр: `--to-encoding`, прочитанная как `--from-encoding`
+    fn pair(&self) -> Option<Mojibake> {
+        let source = self.to_encoding?;
+        Some(Mojibake::new(
+            source,
+            self.from_encoding.unwrap_or(WINDOWS_1252),
+        ))
+    }
+
+    fn languages(&self) -> Languages {
+        if !self.lang.is_empty() {
+            return Languages::new(self.lang.iter().copied());
+        }
+        let Some(encoding) = self.to_encoding else {
+            return Languages::default();
+        };
+        match Language::for_encoding(encoding) {
+            Some(lang) => Languages::new([lang]),
+            None => {
+                eprintln!(
+                    "{}",
+                    tr_format!(
+                        "{}: для кодировки {} нет языка по умолчанию, укажите --lang",
+                        "{}: no default language for encoding {}, specify --lang",
+                        tr("Ошибка", "Error").red(),
+                        encoding_name(encoding)
+                    )
+                );
+                std::process::exit(EXIT_ERRORS);
+            }
+        }
+    }
+
+    fn detector(&self) -> Detector {
+        let mut detector = Detector::new(self.cyr_threshold)
+            .with_languages(self.languages())
+            .with_field_thresholds(self.field_threshold.clone())
+            .with_normalize(!self.no_normalize);
+        if let Some(pair) = self.pair() {
+            detector = detector.with_pair(pair);
+        }
+        match self.dictionary.as_deref().map(Dictionary::load) {
+            Some(Ok(dictionary)) => detector.with_dictionary(dictionary),
+            Some(Err(e)) => {
+                eprintln!("{} --dictionary: {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+            None => detector,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct TextArgs {
+    /// Принудительно считать все .cue и плейлисты файлами в cp1251 (без попыток угадать)
+    #[arg(long)]
+    force_cp1251_cue: bool,
+
+    /// Обновить в плейлистах пути к файлам, переименованным командой rename
+    #[arg(long)]
+    update_playlist_paths: bool,
+}
+
+#[derive(Args, Debug, Default)]
+struct FieldArgs {
+    /// Исправлять только эти поля тегов (через запятую, например: title,artist,album)
+    #[arg(long, value_delimiter = ',', value_name = "FIELD", value_parser = parse_field)]
+    fields: Vec<ItemKey>,
+
+    /// Не трогать эти поля тегов (через запятую, например: comment,lyrics)
+    #[arg(long, value_delimiter = ',', value_name = "FIELD", value_parser = parse_field)]
+    skip_fields: Vec<ItemKey>,
+
+    /// Не трогать пользовательские поля: TXXX, свои поля Vorbis и APE, freeform-атомы MP4
+    #[arg(long)]
+    no_custom_fields: bool,
+}
+
+impl FieldArgs {
+    fn filter(&self) -> FieldFilter {
+        FieldFilter::new(self.fields.clone(), self.skip_fields.clone())
+            .with_skip_custom(self.no_custom_fields)
     }
 }
 
-/// Обработка .cue файла: читаем cp1251 -> пишем utf-8
-fn process_cue(path: &Path, backup_manager: &BackupManager, force_cp1251: bool) -> bool {
-    let mut raw = Vec::new();
-    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_end(&mut raw)) {
-        eprintln!("{} чтения {}: {e}", "Ошибка".red(), path.display());
-        return false;
+#[derive(Args, Debug)]
+struct ReviewArgs {
+    /// Не применять исправления с уверенностью ниже этой (0–100),
+    /// а показать их в конце списком для проверки
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100))]
+    min_confidence: u8,
+
+    /// Показывать исправления по альбомам (каталогам) одной таблицей на альбом,
+    /// а с --interactive спрашивать подтверждение сразу для всего альбома
+    #[arg(long, conflicts_with_all = ["output", "quiet"])]
+    by_album: bool,
+
+    /// Проверять исправления исполнителя, альбома и названия с низкой уверенностью
+    /// по MusicBrainz (нужен интернет): если такой релиз, запись или исполнитель там есть,
+    /// исправлению можно доверять
+    #[arg(long)]
+    online_validate: bool,
+
+    /// Адрес http-зеркала MusicBrainz или локального прокси к нему для --online-validate, например http://localhost:5000/ws/2: musicbrainz.org отвечает только по https
+    #[arg(long, value_name = "URL")]
+    musicbrainz_url: Option<String>,
+
+    /// Сверять названия треков в альбомах, где что-то исправлено, с треклистом релиза
+    /// на Discogs (нужен интернет и персональный токен Discogs): о треке, название которого
+    /// не похоже ни на один трек релиза, выводится предупреждение
+    #[arg(long, value_name = "TOKEN")]
+    discogs_token: Option<String>,
+
+    /// Адрес локального прокси с TLS к API Discogs для --discogs-token, например http://localhost:8080: api.discogs.com отвечает только по https
+    #[arg(long, value_name = "URL")]
+    discogs_url: Option<String>,
+}
+
+impl ReviewArgs {
+    /// Группировать ли вывод по альбомам: таблицы есть только в обычном выводе
+    fn by_album(&self, output: &OutputArgs) -> bool {
+        self.by_album && output.output == OutputFormat::Human && !output.quiet
     }
 
-    // Пробуем определить кодировку:
-    // если force_cp1251 — просто cp1251;
-    // иначе: пробуем cp1251, если неудачно — пробуем utf-8, иначе оставляем как есть.
-    let content = if force_cp1251 {
-        let (decoded, _, had_errors) = WINDOWS_1251.decode(&raw);
-        if had_errors {
+    /// MusicBrainz для --online-validate с кэшем ответов в корне `root`; неверный адрес —
+    /// ошибка до начала обработки, кэш, который не открылся, — только предупреждение
+    fn musicbrainz(&self, root: &Path) -> Option<MusicBrainz> {
+        if !self.online_validate {
+            return None;
+        }
+        let Some(url) = &self.musicbrainz_url else {
             eprintln!(
-                "{}: не удалось полностью декодировать {} как cp1251",
-                "Внимание".yellow(),
-                path.display()
+                "{} {}",
+                tr("Ошибка", "Error").red(),
+                tr(
+                    "--online-validate требует --musicbrainz-url: musicbrainz.org отвечает только по https, нужно http-зеркало или локальный прокси",
+                    "--online-validate requires --musicbrainz-url: musicbrainz.org only answers over https, a plain http mirror or a local proxy is needed",
+                )
+            );
+            std::process::exit(EXIT_ERRORS);
+        };
+        let mut musicbrainz = match MusicBrainz::new(url) {
+            Ok(musicbrainz) => musicbrainz,
+            Err(e) => {
+                eprintln!("{} --musicbrainz-url: {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+        };
+        let cache = state_dir(root).join(musicbrainz::CACHE_FILE);
+        if let Err(e) = musicbrainz.open_cache(&cache) {
+            eprintln!(
+                "{}: {}: {e}",
+                tr("Внимание", "Warning").yellow(),
+                cache.display()
             );
         }
-        decoded.to_string()
-    } else {
-        // 1) пробуем utf-8
-        if String::from_utf8(raw.clone()).is_ok() {
-            // если текст нормальный, просто ничего не делаем
-            return false;
-        } else {
-            // 2) пробуем cp1251
-            let (decoded, _, _) = WINDOWS_1251.decode(&raw);
-            decoded.to_string()
+        Some(musicbrainz)
+    }
+
+    /// Discogs для --discogs-token с кэшем треклистов в корне `root`
+    fn discogs(&self, root: &Path) -> Option<Discogs> {
+        let token = self.discogs_token.as_deref()?;
+        let Some(url) = &self.discogs_url else {
+            eprintln!(
+                "{} {}",
+                tr("Ошибка", "Error").red(),
+                tr(
+                    "--discogs-token требует --discogs-url: api.discogs.com отвечает только по https, нужен локальный прокси с TLS",
+                    "--discogs-token requires --discogs-url: api.discogs.com only answers over https, a local TLS proxy is needed",
+                )
+            );
+            std::process::exit(EXIT_ERRORS);
+        };
+        let mut discogs = match Discogs::new(url, token) {
+            Ok(discogs) => discogs,
+            Err(e) => {
+                eprintln!("{} --discogs-url: {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+        };
+        let cache = state_dir(root).join(discogs::CACHE_FILE);
+        if let Err(e) = discogs.open_cache(&cache) {
+            eprintln!(
+                "{}: {}: {e}",
+                tr("Внимание", "Warning").yellow(),
+                cache.display()
+            );
         }
-    };
+        Some(discogs)
+    }
+}
+
+#[derive(Args, Debug)]
+struct PathArgs {
+    /// Пути к папкам с музыкой или отдельным файлам
+    #[arg(required_unless_present = "files_from")]
+    paths: Vec<PathBuf>,
+
+    /// Взять пути из файла (`-` — из stdin): по одному в строке или через NUL
+    /// (find -print0, fd -0)
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+}
 
-    if let Err(e) = backup_manager.backup_file(path) {
-        eprintln!("{e}");
-        return false;
+impl PathArgs {
+    /// Пути для обработки (см. [`roots`]); завершает процесс, если список не прочитать
+    fn roots(&self) -> Vec<PathBuf> {
+        let mut paths = self.paths.clone();
+        if let Some(list) = &self.files_from {
+            match read_path_list(list) {
+                Ok(list) => paths.extend(list),
+                Err(e) => {
+                    eprintln!("{} {e}", tr("Ошибка", "Error").red());
+                    std::process::exit(EXIT_ERRORS);
+                }
+            }
+        }
+        roots(&paths)
     }
 
-    if let Err(e) = fs::write(path, content.as_bytes()) {
-        eprintln!("{} записи {}: {e}", "Ошибка".red(), path.display());
-        return false;
+    fn reads_stdin(&self) -> bool {
+        self.files_from.as_deref() == Some(Path::new("-"))
     }
+}
 
-    println!("  {}", "→ .cue сохранён в UTF-8".green());
-    true
+/// Список путей --files-from: через NUL, если он есть в списке, иначе по строкам
+fn read_path_list(path: &Path) -> cyrtag_fix::Result<Vec<PathBuf>> {
+    let data = if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path)
+    }
+    .map_err(|source| cyrtag_fix::Error::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let separator = if data.contains(&0) { 0 } else { b'\n' };
+    Ok(data
+        .split(|&b| b == separator)
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(path_from_bytes)
+        .collect())
 }
 
-/// Обработка аудио-файла через lofty
-fn process_audio(path: &Path, backup_manager: &BackupManager, cyr_threshold: f64) -> bool {
-    let parse_opts = ParseOptions::new();
-    let tagged_file = match Probe::open(path).and_then(|p| p.options(parse_opts).read()) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!("{} чтения тегов {}: {e}", "Ошибка".red(), path.display());
-            return false;
+/// Путь из байтов списка: в Unix имена файлов не обязаны быть в UTF-8 (например, в cp1251)
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[derive(Args, Debug)]
+struct ThreadArgs {
+    /// Число потоков обработки (0 — по числу ядер)
+    #[arg(short = 'j', long, default_value_t = 1)]
+    threads: usize,
+}
+
+impl ThreadArgs {
+    fn count(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
         }
-    };
+    }
+}
 
-    let mut tag = match tagged_file.primary_tag() {
-        Some(t) => t.to_owned(),
-        None => match tagged_file.first_tag() {
-            Some(t) => t.to_owned(),
-            None => return false,
-        },
-    };
+#[derive(Args, Debug)]
+struct IncrementalArgs {
+    /// Не проверять файлы, не изменившиеся с прошлого запуска с теми же настройками
+    /// (состояние хранится в .cyrtag-fix/state в корне обработки)
+    #[arg(long)]
+    incremental: bool,
 
-    let mut fixes: Vec<(ItemKey, String)> = Vec::new();
+    /// Отмечать проверенные файлы в расширенном атрибуте user.cyrtag-fix и не проверять
+    /// отмеченные, не изменившиеся с тех пор (отметка переезжает вместе с файлом)
+    #[arg(long)]
+    mark: bool,
+}
 
-    for item in tag.items() {
-        if let Some(text) = item.value().text()
-            && let Some(fixed) = fix_mojibake(text, cyr_threshold)
-        {
-            println!(
-                "  {} {:?}: '{}' -> '{}'",
-                "FIX".cyan(),
-                item.key(),
-                text,
-                fixed
+impl IncrementalArgs {
+    /// Состояние для корня `root` и настроек `settings`; если его не удалось открыть,
+    /// файлы проверяются как обычно
+    fn state(&self, root: &Path, settings: &str) -> Option<State> {
+        if !self.incremental {
+            return None;
+        }
+        match State::open(state_dir(root), &versioned(settings)) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!(
+                    "{}: {e}; {}",
+                    tr("Внимание", "Warning").yellow(),
+                    tr("все файлы будут проверены", "all files will be checked")
+                );
+                None
+            }
+        }
+    }
+
+    /// Отметки о проверке в атрибутах файлов для настроек `settings`
+    fn marker(&self, settings: &str) -> Option<Marker> {
+        self.mark.then(|| Marker::new(&versioned(settings)))
+    }
+}
+
+/// Настройки вместе с версией программы: после обновления файлы проверяются заново
+fn versioned(settings: &str) -> String {
+    format!("{} {settings}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Журнал хода запуска для --resume; в пробном запуске не ведётся
+fn start_progress(root: &Path, resume: bool, dry_run: bool, human: bool) -> Option<Progress> {
+    if dry_run {
+        return None;
+    }
+    let root = state_dir(root);
+    let interrupted = progress::progress_path(root).exists();
+    if resume && !interrupted {
+        eprintln!(
+            "{}: {}",
+            tr("Внимание", "Warning").yellow(),
+            tr(
+                "прерванный запуск не найден, обработка начнётся с начала",
+                "no interrupted run found, starting from the beginning"
+            )
+        );
+    } else if !resume && interrupted {
+        eprintln!(
+            "{}: {}",
+            tr("Внимание", "Warning").yellow(),
+            tr(
+                "предыдущий запуск был прерван и начнётся заново; чтобы продолжить его, используйте --resume",
+                "the previous run was interrupted and will start over; use --resume to continue it"
+            )
+        );
+    }
+    match Progress::start(root, resume) {
+        Ok(progress) => {
+            if human && progress.resumed() > 0 {
+                println!(
+                    "{}",
+                    tr_format!(
+                        "Продолжение прерванного запуска: уже обработано файлов {}",
+                        "Resuming the interrupted run: {} files already processed",
+                        progress.resumed()
+                    )
+                );
+            }
+            Some(progress)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}: {e}; {}",
+                tr("Внимание", "Warning").yellow(),
+                tr(
+                    "продолжить этот запуск после прерывания не получится",
+                    "this run can't be resumed if interrupted"
+                )
             );
-            fixes.push((item.key().clone(), fixed));
+            None
         }
     }
+}
+
+/// Удалить журнал хода, если запуск дошёл до конца, а не остановлен через [q]uit или Ctrl-C
+fn finish_progress(processor: &mut Processor) {
+    let quit = processor
+        .prompter
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .quit();
+    if let Some(progress) = processor.progress.take()
+        && !quit
+        && !interrupt::interrupted()
+        && !processor.failed_fast()
+        && let Err(e) = progress.finish()
+    {
+        eprintln!("{}: {e}", tr("Внимание", "Warning").yellow());
+    }
+}
 
-    if fixes.is_empty() {
-        return false;
+/// Сжать состояние --incremental после обработки
+fn compact_state(processor: &Processor) {
+    if let Some(state) = &processor.state
+        && let Err(e) = state.compact()
+    {
+        eprintln!("{}: {e}", tr("Внимание", "Warning").yellow());
     }
-    for (key, fixed) in fixes {
-        tag.insert_text(key, fixed);
+}
+
+#[derive(Args, Debug, Default)]
+struct FilterArgs {
+    /// Пропускать файлы и каталоги по glob-шаблону пути внутри обрабатываемого каталога
+    /// (например, '**/@eaDir/**'); можно указать несколько раз
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<Pattern>,
+
+    /// Обрабатывать только файлы, подходящие под glob-шаблон (например, '**/2003 - */**');
+    /// можно указать несколько раз
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<Pattern>,
+
+    /// Обрабатывать файлы только с этими расширениями вместо стандартного набора
+    /// (через запятую, например: mp3,flac,cue)
+    #[arg(long, value_delimiter = ',', value_name = "EXT", value_parser = parse_extension)]
+    extensions: Vec<String>,
+
+    /// Наибольшая глубина обхода, как у find -maxdepth: 1 — только файлы в самом каталоге
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Заходить по символическим ссылкам на каталоги и файлы; циклы ссылок пропускаются
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Обходить скрытые файлы и каталоги, а также служебные каталоги NAS и Windows
+    /// (@eaDir, #recycle, $RECYCLE.BIN, System Volume Information …): по умолчанию они пропускаются
+    #[arg(long)]
+    hidden: bool,
+
+    /// Определять формат аудио-файлов по содержимому, а не по расширению: для файлов без
+    /// расширения или с чужим (FLAC с именем .mp3); каждый файл открывается, обход медленнее
+    #[arg(long)]
+    sniff: bool,
+
+    /// Обрабатывать и ZIP-архивы: теги и тексты файлов внутри исправляются в памяти,
+    /// имена файлов в cp866 сохраняются в UTF-8, архив переписывается целиком
+    #[arg(long)]
+    zip: bool,
+
+    /// Исправлять в ZIP-архивах только имена файлов в cp866 и cp1251: они сохраняются в UTF-8,
+    /// содержимое файлов не распаковывается и не меняется
+    #[arg(long)]
+    zip_names: bool,
+}
+
+impl FilterArgs {
+    /// Обрабатывается ли файл с таким расширением (в нижнем регистре)
+    fn supports_extension(&self, ext: &str) -> bool {
+        if ext == zip::EXTENSION {
+            return self.zip || self.zip_names;
+        }
+        if self.extensions.is_empty() {
+            AUDIO_EXTENSIONS.contains(ext) || TEXT_EXTENSIONS.contains(ext)
+        } else {
+            self.extensions.iter().any(|e| e == ext)
+        }
     }
 
-    if let Err(e) = backup_manager.backup_file(path) {
-        eprintln!("{e}");
-        return false;
+    fn supports(&self, path: &Path) -> bool {
+        extension(path).is_some_and(|ext| self.supports_extension(&ext))
     }
 
-    if let Err(e) = tag.save_to_path(path, WriteOptions::default()) {
-        eprintln!(
-            "{} сохранения тегов {}: {e}",
-            "Ошибка".red(),
-            path.display()
-        );
-        return false;
+    /// Расширение, по которому выбирается обработка файла; `None` — файл не обрабатывается.
+    /// С --sniff у аудио-файлов это расширение их настоящего формата; тексты и .bak бэкапы
+    /// по содержимому не определяются
+    fn format_extension(&self, path: &Path) -> Option<String> {
+        let ext = extension(path);
+        let by_name = ext
+            .as_deref()
+            .is_some_and(|ext| self.supports_extension(ext));
+        let sniffable = !ext
+            .as_deref()
+            .is_some_and(|ext| TEXT_EXTENSIONS.contains(ext))
+            && backup::original_path(path).is_none();
+        if self.sniff
+            && sniffable
+            && let Some(sniffed) = audio::sniff_extension(path)
+            && (by_name || self.supports_extension(sniffed))
+        {
+            return Some(sniffed.to_string());
+        }
+        ext.filter(|_| by_name)
     }
 
-    println!("  {}", "→ теги обновлены".green());
-    true
+    /// Нужно ли обходить элемент; сам корень обхода не фильтруется
+    fn accepts(&self, root: &Path, entry: &DirEntry) -> bool {
+        if entry.depth() == 0 {
+            return true;
+        }
+        if !self.hidden && is_hidden(entry) {
+            return false;
+        }
+        let relative = relative_path(root, entry.path());
+        if entry.file_type().is_dir() {
+            // '**/@eaDir' и '**/@eaDir/**' отсекают каталог целиком
+            let with_slash = format!("{relative}/");
+            return !self
+                .exclude
+                .iter()
+                .any(|p| p.matches(&relative) || p.matches(&with_slash));
+        }
+        !self.exclude.iter().any(|p| p.matches(&relative))
+            && (self.include.is_empty() || self.include.iter().any(|p| p.matches(&relative)))
+    }
 }
 
-impl BackupManager {
-    fn create_backup(&self, path: &Path) -> std::io::Result<()> {
-        let file_name = path.file_name().ok_or_else(|| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Не удалось получить имя файла",
-            )
-        })?;
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Формат вывода результатов
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+
+    /// Подробнее: -v — каждый проверенный файл, -vv — ещё и все решения детектора с оценками
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Показывать только исправленные файлы и итог
+    #[arg(short, long)]
+    quiet: bool,
 
-        let backup_path = path.with_file_name(format!("{}.bak", file_name.to_string_lossy()));
+    /// Сохранить HTML-отчёт со всеми исправлениями, сгруппированными по каталогам альбомов
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Сохранить исправления в CSV (путь, поле, было, стало, кодировка) для Mp3tag и foobar2000
+    #[arg(long, value_name = "FILE")]
+    csv: Option<PathBuf>,
+
+    /// Показывать строки, не исправленные только из-за порога: вариант восстановления и его оценку чуть ниже --cyr-threshold
+    #[arg(long)]
+    show_candidates: bool,
+}
 
-        fs::copy(path, backup_path)?;
-        Ok(())
+impl OutputArgs {
+    fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+    /// Куда собирать отчёты о файлах, если нужен --report или --csv
+    fn reports(&self) -> Option<Mutex<Vec<FileReport>>> {
+        (self.report.is_some() || self.csv.is_some()).then(Mutex::default)
     }
 
-    pub fn backup_file(&self, path: &Path) -> std::io::Result<()> {
-        if self.no_backup {
-            return Ok(());
+    /// Записать HTML-отчёт и CSV по собранным отчётам о файлах
+    fn save_reports(&self, processor: &Processor, root: &Path, title: &str) {
+        let Some(reports) = &processor.reports else {
+            return;
+        };
+        let mut reports = reports.lock().unwrap_or_else(|e| e.into_inner());
+        reports.sort_by(|a, b| a.path.cmp(&b.path));
+        let saved = [
+            self.report.as_ref().map(|path| {
+                (
+                    path,
+                    tr("Отчёт", "Report"),
+                    report::save(path, root, title, &reports),
+                )
+            }),
+            self.csv
+                .as_ref()
+                .map(|path| (path, "CSV", csv::save(path, &reports))),
+        ];
+        for (path, name, result) in saved.into_iter().flatten() {
+            if let Err(e) = result {
+                eprintln!("{} {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+            if self.output == OutputFormat::Human {
+                println!("{name}: {}", path.display());
+            }
         }
-        self.create_backup(path).map_err(|e| {
-            std::io::Error::new(
-                e.kind(),
-                format!(
-                    "{} при создании бэкапа {}: {e}",
-                    "Ошибка".red(),
-                    path.display()
-                ),
-            )
-        })
     }
 }
+
+#[derive(Args, Debug)]
+struct BackupArgs {
+    /// Не создавать .bak файлы (по умолчанию создаются)
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Способ бэкапа аудио-файлов
+    #[arg(long, value_enum, default_value_t = BackupMode::Copy, conflicts_with = "no_backup")]
+    backup_mode: BackupMode,
+
+    /// Складывать бэкапы в отдельный каталог, повторяя структуру исходного дерева
+    #[arg(long, conflicts_with = "no_backup")]
+    backup_dir: Option<PathBuf>,
+
+    /// Сжимать копии файлов (.bak.gz); restore распаковывает их сам
+    #[arg(long, value_enum, conflicts_with = "no_backup")]
+    backup_compress: Option<BackupCompress>,
+}
+
+/// Как записывать исправленные файлы
+#[derive(Args, Debug)]
+struct WriteArgs {
+    /// BOM UTF-8 в записываемых .cue, плейлистах и других текстовых файлах
+    #[arg(long, value_enum, default_value_t = BomMode::Keep)]
+    bom: BomMode,
+
+    /// Переводы строк в записываемых текстовых файлах
+    #[arg(long, value_enum, default_value_t = LineEndingMode::Keep)]
+    line_endings: LineEndingMode,
+
+    /// Версия ID3v2 в исправленных тегах: keep — как в файле, v2.3 или v2.4 — перевести в неё
+    #[arg(long, value_enum, default_value_t = Id3Mode::Keep)]
+    id3: Id3Mode,
+
+    /// Переписывать в UTF-8 (UTF-16 в ID3v2.3) все фреймы ID3v2 в Latin-1 с не-ASCII текстом,
+    /// даже если кракозябр в них нет: такие фреймы плееры показывают по-разному
+    #[arg(long)]
+    reencode_frames: bool,
+
+    /// ID3v1 (его предпочитают некоторые плееры): keep — не трогать, cp1251 или translit —
+    /// пересобрать из исправленного ID3v2, strip — удалить
+    #[arg(long, value_enum, default_value_t = Id3v1Mode::Keep)]
+    id3v1: Id3v1Mode,
+
+    /// Буква ё в текстовых полях тегов: keep — не трогать, to-e — заменять на е, restore —
+    /// возвращать по словарю; иначе плееры делят исполнителей и альбомы надвое
+    #[arg(long, value_enum, default_value_t = YoMode::Keep)]
+    yo: YoMode,
+
+    /// Записывать текстовые поля тегов латиницей (после исправления кракозябр) — для
+    /// магнитол и плееров без кириллицы; отменяется через undo, как и исправления
+    #[arg(long, value_enum, value_name = "SCHEME")]
+    transliterate: Option<TransliterateMode>,
+
+    /// В переписываемых тегах убирать пробелы по краям, повторные пробелы и нулевые байты
+    /// во всех текстовых полях: ими дополняли значения старые программы
+    #[arg(long)]
+    trim_tags: bool,
+
+    /// В переписываемых файлах оставлять только основной тег формата: поля, которых в нём
+    /// нет, переносятся из APE, ID3v1 и других тегов, а те удаляются
+    #[arg(long)]
+    strip_other_tags: bool,
+
+    /// Сохранять исправленным файлам прежнее время изменения (mtime)
+    #[arg(long)]
+    preserve_mtime: bool,
+
+    /// Остановиться на первом файле, который не удалось прочитать или записать
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Не менять оригиналы, а записывать исправленные копии в этот каталог, повторяя
+    /// структуру исходного дерева (бэкапы при этом не нужны и не создаются)
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["backup_mode", "backup_dir", "backup_compress"])]
+    output_dir: Option<PathBuf>,
+
+    /// Файлы с несколькими жёсткими ссылками: break — разорвать ссылку, skip — пропустить
+    #[arg(long, value_enum, default_value_t = HardlinkMode::Break)]
+    hardlinks: HardlinkMode,
+
+    /// Каталог раздач: файлы в нём не меняются; можно указать несколько раз
+    #[arg(long, value_name = "DIR")]
+    seed_dir: Vec<PathBuf>,
+
+    /// Запустить команду для каждого исправленного файла: {} заменяется путём, а без {} путь
+    /// добавляется в конец (например, 'beet update -p {}'); можно указать несколько раз
+    #[arg(long, value_name = "CMD")]
+    exec: Vec<Hook>,
+}
+
+impl WriteArgs {
+    fn apply_to(&self, fixer: TextFixer) -> TextFixer {
+        fixer
+            .with_bom(self.bom.policy())
+            .with_line_endings(self.line_endings.policy())
+    }
+
+    /// Зеркальное дерево --output-dir для каталога `root`
+    fn output_dir(&self, root: &Path) -> Option<BackupDir> {
+        let dir = self.output_dir.as_ref()?;
+        Some(BackupDir::new(dir, state_dir(root)))
+    }
+
+    /// Канонические пути каталогов раздач; несуществующие пропускаются
+    fn seed_dirs(&self) -> Vec<PathBuf> {
+        self.seed_dir
+            .iter()
+            .filter_map(|dir| fs::canonicalize(dir).ok())
+            .collect()
+    }
+
+    /// Бэкапы нужны, только если исправляются сами оригиналы
+    fn backup_manager(&self, backup: &BackupArgs, root: &Path) -> BackupManager {
+        if self.output_dir.is_some() {
+            BackupManager::new(true)
+        } else {
+            backup_manager(backup, root)
+        }
+    }
+}
+
+/// Кому сообщить о каталогах с исправленными файлами: медиасерверу и MPD
+#[derive(Args, Debug, Default)]
+struct NotifyArgs {
+    /// После записи попросить медиасервер перечитать каталоги с исправленными файлами
+    #[arg(long, value_enum, value_name = "SERVER")]
+    notify_server: Option<MediaServer>,
+
+    /// Адрес медиасервера, только http:// (например, http://localhost:8096)
+    #[arg(long, alias = "url", value_name = "URL")]
+    notify_url: Option<String>,
+
+    /// API-ключ Jellyfin, X-Plex-Token или пароль Navidrome
+    #[arg(long, alias = "token", value_name = "TOKEN")]
+    notify_token: Option<String>,
+
+    /// Пользователь Navidrome
+    #[arg(long, value_name = "USER")]
+    notify_user: Option<String>,
+
+    /// Каталог, который сервер видит под другим путём, например в Docker: ~/Music=/music
+    #[arg(long, value_name = "LOCAL=REMOTE")]
+    notify_path_map: Option<PathMap>,
+
+    /// После записи обновить базу MPD по каталогам с исправленными файлами; адрес —
+    /// --mpd=host[:port] или путь к сокету (по умолчанию из MPD_HOST и MPD_PORT)
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    mpd: Option<String>,
+
+    /// Каталог музыки MPD (music_directory) на этой машине; без него MPD спрашивается сам,
+    /// что работает только через unix-сокет
+    #[arg(long, value_name = "DIR")]
+    mpd_music_dir: Option<PathBuf>,
+}
+
+impl NotifyArgs {
+    /// Медиасервер для оповещения; неполные настройки — ошибка до начала обработки
+    fn notifier(&self) -> Option<Notifier> {
+        let server = self.notify_server?;
+        match Notifier::new(
+            server,
+            self.notify_url.as_deref(),
+            self.notify_token.as_deref(),
+            self.notify_user.as_deref(),
+            self.notify_path_map.clone(),
+        ) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                eprintln!("{} --notify-server: {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+        }
+    }
+
+    /// Подключение к MPD; неверный адрес — ошибка до начала обработки
+    fn mpd(&self) -> Option<Mpd> {
+        let address = self.mpd.as_deref()?;
+        match Mpd::new(Some(address), self.mpd_music_dir.as_deref()) {
+            Ok(mpd) => Some(mpd),
+            Err(e) => {
+                eprintln!("{} --mpd: {e}", tr("Ошибка", "Error").red());
+                std::process::exit(EXIT_ERRORS);
+            }
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct FixArgs {
+    #[command(flatten)]
+    paths: PathArgs,
+
+    #[command(flatten)]
+    backup: BackupArgs,
+
+    #[command(flatten)]
+    text: TextArgs,
+
+    #[command(flatten)]
+    write: WriteArgs,
+
+    #[command(flatten)]
+    notify: NotifyArgs,
+
+    #[command(flatten)]
+    detect: DetectArgs,
+
+    #[command(flatten)]
+    fields: FieldArgs,
+
+    #[command(flatten)]
+    review: ReviewArgs,
+
+    /// Приводить название и исполнителя альбома, которые после исправления у треков одного
+    /// каталога пишутся чуть по-разному, к значению большинства треков
+    #[arg(long)]
+    normalize_album: bool,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Только показать найденные исправления, ничего не записывая
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Спрашивать подтверждение перед изменением каждого файла
+    #[arg(short, long, conflicts_with_all = ["dry_run", "threads", "output", "quiet"])]
+    interactive: bool,
+
+    /// Продолжить прерванный запуск: файлы, обработанные в нём, пропускаются
+    #[arg(long, conflicts_with = "dry_run")]
+    resume: bool,
+
+    /// После обработки следить за каталогами и исправлять новые и изменённые файлы, как только
+    /// их перестали записывать; остановить — Ctrl-C
+    #[arg(long, conflicts_with = "interactive")]
+    watch: bool,
+
+    /// Как часто перечитывать каталоги с --watch, в секундах
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 10,
+        requires = "watch",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    watch_interval: u64,
+
+    #[command(flatten)]
+    threads: ThreadArgs,
+
+    #[command(flatten)]
+    incremental: IncrementalArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct ScanArgs {
+    #[command(flatten)]
+    paths: PathArgs,
+
+    #[command(flatten)]
+    text: TextArgs,
+
+    #[command(flatten)]
+    detect: DetectArgs,
+
+    #[command(flatten)]
+    fields: FieldArgs,
+
+    #[command(flatten)]
+    review: ReviewArgs,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    #[command(flatten)]
+    threads: ThreadArgs,
+
+    #[command(flatten)]
+    incremental: IncrementalArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Сохранить найденные исправления в план (JSON) для просмотра, правки и команды apply
+    #[arg(long, value_name = "FILE")]
+    plan: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ApplyArgs {
+    /// Файл плана, сохранённый командой scan --plan
+    plan: PathBuf,
+
+    #[command(flatten)]
+    backup: BackupArgs,
+
+    #[command(flatten)]
+    write: WriteArgs,
+
+    #[command(flatten)]
+    notify: NotifyArgs,
+
+    /// Только показать исправления из плана, ничего не записывая
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct RestoreArgs {
+    /// Путь к папке с музыкой
+    path: PathBuf,
+
+    /// Не удалять .bak файлы после восстановления
+    #[arg(long)]
+    keep_backup: bool,
+
+    /// Восстанавливать из отдельного каталога бэкапов (см. fix --backup-dir)
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+
+    /// Только показать, что будет восстановлено, ничего не изменяя
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+struct CleanBackupsArgs {
+    /// Путь к папке с музыкой, на которой запускался fix
+    path: PathBuf,
+
+    /// Удалять только бэкапы старше этого срока: 30d, 12h, 2w (по умолчанию — все)
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    older_than: Option<u64>,
+
+    /// Только показать, что будет удалено, ничего не изменяя
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+struct UndoArgs {
+    /// Путь к папке с музыкой, на которой запускался fix
+    path: PathBuf,
+
+    /// Идентификатор запуска (по умолчанию — последний)
+    #[arg(long)]
+    run: Option<String>,
+
+    /// Показать сохранённые запуски
+    #[arg(long, conflicts_with = "run")]
+    list: bool,
+
+    /// Только показать, что будет откачено, ничего не изменяя
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+struct RenameArgs {
+    /// Путь к папке с музыкой
+    path: PathBuf,
+
+    #[command(flatten)]
+    detect: DetectArgs,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Только показать новые имена, ничего не переименовывая
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Также исправлять имена каталогов (начиная с самых глубоких)
+    #[arg(long)]
+    dirs: bool,
+
+    /// Откатить переименования по журналу предыдущего запуска
+    #[arg(long)]
+    undo: bool,
+}
+
+#[derive(Args, Debug)]
+struct FixStringArgs {
+    /// Текст с кракозябрами (несколько аргументов склеиваются через пробел);
+    /// без него текст читается из stdin
+    text: Vec<String>,
+
+    #[command(flatten)]
+    detect: DetectArgs,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    /// Оболочка
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+/// Описание командной строки на выбранном языке сообщений
+fn cli_command() -> clap::Command {
+    let command = Cli::command();
+    if locale::get() == Locale::En {
+        help::english(command)
+    } else {
+        command
+    }
+}
+
+fn main() {
+    enable_utf8_console();
+    locale::set(early_locale());
+    let matches = cli_command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    cli.color.apply();
+    locale::set(cli.locale.locale());
+
+    match Config::load(cli.config.as_deref()) {
+        Ok(config) => config.apply(&mut cli.command, &matches),
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+    }
+    if let Some(path) = &cli.log_file {
+        if let Err(source) = log::open(path, cli.log_level) {
+            let e = cyrtag_fix::Error::Write {
+                path: path.clone(),
+                source,
+            };
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+        let args: Vec<String> = std::env::args().collect();
+        log::write(
+            LogLevel::Info,
+            &tr_format!("Запуск: {}", "Started: {}", args.join(" ")),
+        );
+    }
+
+    match cli.command {
+        Command::Fix(args) => run_fix(*args),
+        Command::Scan(args) => run_scan(args),
+        Command::Apply(args) => run_apply(args),
+        Command::Restore(args) => run_restore(args),
+        Command::CleanBackups(args) => run_clean_backups(args),
+        Command::Rename(args) => run_rename(args),
+        Command::Undo(args) => run_undo(args),
+        Command::FixString(args) => run_fix_string(args),
+        Command::Completions(args) => run_completions(args),
+    }
+}
+
+/// Исправленный текст — в stdout (удобно для конвейеров), найденные кракозябры — в stderr
+fn run_fix_string(args: FixStringArgs) {
+    let text = if args.text.is_empty() {
+        let mut text = String::new();
+        if let Err(source) = io::stdin().lock().read_to_string(&mut text) {
+            let e = cyrtag_fix::Error::Read {
+                path: PathBuf::from("-"),
+                source,
+            };
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+        text
+    } else {
+        args.text.join(" ")
+    };
+
+    let detector = args.detect.detector();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = 0usize;
+    for (i, line) in lines.iter().enumerate() {
+        let Some(detection) = detector.detect(line) else {
+            println!("{line}");
+            continue;
+        };
+        found += 1;
+        println!("{}", detection.text);
+
+        let chain: Vec<String> = detection.chain.iter().map(|m| m.to_string()).collect();
+        let details = tr_format!(
+            "{}, уверенность {}%",
+            "{}, confidence {}%",
+            chain.join(tr(", затем ", ", then ")),
+            detection.confidence
+        );
+        if lines.len() > 1 {
+            eprintln!(
+                "{}",
+                tr_format!("  строка {}: {}", "  line {}: {}", i + 1, details).dimmed()
+            );
+        } else {
+            eprintln!("  {}", details.dimmed());
+        }
+    }
+    if found == 0 {
+        eprintln!(
+            "  {}",
+            tr("Кракозябр не найдено", "No mojibake found").dimmed()
+        );
+    }
+}
+
+fn run_completions(args: CompletionsArgs) {
+    print!("{}", completions::generate(args.shell, cli_command()));
+}
+
+fn run_fix(mut args: FixArgs) {
+    interrupt::install();
+    let human = args.output.output == OutputFormat::Human;
+    if args.interactive && args.paths.reads_stdin() {
+        eprintln!(
+            "{}",
+            tr_format!(
+                "{}: --interactive нельзя совместить с --files-from -: ответы тоже читаются из stdin",
+                "{}: --interactive can't be combined with --files-from -: answers are read from stdin too",
+                tr("Ошибка", "Error").red()
+            )
+        );
+        std::process::exit(EXIT_ERRORS);
+    }
+    let notifier = args.notify.notifier();
+    let mpd = args.notify.mpd();
+    let roots = args.paths.roots();
+    let root = common_root(&roots);
+    if human {
+        start_roots(&roots);
+    }
+
+    let detector = args.detect.detector();
+    let transliterate = args.write.transliterate.map(TransliterateMode::scheme);
+    let settings = format!(
+        "fix {:?} {:?} {:?} {} {} {:?} {:?} {} {:?} {:?} {} {:?} {}",
+        args.text,
+        args.detect,
+        args.fields,
+        args.review.min_confidence,
+        args.review.online_validate,
+        args.write.bom,
+        args.write.line_endings,
+        args.write.reencode_frames,
+        args.write.id3v1,
+        args.write.transliterate,
+        args.write.trim_tags,
+        args.write.yo,
+        args.write.strip_other_tags
+    );
+    let mut processor = Processor {
+        text_fixer: args
+            .write
+            .apply_to(text_fixer(&args.text, detector.clone(), &root)),
+        audio_fixer: AudioFixer::new(detector.clone())
+            .with_fields(args.fields.filter())
+            .with_sniff(args.filter.sniff)
+            .with_id3(args.write.id3.policy())
+            .with_reencode_frames(args.write.reencode_frames)
+            .with_id3v1(args.write.id3v1.policy())
+            .with_yo(args.write.yo.yo())
+            .with_transliterate(transliterate)
+            .with_trim(args.write.trim_tags)
+            .with_strip_others(args.write.strip_other_tags),
+        asf_fixer: AsfFixer::new(detector)
+            .with_fields(args.fields.filter())
+            .with_yo(args.write.yo.yo())
+            .with_transliterate(transliterate)
+            .with_trim(args.write.trim_tags),
+        backup_manager: args.write.backup_manager(&args.backup, &root),
+        prompter: Mutex::new(Prompter::new(args.interactive)),
+        dry_run: args.dry_run,
+        output: args.output.output,
+        verbosity: args.output.verbosity(),
+        filter: std::mem::take(&mut args.filter),
+        min_confidence: args.review.min_confidence,
+        review: Mutex::default(),
+        show_candidates: args.output.show_candidates,
+        unrecoverable: Mutex::default(),
+        failed: Mutex::default(),
+        fail_fast: args.write.fail_fast,
+        plan: None,
+        reports: args.output.reports(),
+        preserve_mtime: args.write.preserve_mtime,
+        output_dir: args.write.output_dir(&root),
+        hardlinks: args.write.hardlinks,
+        seed_dirs: args.write.seed_dirs(),
+        state: args.incremental.state(&root, &settings),
+        marker: args.incremental.marker(&settings),
+        progress: start_progress(&root, args.resume, args.dry_run, human),
+        hooks: std::mem::take(&mut args.write.exec),
+        notifier,
+        mpd,
+        changed_dirs: Mutex::default(),
+        by_album: args.review.by_album(&args.output),
+        normalize_album: args.normalize_album,
+        album_tracks: Mutex::default(),
+        musicbrainz: args.review.musicbrainz(&root),
+        discogs: args.review.discogs(&root),
+    };
+    let summary = processor.process_tree(&roots, &root, args.threads.count());
+    compact_state(&processor);
+    finish_progress(&mut processor);
+    processor.notify_changes();
+    let summary = if args.watch && !summary.interrupted {
+        let interval = Duration::from_secs(args.watch_interval);
+        let summary = watch::watch(
+            &processor,
+            &roots,
+            &root,
+            interval,
+            args.threads.count(),
+            summary,
+        );
+        compact_state(&processor);
+        summary
+    } else {
+        summary
+    };
+    log_summary(&summary);
+    let title = if args.dry_run {
+        tr(
+            "cyrtag-fix fix (пробный запуск)",
+            "cyrtag-fix fix (dry run)",
+        )
+    } else {
+        "cyrtag-fix fix"
+    };
+    args.output.save_reports(&processor, &root, title);
+
+    if !human {
+        exit_if_interrupted(&summary, true);
+        exit_if_failed(&summary, !args.dry_run);
+        return;
+    }
+    if args.dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов будет исправлено (пробный запуск, файлы не изменены).",
+                "{} {} files would be fixed (dry run, no files changed).",
+                done_label(&summary),
+                summary.fixed.to_string().bold()
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов было исправлено.",
+                "{} {} files fixed.",
+                done_label(&summary),
+                summary.fixed.to_string().bold()
+            )
+        );
+        if let Some(manifest) = processor.backup_manager.manifest()
+            && manifest.path().exists()
+        {
+            println!(
+                "{}",
+                tr_format!(
+                    "Манифест запуска {}: {} (cyrtag-fix undo --run {})",
+                    "Run manifest {}: {} (cyrtag-fix undo --run {})",
+                    manifest.id(),
+                    manifest.path().display(),
+                    manifest.id()
+                )
+            );
+        }
+    }
+    print_summary(&summary, args.output.verbosity());
+    print_review(&processor);
+    print_unrecoverable(&processor);
+    print_failed(&processor);
+    exit_if_interrupted(&summary, true);
+    exit_if_failed(&summary, !args.dry_run);
+}
+
+/// Завершиться с кодом 2, если в запуске были ошибки; после остановки по --fail-fast —
+/// ещё и объяснить, что обработаны не все файлы
+fn exit_if_failed(summary: &Summary, resumable: bool) {
+    if summary.failed_fast {
+        let message = tr(
+            "обработка остановлена на первой ошибке (--fail-fast), остальные файлы не обработаны",
+            "processing stopped at the first error (--fail-fast), the remaining files were not processed",
+        );
+        log::write(LogLevel::Error, message);
+        eprintln!("{}: {message}", tr("Остановлено", "Stopped").red());
+        if resumable {
+            eprintln!(
+                "{}",
+                tr(
+                    "Устранив причину, продолжите с этого места: тот же запуск с --resume",
+                    "Once the cause is fixed, continue from here: run the same command with --resume"
+                )
+            );
+        }
+    }
+    if summary.errors > 0 {
+        std::process::exit(EXIT_ERRORS);
+    }
+}
+
+/// «Готово!» в итоге запуска, «Прервано!», если его остановили по Ctrl-C, или
+/// «Остановлено!» после ошибки с --fail-fast
+fn done_label(summary: &Summary) -> ColoredString {
+    if summary.interrupted {
+        tr("Прервано!", "Interrupted!").yellow().bold()
+    } else if summary.failed_fast {
+        tr("Остановлено!", "Stopped!").red().bold()
+    } else {
+        tr("Готово!", "Done!").green().bold()
+    }
+}
+
+/// После остановки по Ctrl-C или SIGTERM: сообщить, где остановилась обработка,
+/// и завершиться с кодом 130; `resumable` — запуск можно продолжить через --resume
+fn exit_if_interrupted(summary: &Summary, resumable: bool) {
+    if !summary.interrupted {
+        return;
+    }
+    let message = match &summary.last {
+        Some(path) => tr_format!(
+            "обработка остановлена после {}",
+            "processing stopped after {}",
+            path.display()
+        ),
+        None => tr(
+            "обработка остановлена до первого файла",
+            "processing stopped before the first file",
+        )
+        .to_string(),
+    };
+    log::write(LogLevel::Warn, &message);
+    eprintln!("{}: {message}", tr("Прервано", "Interrupted").yellow());
+    if resumable {
+        eprintln!(
+            "{}",
+            tr(
+                "Продолжить с этого места: тот же запуск с --resume",
+                "To continue from here, run the same command with --resume"
+            )
+        );
+    }
+    std::process::exit(interrupt::EXIT_INTERRUPTED);
+}
+
+/// Итог запуска в журнал --log-file
+fn log_summary(summary: &Summary) {
+    log::write(
+        LogLevel::Info,
+        &tr_format!(
+            "Готово: исправлено файлов {}, пропущено {}, отложено исправлений {}, ошибок {}, {:.2} с",
+            "Done: files fixed {}, skipped {}, fixes deferred {}, errors {}, {:.2} s",
+            summary.fixed,
+            summary.skipped,
+            summary.review,
+            summary.errors,
+            summary.elapsed.as_secs_f64()
+        ),
+    );
+}
+
+/// Статистика запуска: форматы, поля, кодировки, ошибки и время (кроме -q)
+fn print_summary(summary: &Summary, verbosity: Verbosity) {
+    if verbosity == Verbosity::Quiet {
+        return;
+    }
+    let counts = |map: &BTreeMap<String, usize>| {
+        let mut counts: Vec<_> = map.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        counts
+            .iter()
+            .map(|(name, count)| format!("{name}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let total = |map: &BTreeMap<String, usize>| map.values().sum::<usize>().to_string();
+
+    println!("{}", tr("Статистика:", "Statistics:").bold());
+    // Без скобок, если ничего не проверено: например, с --incremental всё уже проверено раньше
+    let formats = if summary.formats.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", counts(&summary.formats))
+    };
+    println!(
+        "{}",
+        tr_format!(
+            "  Проверено файлов: {}{formats}",
+            "  Files checked: {}{formats}",
+            total(&summary.formats).bold()
+        )
+    );
+    if !summary.fields.is_empty() {
+        println!(
+            "{}",
+            tr_format!(
+                "  Исправлений по полям: {} ({})",
+                "  Fixes by field: {} ({})",
+                total(&summary.fields).bold(),
+                counts(&summary.fields)
+            )
+        );
+        println!(
+            "{}",
+            tr_format!(
+                "  Исходные кодировки: {}",
+                "  Source encodings: {}",
+                counts(&summary.encodings)
+            )
+        );
+    }
+    if summary.unchanged > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Не изменились с прошлой проверки: {}",
+                "  Unchanged since last check: {}",
+                summary.unchanged.to_string().bold()
+            )
+        );
+    }
+    if summary.resumed > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Обработаны в прерванном запуске: {}",
+                "  Processed in the interrupted run: {}",
+                summary.resumed.to_string().bold()
+            )
+        );
+    }
+    if summary.skipped > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Пропущено файлов: {}",
+                "  Files skipped: {}",
+                summary.skipped.to_string().bold()
+            )
+        );
+    }
+    if summary.review > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Отложено для проверки: {}",
+                "  Deferred for review: {}",
+                summary.review.to_string().bold()
+            )
+        );
+    }
+    if summary.normalized > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Поля альбома приведены к большинству: {}",
+                "  Album fields normalized: {}",
+                summary.normalized.to_string().bold()
+            )
+        );
+    }
+    if summary.would_normalize > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Поля альбома будут приведены к большинству: {} (пробный запуск)",
+                "  Album fields would be normalized: {} (dry run)",
+                summary.would_normalize.to_string().bold()
+            )
+        );
+    }
+    if summary.unlisted > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Названий нет в треклисте Discogs: {}",
+                "  Titles missing from the Discogs tracklist: {}",
+                summary.unlisted.to_string().bold()
+            )
+        );
+    }
+    if summary.unrecoverable > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "  Не восстановить: {}",
+                "  Unrecoverable: {}",
+                summary.unrecoverable.to_string().bold()
+            )
+        );
+    }
+    println!(
+        "{}",
+        tr_format!(
+            "  Ошибок: {}",
+            "  Errors: {}",
+            summary.errors.to_string().bold()
+        )
+    );
+    println!(
+        "{}",
+        tr_format!(
+            "  Время работы: {:.2} с",
+            "  Elapsed: {:.2} s",
+            summary.elapsed.as_secs_f64()
+        )
+    );
+}
+
+/// Список исправлений, отложенных из-за низкой уверенности
+fn print_review(processor: &Processor) {
+    let mut review = processor.review.lock().unwrap_or_else(|e| e.into_inner());
+    if review.is_empty() {
+        return;
+    }
+    review.sort();
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} исправлений не применено, уверенность ниже {}% — проверьте их вручную:",
+            "{} {} fixes not applied, confidence below {}% — review them manually:",
+            tr("Внимание:", "Warning:").yellow().bold(),
+            review.len().to_string().bold(),
+            processor.min_confidence
+        )
+    );
+    for line in review.iter() {
+        println!("  {} {line}", "REVIEW".yellow());
+    }
+}
+
+/// Итоговый список значений, которые уже не восстановить
+fn print_unrecoverable(processor: &Processor) {
+    let mut values = processor
+        .unrecoverable
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if values.is_empty() {
+        return;
+    }
+    values.sort();
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} значений не восстановить: исходные байты заменены на «?» или U+FFFD — заполните эти теги заново:",
+            "{} {} values cannot be recovered: the original bytes were replaced with '?' or U+FFFD — re-tag them:",
+            tr("Внимание:", "Warning:").yellow().bold(),
+            values.len().to_string().bold()
+        )
+    );
+    for line in values.iter() {
+        println!("  {} {line}", "LOST".red());
+    }
+}
+
+/// Итоговый список файлов, которые не удалось обработать, с причинами: сообщения об
+/// ошибках по ходу длинного запуска уходят далеко вверх
+fn print_failed(processor: &Processor) {
+    let mut failed = processor.failed.lock().unwrap_or_else(|e| e.into_inner());
+    if failed.is_empty() {
+        return;
+    }
+    failed.sort();
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} файлов не удалось обработать:",
+            "{} {} files failed:",
+            tr("Внимание:", "Warning:").yellow().bold(),
+            failed.len().to_string().bold()
+        )
+    );
+    for line in failed.iter() {
+        println!("  {} {line}", "FAIL".red());
+    }
+}
+
+fn text_fixer(args: &TextArgs, detector: Detector, path: &Path) -> TextFixer {
+    let pair = detector.pair;
+    let mut fixer = TextFixer::new(args.force_cp1251_cue, detector);
+    if let Some(pair) = pair
+        && pair.source != UTF_8
+    {
+        fixer = fixer.with_legacy_encoding(pair.source);
+    }
+    if !args.update_playlist_paths {
+        return fixer;
+    }
+    match RenameJournal::in_dir(state_dir(path)).entries() {
+        Ok(renames) => fixer.with_renames(renames),
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+    }
+}
+
+fn backup_manager(args: &BackupArgs, path: &Path) -> BackupManager {
+    let root = state_dir(path);
+    let mut bm = BackupManager::new(args.no_backup);
+    if args.backup_mode == BackupMode::Manifest {
+        bm = bm.with_manifest(Manifest::new(root));
+    }
+    if let Some(dir) = &args.backup_dir {
+        bm = bm.with_backup_dir(BackupDir::new(dir, root));
+    }
+    if args.backup_compress == Some(BackupCompress::Gzip) {
+        bm = bm.with_compression();
+    }
+    bm.with_journal(BackupJournal::in_dir(root))
+}
+
+fn run_scan(mut args: ScanArgs) {
+    interrupt::install();
+    let human = args.output.output == OutputFormat::Human;
+    let roots = args.paths.roots();
+    let root = common_root(&roots);
+    if human {
+        start_roots(&roots);
+    }
+
+    let detector = args.detect.detector();
+    let settings = format!(
+        "scan {:?} {:?} {:?} {} {}",
+        args.text,
+        args.detect,
+        args.fields,
+        args.review.min_confidence,
+        args.review.online_validate
+    );
+    let processor = Processor {
+        text_fixer: text_fixer(&args.text, detector.clone(), &root),
+        audio_fixer: AudioFixer::new(detector.clone())
+            .with_fields(args.fields.filter())
+            .with_sniff(args.filter.sniff),
+        asf_fixer: AsfFixer::new(detector).with_fields(args.fields.filter()),
+        backup_manager: BackupManager::new(true),
+        prompter: Mutex::new(Prompter::new(false)),
+        dry_run: true,
+        output: args.output.output,
+        verbosity: args.output.verbosity(),
+        filter: std::mem::take(&mut args.filter),
+        min_confidence: args.review.min_confidence,
+        review: Mutex::default(),
+        show_candidates: args.output.show_candidates,
+        unrecoverable: Mutex::default(),
+        failed: Mutex::default(),
+        fail_fast: false,
+        plan: args.plan.is_some().then(|| Mutex::new(Plan::new(&root))),
+        reports: args.output.reports(),
+        preserve_mtime: false,
+        output_dir: None,
+        hardlinks: HardlinkMode::Break,
+        seed_dirs: Vec::new(),
+        state: args.incremental.state(&root, &settings),
+        marker: args.incremental.marker(&settings),
+        progress: None,
+        hooks: Vec::new(),
+        notifier: None,
+        mpd: None,
+        changed_dirs: Mutex::default(),
+        by_album: args.review.by_album(&args.output),
+        normalize_album: false,
+        album_tracks: Mutex::default(),
+        musicbrainz: args.review.musicbrainz(&root),
+        discogs: args.review.discogs(&root),
+    };
+    let summary = processor.process_tree(&roots, &root, args.threads.count());
+    compact_state(&processor);
+    log_summary(&summary);
+
+    if let (Some(path), Some(plan)) = (&args.plan, &processor.plan) {
+        let mut plan = plan.lock().unwrap_or_else(|e| e.into_inner());
+        plan.files.sort_by(|a, b| a.path.cmp(&b.path));
+        if let Err(e) = plan.save(path) {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+        if human {
+            println!(
+                "{}",
+                tr_format!(
+                    "План: {} исправлений в {} файлах сохранён в {} (cyrtag-fix apply {})",
+                    "Plan: {} fixes in {} files saved to {} (cyrtag-fix apply {})",
+                    plan.change_count().to_string().bold(),
+                    plan.files.len().to_string().bold(),
+                    path.display(),
+                    path.display()
+                )
+            );
+        }
+    }
+    args.output
+        .save_reports(&processor, &root, "cyrtag-fix scan");
+
+    if human {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов содержат кракозябры.",
+                "{} {} files contain mojibake.",
+                done_label(&summary),
+                summary.fixed.to_string().bold()
+            )
+        );
+        print_summary(&summary, args.output.verbosity());
+        print_review(&processor);
+        print_unrecoverable(&processor);
+        print_failed(&processor);
+    }
+    exit_if_interrupted(&summary, false);
+
+    let code = if summary.errors > 0 {
+        EXIT_ERRORS
+    } else if summary.fixed > 0 {
+        EXIT_FOUND
+    } else {
+        0
+    };
+    std::process::exit(code);
+}
+
+fn run_apply(mut args: ApplyArgs) {
+    interrupt::install();
+    let notifier = args.notify.notifier();
+    let mpd = args.notify.mpd();
+    let plan = match Plan::load(&args.plan) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+    };
+    let human = args.output.output == OutputFormat::Human;
+    if human {
+        start(&plan.root);
+    } else {
+        check_path(&plan.root);
+    }
+
+    // Детектор не используется: исправления берутся из плана как есть
+    let detector = Detector::default();
+    let processor = Processor {
+        text_fixer: args.write.apply_to(TextFixer::new(false, detector.clone())),
+        audio_fixer: AudioFixer::new(detector.clone())
+            .with_sniff(true)
+            .with_id3(args.write.id3.policy())
+            .with_reencode_frames(args.write.reencode_frames)
+            .with_id3v1(args.write.id3v1.policy()),
+        asf_fixer: AsfFixer::new(detector),
+        backup_manager: args.write.backup_manager(&args.backup, &plan.root),
+        prompter: Mutex::new(Prompter::new(false)),
+        dry_run: args.dry_run,
+        output: args.output.output,
+        verbosity: args.output.verbosity(),
+        // Файлы плана могли быть найдены с --sniff: формат всегда по содержимому
+        filter: FilterArgs {
+            sniff: true,
+            ..FilterArgs::default()
+        },
+        min_confidence: 0,
+        review: Mutex::default(),
+        show_candidates: args.output.show_candidates,
+        unrecoverable: Mutex::default(),
+        failed: Mutex::default(),
+        fail_fast: args.write.fail_fast,
+        plan: None,
+        reports: args.output.reports(),
+        preserve_mtime: args.write.preserve_mtime,
+        output_dir: args.write.output_dir(&plan.root),
+        hardlinks: args.write.hardlinks,
+        seed_dirs: args.write.seed_dirs(),
+        state: None,
+        marker: None,
+        progress: None,
+        hooks: std::mem::take(&mut args.write.exec),
+        notifier,
+        mpd,
+        changed_dirs: Mutex::default(),
+        by_album: false,
+        normalize_album: false,
+        album_tracks: Mutex::default(),
+        musicbrainz: None,
+        discogs: None,
+    };
+    let summary = processor.process_plan(&plan);
+    processor.notify_changes();
+    log_summary(&summary);
+    let title = if args.dry_run {
+        tr(
+            "cyrtag-fix apply (пробный запуск)",
+            "cyrtag-fix apply (dry run)",
+        )
+    } else {
+        "cyrtag-fix apply"
+    };
+    args.output.save_reports(&processor, &plan.root, title);
+
+    if human {
+        if args.dry_run {
+            println!(
+                "{}",
+                tr_format!(
+                    "{} {} файлов будет исправлено по плану (пробный запуск, файлы не изменены).",
+                    "{} {} files would be fixed by the plan (dry run, no files changed).",
+                    done_label(&summary),
+                    summary.fixed.to_string().bold()
+                )
+            );
+        } else {
+            println!(
+                "{}",
+                tr_format!(
+                    "{} {} файлов было исправлено по плану.",
+                    "{} {} files fixed by the plan.",
+                    done_label(&summary),
+                    summary.fixed.to_string().bold()
+                )
+            );
+            if let Some(manifest) = processor.backup_manager.manifest()
+                && manifest.path().exists()
+            {
+                println!(
+                    "{}",
+                    tr_format!(
+                        "Манифест запуска {}: {} (cyrtag-fix undo --run {})",
+                        "Run manifest {}: {} (cyrtag-fix undo --run {})",
+                        manifest.id(),
+                        manifest.path().display(),
+                        manifest.id()
+                    )
+                );
+            }
+        }
+        print_summary(&summary, args.output.verbosity());
+        print_failed(&processor);
+    }
+    exit_if_interrupted(&summary, false);
+    exit_if_failed(&summary, false);
+}
+
+fn run_restore(args: RestoreArgs) {
+    start(&args.path);
+
+    let backup_dir = args
+        .backup_dir
+        .as_ref()
+        .map(|dir| BackupDir::new(dir, state_dir(&args.path)));
+    let walk_root = backup_dir.as_ref().map_or(args.path.as_path(), |b| &b.dir);
+
+    // Резервные копии ищутся и в скрытых каталогах: их мог создать и сам пользователь
+    let filter = FilterArgs {
+        hidden: true,
+        ..FilterArgs::default()
+    };
+    // У файла, исправленного несколько раз, несколько бэкапов (`.bak`, `.bak.1`…):
+    // восстанавливается самый первый, остальные — промежуточные состояния
+    let mut backups: BTreeMap<PathBuf, Vec<(u32, PathBuf)>> = BTreeMap::new();
+    for entry in walk_files(walk_root, &filter) {
+        let path = entry.path();
+        let original = match &backup_dir {
+            Some(backup_dir) => backup_dir.original_path(path),
+            None => backup::original_path(path),
+        };
+        let Some(original) = original else {
+            continue;
+        };
+        if !filter.supports(&original) {
+            continue;
+        }
+        let (_, number) = backup::split_number(path);
+        backups
+            .entry(original)
+            .or_default()
+            .push((number, path.to_path_buf()));
+    }
+
+    let mut count_restored = 0usize;
+    let mut count_failed = 0usize;
+    for (original, mut paths) in backups {
+        if args.dry_run {
+            println!("{:<6} {}", "[BAK]".yellow(), original.display());
+            count_restored += 1;
+            continue;
+        }
+
+        paths.sort();
+        let (_, first) = paths.remove(0);
+        let restored = match &backup_dir {
+            Some(backup_dir) => backup::restore_from_dir(&first, backup_dir, args.keep_backup),
+            None => backup::restore_backup(&first, args.keep_backup),
+        };
+        match restored {
+            Ok(original) => {
+                println!("{:<6} {}", "[BAK]".yellow(), original.display());
+                count_restored += 1;
+            }
+            Err(e) => {
+                eprintln!("{} {e}", tr("Ошибка", "Error").red());
+                count_failed += 1;
+                continue;
+            }
+        }
+        if args.keep_backup {
+            continue;
+        }
+        for (_, path) in paths {
+            if let Err(source) = fs::remove_file(&path) {
+                let e = cyrtag_fix::Error::Restore { path, source };
+                eprintln!("{} {e}", tr("Ошибка", "Error").red());
+                count_failed += 1;
+            }
+        }
+    }
+
+    if args.dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов будет восстановлено (пробный запуск, файлы не изменены).",
+                "{} {} files would be restored (dry run, no files changed).",
+                tr("Готово!", "Done!").green().bold(),
+                count_restored.to_string().bold()
+            )
+        );
+        return;
+    }
+
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} файлов было восстановлено.",
+            "{} {} files restored.",
+            tr("Готово!", "Done!").green().bold(),
+            count_restored.to_string().bold()
+        )
+    );
+    if count_failed > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов восстановить не удалось.",
+                "{} {} files could not be restored.",
+                tr("Внимание:", "Warning:").yellow().bold(),
+                count_failed.to_string().bold()
+            )
+        );
+    }
+}
+
+fn run_clean_backups(args: CleanBackupsArgs) {
+    start(&args.path);
+
+    let journal = BackupJournal::in_dir(state_dir(&args.path));
+    let entries = match journal.entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(EXIT_ERRORS);
+        }
+    };
+
+    let now = time::now_secs();
+    let mut kept = Vec::new();
+    let mut count_removed = 0usize;
+    let mut count_failed = 0usize;
+    let mut freed = 0u64;
+    for (created, backup) in entries {
+        // бэкап уже восстановлен restore или удалён вручную: из журнала он уходит
+        let Some(metadata) = fs::symlink_metadata(&backup)
+            .ok()
+            .filter(fs::Metadata::is_file)
+        else {
+            continue;
+        };
+        if args
+            .older_than
+            .is_some_and(|age| now.saturating_sub(created) < age)
+        {
+            kept.push((created, backup));
+            continue;
+        }
+        if !args.dry_run
+            && let Err(e) = fs::remove_file(&backup)
+        {
+            eprintln!(
+                "{} {} {}: {e}",
+                tr("Ошибка", "Error").red(),
+                tr("удаления", "removing"),
+                backup.display()
+            );
+            kept.push((created, backup));
+            count_failed += 1;
+            continue;
+        }
+        println!("{:<6} {}", "[DEL]".red(), backup.display());
+        count_removed += 1;
+        freed += metadata.len();
+    }
+
+    const MB: f64 = 1024.0 * 1024.0;
+    let freed = format!("{:.1}", freed as f64 / MB);
+    if args.dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} бэкапов будет удалено, {} МБ (пробный запуск, файлы не изменены).",
+                "{} {} backups would be removed, {} MB (dry run, no files changed).",
+                tr("Готово!", "Done!").green().bold(),
+                count_removed.to_string().bold(),
+                freed
+            )
+        );
+        return;
+    }
+
+    if let Err(e) = journal.replace(&kept) {
+        eprintln!("{} {e}", tr("Ошибка", "Error").red());
+    }
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} бэкапов удалено, освобождено {} МБ.",
+            "{} {} backups removed, {} MB freed.",
+            tr("Готово!", "Done!").green().bold(),
+            count_removed.to_string().bold(),
+            freed
+        )
+    );
+    if count_failed > 0 {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} бэкапов удалить не удалось.",
+                "{} {} backups could not be removed.",
+                tr("Внимание:", "Warning:").yellow().bold(),
+                count_failed.to_string().bold()
+            )
+        );
+        std::process::exit(EXIT_ERRORS);
+    }
+}
+
+fn run_rename(args: RenameArgs) {
+    start(&args.path);
+
+    let journal = RenameJournal::in_dir(state_dir(&args.path));
+    if args.undo {
+        undo_renames(&journal, args.dry_run);
+        return;
+    }
+
+    let renamer = Renamer::new(args.detect.detector());
+    let mut count_renamed = 0usize;
+    let mut planned = HashSet::new();
+
+    // Сначала собираем список, чтобы не обходить уже переименованные файлы.
+    // Каталоги идут после файлов и от самых глубоких к корню: переименование
+    // родителя не должно ломать ещё не обработанные пути внутри него.
+    let mut renames: Vec<(PathBuf, PathBuf)> = walk_files(&args.path, &args.filter)
+        .map(|entry| entry.into_path())
+        .filter(|path| args.filter.format_extension(path).is_some())
+        .filter_map(|path| renamer.fixed_path(&path).map(|fixed| (path, fixed)))
+        .collect();
+    if args.dirs {
+        renames.extend(
+            walk_dirs(&args.path, &args.filter)
+                .map(|entry| entry.into_path())
+                .filter_map(|path| renamer.fixed_dir_path(&path).map(|fixed| (path, fixed))),
+        );
+    }
+
+    for (path, fixed) in renames {
+        println!(
+            "  {} '{}' -> '{}'",
+            "FIX".cyan(),
+            file_name(&path),
+            file_name(&fixed)
+        );
+
+        // Два разных файла не должны получить одно и то же имя
+        if fixed.exists() || !planned.insert(fixed.clone()) {
+            eprintln!(
+                "{}",
+                tr_format!(
+                    "{}: {} уже существует, файл пропущен",
+                    "{}: {} already exists, file skipped",
+                    tr("Внимание", "Warning").yellow(),
+                    fixed.display()
+                )
+            );
+            continue;
+        }
+
+        if !args.dry_run {
+            if let Err(e) = renamer.apply(&path, &fixed) {
+                eprintln!("{} {e}", tr("Ошибка", "Error").red());
+                continue;
+            }
+            if let Err(e) = journal.record(&path, &fixed) {
+                eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            }
+        }
+
+        println!("{:<6} {}", "[MV]".bright_blue(), fixed.display());
+        count_renamed += 1;
+    }
+
+    if args.dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов и каталогов будет переименовано (пробный запуск, файлы не изменены).",
+                "{} {} files and directories would be renamed (dry run, no files changed).",
+                tr("Готово!", "Done!").green().bold(),
+                count_renamed.to_string().bold()
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов и каталогов было переименовано.",
+                "{} {} files and directories renamed.",
+                tr("Готово!", "Done!").green().bold(),
+                count_renamed.to_string().bold()
+            )
+        );
+        if count_renamed > 0 {
+            println!(
+                "{}",
+                tr_format!(
+                    "Журнал для отката: {} (cyrtag-fix rename --undo)",
+                    "Undo journal: {} (cyrtag-fix rename --undo)",
+                    journal.path().display()
+                )
+            );
+        }
+    }
+}
+
+/// Откат переименований по журналу в обратном порядке
+fn undo_renames(journal: &RenameJournal, dry_run: bool) {
+    let entries = match journal.entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            return;
+        }
+    };
+
+    let renamer = Renamer::default();
+    let mut count_restored = 0usize;
+    let mut failed = Vec::new();
+
+    for (from, to) in entries.into_iter().rev() {
+        println!(
+            "  {} '{}' -> '{}'",
+            "UNDO".cyan(),
+            file_name(&to),
+            file_name(&from)
+        );
+
+        if !dry_run && let Err(e) = renamer.apply(&to, &from) {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            failed.push((from, to));
+            continue;
+        }
+
+        println!("{:<6} {}", "[MV]".bright_blue(), from.display());
+        count_restored += 1;
+    }
+
+    if dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов будет переименовано обратно (пробный запуск, файлы не изменены).",
+                "{} {} files would be renamed back (dry run, no files changed).",
+                tr("Готово!", "Done!").green().bold(),
+                count_restored.to_string().bold()
+            )
+        );
+        return;
+    }
+
+    // В журнале остаются только записи, которые не удалось откатить
+    failed.reverse();
+    if let Err(e) = journal.replace(&failed) {
+        eprintln!("{} {e}", tr("Ошибка", "Error").red());
+    }
+
+    println!(
+        "{}",
+        tr_format!(
+            "{} {} файлов было переименовано обратно.",
+            "{} {} files renamed back.",
+            tr("Готово!", "Done!").green().bold(),
+            count_restored.to_string().bold()
+        )
+    );
+}
+
+/// Каталог для журналов и манифестов: сам путь или, для файла, его родитель
+fn state_dir(path: &Path) -> &Path {
+    if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    }
+}
+
+fn run_undo(args: UndoArgs) {
+    start(&args.path);
+
+    let root = state_dir(&args.path);
+    let runs = match manifest::list_runs(root) {
+        Ok(runs) => runs,
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(1);
+        }
+    };
+
+    if args.list {
+        for run in &runs {
+            println!("  {run}");
+        }
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} запусков в {}",
+                "{} {} runs in {}",
+                tr("Готово!", "Done!").green().bold(),
+                runs.len().to_string().bold(),
+                manifest::runs_dir(root).display()
+            )
+        );
+        return;
+    }
+
+    let Some(run) = args.run.or_else(|| runs.last().cloned()) else {
+        eprintln!(
+            "{}",
+            tr_format!(
+                "{}: в {} нет сохранённых запусков",
+                "{}: no saved runs in {}",
+                tr("Ошибка", "Error").red(),
+                manifest::runs_dir(root).display()
+            )
+        );
+        std::process::exit(1);
+    };
+
+    let records = match manifest::load_run(root, &run) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            std::process::exit(1);
+        }
+    };
+
+    let mut count_restored = 0usize;
+    // В обратном порядке: если файл менялся дважды, последним запишется самое раннее значение
+    for record in records.iter().rev() {
+        for field in &record.fields {
+            println!("  {} {}: '{}'", "UNDO".cyan(), field.key, field.value);
+        }
+
+        if !args.dry_run
+            && let Err(e) = manifest::undo_file(record)
+        {
+            eprintln!("{} {e}", tr("Ошибка", "Error").red());
+            continue;
+        }
+
+        println!("{:<6} {}", "[UNDO]".yellow(), record.path.display());
+        count_restored += 1;
+    }
+
+    if args.dry_run {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов будет откачено из запуска {run} (пробный запуск, файлы не изменены).",
+                "{} {} files would be rolled back from run {run} (dry run, no files changed).",
+                tr("Готово!", "Done!").green().bold(),
+                count_restored.to_string().bold()
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            tr_format!(
+                "{} {} файлов было откачено из запуска {run}.",
+                "{} {} files rolled back from run {run}.",
+                tr("Готово!", "Done!").green().bold(),
+                count_restored.to_string().bold()
+            )
+        );
+    }
+}
+
+/// Проверка пути и приветствие; при отсутствии пути завершает процесс
+fn start(path: &Path) {
+    check_path(path);
+
+    println!(
+        "{} {}",
+        tr("Старт обработки каталога:", "Processing directory:")
+            .green()
+            .bold(),
+        path.display()
+    );
+}
+
+/// Приветствие fix и scan
+fn start_roots(roots: &[PathBuf]) {
+    match roots {
+        [] => {
+            println!("{}", tr("Список путей пуст.", "The path list is empty."));
+            return;
+        }
+        [path] => {
+            start(path);
+            return;
+        }
+        _ => {}
+    }
+    // Длинный список из --files-from целиком не печатается
+    const SHOWN: usize = 5;
+    let mut paths: Vec<String> = roots
+        .iter()
+        .take(SHOWN)
+        .map(|path| path.display().to_string())
+        .collect();
+    if roots.len() > SHOWN {
+        paths.push(tr_format!(
+            "… и ещё {}",
+            "… and {} more",
+            roots.len() - SHOWN
+        ));
+    }
+    println!(
+        "{} {}",
+        tr("Старт обработки путей:", "Processing paths:")
+            .green()
+            .bold(),
+        paths.join(", ")
+    );
+}
+
+/// Пути из командной строки без повторов и без путей внутри других путей из списка.
+/// Несколько путей приводятся к абсолютным, чтобы у них был общий каталог.
+/// Завершает процесс, если какого-то пути нет
+fn roots(paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths.iter().for_each(|path| check_path(path));
+    if let [path] = paths {
+        return vec![path.clone()];
+    }
+    let paths: Vec<(PathBuf, PathBuf)> = paths
+        .iter()
+        .map(|path| {
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            (path, canonical)
+        })
+        .collect();
+    let all: HashSet<&Path> = paths
+        .iter()
+        .map(|(_, canonical)| canonical.as_path())
+        .collect();
+    let mut seen = HashSet::new();
+    paths
+        .iter()
+        // Путь лишний, если он внутри другого пути из списка или уже встречался
+        .filter(|(_, canonical)| {
+            !canonical.ancestors().skip(1).any(|dir| all.contains(dir))
+                && seen.insert(canonical.as_path())
+        })
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// Общий каталог путей: в нём хранятся манифесты и журналы, от него строятся пути
+/// в --backup-dir, --output-dir и в плане. Для одного пути — он сам.
+/// Завершает процесс, если общего каталога нет (пути на разных дисках)
+fn common_root(roots: &[PathBuf]) -> PathBuf {
+    let [first, rest @ ..] = roots else {
+        return PathBuf::new();
+    };
+    if rest.is_empty() {
+        return first.clone();
+    }
+    let mut root = state_dir(first).to_path_buf();
+    for path in rest {
+        while !path.starts_with(&root) {
+            if !root.pop() {
+                eprintln!(
+                    "{}",
+                    tr_format!(
+                        "{}: у путей {} и {} нет общего каталога",
+                        "{}: paths {} and {} have no common directory",
+                        tr("Ошибка", "Error").red(),
+                        first.display(),
+                        path.display()
+                    )
+                );
+                std::process::exit(EXIT_ERRORS);
+            }
+        }
+    }
+    root
+}
+
+/// Завершает процесс, если путь не существует
+fn check_path(path: &Path) {
+    if !path.exists() {
+        eprintln!(
+            "{}",
+            tr_format!(
+                "{}: путь не найден: {}",
+                "{}: path not found: {}",
+                tr("Ошибка", "Error").red(),
+                path.display()
+            )
+        );
+        std::process::exit(EXIT_ERRORS);
+    }
+}
+
+/// Рекурсивный обход файлов каталога; ошибки обхода печатаются и пропускаются
+fn walk_files<'a>(root: &'a Path, filter: &'a FilterArgs) -> impl Iterator<Item = DirEntry> + 'a {
+    walk(root, WalkDir::new(root), filter).filter(|entry| entry.file_type().is_file())
+}
+
+/// Рекурсивный обход вложенных каталогов, от самых глубоких к корню (сам корень не входит)
+fn walk_dirs<'a>(root: &'a Path, filter: &'a FilterArgs) -> impl Iterator<Item = DirEntry> + 'a {
+    walk(
+        root,
+        WalkDir::new(root).min_depth(1).contents_first(true),
+        filter,
+    )
+    .filter(|entry| entry.file_type().is_dir())
+}
+
+fn walk<'a>(
+    root: &'a Path,
+    walker: WalkDir,
+    filter: &'a FilterArgs,
+) -> impl Iterator<Item = DirEntry> + 'a {
+    let mut walker = walker.follow_links(filter.follow_symlinks);
+    if let Some(depth) = filter.max_depth {
+        walker = walker.max_depth(depth);
+    }
+    walker
+        .into_iter()
+        .filter_entry(move |entry| filter.accepts(root, entry))
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(err) if err.loop_ancestor().is_some() => {
+                let message = tr_format!(
+                    "цикл символических ссылок, пропущено: {}",
+                    "symbolic link loop, skipped: {}",
+                    err.path().unwrap_or(root).display()
+                );
+                eprintln!("{}: {message}", tr("Внимание", "Warning").yellow());
+                log::write(LogLevel::Warn, &message);
+                None
+            }
+            Err(err) => {
+                WALK_ERRORS
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(format!("{}: {err}", tr("Ошибка обхода", "Walk error")));
+                eprintln!("{}: {}", tr("Ошибка обхода", "Walk error").red(), err);
+                log::write(
+                    LogLevel::Error,
+                    &tr_format!("Ошибка обхода: {err}", "Walk error: {err}"),
+                );
+                None
+            }
+        })
+}
+
+/// Служебные каталоги Synology, QNAP, Windows и Linux: миниатюры, корзины, снимки
+const SYSTEM_DIRS: &[&str] = &[
+    "@eaDir",
+    "@Recycle",
+    "@Recently-Snapshot",
+    "#recycle",
+    "#snapshot",
+    "$RECYCLE.BIN",
+    "RECYCLER",
+    "System Volume Information",
+    "lost+found",
+];
+
+/// Скрытый файл или каталог (имя с точкой, а в Windows — атрибут «скрытый» или «системный»)
+/// либо служебный каталог из [`SYSTEM_DIRS`]
+fn is_hidden(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    name.starts_with('.')
+        || (entry.file_type().is_dir() && SYSTEM_DIRS.iter().any(|d| d.eq_ignore_ascii_case(&name)))
+        || has_hidden_attribute(entry)
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(entry: &DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    entry.metadata().is_ok_and(|metadata| {
+        metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0
+    })
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_entry: &DirEntry) -> bool {
+    false
+}
+
+/// Путь относительно корня обхода с разделителем `/` — для сравнения с glob-шаблонами
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase())
+}
+
+/// Расширение из --extensions: без точки и в нижнем регистре
+fn parse_extension(ext: &str) -> Result<String, String> {
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    if ext.is_empty() {
+        return Err(tr("пустое расширение", "empty extension").to_string());
+    }
+    Ok(ext)
+}
+
+fn parse_language(code: &str) -> Result<Language, String> {
+    Language::from_code(code).ok_or_else(|| {
+        tr_format!(
+            "неизвестный язык {code}, допустимы: ru, uk, be, el, tr",
+            "unknown language {code}, possible values: ru, uk, be, el, tr"
+        )
+    })
+}
+
+/// Кодировка по имени: cp1253, windows-1254, koi8-u, iso-8859-7, utf-8 …
+fn parse_encoding(name: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(name.trim().as_bytes())
+        .ok_or_else(|| tr_format!("неизвестная кодировка {name}", "unknown encoding {name}"))
+}
+
+fn parse_field(name: &str) -> Result<ItemKey, String> {
+    fields::parse_field(name)
+        .ok_or_else(|| tr_format!("неизвестное поле {name}", "unknown field {name}"))
+}
+
+/// `поле=порог` для --field-threshold
+fn parse_age(value: &str) -> Result<u64, String> {
+    time::parse_duration(value).ok_or_else(|| {
+        tr_format!(
+            "неверный срок: {value} (ожидалось, например, 30d, 12h или 2w)",
+            "invalid age: {value} (expected e.g. 30d, 12h or 2w)"
+        )
+    })
+}
+
+fn parse_field_threshold(value: &str) -> Result<(ItemKey, f64), String> {
+    let (name, threshold) = value.split_once('=').ok_or_else(|| {
+        tr_format!(
+            "ожидалось поле=порог: {value}",
+            "expected field=threshold: {value}"
+        )
+    })?;
+    let threshold = threshold.trim().parse().map_err(|_| {
+        tr_format!(
+            "неверный порог: {threshold}",
+            "invalid threshold: {threshold}"
+        )
+    })?;
+    Ok((parse_field(name.trim())?, threshold))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,413 @@
+//! Манифест запуска: исходные значения тегов вместо полных копий аудио-файлов.
+//!
+//! Каждый запуск пишет `.cyrtag-fix/runs/<id>.jsonl` в корне обработанного каталога:
+//! первая строка — заголовок запуска, далее по строке на каждый изменённый файл.
+//! Запись идёт до сохранения тегов, поэтому прерванный запуск тоже можно откатить.
+
+use crate::atomic;
+use crate::audio::{self, Id3Version, read_tags, tag_values};
+use crate::error::{Error, Result};
+use crate::fields::{PICTURE_FIELD, picture_key};
+use crate::json::Json;
+use crate::locale::tr;
+use crate::time;
+use lofty::prelude::*;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Каталог с манифестами относительно корня обработки
+pub const RUNS_DIR: &str = ".cyrtag-fix/runs";
+const MANIFEST_EXTENSION: &str = "jsonl";
+
+/// Исходное значение одного поля тега
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRecord {
+    /// Ключ в «родном» для формата тега виде (`TIT2`, `TITLE`, `©nam`)
+    pub key: String,
+    pub value: String,
+}
+
+/// Исходные значения изменённых полей одного файла
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    pub time: u64,
+    pub tag_type: TagType,
+    pub fields: Vec<FieldRecord>,
+}
+
+impl FileRecord {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("path", self.path.display().to_string().into()),
+            ("time", time::format_iso8601(self.time).into()),
+            ("tag_type", tag_type_name(self.tag_type).into()),
+            (
+                "fields",
+                Json::Array(
+                    self.fields
+                        .iter()
+                        .map(|f| {
+                            Json::object([
+                                ("key", f.key.as_str().into()),
+                                ("value", f.value.as_str().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Option<Self> {
+        let fields = json
+            .get("fields")?
+            .as_array()?
+            .iter()
+            .map(|f| {
+                Some(FieldRecord {
+                    key: f.get("key")?.as_str()?.to_string(),
+                    value: f.get("value")?.as_str()?.to_string(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            path: PathBuf::from(json.get("path")?.as_str()?),
+            time: 0,
+            tag_type: parse_tag_type(json.get("tag_type")?.as_str()?)?,
+            fields,
+        })
+    }
+
+    /// Запись исходных значений полей `tag` для ключей `keys`. У поля с несколькими
+    /// значениями (несколько ARTIST у Vorbis, TPE1 с `\0` в ID3v2.4) записываются все
+    /// по порядку, а повторный ключ — один раз. Описания картинок записываются под
+    /// [`PICTURE_FIELD`] для всех картинок тега
+    pub fn from_tag<'a>(
+        path: &Path,
+        tag: &Tag,
+        keys: impl IntoIterator<Item = &'a ItemKey>,
+    ) -> Self {
+        let tag_type = tag.tag_type();
+        let mut seen: Vec<&ItemKey> = Vec::new();
+        let mut fields = Vec::new();
+        for key in keys {
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            let name = if *key == picture_key() {
+                PICTURE_FIELD
+            } else if let Some(name) = key.map_key(tag_type, true) {
+                name
+            } else {
+                continue;
+            };
+            fields.extend(tag_values(tag, key).into_iter().map(|value| FieldRecord {
+                key: name.to_string(),
+                value: value.to_string(),
+            }));
+        }
+
+        Self {
+            path: path.to_path_buf(),
+            time: time::now_secs(),
+            tag_type,
+            fields,
+        }
+    }
+}
+
+pub fn tag_type_name(tag_type: TagType) -> &'static str {
+    match tag_type {
+        TagType::Ape => "ape",
+        TagType::Id3v1 => "id3v1",
+        TagType::Id3v2 => "id3v2",
+        TagType::Mp4Ilst => "mp4",
+        TagType::VorbisComments => "vorbis",
+        TagType::RiffInfo => "riff-info",
+        TagType::AiffText => "aiff-text",
+        _ => "unknown",
+    }
+}
+
+pub(crate) fn parse_tag_type(name: &str) -> Option<TagType> {
+    Some(match name {
+        "ape" => TagType::Ape,
+        "id3v1" => TagType::Id3v1,
+        "id3v2" => TagType::Id3v2,
+        "mp4" => TagType::Mp4Ilst,
+        "vorbis" => TagType::VorbisComments,
+        "riff-info" => TagType::RiffInfo,
+        "aiff-text" => TagType::AiffText,
+        _ => return None,
+    })
+}
+
+/// Манифест текущего запуска; файл создаётся при первой записи
+pub struct Manifest {
+    id: String,
+    root: PathBuf,
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl Manifest {
+    /// Новый запуск в каталоге `root`; идентификатор — время старта
+    pub fn new(root: &Path) -> Self {
+        let started = time::now_secs();
+        let dir = runs_dir(root);
+        let base = time::format_compact(started);
+
+        let mut id = base.clone();
+        let mut n = 1;
+        while dir.join(format!("{id}.{MANIFEST_EXTENSION}")).exists() {
+            n += 1;
+            id = format!("{base}-{n}");
+        }
+
+        Self {
+            path: dir.join(format!("{id}.{MANIFEST_EXTENSION}")),
+            id,
+            root: root.to_path_buf(),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn error(&self, source: io::Error) -> Error {
+        Error::Manifest {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    fn open(&self) -> io::Result<File> {
+        fs::create_dir_all(runs_dir(&self.root))?;
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&self.path)?;
+        let header = Json::object([
+            ("run", self.id.as_str().into()),
+            ("started", time::format_iso8601(time::now_secs()).into()),
+            ("root", self.root.display().to_string().into()),
+        ]);
+        writeln!(file, "{header}")?;
+        Ok(file)
+    }
+
+    /// Дописать запись и сбросить её на диск до изменения самого файла
+    pub fn record(&self, record: &FileRecord) -> Result<()> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(self.open().map_err(|e| self.error(e))?);
+        }
+        let file = guard.as_mut().expect("файл манифеста открыт выше");
+
+        writeln!(file, "{}", record.to_json())
+            .and_then(|_| file.sync_data())
+            .map_err(|e| self.error(e))
+    }
+}
+
+/// Каталог манифестов для корня `root`
+pub fn runs_dir(root: &Path) -> PathBuf {
+    root.join(RUNS_DIR)
+}
+
+/// Идентификаторы сохранённых запусков, от старых к новым
+pub fn list_runs(root: &Path) -> Result<Vec<String>> {
+    let dir = runs_dir(root);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => return Err(Error::Manifest { path: dir, source }),
+    };
+
+    let mut runs: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == MANIFEST_EXTENSION)
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    runs.sort();
+    Ok(runs)
+}
+
+/// Записи запуска `id` в порядке изменения файлов
+pub fn load_run(root: &Path, id: &str) -> Result<Vec<FileRecord>> {
+    let path = runs_dir(root).join(format!("{id}.{MANIFEST_EXTENSION}"));
+    let content = fs::read_to_string(&path).map_err(|source| Error::Manifest {
+        path: path.clone(),
+        source,
+    })?;
+
+    content
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            Json::parse(line)
+                .ok()
+                .as_ref()
+                .and_then(FileRecord::from_json)
+                .ok_or_else(|| Error::Manifest {
+                    path: path.clone(),
+                    source: io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        tr("повреждённая запись", "corrupted record"),
+                    ),
+                })
+        })
+        .collect()
+}
+
+/// Вернуть исходные значения полей из записи манифеста
+pub fn undo_file(record: &FileRecord) -> Result<()> {
+    let path = &record.path;
+    // Формат — по содержимому: файл мог быть исправлен с --sniff под чужим расширением
+    let mut tagged_file = read_tags(path, true)?;
+
+    if tagged_file.tag(record.tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(record.tag_type));
+    }
+    let tag = tagged_file
+        .tag_mut(record.tag_type)
+        .expect("тег добавлен выше");
+
+    let mut keys: Vec<&str> = Vec::new();
+    for field in &record.fields {
+        if !keys.contains(&field.key.as_str()) {
+            keys.push(&field.key);
+        }
+    }
+    for name in keys {
+        let values = record.fields.iter().filter(|f| f.key == name);
+        // Описания возвращаются, только если картинок столько же: иначе неясно, какой что
+        if name == PICTURE_FIELD {
+            if tag.pictures().len() == values.clone().count() {
+                for (i, field) in values.enumerate() {
+                    let mut picture = tag.pictures()[i].clone();
+                    picture.set_description(Some(field.value.clone()).filter(|d| !d.is_empty()));
+                    tag.set_picture(i, picture);
+                }
+            }
+            continue;
+        }
+        let key = ItemKey::from_key(record.tag_type, name);
+        let items: Vec<TagItem> = tag.take(&key).collect();
+        // Столько же значений, сколько записано: язык и описание (COMM, USLT) остаются
+        // у своих значений. Иначе поле собирается из записанных значений заново
+        let restored: Vec<TagItem> = if items.len() == values.clone().count() {
+            items
+                .into_iter()
+                .zip(values)
+                .map(|(item, field)| {
+                    let mut restored =
+                        TagItem::new(key.clone(), ItemValue::Text(field.value.clone()));
+                    restored.set_lang(*item.lang());
+                    restored.set_description(item.description().to_string());
+                    restored
+                })
+                .collect()
+        } else {
+            values
+                .map(|field| TagItem::new(key.clone(), ItemValue::Text(field.value.clone())))
+                .collect()
+        };
+        // push_text отбросил бы пользовательские поля (TXXX, свои поля Vorbis)
+        for item in restored {
+            tag.push_unchecked(item);
+        }
+    }
+
+    atomic::modify(path, |file| {
+        // Тег сохраняется так же, как при исправлении: версия ID3v2, с которой файл был
+        // записан, и заполнение остаются прежними
+        let pictures = audio::picture_data(file);
+        let options =
+            audio::write_options(file, Id3Version::Keep, record.tag_type == TagType::Id3v2);
+        audio::save_tag(file, tag.clone(), options).map_err(|source| Error::SaveTags {
+            path: path.clone(),
+            source,
+        })?;
+        audio::check_pictures(path, file, &pictures)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AudioFixer, BackupManager, Detector};
+
+    /// MP3 с тегом ID3v2.3: название `title` в Latin-1 и `padding` байтов заполнения,
+    /// затем тишина MPEG-1 Layer III
+    fn mp3(title: &[u8], padding: usize) -> Vec<u8> {
+        let mut frame = b"TIT2".to_vec();
+        frame.extend((title.len() as u32 + 1).to_be_bytes());
+        frame.extend([0, 0, 0]);
+        frame.extend(title);
+        let size = (frame.len() + padding) as u32;
+        let mut bytes = b"ID3\x03\x00\x00".to_vec();
+        bytes.extend((0..4).rev().map(|i| (size >> (7 * i)) as u8 & 0x7f));
+        bytes.extend(frame);
+        bytes.extend(vec![0; padding]);
+        for _ in 0..20 {
+            bytes.extend([0xff, 0xfb, 0x90, 0x64]);
+            bytes.extend([0; 413]);
+        }
+        bytes
+    }
+
+    fn title(path: &Path) -> String {
+        let tagged_file = read_tags(path, false).unwrap();
+        let tag = tagged_file.tag(TagType::Id3v2).unwrap();
+        tag.get_string(&ItemKey::TrackTitle).unwrap().to_string()
+    }
+
+    #[test]
+    fn undo_keeps_id3v2_version_and_padding() {
+        let root = std::env::temp_dir().join(format!("cyrtag-fix-undo-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("a.mp3");
+        let (title_bytes, _, _) = encoding_rs::WINDOWS_1251.encode("Львица рока");
+        let original = mp3(&title_bytes, 256);
+        fs::write(&path, &original).unwrap();
+        let original_title = title(&path);
+
+        let fixer = AudioFixer::new(Detector::default());
+        let backups = BackupManager::new(false).with_manifest(Manifest::new(&root));
+        let fix = fixer.scan(&path).unwrap().expect("кракозябры в названии");
+        fixer.apply(&path, fix, &backups).unwrap();
+        assert_eq!(title(&path), "Львица рока");
+
+        let records = load_run(&root, backups.manifest().unwrap().id()).unwrap();
+        for record in &records {
+            undo_file(record).unwrap();
+        }
+        let undone = fs::read(&path).unwrap();
+        let padding = |bytes: &[u8]| audio::id3v2_padding(&mut io::Cursor::new(bytes));
+        let restored_title = title(&path);
+        fs::remove_dir_all(&root).unwrap();
+
+        // версия, ревизия и флаги заголовка
+        assert_eq!(undone[..6], original[..6]);
+        assert_eq!(padding(&undone), padding(&original));
+        assert_eq!(restored_title, original_title);
+    }
+}
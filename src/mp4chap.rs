@@ -0,0 +1,683 @@
+//! Названия глав MP4/M4B (аудиокниги), которых lofty не читает
+//!
+//! Главы хранятся двумя способами, и программы обычно пишут оба:
+//! - Nero: атом `moov/udta/chpl` — время начала и название (до 255 байт) каждой главы;
+//! - QuickTime: текстовая дорожка, на которую ссылается `tref/chap` звуковой дорожки;
+//!   название главы — отдельный сэмпл в `mdat`: длина (u16) и текст в UTF-8 или UTF-16 с BOM.
+//!
+//! Названия не в UTF-8 читаются как Latin-1 — так их показывают плееры — и после исправления
+//! записываются в UTF-8. Если длина названий меняется, пересчитываются размеры атомов,
+//! в которых они лежат, размеры сэмплов (`stsz`) и смещения блоков всех дорожек (`stco`,
+//! `co64`). Остальное содержимое файла сохраняется байт в байт: в памяти собираются только
+//! изменённые места, а всё между ними, в том числе `mdat`, копируется потоком.
+//! Фрагментированные MP4 (с `moof`) не поддерживаются.
+
+use crate::locale::tr;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Атомы, в которых ищутся главы и таблицы сэмплов
+const CONTAINERS: [&[u8; 4]; 8] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"tref", b"edts",
+];
+
+/// Предел длины названия главы Nero в байтах
+const NERO_TITLE_LEN: usize = 255;
+
+/// Предел размера сэмпла с названием главы QuickTime: большие сэмплы — не текст
+const MAX_SAMPLE_LEN: u64 = 64 * 1024;
+
+type ParseResult<T> = std::result::Result<T, &'static str>;
+
+struct Atom {
+    kind: [u8; 4],
+    /// Начало заголовка в файле
+    offset: u64,
+    /// Длина заголовка: 8, а с 64-битным размером — 16
+    header: u64,
+    /// Размер вместе с заголовком
+    size: u64,
+    /// Размер в заголовке — 0: атом продолжается до конца файла
+    to_end: bool,
+    children: Vec<Atom>,
+}
+
+impl Atom {
+    fn body(&self) -> u64 {
+        self.offset + self.header
+    }
+
+    fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+
+    fn path(&self, path: &[&[u8; 4]]) -> Option<&Atom> {
+        path.iter().try_fold(self, |atom, kind| {
+            atom.children.iter().find(|child| &child.kind == *kind)
+        })
+    }
+
+    /// Этот атом и все вложенные
+    fn walk<'a>(&'a self, atoms: &mut Vec<&'a Atom>) {
+        atoms.push(self);
+        for child in &self.children {
+            child.walk(atoms);
+        }
+    }
+}
+
+/// Название главы Nero
+struct NeroChapter {
+    /// Начало главы в единицах по 100 нс
+    start: u64,
+    title: Vec<u8>,
+}
+
+/// Атом `chpl`
+struct Nero {
+    body: u64,
+    len: u64,
+    /// Версия, флаги и (в версии 1) ещё четыре байта до числа глав
+    prefix: Vec<u8>,
+    chapters: Vec<NeroChapter>,
+}
+
+/// Сэмпл с названием главы QuickTime
+struct Sample {
+    offset: u64,
+    size: u64,
+    /// Где в `stsz` записан размер сэмпла; `None` — у всех сэмплов дорожки один размер
+    size_entry: Option<u64>,
+    text: Vec<u8>,
+    /// Атомы после текста (`encd`, `styl`)
+    rest: Vec<u8>,
+}
+
+/// Где в файле записаны названия глав
+struct Layout {
+    atoms: Vec<Atom>,
+    nero: Option<Nero>,
+    samples: Vec<Sample>,
+}
+
+/// Изменение байтов файла: `len` байтов с `offset` заменяются на `data`
+struct Edit {
+    offset: u64,
+    len: u64,
+    data: Vec<u8>,
+}
+
+/// Исправленные названия глав: изменения файла по возрастанию смещения, без пересечений
+pub struct Rewrite {
+    edits: Vec<Edit>,
+}
+
+/// Названия глав файла по порядку, без повторов; пусто, если глав нет или файл не читается
+pub fn read_titles<R: Read + Seek>(file: &mut R) -> Vec<String> {
+    let Ok(layout) = Layout::read(file) else {
+        return Vec::new();
+    };
+    let nero = layout.nero.iter().flat_map(|nero| &nero.chapters);
+    let mut titles: Vec<String> = Vec::new();
+    for title in nero
+        .map(|chapter| &chapter.title)
+        .chain(layout.samples.iter().map(|sample| &sample.text))
+    {
+        let title = decode(title);
+        if !titles.contains(&title) {
+            titles.push(title);
+        }
+    }
+    titles
+}
+
+/// Изменения файла `file`, которые заменяют названия глав через `replace` (`None` — оставить
+/// как есть); `None`, если ничего не изменилось
+pub fn replace_titles<R: Read + Seek>(
+    file: &mut R,
+    replace: impl Fn(&str) -> Option<String>,
+) -> ParseResult<Option<Rewrite>> {
+    let layout = Layout::read(file)?;
+    let mut edits = Vec::new();
+    // Новые размеры сэмплов и атомов, смещения блоков: (позиция, значение, ширина в байтах)
+    let mut patches: Vec<(u64, u64, usize)> = Vec::new();
+
+    if let Some(nero) = &layout.nero {
+        let mut changed = false;
+        let mut body = nero.prefix.clone();
+        body.push(nero.chapters.len() as u8);
+        for chapter in &nero.chapters {
+            let title = match replace(&decode(&chapter.title)) {
+                Some(fixed) => {
+                    changed = true;
+                    truncate(&fixed, NERO_TITLE_LEN).as_bytes().to_vec()
+                }
+                None => chapter.title.clone(),
+            };
+            body.extend(chapter.start.to_be_bytes());
+            body.push(title.len() as u8);
+            body.extend(title);
+        }
+        if changed {
+            edits.push(Edit {
+                offset: nero.body,
+                len: nero.len,
+                data: body,
+            });
+        }
+    }
+
+    for sample in &layout.samples {
+        let Some(fixed) = replace(&decode(&sample.text)) else {
+            continue;
+        };
+        let text = match sample.text.get(..2) {
+            Some([0xFE, 0xFF]) => utf16(&fixed, u16::to_be_bytes, [0xFE, 0xFF]),
+            Some([0xFF, 0xFE]) => utf16(&fixed, u16::to_le_bytes, [0xFF, 0xFE]),
+            _ => fixed.into_bytes(),
+        };
+        let Ok(len) = u16::try_from(text.len()) else {
+            continue;
+        };
+        let mut data = len.to_be_bytes().to_vec();
+        data.extend(text);
+        data.extend(&sample.rest);
+        match sample.size_entry {
+            Some(entry) => patches.push((entry, data.len() as u64, 4)),
+            None if data.len() as u64 != sample.size => continue,
+            None => {}
+        }
+        edits.push(Edit {
+            offset: sample.offset,
+            len: sample.size,
+            data,
+        });
+    }
+    if edits.is_empty() {
+        return Ok(None);
+    }
+
+    let delta = |edit: &Edit| edit.data.len() as i64 - edit.len as i64;
+    let mut atoms = Vec::new();
+    for atom in &layout.atoms {
+        atom.walk(&mut atoms);
+    }
+    for atom in &atoms {
+        let inside: i64 = edits
+            .iter()
+            .filter(|edit| edit.offset >= atom.body() && edit.offset < atom.end())
+            .map(delta)
+            .sum();
+        if inside == 0 || atom.to_end {
+            continue;
+        }
+        let size = atom.size.checked_add_signed(inside).ok_or_else(overflow)?;
+        if atom.header == 16 {
+            patches.push((atom.offset + 8, size, 8));
+        } else {
+            patches.push((
+                atom.offset,
+                u32::try_from(size).map_err(|_| overflow())?.into(),
+                4,
+            ));
+        }
+    }
+    // Блоки после изменённых мест сдвигаются; блок, который начинается с изменённого
+    // сэмпла, остаётся на месте
+    let shift = |offset: u64| -> i64 {
+        edits
+            .iter()
+            .filter(|edit| edit.offset < offset)
+            .map(delta)
+            .sum()
+    };
+    for atom in atoms
+        .iter()
+        .filter(|atom| matches!(&atom.kind, b"stco" | b"co64"))
+    {
+        let width = if &atom.kind == b"stco" { 4 } else { 8 };
+        let table = table(file, atom, width, 0)?;
+        for (i, &offset) in table.iter().enumerate() {
+            let shifted = offset
+                .checked_add_signed(shift(offset))
+                .ok_or_else(overflow)?;
+            if width == 4 && shifted > u64::from(u32::MAX) {
+                return Err(overflow());
+            }
+            if shifted != offset {
+                patches.push((atom.body() + 8 + (i * width) as u64, shifted, width));
+            }
+        }
+    }
+
+    // Поля размеров и смещений лежат вне изменённых названий, и их замена длину не меняет
+    edits.extend(patches.into_iter().map(|(offset, value, width)| Edit {
+        offset,
+        len: width as u64,
+        data: value.to_be_bytes()[8 - width..].to_vec(),
+    }));
+    edits.sort_by_key(|edit| edit.offset);
+    Ok(Some(Rewrite { edits }))
+}
+
+impl Rewrite {
+    /// Записать в `out` файл `file` с изменениями; байты между ними копируются потоком
+    pub fn write<R: Read + Seek, W: Write>(&self, file: &mut R, out: &mut W) -> io::Result<()> {
+        let mut pos = 0;
+        for edit in &self.edits {
+            file.seek(SeekFrom::Start(pos))?;
+            let len = edit.offset - pos;
+            if io::copy(&mut file.by_ref().take(len), out)? != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            out.write_all(&edit.data)?;
+            pos = edit.offset + edit.len;
+        }
+        file.seek(SeekFrom::Start(pos))?;
+        io::copy(file, out)?;
+        Ok(())
+    }
+}
+
+impl Layout {
+    fn read<R: Read + Seek>(file: &mut R) -> ParseResult<Self> {
+        let len = file.seek(SeekFrom::End(0)).map_err(|_| truncated())?;
+        let atoms = read_atoms(file, 0, len)?;
+        if atoms.iter().any(|atom| &atom.kind == b"moof") {
+            return Err(tr(
+                "фрагментированный MP4 не поддерживается",
+                "fragmented MP4 is not supported",
+            ));
+        }
+        let Some(moov) = atoms.iter().find(|atom| &atom.kind == b"moov") else {
+            return Err(tr("нет атома moov", "no moov atom"));
+        };
+
+        let nero = match moov.path(&[b"udta", b"chpl"]) {
+            Some(chpl) => parse_nero(chpl, &read_body(file, chpl)?),
+            None => None,
+        };
+
+        let tracks: Vec<&Atom> = moov
+            .children
+            .iter()
+            .filter(|atom| &atom.kind == b"trak")
+            .collect();
+        let mut chapter_ids = Vec::new();
+        for trak in &tracks {
+            if let Some(chap) = trak.path(&[b"tref", b"chap"]) {
+                let ids = read_body(file, chap)?;
+                chapter_ids.extend(
+                    ids.chunks_exact(4)
+                        .map(|id| u32::from_be_bytes(id.try_into().unwrap())),
+                );
+            }
+        }
+        let mut samples = Vec::new();
+        for trak in tracks {
+            let Some(tkhd) = trak.path(&[b"tkhd"]) else {
+                continue;
+            };
+            let tkhd = read_body(file, tkhd)?;
+            let id_at = if tkhd.first() == Some(&1) { 20 } else { 12 };
+            let id = tkhd
+                .get(id_at..id_at + 4)
+                .map(|id| u32::from_be_bytes(id.try_into().unwrap()));
+            if id.is_some_and(|id| chapter_ids.contains(&id))
+                && let Some(stbl) = trak.path(&[b"mdia", b"minf", b"stbl"])
+            {
+                samples.extend(read_samples(file, stbl)?);
+            }
+        }
+
+        Ok(Self {
+            atoms,
+            nero,
+            samples,
+        })
+    }
+}
+
+fn overflow() -> &'static str {
+    tr(
+        "размеры и смещения MP4 не помещаются в 32 бита",
+        "MP4 sizes and offsets do not fit in 32 bits",
+    )
+}
+
+fn truncated() -> &'static str {
+    tr("MP4 обрезан", "MP4 truncated")
+}
+
+fn read_atoms<R: Read + Seek>(file: &mut R, start: u64, end: u64) -> ParseResult<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    let mut offset = start;
+    while offset + 8 <= end {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(|_| truncated())?;
+        let kind: [u8; 4] = header[4..].try_into().unwrap();
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (end - offset, 8),
+            1 => {
+                let mut size = [0; 8];
+                file.read_exact(&mut size).map_err(|_| truncated())?;
+                (u64::from_be_bytes(size), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        if size < header_len || size > end - offset {
+            return Err(tr("неверный размер атома MP4", "invalid MP4 atom size"));
+        }
+        let children = if CONTAINERS.contains(&&kind) {
+            read_atoms(file, offset + header_len, offset + size)?
+        } else {
+            Vec::new()
+        };
+        atoms.push(Atom {
+            kind,
+            offset,
+            header: header_len,
+            size,
+            to_end: header[..4] == [0; 4],
+            children,
+        });
+        offset += size;
+    }
+    Ok(atoms)
+}
+
+fn read_body<R: Read + Seek>(file: &mut R, atom: &Atom) -> ParseResult<Vec<u8>> {
+    let mut body = vec![0; (atom.size - atom.header) as usize];
+    file.seek(SeekFrom::Start(atom.body()))
+        .and_then(|_| file.read_exact(&mut body))
+        .map_err(|_| truncated())?;
+    Ok(body)
+}
+
+/// Главы из содержимого `chpl`; `None`, если оно не читается
+fn parse_nero(chpl: &Atom, body: &[u8]) -> Option<Nero> {
+    let prefix_len = if *body.first()? == 1 { 8 } else { 4 };
+    let count = *body.get(prefix_len)?;
+    let mut pos = prefix_len + 1;
+    let mut chapters = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let start = u64::from_be_bytes(body.get(pos..pos + 8)?.try_into().unwrap());
+        let len = *body.get(pos + 8)? as usize;
+        let title = body.get(pos + 9..pos + 9 + len)?.to_vec();
+        pos += 9 + len;
+        chapters.push(NeroChapter { start, title });
+    }
+    Some(Nero {
+        body: chpl.body(),
+        len: chpl.size - chpl.header,
+        prefix: body[..prefix_len].to_vec(),
+        chapters,
+    })
+}
+
+/// Значения таблицы `stco`, `co64` или `stsz` (после `skip` байтов полей перед числом записей)
+fn table<R: Read + Seek>(
+    file: &mut R,
+    atom: &Atom,
+    width: usize,
+    skip: u64,
+) -> ParseResult<Vec<u64>> {
+    let start = atom.body() + 4 + skip;
+    let mut count = [0; 4];
+    file.seek(SeekFrom::Start(start))
+        .and_then(|_| file.read_exact(&mut count))
+        .map_err(|_| truncated())?;
+    let count = u32::from_be_bytes(count) as u64;
+    if count * width as u64 > atom.end().saturating_sub(start + 4) {
+        return Err(truncated());
+    }
+    let mut values = vec![0; count as usize * width];
+    file.read_exact(&mut values).map_err(|_| truncated())?;
+    Ok(values
+        .chunks_exact(width)
+        .map(|bytes| {
+            let mut value = [0; 8];
+            value[8 - width..].copy_from_slice(bytes);
+            u64::from_be_bytes(value)
+        })
+        .collect())
+}
+
+/// Сэмплы дорожки с главами по таблицам `stsz`, `stsc` и `stco`/`co64`
+fn read_samples<R: Read + Seek>(file: &mut R, stbl: &Atom) -> ParseResult<Vec<Sample>> {
+    let child = |kind: &[u8; 4]| stbl.children.iter().find(|atom| &atom.kind == kind);
+    let (Some(stsz), Some(stsc)) = (child(b"stsz"), child(b"stsc")) else {
+        return Ok(Vec::new());
+    };
+    let (chunks, width) = match (child(b"stco"), child(b"co64")) {
+        (Some(stco), _) => (stco, 4),
+        (None, Some(co64)) => (co64, 8),
+        (None, None) => return Ok(Vec::new()),
+    };
+    let stsz_body = read_body(file, stsz)?;
+    let fixed_size = stsz_body
+        .get(4..8)
+        .map(|size| u32::from_be_bytes(size.try_into().unwrap()))
+        .ok_or_else(truncated)?;
+    let sizes = if fixed_size == 0 {
+        table(file, stsz, 4, 4)?
+    } else {
+        let count = stsz_body.get(8..12).ok_or_else(truncated)?;
+        vec![u64::from(fixed_size); u32::from_be_bytes(count.try_into().unwrap()) as usize]
+    };
+    let runs = stsc_runs(&read_body(file, stsc)?)?;
+    let chunk_offsets = table(file, chunks, width, 0)?;
+
+    let mut samples = Vec::new();
+    let mut index = 0;
+    for (i, &chunk) in chunk_offsets.iter().enumerate() {
+        let chunk_number = i as u32 + 1;
+        let per_chunk = runs
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= chunk_number)
+            .map_or(0, |&(_, count)| count);
+        let mut offset = chunk;
+        for _ in 0..per_chunk {
+            let Some(&size) = sizes.get(index) else {
+                break;
+            };
+            let size_entry = (fixed_size == 0).then(|| stsz.body() + 12 + index as u64 * 4);
+            index += 1;
+            if (2..=MAX_SAMPLE_LEN).contains(&size) {
+                let mut data = vec![0; size as usize];
+                file.seek(SeekFrom::Start(offset))
+                    .and_then(|_| file.read_exact(&mut data))
+                    .map_err(|_| truncated())?;
+                let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+                if let Some(text) = data.get(2..2 + len) {
+                    samples.push(Sample {
+                        offset,
+                        size,
+                        size_entry,
+                        text: text.to_vec(),
+                        rest: data[2 + len..].to_vec(),
+                    });
+                }
+            }
+            offset += size;
+        }
+    }
+    Ok(samples)
+}
+
+/// Записи `stsc`: (первый блок, сэмплов в блоке)
+fn stsc_runs(body: &[u8]) -> ParseResult<Vec<(u32, u32)>> {
+    let count = body.get(4..8).ok_or_else(truncated)?;
+    let count = u32::from_be_bytes(count.try_into().unwrap()) as usize;
+    let entries = body.get(8..8 + count * 12).ok_or_else(truncated)?;
+    Ok(entries
+        .chunks_exact(12)
+        .map(|entry| {
+            (
+                u32::from_be_bytes(entry[..4].try_into().unwrap()),
+                u32::from_be_bytes(entry[4..8].try_into().unwrap()),
+            )
+        })
+        .collect())
+}
+
+/// Текст названия: UTF-16 с BOM, UTF-8, а если это не UTF-8 — Latin-1
+fn decode(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| from([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => bytes.iter().map(|&byte| char::from(byte)).collect(),
+        },
+    }
+}
+
+fn utf16(text: &str, to: fn(u16) -> [u8; 2], bom: [u8; 2]) -> Vec<u8> {
+    let mut bytes = bom.to_vec();
+    bytes.extend(text.encode_utf16().flat_map(to));
+    bytes
+}
+
+/// Не больше `len` байтов `text`, по границе символа
+fn truncate(text: &str, len: usize) -> &str {
+    let mut end = len.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const AUDIO: &[u8] = b"AUDIO-DATA";
+    /// Атом `encd` после текста второй главы
+    const ENCD: &[u8] = b"\0\0\0\x0cencd\0\0\x01\0";
+
+    fn atom(kind: &[u8; 4], parts: &[&[u8]]) -> Vec<u8> {
+        let body = parts.concat();
+        let mut out = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+        out.extend(kind);
+        out.extend(body);
+        out
+    }
+
+    fn be32(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    fn trak(id: u32, extra: &[u8], stsz: &[u8], offsets: &[u32]) -> Vec<u8> {
+        let tkhd = atom(b"tkhd", &[&be32(&[0, 0, 0, id, 0])]);
+        let chunks = offsets.len() as u32;
+        let stsc = atom(b"stsc", &[&be32(&[0, 1, 1, 1, 1])]);
+        let stco = atom(b"stco", &[&be32(&[0, chunks]), &be32(offsets)]);
+        let stbl = atom(b"stbl", &[&atom(b"stsz", &[stsz]), &stsc, &stco]);
+        let mdia = atom(b"mdia", &[&atom(b"minf", &[&stbl])]);
+        atom(b"trak", &[&tkhd, extra, &mdia])
+    }
+
+    fn sample(title: &[u8], rest: &[u8]) -> Vec<u8> {
+        let mut out = (title.len() as u16).to_be_bytes().to_vec();
+        out.extend(title);
+        out.extend(rest);
+        out
+    }
+
+    /// `moov` перед `mdat`: главы Nero и текстовая дорожка с двумя главами в отдельных
+    /// блоках, за ними блок звуковой дорожки
+    fn sample_file() -> Vec<u8> {
+        let samples = [sample(b"Intro", &[]), sample(b"Outro", ENCD)];
+        let mut chpl = be32(&[0x0100_0000, 0]);
+        chpl.push(2);
+        for (start, title) in [(0u64, &b"Intro"[..]), (600_000_000, b"Outro")] {
+            chpl.extend(start.to_be_bytes());
+            chpl.push(title.len() as u8);
+            chpl.extend(title);
+        }
+        let sizes = be32(&[0, 0, 2, samples[0].len() as u32, samples[1].len() as u32]);
+        let moov = |offsets: [u32; 3]| {
+            let tref = atom(b"tref", &[&atom(b"chap", &[&be32(&[2])])]);
+            let audio = trak(1, &tref, &be32(&[0, AUDIO.len() as u32, 1]), &offsets[2..]);
+            let text = trak(2, &[], &sizes, &offsets[..2]);
+            let udta = atom(b"udta", &[&atom(b"chpl", &[&chpl])]);
+            atom(b"moov", &[&audio, &text, &udta])
+        };
+        let ftyp = atom(b"ftyp", &[b"M4B \0\0\0\0"]);
+        let mdat = ftyp.len() + moov([0; 3]).len() + 8;
+        let offsets = [
+            mdat,
+            mdat + samples[0].len(),
+            mdat + samples[0].len() + samples[1].len(),
+        ];
+        let moov = moov(offsets.map(|offset| offset as u32));
+        let mdat = atom(b"mdat", &[&samples[0], &samples[1], AUDIO]);
+        [ftyp, moov, mdat].concat()
+    }
+
+    /// Размер каждого контейнера равен заголовку и вложенным атомам, вместе — длине файла
+    fn assert_sizes(atoms: &[Atom], start: u64, end: u64) {
+        assert_eq!(atoms.first().map_or(end, |atom| atom.offset), start);
+        assert_eq!(atoms.last().map_or(start, Atom::end), end);
+        for atom in atoms {
+            if CONTAINERS.contains(&&atom.kind) {
+                assert_sizes(&atom.children, atom.body(), atom.end());
+            }
+        }
+    }
+
+    #[test]
+    fn rewrite_updates_sizes_and_offsets() {
+        let mut file = Cursor::new(sample_file());
+        assert_eq!(read_titles(&mut file), ["Intro", "Outro"]);
+        let rewrite = replace_titles(&mut file, |title| {
+            (title == "Intro").then(|| "Вступление".to_string())
+        })
+        .unwrap()
+        .unwrap();
+        let mut out = Vec::new();
+        rewrite.write(&mut file, &mut out).unwrap();
+
+        let mut file = Cursor::new(out);
+        assert_eq!(read_titles(&mut file), ["Вступление", "Outro"]);
+        let layout = Layout::read(&mut file).unwrap();
+        assert_sizes(&layout.atoms, 0, file.get_ref().len() as u64);
+
+        let nero = layout.nero.as_ref().unwrap();
+        assert_eq!(nero.chapters[0].title, "Вступление".as_bytes());
+        assert_eq!(nero.chapters[1].start, 600_000_000);
+
+        let mdat = layout.atoms[2].body();
+        let [first, second] = &layout.samples[..] else {
+            panic!("две главы QuickTime");
+        };
+        assert_eq!(first.text, "Вступление".as_bytes());
+        assert_eq!(
+            (first.offset, first.size),
+            (mdat, 2 + first.text.len() as u64)
+        );
+        assert_eq!((&second.text[..], &second.rest[..]), (&b"Outro"[..], ENCD));
+        assert_eq!(second.offset, first.offset + first.size);
+
+        let stco = layout.atoms[1]
+            .path(&[b"trak", b"mdia", b"minf", b"stbl", b"stco"])
+            .unwrap();
+        let audio = table(&mut file, stco, 4, 0).unwrap()[0] as usize;
+        assert_eq!(audio as u64, second.offset + second.size);
+        assert_eq!(&file.get_ref()[audio..audio + AUDIO.len()], AUDIO);
+    }
+}
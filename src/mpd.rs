@@ -0,0 +1,273 @@
+//! Обновление базы MPD (--mpd): после исправления MPD получает команду `update` для каждого
+//! каталога с исправленными файлами (путь — относительно его music_directory) и сразу
+//! подхватывает новые теги.
+//!
+//! Адрес — `host[:port]` или путь к unix-сокету, по умолчанию из MPD_HOST и MPD_PORT, как у
+//! mpc; пароль — `пароль@адрес`
+
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::tr_format;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_HOST: &str = "localhost";
+const DEFAULT_PORT: u16 = 6600;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Куда подключаться
+#[derive(Debug, Clone)]
+enum Address {
+    Tcp(String, u16),
+    #[cfg(unix)]
+    Socket(PathBuf),
+}
+
+/// Настроенное подключение к MPD
+#[derive(Debug, Clone)]
+pub struct Mpd {
+    address: Address,
+    password: Option<String>,
+    /// music_directory MPD на этой машине; `None` — спросить у MPD
+    music_dir: Option<PathBuf>,
+}
+
+/// Соединение с MPD по TCP или unix-сокету
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+impl Mpd {
+    /// `address` — из --mpd; пустой или `None` — из MPD_HOST и MPD_PORT
+    pub fn new(address: Option<&str>, music_dir: Option<&Path>) -> Result<Self, String> {
+        let host = address
+            .filter(|a| !a.is_empty())
+            .map(str::to_string)
+            .or_else(|| std::env::var("MPD_HOST").ok().filter(|h| !h.is_empty()))
+            .unwrap_or_else(|| DEFAULT_HOST.to_string());
+        let default_port = match std::env::var("MPD_PORT") {
+            Ok(port) => port
+                .parse()
+                .map_err(|_| tr_format!("неверный MPD_PORT: {port}", "invalid MPD_PORT: {port}"))?,
+            Err(_) => DEFAULT_PORT,
+        };
+        // пароль@адрес: пароль — до последней @
+        let (password, host) = match host.rsplit_once('@') {
+            Some((password, host)) if !password.is_empty() => {
+                (Some(password.to_string()), host.to_string())
+            }
+            _ => (None, host),
+        };
+        let music_dir = music_dir
+            .map(|dir| std::path::absolute(dir).map_err(|e| e.to_string()))
+            .transpose()?;
+        Ok(Self {
+            address: parse_address(&host, default_port)?,
+            password,
+            music_dir,
+        })
+    }
+
+    /// Обновить в базе MPD каталоги `dirs` (абсолютные пути); вложенные каталоги уже
+    /// обновлённого не повторяются. Возвращает, сколько команд update отправлено
+    pub fn update(&self, dirs: &[PathBuf]) -> Result<usize, String> {
+        let mut connection = self.connect()?;
+        if let Some(password) = &self.password {
+            connection.command(&format!("password {}", quote(password)))?;
+        }
+        let music_dir = match &self.music_dir {
+            Some(dir) => dir.clone(),
+            None => connection.music_directory()?,
+        };
+
+        let mut updated: Vec<&Path> = Vec::new();
+        let mut outside = Vec::new();
+        let mut sorted: Vec<&PathBuf> = dirs.iter().collect();
+        sorted.sort();
+        for dir in sorted {
+            let Ok(relative) = dir.strip_prefix(&music_dir) else {
+                outside.push(dir.display().to_string());
+                continue;
+            };
+            if updated.iter().any(|done| relative.starts_with(done)) {
+                continue;
+            }
+            // Пути в базе MPD всегда через /
+            let uri: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            connection.command(&format!("update {}", quote(&uri.join("/"))))?;
+            updated.push(relative);
+        }
+        if !outside.is_empty() {
+            return Err(tr_format!(
+                "каталоги вне music_directory {} (нужен --mpd-music-dir?): {}",
+                "directories outside music_directory {} (missing --mpd-music-dir?): {}",
+                music_dir.display(),
+                outside.join(", ")
+            ));
+        }
+        Ok(updated.len())
+    }
+
+    fn connect(&self) -> Result<Connection, String> {
+        let stream: Box<dyn Stream> = match &self.address {
+            Address::Tcp(host, port) => {
+                let addrs = (host.as_str(), *port)
+                    .to_socket_addrs()
+                    .map_err(|e| format!("{host}: {e}"))?;
+                let mut last_error = None;
+                let mut connected = None;
+                for addr in addrs {
+                    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                        Ok(stream) => {
+                            connected = Some(stream);
+                            break;
+                        }
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+                let stream = connected.ok_or_else(|| {
+                    let reason = last_error.map_or_else(String::new, |e| format!(": {e}"));
+                    format!("{host}:{port}{reason}")
+                })?;
+                stream
+                    .set_read_timeout(Some(READ_TIMEOUT))
+                    .map_err(|e| e.to_string())?;
+                Box::new(stream)
+            }
+            #[cfg(unix)]
+            Address::Socket(path) => {
+                let stream = std::os::unix::net::UnixStream::connect(path)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                stream
+                    .set_read_timeout(Some(READ_TIMEOUT))
+                    .map_err(|e| e.to_string())?;
+                Box::new(stream)
+            }
+        };
+        let mut connection = Connection {
+            reader: BufReader::new(stream),
+        };
+        let greeting = connection.line()?;
+        if !greeting.starts_with("OK MPD ") {
+            return Err(tr_format!(
+                "это не MPD: {greeting}",
+                "not an MPD server: {greeting}"
+            ));
+        }
+        Ok(connection)
+    }
+}
+
+/// Протокол MPD: команда — строка, ответ — строки `ключ: значение` и `OK` или `ACK …`
+struct Connection {
+    reader: BufReader<Box<dyn Stream>>,
+}
+
+impl Connection {
+    fn line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(tr("MPD закрыл соединение", "MPD closed the connection").to_string()),
+            Ok(_) => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Отправить команду и вернуть строки ответа до `OK`
+    fn command(&mut self, command: &str) -> Result<Vec<String>, String> {
+        let stream = self.reader.get_mut();
+        stream
+            .write_all(format!("{command}\n").as_bytes())
+            .and_then(|()| stream.flush())
+            .map_err(|e| e.to_string())?;
+        let mut lines = Vec::new();
+        loop {
+            let line = self.line()?;
+            if line == "OK" {
+                return Ok(lines);
+            }
+            if let Some(error) = line.strip_prefix("ACK ") {
+                // ACK [код@номер] {команда} сообщение
+                let message = error.rsplit_once("} ").map_or(error, |(_, m)| m);
+                let name = command.split(' ').next().unwrap_or(command);
+                return Err(format!("{name}: {message}"));
+            }
+            lines.push(line);
+        }
+    }
+
+    /// music_directory из настроек MPD; команда config доступна только через unix-сокет
+    fn music_directory(&mut self) -> Result<PathBuf, String> {
+        let lines = self.command("config").map_err(|e| {
+            tr_format!(
+                "{e}; укажите каталог музыки MPD через --mpd-music-dir",
+                "{e}; set the MPD music directory with --mpd-music-dir"
+            )
+        })?;
+        lines
+            .iter()
+            .find_map(|line| line.strip_prefix("music_directory: "))
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                tr(
+                    "MPD не сообщил music_directory; укажите его через --mpd-music-dir",
+                    "MPD did not report music_directory; set it with --mpd-music-dir",
+                )
+                .to_string()
+            })
+    }
+}
+
+/// `host`, `host:port`, `[ipv6]:port` или путь к unix-сокету
+fn parse_address(host: &str, default_port: u16) -> Result<Address, String> {
+    if host.starts_with('/') || host.starts_with('~') {
+        #[cfg(unix)]
+        {
+            let path = match host.strip_prefix("~/") {
+                Some(rest) => std::env::var_os("HOME")
+                    .map_or_else(|| PathBuf::from(host), |home| Path::new(&home).join(rest)),
+                None => PathBuf::from(host),
+            };
+            return Ok(Address::Socket(path));
+        }
+        #[cfg(not(unix))]
+        return Err(tr(
+            "unix-сокеты доступны только в Unix, укажите host:port",
+            "unix sockets are only available on Unix, use host:port",
+        )
+        .to_string());
+    }
+    let (host, port) = match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.ends_with(']') && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            let port = port
+                .parse()
+                .map_err(|_| tr_format!("неверный порт: {port}", "invalid port: {port}"))?;
+            (name, port)
+        }
+        _ => (host, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(tr("пустой адрес MPD", "empty MPD address").to_string());
+    }
+    Ok(Address::Tcp(host.to_string(), port))
+}
+
+/// Аргумент команды MPD в кавычках
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
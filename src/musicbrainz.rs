@@ -0,0 +1,237 @@
+//! Онлайн-проверка исправлений по MusicBrainz (--online-validate): для исправлений с низкой
+//! уверенностью ищется релиз, запись или исполнитель с восстановленным названием, и если
+//! он нашёлся, исправлению можно доверять.
+//!
+//! Запросы идут не чаще раза в секунду, как требуют правила MusicBrainz, а ответы
+//! сохраняются в `.cyrtag-fix/musicbrainz.tsv` в корне обработки: повторный запуск по той же
+//! библиотеке в сеть почти не ходит. Как и оповещение медиасервера, запросы идут по HTTP без
+//! сторонних библиотек, а musicbrainz.org перенаправляет http на https, поэтому адрес по
+//! умолчанию не задан: нужно http-зеркало или локальный прокси (--musicbrainz-url)
+
+use crate::server::{Url, encode, http_request};
+use cyrtag_fix::album::fold;
+use cyrtag_fix::json::Json;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Сохранённые ответы относительно корня обработки
+pub const CACHE_FILE: &str = ".cyrtag-fix/musicbrainz.tsv";
+/// MusicBrainz разрешает не больше одного запроса в секунду
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Наименьшая оценка совпадения в ответе поиска (0–100)
+const MIN_SCORE: f64 = 90.0;
+
+/// Что искать
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Release,
+    Recording,
+    Artist,
+}
+
+impl Entity {
+    fn name(self) -> &'static str {
+        match self {
+            Entity::Release => "release",
+            Entity::Recording => "recording",
+            Entity::Artist => "artist",
+        }
+    }
+
+    /// Поле ответа со списком найденного
+    fn results(self) -> &'static str {
+        match self {
+            Entity::Release => "releases",
+            Entity::Recording => "recordings",
+            Entity::Artist => "artists",
+        }
+    }
+
+    /// Поле с названием у найденного
+    fn title(self) -> &'static str {
+        match self {
+            Entity::Artist => "name",
+            _ => "title",
+        }
+    }
+}
+
+/// Подключение к MusicBrainz с ограничением частоты запросов и кэшем ответов
+pub struct MusicBrainz {
+    url: Url,
+    /// Ответы по запросам: найдено или нет
+    cache: Mutex<HashMap<String, bool>>,
+    /// Файл, куда дописываются новые ответы
+    cache_file: Option<Mutex<File>>,
+    /// Когда можно отправить следующий запрос
+    next_request: Mutex<Instant>,
+    /// После ошибки сети проверка отключается до конца запуска
+    disabled: AtomicBool,
+}
+
+impl MusicBrainz {
+    /// `url` — из --musicbrainz-url
+    pub fn new(url: &str) -> Result<Self, String> {
+        Ok(Self {
+            url: url.parse()?,
+            cache: Mutex::default(),
+            cache_file: None,
+            next_request: Mutex::new(Instant::now()),
+            disabled: AtomicBool::new(false),
+        })
+    }
+
+    /// Загрузить сохранённые ответы из `path` и дописывать туда новые
+    pub fn open_cache(&mut self, path: &Path) -> io::Result<()> {
+        let cache = match fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .filter_map(|line| {
+                    let (found, query) = line.split_once('\t')?;
+                    Some((query.to_string(), found == "1"))
+                })
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.cache = Mutex::new(cache);
+        self.cache_file = Some(Mutex::new(file));
+        Ok(())
+    }
+
+    /// Есть ли в MusicBrainz `entity` с названием `title` (у исполнителя `artist`).
+    /// `Ok(None)` — проверка отключена после ошибки сети
+    pub fn exists(
+        &self,
+        entity: Entity,
+        title: &str,
+        artist: Option<&str>,
+    ) -> Result<Option<bool>, String> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let query = query(entity, title, artist);
+        if let Some(found) = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&query)
+        {
+            return Ok(Some(*found));
+        }
+
+        let found = match self.search(entity, &query, title, artist) {
+            Ok(found) => found,
+            Err(e) => {
+                self.disabled.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(query.clone(), found);
+        // Кэш только ускоряет повторные запуски: ошибка записи не мешает проверке
+        if let Some(file) = &self.cache_file {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = writeln!(file, "{}\t{query}", u8::from(found));
+        }
+        Ok(Some(found))
+    }
+
+    /// Поиск с соблюдением частоты запросов
+    fn search(
+        &self,
+        entity: Entity,
+        query: &str,
+        title: &str,
+        artist: Option<&str>,
+    ) -> Result<bool, String> {
+        let path = format!(
+            "/{}/?query={}&fmt=json&limit=10",
+            entity.name(),
+            encode(query)
+        );
+        let body = {
+            let mut next = self.next_request.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            if *next > now {
+                thread::sleep(*next - now);
+            }
+            let body = http_request(
+                &self.url,
+                "GET",
+                &path,
+                &[("Accept", "application/json")],
+                None,
+            );
+            *next = Instant::now() + REQUEST_INTERVAL;
+            body?
+        };
+        let json = Json::parse(&body).map_err(|e| format!("MusicBrainz: {e}"))?;
+        Ok(json
+            .get(entity.results())
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .any(|item| matches(entity, item, title, artist)))
+    }
+}
+
+/// Запрос поиска Lucene: название и, если известен, исполнитель
+fn query(entity: Entity, title: &str, artist: Option<&str>) -> String {
+    let quoted = |value: &str| {
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+    let mut query = format!("{}:{}", entity.name(), quoted(title));
+    if let Some(artist) = artist.filter(|_| entity != Entity::Artist) {
+        query.push_str(&format!(" AND artist:{}", quoted(artist)));
+    }
+    query
+}
+
+/// Совпадает ли найденное с искомым: высокая оценка поиска, то же название и тот же
+/// исполнитель среди указанных у релиза или записи
+fn matches(entity: Entity, item: &Json, title: &str, artist: Option<&str>) -> bool {
+    let same = |a: &str, b: &str| fold(a) == fold(b);
+    let score = item.get("score").and_then(|score| {
+        score
+            .as_f64()
+            .or_else(|| score.as_str().and_then(|s| s.parse().ok()))
+    });
+    if score.is_none_or(|score| score < MIN_SCORE)
+        || !item
+            .get(entity.title())
+            .and_then(Json::as_str)
+            .is_some_and(|found| same(found, title))
+    {
+        return false;
+    }
+    let Some(artist) = artist.filter(|_| entity != Entity::Artist) else {
+        return true;
+    };
+    item.get("artist-credit")
+        .and_then(Json::as_array)
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|credit| {
+            [
+                credit.get("name"),
+                credit.get("artist").and_then(|a| a.get("name")),
+            ]
+        })
+        .flatten()
+        .filter_map(Json::as_str)
+        .any(|name| same(name, artist))
+}
@@ -0,0 +1,328 @@
+//! Очистка исправленных значений: после перекодирования в тексте остаются разложенные
+//! буквы (`и` + U+0306 вместо `й`), BOM, неразрывные пробелы, управляющие символы
+//! и символы нулевой ширины, которые плееры показывают квадратиками или не показывают
+//! вовсе, а при поиске и сортировке они мешают.
+//!
+//! Буквы собираются в NFC только для кириллицы, греческого и латиницы Latin-1 и
+//! Latin Extended-A — полные таблицы Unicode программе не нужны.
+//!
+//! Там же — уборка пробелов в тегах (--trim-tags): старые программы для cp1251 дополняли
+//! значения пробелами и нулевыми байтами до длины поля
+
+use phf::{Map, phf_map};
+use std::borrow::Cow;
+
+/// Буква и следующий за ней комбинируемый знак → составная буква NFC
+static COMPOSE: Map<&'static str, char> = phf_map! {
+    "A\u{300}" => 'À',
+    "A\u{301}" => 'Á',
+    "A\u{302}" => 'Â',
+    "A\u{303}" => 'Ã',
+    "A\u{308}" => 'Ä',
+    "A\u{30a}" => 'Å',
+    "C\u{327}" => 'Ç',
+    "E\u{300}" => 'È',
+    "E\u{301}" => 'É',
+    "E\u{302}" => 'Ê',
+    "E\u{308}" => 'Ë',
+    "I\u{300}" => 'Ì',
+    "I\u{301}" => 'Í',
+    "I\u{302}" => 'Î',
+    "I\u{308}" => 'Ï',
+    "N\u{303}" => 'Ñ',
+    "O\u{300}" => 'Ò',
+    "O\u{301}" => 'Ó',
+    "O\u{302}" => 'Ô',
+    "O\u{303}" => 'Õ',
+    "O\u{308}" => 'Ö',
+    "U\u{300}" => 'Ù',
+    "U\u{301}" => 'Ú',
+    "U\u{302}" => 'Û',
+    "U\u{308}" => 'Ü',
+    "Y\u{301}" => 'Ý',
+    "a\u{300}" => 'à',
+    "a\u{301}" => 'á',
+    "a\u{302}" => 'â',
+    "a\u{303}" => 'ã',
+    "a\u{308}" => 'ä',
+    "a\u{30a}" => 'å',
+    "c\u{327}" => 'ç',
+    "e\u{300}" => 'è',
+    "e\u{301}" => 'é',
+    "e\u{302}" => 'ê',
+    "e\u{308}" => 'ë',
+    "i\u{300}" => 'ì',
+    "i\u{301}" => 'í',
+    "i\u{302}" => 'î',
+    "i\u{308}" => 'ï',
+    "n\u{303}" => 'ñ',
+    "o\u{300}" => 'ò',
+    "o\u{301}" => 'ó',
+    "o\u{302}" => 'ô',
+    "o\u{303}" => 'õ',
+    "o\u{308}" => 'ö',
+    "u\u{300}" => 'ù',
+    "u\u{301}" => 'ú',
+    "u\u{302}" => 'û',
+    "u\u{308}" => 'ü',
+    "y\u{301}" => 'ý',
+    "y\u{308}" => 'ÿ',
+    "A\u{304}" => 'Ā',
+    "a\u{304}" => 'ā',
+    "A\u{306}" => 'Ă',
+    "a\u{306}" => 'ă',
+    "A\u{328}" => 'Ą',
+    "a\u{328}" => 'ą',
+    "C\u{301}" => 'Ć',
+    "c\u{301}" => 'ć',
+    "C\u{302}" => 'Ĉ',
+    "c\u{302}" => 'ĉ',
+    "C\u{307}" => 'Ċ',
+    "c\u{307}" => 'ċ',
+    "C\u{30c}" => 'Č',
+    "c\u{30c}" => 'č',
+    "D\u{30c}" => 'Ď',
+    "d\u{30c}" => 'ď',
+    "E\u{304}" => 'Ē',
+    "e\u{304}" => 'ē',
+    "E\u{306}" => 'Ĕ',
+    "e\u{306}" => 'ĕ',
+    "E\u{307}" => 'Ė',
+    "e\u{307}" => 'ė',
+    "E\u{328}" => 'Ę',
+    "e\u{328}" => 'ę',
+    "E\u{30c}" => 'Ě',
+    "e\u{30c}" => 'ě',
+    "G\u{302}" => 'Ĝ',
+    "g\u{302}" => 'ĝ',
+    "G\u{306}" => 'Ğ',
+    "g\u{306}" => 'ğ',
+    "G\u{307}" => 'Ġ',
+    "g\u{307}" => 'ġ',
+    "G\u{327}" => 'Ģ',
+    "g\u{327}" => 'ģ',
+    "H\u{302}" => 'Ĥ',
+    "h\u{302}" => 'ĥ',
+    "I\u{303}" => 'Ĩ',
+    "i\u{303}" => 'ĩ',
+    "I\u{304}" => 'Ī',
+    "i\u{304}" => 'ī',
+    "I\u{306}" => 'Ĭ',
+    "i\u{306}" => 'ĭ',
+    "I\u{328}" => 'Į',
+    "i\u{328}" => 'į',
+    "I\u{307}" => 'İ',
+    "J\u{302}" => 'Ĵ',
+    "j\u{302}" => 'ĵ',
+    "K\u{327}" => 'Ķ',
+    "k\u{327}" => 'ķ',
+    "L\u{301}" => 'Ĺ',
+    "l\u{301}" => 'ĺ',
+    "L\u{327}" => 'Ļ',
+    "l\u{327}" => 'ļ',
+    "L\u{30c}" => 'Ľ',
+    "l\u{30c}" => 'ľ',
+    "N\u{301}" => 'Ń',
+    "n\u{301}" => 'ń',
+    "N\u{327}" => 'Ņ',
+    "n\u{327}" => 'ņ',
+    "N\u{30c}" => 'Ň',
+    "n\u{30c}" => 'ň',
+    "O\u{304}" => 'Ō',
+    "o\u{304}" => 'ō',
+    "O\u{306}" => 'Ŏ',
+    "o\u{306}" => 'ŏ',
+    "O\u{30b}" => 'Ő',
+    "o\u{30b}" => 'ő',
+    "R\u{301}" => 'Ŕ',
+    "r\u{301}" => 'ŕ',
+    "R\u{327}" => 'Ŗ',
+    "r\u{327}" => 'ŗ',
+    "R\u{30c}" => 'Ř',
+    "r\u{30c}" => 'ř',
+    "S\u{301}" => 'Ś',
+    "s\u{301}" => 'ś',
+    "S\u{302}" => 'Ŝ',
+    "s\u{302}" => 'ŝ',
+    "S\u{327}" => 'Ş',
+    "s\u{327}" => 'ş',
+    "S\u{30c}" => 'Š',
+    "s\u{30c}" => 'š',
+    "T\u{327}" => 'Ţ',
+    "t\u{327}" => 'ţ',
+    "T\u{30c}" => 'Ť',
+    "t\u{30c}" => 'ť',
+    "U\u{303}" => 'Ũ',
+    "u\u{303}" => 'ũ',
+    "U\u{304}" => 'Ū',
+    "u\u{304}" => 'ū',
+    "U\u{306}" => 'Ŭ',
+    "u\u{306}" => 'ŭ',
+    "U\u{30a}" => 'Ů',
+    "u\u{30a}" => 'ů',
+    "U\u{30b}" => 'Ű',
+    "u\u{30b}" => 'ű',
+    "U\u{328}" => 'Ų',
+    "u\u{328}" => 'ų',
+    "W\u{302}" => 'Ŵ',
+    "w\u{302}" => 'ŵ',
+    "Y\u{302}" => 'Ŷ',
+    "y\u{302}" => 'ŷ',
+    "Y\u{308}" => 'Ÿ',
+    "Z\u{301}" => 'Ź',
+    "z\u{301}" => 'ź',
+    "Z\u{307}" => 'Ż',
+    "z\u{307}" => 'ż',
+    "Z\u{30c}" => 'Ž',
+    "z\u{30c}" => 'ž',
+    "¨\u{301}" => '΅',
+    "Α\u{301}" => 'Ά',
+    "Ε\u{301}" => 'Έ',
+    "Η\u{301}" => 'Ή',
+    "Ι\u{301}" => 'Ί',
+    "Ο\u{301}" => 'Ό',
+    "Υ\u{301}" => 'Ύ',
+    "Ω\u{301}" => 'Ώ',
+    "ϊ\u{301}" => 'ΐ',
+    "Ι\u{308}" => 'Ϊ',
+    "Υ\u{308}" => 'Ϋ',
+    "α\u{301}" => 'ά',
+    "ε\u{301}" => 'έ',
+    "η\u{301}" => 'ή',
+    "ι\u{301}" => 'ί',
+    "ϋ\u{301}" => 'ΰ',
+    "ι\u{308}" => 'ϊ',
+    "υ\u{308}" => 'ϋ',
+    "ο\u{301}" => 'ό',
+    "υ\u{301}" => 'ύ',
+    "ω\u{301}" => 'ώ',
+    "ϒ\u{301}" => 'ϓ',
+    "ϒ\u{308}" => 'ϔ',
+    "Е\u{300}" => 'Ѐ',
+    "Е\u{308}" => 'Ё',
+    "Г\u{301}" => 'Ѓ',
+    "І\u{308}" => 'Ї',
+    "К\u{301}" => 'Ќ',
+    "И\u{300}" => 'Ѝ',
+    "У\u{306}" => 'Ў',
+    "И\u{306}" => 'Й',
+    "и\u{306}" => 'й',
+    "е\u{300}" => 'ѐ',
+    "е\u{308}" => 'ё',
+    "г\u{301}" => 'ѓ',
+    "і\u{308}" => 'ї',
+    "к\u{301}" => 'ќ',
+    "и\u{300}" => 'ѝ',
+    "у\u{306}" => 'ў',
+    "Ѵ\u{30f}" => 'Ѷ',
+    "ѵ\u{30f}" => 'ѷ',
+    "Ж\u{306}" => 'Ӂ',
+    "ж\u{306}" => 'ӂ',
+    "А\u{306}" => 'Ӑ',
+    "а\u{306}" => 'ӑ',
+    "А\u{308}" => 'Ӓ',
+    "а\u{308}" => 'ӓ',
+    "Е\u{306}" => 'Ӗ',
+    "е\u{306}" => 'ӗ',
+    "Ә\u{308}" => 'Ӛ',
+    "ә\u{308}" => 'ӛ',
+    "Ж\u{308}" => 'Ӝ',
+    "ж\u{308}" => 'ӝ',
+    "З\u{308}" => 'Ӟ',
+    "з\u{308}" => 'ӟ',
+    "И\u{304}" => 'Ӣ',
+    "и\u{304}" => 'ӣ',
+    "И\u{308}" => 'Ӥ',
+    "и\u{308}" => 'ӥ',
+    "О\u{308}" => 'Ӧ',
+    "о\u{308}" => 'ӧ',
+    "Ө\u{308}" => 'Ӫ',
+    "ө\u{308}" => 'ӫ',
+    "Э\u{308}" => 'Ӭ',
+    "э\u{308}" => 'ӭ',
+    "У\u{304}" => 'Ӯ',
+    "у\u{304}" => 'ӯ',
+    "У\u{308}" => 'Ӱ',
+    "у\u{308}" => 'ӱ',
+    "У\u{30b}" => 'Ӳ',
+    "у\u{30b}" => 'ӳ',
+    "Ч\u{308}" => 'Ӵ',
+    "ч\u{308}" => 'ӵ',
+    "Ы\u{308}" => 'Ӹ',
+    "ы\u{308}" => 'ӹ',
+};
+
+/// `text`, собранный в NFC, без управляющих символов (кроме табуляции и переводов строк),
+/// BOM и символов нулевой ширины; неразрывные пробелы заменяются обычными
+pub fn normalize(text: &str) -> Cow<'_, str> {
+    if !text
+        .chars()
+        .any(|c| is_junk(c) || is_space(c) || is_combining(c))
+    {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_junk(c) {
+            continue;
+        }
+        if is_space(c) {
+            out.push(' ');
+            continue;
+        }
+        if is_combining(c)
+            && let Some(base) = out.pop()
+        {
+            let pair: String = [base, c].into_iter().collect();
+            match COMPOSE.get(pair.as_str()) {
+                Some(&composed) => out.push(composed),
+                None => out.push_str(&pair),
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// Комбинируемые диакритические знаки (U+0300–U+036F)
+fn is_combining(c: char) -> bool {
+    ('\u{300}'..='\u{36f}').contains(&c)
+}
+
+/// Неразрывные пробелы
+fn is_space(c: char) -> bool {
+    matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// Управляющие символы C0 и C1, мягкий перенос, пробел нулевой ширины и BOM. Соединители
+/// нулевой ширины (U+200C, U+200D) остаются: из них собираются эмодзи
+fn is_junk(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+        || matches!(c, '\u{ad}' | '\u{200b}' | '\u{2060}' | '\u{feff}')
+}
+
+/// `text` без пробелов и нулевых байтов по краям и с одним пробелом вместо нескольких
+/// пробелов и табуляций подряд. В многострочном тексте так обрабатывается каждая строка,
+/// пустые строки и переводы строк сохраняются
+pub fn trim_whitespace(text: &str) -> Cow<'_, str> {
+    let trimmed = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    let mut out = String::with_capacity(trimmed.len());
+    for (i, line) in trimmed.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let (line, cr) = match line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (line, ""),
+        };
+        let words: Vec<&str> = line.split([' ', '\t']).filter(|w| !w.is_empty()).collect();
+        out.push_str(&words.join(" "));
+        out.push_str(cr);
+    }
+    if out == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(out)
+    }
+}
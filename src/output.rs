@@ -0,0 +1,290 @@
+//! Буферизованный вывод по файлам, чтобы строки параллельных потоков не перемешивались
+
+use crate::log::{self, LogLevel};
+use clap::ValueEnum;
+use cyrtag_fix::json::Json;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Формат вывода результатов
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Цветной текст для человека
+    #[default]
+    Human,
+    /// Один JSON-объект на обработанный файл (JSON Lines)
+    Json,
+}
+
+/// Подробность вывода
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Только исправленные файлы и итог (-q)
+    Quiet,
+    #[default]
+    Normal,
+    /// Также каждый проверенный файл (-v)
+    Verbose,
+    /// Также все решения детектора с оценками (-vv)
+    Debug,
+}
+
+/// Итог обработки файла
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Status {
+    /// Кракозябр не найдено
+    #[default]
+    Clean,
+    /// Файл исправлен
+    Fixed,
+    /// Файл был бы исправлен (пробный запуск, scan)
+    WouldFix,
+    /// Исправления найдены, но пользователь отказался или файл нельзя менять
+    /// (жёсткие ссылки, каталог раздачи)
+    Skipped,
+    /// Найдены только исправления с низкой уверенностью, они отложены для проверки
+    Review,
+    /// Ошибка чтения или записи
+    Error,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Clean => "clean",
+            Status::Fixed => "fixed",
+            Status::WouldFix => "would_fix",
+            Status::Skipped => "skipped",
+            Status::Review => "review",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// Одно исправление в отчёте: поле тега или строка текстового файла
+#[derive(Debug, Clone)]
+pub struct ReportFix {
+    pub field: String,
+    /// Ключ поля в «родном» для тега виде (`TIT2`, `TITLE`) или имя атрибута ASF
+    pub key: Option<String>,
+    /// Номер строки текстового файла, начиная с 1
+    pub line: Option<usize>,
+    pub before: String,
+    pub after: String,
+    /// Исходная кодировка текста
+    pub encoding: Option<String>,
+    /// Кодировка, в которой текст был ошибочно прочитан
+    pub misread_as: Option<String>,
+    /// Тип тега аудио-файла, в котором найдено поле
+    pub tag: Option<String>,
+    /// Уверенность в исправлении кракозябр, 0–100
+    pub confidence: Option<u8>,
+    /// Исправление отложено для проверки (уверенность ниже --min-confidence)
+    pub review: bool,
+    /// Файл внутри ZIP-архива, к которому относится исправление
+    pub member: Option<String>,
+}
+
+/// Машиночитаемый итог обработки одного файла
+#[derive(Debug, Clone, Default)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub format: String,
+    pub status: Status,
+    pub fixes: Vec<ReportFix>,
+    pub error: Option<String>,
+    /// Значения тегов, исходные байты которых уже потеряны (`????`, U+FFFD)
+    pub unrecoverable: Vec<String>,
+}
+
+impl FileReport {
+    pub fn new(path: &Path, format: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            format: format.to_string(),
+            status: Status::Clean,
+            fixes: Vec::new(),
+            error: None,
+            unrecoverable: Vec::new(),
+        }
+    }
+
+    /// Отметить ошибку обработки
+    pub fn fail(&mut self, error: impl ToString) {
+        self.error = Some(error.to_string());
+    }
+
+    pub fn to_json(&self) -> Json {
+        let fixes = self
+            .fixes
+            .iter()
+            .map(|fix| {
+                Json::object([
+                    ("field", fix.field.as_str().into()),
+                    ("key", fix.key.clone().into()),
+                    ("line", fix.line.into()),
+                    ("before", fix.before.as_str().into()),
+                    ("after", fix.after.as_str().into()),
+                    ("encoding", fix.encoding.clone().into()),
+                    ("misread_as", fix.misread_as.clone().into()),
+                    ("tag", fix.tag.clone().into()),
+                    ("confidence", fix.confidence.map(usize::from).into()),
+                    ("review", fix.review.into()),
+                    ("member", fix.member.clone().into()),
+                ])
+            })
+            .collect();
+
+        Json::object([
+            ("path", self.path.display().to_string().into()),
+            ("format", self.format.as_str().into()),
+            ("status", self.status.as_str().into()),
+            ("fixes", Json::Array(fixes)),
+            ("error", self.error.clone().into()),
+            (
+                "unrecoverable",
+                Json::Array(
+                    self.unrecoverable
+                        .iter()
+                        .map(|v| v.as_str().into())
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Out,
+    Err,
+}
+
+/// Вывод, относящийся к одному файлу; печатается целиком через [`FileLog::flush`]
+#[derive(Debug, Default)]
+pub struct FileLog {
+    format: OutputFormat,
+    verbosity: Verbosity,
+    lines: Vec<(Stream, String)>,
+    /// Строки для журнала --log-file (в любом формате вывода)
+    log: Vec<(LogLevel, String)>,
+    /// Исправления показываются таблицей альбома (--by-album), а не построчно
+    grouped: bool,
+    /// Отчёт для машиночитаемого вывода; заполняется для поддерживаемых файлов
+    pub report: Option<FileReport>,
+}
+
+impl FileLog {
+    pub fn new(format: OutputFormat, verbosity: Verbosity) -> Self {
+        Self {
+            format,
+            verbosity,
+            ..Self::default()
+        }
+    }
+
+    /// Исправления файла покажет таблица альбома: строки `FIX …` и строка об исправленном
+    /// файле пишутся только в журнал
+    pub fn grouped(mut self) -> Self {
+        self.grouped = true;
+        self
+    }
+
+    /// Строка для stdout (только в человекочитаемом режиме)
+    pub fn out(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        self.log.push((LogLevel::Info, line.clone()));
+        if self.format == OutputFormat::Human {
+            self.lines.push((Stream::Out, line));
+        }
+    }
+
+    /// Подробности исправления (`FIX …`, `→ …`): не показываются с -q,
+    /// но попадают в журнал
+    pub fn detail(&mut self, line: impl Into<String>) {
+        if self.verbosity == Verbosity::Quiet || self.grouped {
+            self.log.push((LogLevel::Info, line.into()));
+        } else {
+            self.out(line);
+        }
+    }
+
+    /// Строка об исправленном файле: показывается и с -q, а с --by-album её заменяет
+    /// таблица альбома
+    pub fn fixed_file(&mut self, line: impl Into<String>) {
+        if self.grouped {
+            self.log.push((LogLevel::Info, line.into()));
+        } else {
+            self.out(line);
+        }
+    }
+
+    /// Строка, которая показывается (и пишется в журнал) только с подробностью `verbosity`
+    pub fn verbose(&mut self, verbosity: Verbosity, line: impl Into<String>) {
+        if self.verbosity >= verbosity {
+            self.out(line);
+        }
+    }
+
+    /// Включена ли подробность `verbosity`
+    pub fn is_verbose(&self, verbosity: Verbosity) -> bool {
+        self.verbosity >= verbosity
+    }
+
+    /// Ошибка: в stderr
+    pub fn err(&mut self, line: impl Into<String>) {
+        self.push_err(LogLevel::Error, line.into());
+    }
+
+    /// Предупреждение: в stderr
+    pub fn warn(&mut self, line: impl Into<String>) {
+        self.push_err(LogLevel::Warn, line.into());
+    }
+
+    fn push_err(&mut self, level: LogLevel, line: String) {
+        self.log.push((level, line.clone()));
+        self.lines.push((Stream::Err, line));
+    }
+
+    /// Начать отчёт о поддерживаемом файле
+    pub fn start_report(&mut self, path: &Path, format: &str) {
+        self.report = Some(FileReport::new(path, format));
+    }
+
+    /// Текущий отчёт о файле
+    pub fn report(&mut self) -> &mut FileReport {
+        self.report.get_or_insert_with(FileReport::default)
+    }
+
+    /// Напечатать накопленные строки одним блоком
+    pub fn flush(&mut self) {
+        log::write_all(self.log.iter().map(|(level, line)| (*level, line.as_str())));
+        self.log.clear();
+        if self.lines.is_empty() {
+            return;
+        }
+
+        let mut stdout = io::stdout().lock();
+        let mut stderr = io::stderr().lock();
+        for (stream, line) in self.lines.drain(..) {
+            let _ = match stream {
+                Stream::Out => writeln!(stdout, "{line}"),
+                Stream::Err => {
+                    let _ = stdout.flush();
+                    writeln!(stderr, "{line}")
+                }
+            };
+        }
+        let _ = stdout.flush();
+    }
+
+    /// Завершить файл: напечатать накопленное и, в режиме JSON, отчёт
+    pub fn finish(&mut self) {
+        if self.format == OutputFormat::Json
+            && let Some(report) = self.report.take()
+        {
+            self.lines.push((Stream::Out, report.to_json().to_string()));
+        }
+        self.flush();
+    }
+}
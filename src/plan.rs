@@ -0,0 +1,195 @@
+//! План исправлений: `scan --plan` сохраняет найденные изменения в JSON, `apply` применяет
+//! ровно их — после просмотра, ручной правки или обработки другими программами.
+//!
+//! Перед записью каждое исходное значение сверяется с `before`: если файл изменился
+//! после составления плана, он не трогается.
+
+use crate::detect::Mojibake;
+use crate::error::{Error, Result};
+use crate::json::Json;
+use crate::time;
+use crate::tr_format;
+use encoding_rs::Encoding;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Версия формата плана
+pub const PLAN_VERSION: usize = 1;
+
+/// Одно изменение плана: поле тега или строка текстового файла
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanChange {
+    /// Поле в том виде, как в выводе: `TrackTitle`, `WM/AlbumTitle`, `line 3`
+    pub field: String,
+    /// Тип тега (`id3v2`, `vorbis`, `asf`); у текстовых файлов не задан
+    pub tag: Option<String>,
+    /// Ключ в «родном» для тега виде (`TIT2`, `TITLE`) или имя атрибута ASF
+    pub key: Option<String>,
+    /// Номер строки текстового файла, начиная с 1
+    pub line: Option<usize>,
+    pub before: String,
+    pub after: String,
+    /// Исходная кодировка текста
+    pub encoding: Option<String>,
+    /// Кодировка, в которой текст был ошибочно прочитан
+    pub misread_as: Option<String>,
+    /// Уверенность в исправлении кракозябр, 0–100
+    pub confidence: Option<u8>,
+}
+
+impl PlanChange {
+    /// Как появились кракозябры — по именам кодировок из плана
+    pub fn mojibake(&self) -> Option<Mojibake> {
+        let source = Encoding::for_label(self.encoding.as_deref()?.as_bytes())?;
+        let misread = Encoding::for_label(self.misread_as.as_deref()?.as_bytes())?;
+        Some(Mojibake::new(source, misread))
+    }
+
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("field", self.field.as_str().into()),
+            ("tag", self.tag.clone().into()),
+            ("key", self.key.clone().into()),
+            ("line", self.line.into()),
+            ("before", self.before.as_str().into()),
+            ("after", self.after.as_str().into()),
+            ("encoding", self.encoding.clone().into()),
+            ("misread_as", self.misread_as.clone().into()),
+            ("confidence", self.confidence.map(usize::from).into()),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Option<Self> {
+        let string = |key| json.get(key).and_then(Json::as_str).map(str::to_string);
+        let number = |key| json.get(key).and_then(Json::as_f64);
+        Some(Self {
+            field: string("field")?,
+            tag: string("tag"),
+            key: string("key"),
+            line: number("line").map(|n| n as usize),
+            before: string("before")?,
+            after: string("after")?,
+            encoding: string("encoding"),
+            misread_as: string("misread_as"),
+            confidence: number("confidence").map(|n| n.clamp(0.0, 100.0) as u8),
+        })
+    }
+}
+
+/// Изменения одного файла
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanFile {
+    /// Путь относительно корня плана
+    pub path: PathBuf,
+    /// Расширение файла в нижнем регистре
+    pub format: String,
+    pub changes: Vec<PlanChange>,
+}
+
+impl PlanFile {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("path", self.path.display().to_string().into()),
+            ("format", self.format.as_str().into()),
+            (
+                "changes",
+                Json::Array(self.changes.iter().map(PlanChange::to_json).collect()),
+            ),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Option<Self> {
+        Some(Self {
+            path: PathBuf::from(json.get("path")?.as_str()?),
+            format: json.get("format")?.as_str()?.to_string(),
+            changes: json
+                .get("changes")?
+                .as_array()?
+                .iter()
+                .map(PlanChange::from_json)
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// План исправлений каталога
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// Каталог, для которого составлен план
+    pub root: PathBuf,
+    pub files: Vec<PlanFile>,
+}
+
+impl Plan {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()),
+            files: Vec::new(),
+        }
+    }
+
+    /// Полный путь к файлу плана
+    pub fn file_path(&self, file: &PlanFile) -> PathBuf {
+        self.root.join(&file.path)
+    }
+
+    /// Число изменений во всех файлах
+    pub fn change_count(&self) -> usize {
+        self.files.iter().map(|f| f.changes.len()).sum()
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("version", PLAN_VERSION.into()),
+            ("root", self.root.display().to_string().into()),
+            ("created", time::format_iso8601(time::now_secs()).into()),
+            (
+                "files",
+                Json::Array(self.files.iter().map(PlanFile::to_json).collect()),
+            ),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<Self> {
+        if json.get("version")?.as_f64()? != PLAN_VERSION as f64 {
+            return None;
+        }
+        Some(Self {
+            root: PathBuf::from(json.get("root")?.as_str()?),
+            files: json
+                .get("files")?
+                .as_array()?
+                .iter()
+                .map(PlanFile::from_json)
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Сохранить план в читаемом виде
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, format!("{}\n", self.to_json().pretty())).map_err(|source| Error::Plan {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let error = |source| Error::Plan {
+            path: path.to_path_buf(),
+            source,
+        };
+        let content = fs::read_to_string(path).map_err(error)?;
+        let json = Json::parse(&content)
+            .map_err(|e| error(io::Error::new(io::ErrorKind::InvalidData, e.to_string())))?;
+        Self::from_json(&json).ok_or_else(|| {
+            error(io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr_format!(
+                    "неизвестный формат плана (ожидалась версия {PLAN_VERSION})",
+                    "unknown plan format (expected version {PLAN_VERSION})"
+                ),
+            ))
+        })
+    }
+}
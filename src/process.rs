@@ -0,0 +1,2188 @@
+//! Обработка дерева файлов: поиск исправлений, подтверждение и запись
+
+use crate::discogs::Discogs;
+use crate::hook::Hook;
+use crate::interrupt;
+use crate::log::{self, LogLevel};
+use crate::mpd::Mpd;
+use crate::musicbrainz::{Entity, MusicBrainz};
+use crate::output::{FileLog, FileReport, OutputFormat, ReportFix, Status, Verbosity};
+use crate::prompt::{Decision, Prompter};
+use crate::server::Notifier;
+use crate::{FilterArgs, HardlinkMode, extension, take_walk_errors, walk_files};
+use colored::*;
+use cyrtag_fix::album;
+use cyrtag_fix::asf::AsfFieldFix;
+use cyrtag_fix::atomic;
+use cyrtag_fix::backup::BackupDir;
+use cyrtag_fix::detect::{self, Mojibake, Trace, encoding_name};
+use cyrtag_fix::fields::field_name;
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::manifest::tag_type_name;
+use cyrtag_fix::plan::{PlanChange, PlanFile};
+use cyrtag_fix::progress::Progress;
+use cyrtag_fix::state::{Marker, State};
+use cyrtag_fix::tr_format;
+use cyrtag_fix::translit::Scheme;
+use cyrtag_fix::yo::Yo;
+use cyrtag_fix::zip::{self, Archive};
+use cyrtag_fix::{
+    ASF_EXTENSIONS, AsfFix, AsfFixer, AudioFix, AudioFixer, BackupManager, FieldFix, Id3v1Fix,
+    Id3v1Policy, LineChange, Plan, TEXT_EXTENSIONS, TextFix, TextFixer,
+};
+use encoding_rs::{Encoding, UTF_8};
+use lofty::prelude::ItemKey;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::DirEntry;
+
+/// Итоги обработки дерева
+#[derive(Debug, Default, Clone)]
+pub struct Summary {
+    /// Исправлено файлов (или было бы исправлено в пробном запуске)
+    pub fixed: usize,
+    /// Файлов с ошибками чтения или записи, включая ошибки обхода
+    pub errors: usize,
+    /// Файлов с исправлениями, которые пользователь отклонил или которые нельзя менять
+    pub skipped: usize,
+    /// Проверено поддерживаемых файлов по форматам (расширениям)
+    pub formats: BTreeMap<String, usize>,
+    /// Исправлений по полям тегов; строки текстовых файлов считаются вместе
+    pub fields: BTreeMap<String, usize>,
+    /// Исправлений по исходной кодировке текста
+    pub encodings: BTreeMap<String, usize>,
+    /// Исправлений с низкой уверенностью, отложенных для проверки
+    pub review: usize,
+    /// Файлов, пропущенных с --incremental: не изменились с прошлой проверки
+    pub unchanged: usize,
+    /// Файлов, пропущенных с --resume: уже обработаны в прерванном запуске
+    pub resumed: usize,
+    /// Время обработки
+    pub elapsed: Duration,
+    /// Обработка остановлена по Ctrl-C или SIGTERM
+    pub interrupted: bool,
+    /// Обработка остановлена на первой ошибке (--fail-fast)
+    pub failed_fast: bool,
+    /// Последний обработанный файл
+    pub last: Option<PathBuf>,
+    /// Файлов, у которых поля альбома приведены к значению большинства (--normalize-album)
+    pub normalized: usize,
+    /// Файлов, у которых поля альбома были бы приведены к большинству (пробный запуск)
+    pub would_normalize: usize,
+    /// Треков, исправленное название которых не нашлось в треклисте Discogs
+    pub unlisted: usize,
+    /// Значений тегов, которые уже не восстановить (`????`, U+FFFD)
+    pub unrecoverable: usize,
+}
+
+/// Аудио-файл каталога для проверки единообразия альбома
+#[derive(Debug)]
+pub struct AlbumTrack {
+    path: PathBuf,
+    status: Status,
+    /// Исправления файла без отложенных для проверки: было → стало
+    fixes: Vec<(String, String)>,
+}
+
+/// Самое длинное значение в таблице альбома (--by-album); длиннее — обрезается
+const ALBUM_CELL_WIDTH: usize = 40;
+/// С --online-validate по MusicBrainz проверяются исправления с уверенностью ниже этой
+/// (или ниже --min-confidence, если он больше)
+const ONLINE_VALIDATE_BELOW: u8 = 80;
+/// Уверенность исправления, подтверждённого MusicBrainz
+const ONLINE_VALIDATED: u8 = 95;
+
+/// Название строк текстовых файлов в статистике по полям
+fn text_lines_field() -> &'static str {
+    tr("строки текста", "text lines")
+}
+
+impl Summary {
+    /// Учесть итог обработки одного файла
+    fn add(&mut self, status: Status, report: Option<&FileReport>) {
+        match status {
+            Status::Fixed | Status::WouldFix => self.fixed += 1,
+            Status::Error => self.errors += 1,
+            Status::Skipped => self.skipped += 1,
+            Status::Clean | Status::Review => {}
+        }
+        let Some(report) = report else {
+            return;
+        };
+        *self.formats.entry(report.format.clone()).or_default() += 1;
+        self.unrecoverable += report.unrecoverable.len();
+        for fix in &report.fixes {
+            if fix.review {
+                self.review += 1;
+                continue;
+            }
+            if !matches!(status, Status::Fixed | Status::WouldFix) {
+                continue;
+            }
+            let field = match fix.line {
+                Some(_) => text_lines_field().to_string(),
+                None => fix.field.clone(),
+            };
+            *self.fields.entry(field).or_default() += 1;
+            if let Some(encoding) = &fix.encoding {
+                *self.encodings.entry(encoding.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    /// Добавить итоги ещё одного прохода (например, очередной проверки в --watch)
+    pub fn merge(&mut self, other: Summary) {
+        self.fixed += other.fixed;
+        self.errors += other.errors;
+        self.skipped += other.skipped;
+        self.review += other.review;
+        self.unchanged += other.unchanged;
+        self.resumed += other.resumed;
+        self.normalized += other.normalized;
+        self.would_normalize += other.would_normalize;
+        self.unlisted += other.unlisted;
+        self.unrecoverable += other.unrecoverable;
+        for (map, other) in [
+            (&mut self.formats, other.formats),
+            (&mut self.fields, other.fields),
+            (&mut self.encodings, other.encodings),
+        ] {
+            for (name, count) in other {
+                *map.entry(name).or_default() += count;
+            }
+        }
+        self.elapsed += other.elapsed;
+        self.interrupted |= other.interrupted;
+        self.failed_fast |= other.failed_fast;
+        self.last = other.last.or(self.last.take());
+    }
+}
+
+/// Всё, что нужно для обработки одного файла
+pub struct Processor {
+    pub text_fixer: TextFixer,
+    pub audio_fixer: AudioFixer,
+    pub asf_fixer: AsfFixer,
+    pub backup_manager: BackupManager,
+    pub prompter: Mutex<Prompter>,
+    pub dry_run: bool,
+    pub output: OutputFormat,
+    pub verbosity: Verbosity,
+    /// Шаблоны --exclude/--include для обхода
+    pub filter: FilterArgs,
+    /// Исправления с меньшей уверенностью (0–100) не применяются, а откладываются для проверки
+    pub min_confidence: u8,
+    /// Отложенные исправления для итогового списка
+    pub review: Mutex<Vec<String>>,
+    /// --show-candidates: показывать строки, оценка которых немного не дотянула до порога
+    pub show_candidates: bool,
+    /// Значения, которые уже не восстановить, для итогового списка
+    pub unrecoverable: Mutex<Vec<String>>,
+    /// Ошибки обработки файлов и обхода каталогов для итогового списка
+    pub failed: Mutex<Vec<String>>,
+    /// --fail-fast: после первой ошибки новые файлы не берутся
+    pub fail_fast: bool,
+    /// План исправлений для scan --plan: сюда собираются найденные изменения
+    pub plan: Option<Mutex<Plan>>,
+    /// Отчёты о файлах с исправлениями или ошибками для --report
+    pub reports: Option<Mutex<Vec<FileReport>>>,
+    /// Возвращать исправленным файлам прежнее время изменения
+    pub preserve_mtime: bool,
+    /// --output-dir: исправленные копии пишутся в зеркальное дерево, оригиналы не меняются
+    pub output_dir: Option<BackupDir>,
+    /// Что делать с файлами, у которых есть другие жёсткие ссылки
+    pub hardlinks: HardlinkMode,
+    /// Каталоги раздач (--seed-dir): файлы в них не меняются; пути канонические
+    pub seed_dirs: Vec<PathBuf>,
+    /// Состояние для --incremental: неизменившиеся файлы не проверяются повторно
+    pub state: Option<State>,
+    /// Отметки о проверке в атрибутах файлов для --mark
+    pub marker: Option<Marker>,
+    /// Журнал хода запуска для --resume
+    pub progress: Option<Progress>,
+    /// Команды --exec для каждого исправленного файла
+    pub hooks: Vec<Hook>,
+    /// Медиасервер, которому сообщить о каталогах с исправленными файлами
+    pub notifier: Option<Notifier>,
+    /// База MPD, которую обновить по каталогам с исправленными файлами
+    pub mpd: Option<Mpd>,
+    /// Каталоги с исправленными файлами, о которых ещё не сообщили медиасерверу и MPD
+    pub changed_dirs: Mutex<BTreeSet<PathBuf>>,
+    /// --by-album: файлы обрабатываются по каталогам, исправления показываются таблицей
+    /// альбома, а с --interactive подтверждаются сразу для всего альбома
+    pub by_album: bool,
+    /// --normalize-album: поля альбома, разошедшиеся после исправления, приводятся
+    /// к значению большинства треков каталога
+    pub normalize_album: bool,
+    /// Аудио-файлы по каталогам для проверки единообразия альбома после прохода
+    pub album_tracks: Mutex<BTreeMap<PathBuf, Vec<AlbumTrack>>>,
+    /// --online-validate: проверка исправлений с низкой уверенностью по MusicBrainz
+    pub musicbrainz: Option<MusicBrainz>,
+    /// --discogs-token: сверка названий треков с треклистом Discogs
+    pub discogs: Option<Discogs>,
+}
+
+impl Processor {
+    /// Обработка всех поддерживаемых файлов в путях `roots` (каталогах или отдельных файлах)
+    /// в `threads` потоков; пути в плане строятся от общего каталога `root`
+    pub fn process_tree(&self, roots: &[PathBuf], root: &Path, threads: usize) -> Summary {
+        let files = roots
+            .iter()
+            .flat_map(|path| walk_files(path, &self.filter))
+            .map(DirEntry::into_path);
+        let mut summary = self.process_paths(files, root, threads);
+        let walk_errors = take_walk_errors();
+        summary.errors += walk_errors.len();
+        self.failed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(walk_errors);
+        summary
+    }
+
+    /// Обработка файлов `paths` в `threads` потоков; пути в плане строятся от `root`.
+    /// С --by-album файлы сначала группируются по каталогам, и каждый поток берёт альбом целиком
+    pub fn process_paths(
+        &self,
+        paths: impl Iterator<Item = PathBuf> + Send,
+        root: &Path,
+        threads: usize,
+    ) -> Summary {
+        let started = Instant::now();
+        let summary = Mutex::new(Summary::default());
+        let batches: Box<dyn Iterator<Item = Vec<PathBuf>> + Send> = if self.by_album {
+            Box::new(albums(paths).into_iter())
+        } else {
+            Box::new(paths.map(|path| vec![path]))
+        };
+        let batches = Mutex::new(batches);
+
+        thread::scope(|s| {
+            for _ in 0..threads.max(1) {
+                s.spawn(|| {
+                    loop {
+                        if self.stopped() {
+                            break;
+                        }
+                        let Some(batch) = batches.lock().unwrap().next() else {
+                            break;
+                        };
+                        if self.by_album {
+                            self.process_album(&batch, root, &summary);
+                        } else {
+                            for path in &batch {
+                                self.process_path(path, root, &summary, FileLog::new);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut summary = summary.into_inner().unwrap_or_else(|e| e.into_inner());
+        self.check_albums(&mut summary);
+        summary.elapsed = started.elapsed();
+        summary.interrupted = interrupt::interrupted();
+        summary.failed_fast = self.failed_fast();
+        summary
+    }
+
+    /// Пора ли остановиться: [q]uit, Ctrl-C или ошибка с --fail-fast
+    fn stopped(&self) -> bool {
+        self.prompter().quit() || interrupt::interrupted() || self.failed_fast()
+    }
+
+    /// Была ли ошибка, после которой --fail-fast останавливает обработку
+    pub fn failed_fast(&self) -> bool {
+        self.fail_fast
+            && !self
+                .failed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_empty()
+    }
+
+    /// Обработка одного файла из обхода с учётом --resume и --incremental; `new_log` создаёт
+    /// вывод файла. Возвращает отчёт о файле, если он остался после вывода
+    fn process_path(
+        &self,
+        path: &Path,
+        root: &Path,
+        summary: &Mutex<Summary>,
+        new_log: impl Fn(OutputFormat, Verbosity) -> FileLog,
+    ) -> Option<FileReport> {
+        if self.progress.as_ref().is_some_and(|p| p.is_done(path)) {
+            summary.lock().unwrap_or_else(|e| e.into_inner()).resumed += 1;
+            return None;
+        }
+        let mut log = new_log(self.output, self.verbosity);
+        if self.is_unchanged(path, &mut log) {
+            summary.lock().unwrap_or_else(|e| e.into_inner()).unchanged += 1;
+            log.finish();
+            return None;
+        }
+        let status = self.process_file(path, None, &mut log);
+        self.remember(path, status, &mut log);
+        // На файле, где пользователь выбрал [q]uit, ответа не было: при
+        // продолжении его нужно спросить снова. Файл, на котором остановил --fail-fast,
+        // после --resume тоже пробуется снова
+        if let Some(progress) = &self.progress
+            && !self.prompter().quit()
+            && !(self.fail_fast && status == Status::Error)
+            && let Err(e) = progress.record(path)
+        {
+            log.warn(format!("{}: {e}", tr("Внимание", "Warning").yellow()));
+        }
+        if status == Status::WouldFix {
+            self.add_to_plan(root, &mut log);
+        }
+        {
+            let mut summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+            summary.add(status, log.report.as_ref());
+            summary.last = Some(path.to_path_buf());
+        }
+        self.add_to_reports(&log);
+        self.add_failed(&log);
+        log.finish();
+        log.report.take()
+    }
+
+    /// Обработка альбома (--by-album): исправления всех файлов каталога показываются одной
+    /// таблицей. С --interactive исправления сначала только ищутся, и вопрос задаётся один
+    /// раз на весь альбом; ответ применяется ко всем его файлам
+    fn process_album(&self, files: &[PathBuf], root: &Path, summary: &Mutex<Summary>) {
+        let Some(dir) = files.first().and_then(|path| path.parent()) else {
+            return;
+        };
+        let grouped = |format, verbosity| FileLog::new(format, verbosity).grouped();
+        let mut shown = false;
+        if self.prompter().is_enabled() {
+            self.prompter().set_preview(true);
+            let preview: Vec<FileReport> = files
+                .iter()
+                // Файлы, которые этот запуск всё равно пропустит (--resume, --incremental)
+                .filter(|path| {
+                    !self.progress.as_ref().is_some_and(|p| p.is_done(path))
+                        && !self.is_marked_unchanged(path)
+                })
+                .filter_map(|path| {
+                    let mut log = grouped(self.output, self.verbosity);
+                    self.process_file(path, None, &mut log);
+                    log.report.take()
+                })
+                .collect();
+            self.prompter().set_preview(false);
+            // Если исправлять нечего, спрашивать не о чем
+            if let Some(table) = album_table(dir, &preview) {
+                println!("{table}");
+                shown = true;
+                self.prompter().confirm_album(dir);
+                if self.prompter().quit() {
+                    return;
+                }
+            }
+        }
+
+        let reports: Vec<FileReport> = files
+            .iter()
+            .take_while(|_| !self.failed_fast())
+            .filter_map(|path| self.process_path(path, root, summary, grouped))
+            .collect();
+        if !shown && let Some(table) = album_table(dir, &reports) {
+            println!("{table}");
+        }
+        if let Some(result) = album_result(&reports) {
+            println!("{result}");
+        }
+    }
+
+    /// Применение плана: обрабатываются только файлы и изменения из него
+    pub fn process_plan(&self, plan: &Plan) -> Summary {
+        let started = Instant::now();
+        let mut summary = Summary::default();
+        for file in &plan.files {
+            if self.stopped() {
+                break;
+            }
+            let mut log = FileLog::new(self.output, self.verbosity);
+            let path = plan.file_path(file);
+            let status = self.process_file(&path, Some(&file.changes), &mut log);
+            summary.add(status, log.report.as_ref());
+            summary.last = Some(path);
+            self.add_to_reports(&log);
+            self.add_failed(&log);
+            log.finish();
+        }
+        summary.elapsed = started.elapsed();
+        summary.interrupted = interrupt::interrupted();
+        summary.failed_fast = self.failed_fast();
+        summary
+    }
+
+    /// Проверен ли файл раньше и не изменился ли с тех пор: по состоянию --incremental
+    /// или по отметке --mark
+    fn is_marked_unchanged(&self, path: &Path) -> bool {
+        self.state.as_ref().is_some_and(|s| s.is_unchanged(path))
+            || self.marker.as_ref().is_some_and(|m| m.is_unchanged(path))
+    }
+
+    /// Не изменился ли файл с прошлой проверки с --incremental или --mark
+    fn is_unchanged(&self, path: &Path, log: &mut FileLog) -> bool {
+        if !self.is_marked_unchanged(path) {
+            return false;
+        }
+        log.verbose(
+            Verbosity::Verbose,
+            format!(
+                "{} {}",
+                path.display(),
+                tr(
+                    "(не изменился с прошлой проверки)",
+                    "(unchanged since last check)"
+                )
+                .dimmed()
+            ),
+        );
+        true
+    }
+
+    /// Запомнить файл в состоянии --incremental и отметить его (--mark), если в нём нечего
+    /// исправлять: он проверен и чист или уже исправлен на месте
+    fn remember(&self, path: &Path, status: Status, log: &mut FileLog) {
+        if self.state.is_none() && self.marker.is_none() {
+            return;
+        }
+        let done = match status {
+            Status::Clean => log.report.is_some(),
+            Status::Fixed => self.output_dir.is_none(),
+            _ => false,
+        };
+        if !done {
+            return;
+        }
+        if let Some(state) = &self.state
+            && let Err(e) = state.record(path, status.as_str())
+        {
+            log.warn(format!("{}: {e}", tr("Внимание", "Warning").yellow()));
+        }
+        if let Some(marker) = &self.marker
+            && let Err(e) = marker.record(path, status.as_str())
+        {
+            log.warn(format!("{}: {e}", tr("Внимание", "Warning").yellow()));
+        }
+    }
+
+    /// Запомнить ошибку обработки файла для итогового списка
+    fn add_failed(&self, log: &FileLog) {
+        if let Some(report) = &log.report
+            && report.status == Status::Error
+            && let Some(error) = &report.error
+        {
+            self.failed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(error.clone());
+        }
+    }
+
+    /// Сохранить отчёт о файле для --report, если в нём есть исправления, ошибка или
+    /// значения, которые не восстановить
+    fn add_to_reports(&self, log: &FileLog) {
+        let (Some(reports), Some(report)) = (&self.reports, &log.report) else {
+            return;
+        };
+        if !report.fixes.is_empty() || report.error.is_some() || !report.unrecoverable.is_empty() {
+            reports
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(report.clone());
+        }
+    }
+
+    /// Добавить найденные исправления файла в план (кроме отложенных для проверки)
+    fn add_to_plan(&self, root: &Path, log: &mut FileLog) {
+        let (Some(plan), Some(report)) = (&self.plan, &log.report) else {
+            return;
+        };
+        let file = plan_file(root, report);
+        if !file.changes.is_empty() {
+            plan.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .files
+                .push(file);
+        }
+    }
+
+    fn prompter(&self) -> std::sync::MutexGuard<'_, Prompter> {
+        self.prompter.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Итоговый статус найденного исправления с учётом режима и ответа пользователя
+    fn decide(&self, path: &Path, log: &mut FileLog) -> Status {
+        if self.dry_run || self.prompter().is_previewing() {
+            Status::WouldFix
+        } else if !self.may_modify(path, log) || self.confirm(path, log) == Decision::Skip {
+            Status::Skipped
+        } else {
+            Status::Fixed
+        }
+    }
+
+    /// Обработка одного файла по расширению (с --sniff — по содержимому); с `changes` — только изменения из плана
+    fn process_file(
+        &self,
+        path: &Path,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> Status {
+        if self.backup_manager.is_backup(path)
+            || self
+                .output_dir
+                .as_ref()
+                .is_some_and(|dir| dir.contains(path))
+            || self.prompter().is_skipped(path)
+        {
+            return Status::Clean;
+        }
+        let Some(ext) = self.filter.format_extension(path) else {
+            return Status::Clean;
+        };
+
+        // Всё, что не текст, читаем как аудио: lofty сам определит формат
+        log.start_report(path, &ext);
+        let ((status, label), unrecoverable) = detect::unrecoverable(|| {
+            let debug = log.is_verbose(Verbosity::Debug);
+            if debug || self.show_candidates {
+                let (processed, traces) =
+                    detect::traced(|| self.process_by_format(path, &ext, changes, log));
+                for trace in &traces {
+                    if debug {
+                        log_trace(log, trace);
+                    }
+                    if self.show_candidates {
+                        log_near_miss(log, trace);
+                    }
+                }
+                processed
+            } else {
+                self.process_by_format(path, &ext, changes, log)
+            }
+        });
+        self.add_unrecoverable(path, unrecoverable, log);
+
+        if matches!(status, Status::Fixed | Status::WouldFix) {
+            log.fixed_file(format!("{label:<6} {}", path.display()));
+        } else {
+            let state = match status {
+                Status::Clean => tr("без изменений", "unchanged"),
+                Status::Skipped => tr("пропущен", "skipped"),
+                Status::Review => tr("только исправления для проверки", "only fixes for review"),
+                Status::Error => tr("ошибка", "error"),
+                Status::Fixed | Status::WouldFix => unreachable!(),
+            };
+            log.verbose(
+                Verbosity::Verbose,
+                format!(
+                    "{label:<6} {} {}",
+                    path.display(),
+                    format!("({state})").dimmed()
+                ),
+            );
+        }
+        if status == Status::Fixed {
+            self.run_hooks(path, log);
+            self.add_changed_dir(path);
+        }
+        log.report().status = status;
+        status
+    }
+
+    /// Запомнить каталог исправленного файла (с --output-dir — каталог копии)
+    fn add_changed_dir(&self, path: &Path) {
+        let output_path = self.output_path(path);
+        let dir = output_path.parent().unwrap_or(Path::new("."));
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        self.changed_dirs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(dir);
+    }
+
+    /// Сообщить медиасерверу и MPD о каталогах, где с прошлого раза исправлены файлы;
+    /// неудача — только предупреждение: файлы уже исправлены
+    pub fn notify_changes(&self) {
+        if self.notifier.is_none() && self.mpd.is_none() {
+            return;
+        }
+        let dirs: Vec<PathBuf> =
+            std::mem::take(&mut *self.changed_dirs.lock().unwrap_or_else(|e| e.into_inner()))
+                .into_iter()
+                .collect();
+        if dirs.is_empty() {
+            return;
+        }
+        if let Some(notifier) = &self.notifier {
+            let result = notifier.notify(&dirs).map(|()| dirs.len());
+            self.notified(notifier.server.name(), result);
+        }
+        if let Some(mpd) = &self.mpd {
+            self.notified("MPD", mpd.update(&dirs));
+        }
+    }
+
+    /// Итог оповещения `name`: число каталогов, обновление которых запрошено, или ошибка
+    fn notified(&self, name: &str, result: Result<usize, String>) {
+        match result {
+            Ok(count) => {
+                let message = tr_format!(
+                    "{name}: запрошено обновление каталогов: {count}",
+                    "{name}: refresh requested for directories: {count}"
+                );
+                log::write(LogLevel::Info, &message);
+                if self.output == OutputFormat::Human && self.verbosity != Verbosity::Quiet {
+                    println!("{message}");
+                }
+            }
+            Err(e) => {
+                log::write(LogLevel::Warn, &format!("{name}: {e}"));
+                eprintln!("{}: {name}: {e}", tr("Внимание", "Warning").yellow());
+            }
+        }
+    }
+
+    /// Запустить команды --exec для исправленного файла (с --output-dir — для его копии);
+    /// вывод команды попадает в подробности файла, неудача — в предупреждения
+    fn run_hooks(&self, path: &Path, log: &mut FileLog) {
+        let output_path = self.output_path(path);
+        for hook in &self.hooks {
+            let warn = |log: &mut FileLog, message: String| {
+                log.warn(format!(
+                    "{}: --exec {hook}: {message} ({})",
+                    tr("Внимание", "Warning").yellow(),
+                    output_path.display()
+                ));
+            };
+            let output = match hook.run(&output_path) {
+                Ok(output) => output,
+                Err(e) => {
+                    warn(log, e.to_string());
+                    continue;
+                }
+            };
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                log.detail(format!("  {}", line.dimmed()));
+            }
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let message = match output.status.code() {
+                    Some(code) => tr_format!(
+                        "команда завершилась с кодом {code}",
+                        "command exited with code {code}"
+                    ),
+                    None => {
+                        tr("команда прервана сигналом", "command killed by a signal").to_string()
+                    }
+                };
+                warn(log, message);
+                for line in stderr.lines() {
+                    log.warn(format!("  {line}"));
+                }
+            }
+        }
+    }
+
+    /// Обработка файла обработчиком его формата; возвращает итог и метку формата для вывода
+    fn process_by_format(
+        &self,
+        path: &Path,
+        ext: &str,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> (Status, ColoredString) {
+        if TEXT_EXTENSIONS.contains(ext) {
+            (
+                self.process_text(path, changes, log),
+                format!("[{}]", ext.to_uppercase()).magenta(),
+            )
+        } else if ext == zip::EXTENSION {
+            (
+                self.process_zip(path, changes, log),
+                format!("[{}]", ext.to_uppercase()).yellow(),
+            )
+        } else if ASF_EXTENSIONS.contains(ext) {
+            (
+                self.process_asf(path, changes, log),
+                format!("[{}]", ext.to_uppercase()).bright_blue(),
+            )
+        } else {
+            let status = self.process_audio(path, changes, log);
+            if changes.is_none() {
+                self.add_album_track(path, status, log);
+            }
+            (status, format!("[{}]", ext.to_uppercase()).bright_blue())
+        }
+    }
+
+    /// Запомнить аудио-файл для проверки единообразия альбома после прохода
+    fn add_album_track(&self, path: &Path, status: Status, log: &mut FileLog) {
+        if status == Status::Error || self.prompter().is_previewing() {
+            return;
+        }
+        let fixes = match status {
+            Status::Fixed | Status::WouldFix => log
+                .report()
+                .fixes
+                .iter()
+                .filter(|fix| !fix.review)
+                .map(|fix| (fix.before.clone(), fix.after.clone()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        self.album_tracks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(dir)
+            .or_default()
+            .push(AlbumTrack {
+                path: path.to_path_buf(),
+                status,
+                fixes,
+            });
+    }
+
+    /// Проверка каталогов, где что-то исправлено: если название или исполнитель альбома
+    /// у треков после исправления пишутся чуть по-разному — предупреждение, а с
+    /// --normalize-album — запись значения большинства. С --discogs-token названия треков
+    /// ещё и сверяются с треклистом Discogs
+    fn check_albums(&self, summary: &mut Summary) {
+        let albums =
+            std::mem::take(&mut *self.album_tracks.lock().unwrap_or_else(|e| e.into_inner()));
+        for (dir, tracks) in albums {
+            if self.stopped() {
+                break;
+            }
+            if !tracks
+                .iter()
+                .any(|track| matches!(track.status, Status::Fixed | Status::WouldFix))
+            {
+                continue;
+            }
+            let values: Vec<album::Track> = tracks
+                .iter()
+                .filter_map(|track| self.album_values(track))
+                .collect();
+            let mut log = FileLog::new(self.output, self.verbosity);
+            if let Some(discogs) = &self.discogs {
+                summary.unlisted +=
+                    self.verify_tracklist(discogs, &dir, &values, &tracks, &mut log);
+            }
+            let mismatches = album::mismatches(&values);
+            if !mismatches.is_empty() {
+                let count = self.normalize_album(&dir, &mismatches, &tracks, &mut log);
+                if self.dry_run {
+                    summary.would_normalize += count;
+                } else {
+                    summary.normalized += count;
+                }
+            }
+            log.finish();
+        }
+    }
+
+    /// Поля альбома трека после исправления: у записанного файла — из него самого (или его
+    /// копии в --output-dir), в пробном запуске — с исправлениями, которые записались бы
+    fn album_values(&self, track: &AlbumTrack) -> Option<album::Track> {
+        let source = match track.status {
+            Status::Fixed => self.output_path(&track.path),
+            _ => track.path.clone(),
+        };
+        let values = self
+            .audio_fixer
+            .album_values(&source)
+            .ok()?
+            .into_iter()
+            .map(|(key, value)| {
+                let fixed = track
+                    .fixes
+                    .iter()
+                    .find(|(before, _)| *before == value && track.status != Status::Fixed)
+                    .map_or(value, |(_, after)| after.clone());
+                (key, fixed)
+            })
+            .collect();
+        Some(album::Track {
+            path: track.path.clone(),
+            values,
+        })
+    }
+
+    /// Сверить треки альбома `dir` с треклистом релиза Discogs, найденного по исполнителю
+    /// и названию альбома большинства треков: предупредить о треках, исправленное название
+    /// которых не похоже ни на один трек релиза. Возвращает, сколько таких треков
+    fn verify_tracklist(
+        &self,
+        discogs: &Discogs,
+        dir: &Path,
+        values: &[album::Track],
+        tracks: &[AlbumTrack],
+        log: &mut FileLog,
+    ) -> usize {
+        let majority = |keys: &[ItemKey]| {
+            let mut counts: Vec<(&str, usize)> = Vec::new();
+            for track in values {
+                let Some(value) = keys.iter().find_map(|key| track.get(key)) else {
+                    continue;
+                };
+                match counts.iter_mut().find(|(v, _)| *v == value) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((value, 1)),
+                }
+            }
+            counts.sort_by_key(|(_, count)| Reverse(*count));
+            counts.first().map(|(value, _)| *value)
+        };
+        let (Some(artist), Some(album)) = (
+            majority(&[ItemKey::AlbumArtist, ItemKey::TrackArtist]),
+            majority(&[ItemKey::AlbumTitle]),
+        ) else {
+            return 0;
+        };
+        let release = format!("'{artist}' — '{album}'");
+        let tracklist = match discogs.tracklist(artist, album) {
+            Ok(Some(tracklist)) => tracklist,
+            Ok(None) => {
+                log.verbose(
+                    Verbosity::Verbose,
+                    format!(
+                        "{} {}: {}",
+                        "[DISCOGS]".bold(),
+                        dir.display(),
+                        tr_format!("релиз не найден: {release}", "release not found: {release}")
+                            .dimmed()
+                    ),
+                );
+                return 0;
+            }
+            Err(e) => {
+                log.warn(tr_format!(
+                    "{}: Discogs: {e}; сверка с треклистом отключена до конца запуска",
+                    "{}: Discogs: {e}; tracklist check is off for the rest of the run",
+                    tr("Внимание", "Warning").yellow()
+                ));
+                return 0;
+            }
+        };
+
+        let mut unlisted = 0;
+        for track in values {
+            let Some(title) = track.get(&ItemKey::TrackTitle) else {
+                continue;
+            };
+            let fixed = tracks
+                .iter()
+                .any(|t| t.path == track.path && t.fixes.iter().any(|(_, after)| after == title));
+            if !fixed
+                || tracklist
+                    .iter()
+                    .any(|listed| album::resembles(title, listed))
+            {
+                continue;
+            }
+            log.warn(tr_format!(
+                "{}: {}: название '{title}' не похоже ни на один трек релиза Discogs {release}; проверьте исправление",
+                "{}: {}: title '{title}' matches no track of the Discogs release {release}; check the fix",
+                tr("Внимание", "Warning").yellow(),
+                track.path.display()
+            ));
+            unlisted += 1;
+        }
+        unlisted
+    }
+
+    /// Предупредить о полях `mismatches` альбома `dir`, а с --normalize-album — записать
+    /// значения большинства остальным трекам. Возвращает, сколько файлов выровнено (в пробном
+    /// запуске — было бы выровнено)
+    fn normalize_album(
+        &self,
+        dir: &Path,
+        mismatches: &[album::Mismatch],
+        tracks: &[AlbumTrack],
+        log: &mut FileLog,
+    ) -> usize {
+        // Что записать в каждый файл: поле, прежнее значение, новое
+        let mut changes: BTreeMap<&Path, Vec<(ItemKey, String, String)>> = BTreeMap::new();
+        for mismatch in mismatches {
+            let field = format!("{:?}", mismatch.key);
+            let variants: Vec<String> = mismatch
+                .variants
+                .iter()
+                .map(|(value, count)| format!("'{value}' ({count})"))
+                .collect();
+            if !self.normalize_album {
+                log.warn(tr_format!(
+                    "{}: {}: {field} у треков пишется по-разному: {}; привести к значению большинства — --normalize-album",
+                    "{}: {}: {field} differs between tracks: {}; use --normalize-album to apply the majority value",
+                    tr("Внимание", "Warning").yellow(),
+                    dir.display(),
+                    variants.join(", ")
+                ));
+                continue;
+            }
+            log.out(format!(
+                "{} {}: {field} → '{}' {}",
+                "[ALBUM]".bold(),
+                dir.display(),
+                mismatch.value,
+                format!("({})", variants.join(", ")).dimmed()
+            ));
+            for (path, before) in &mismatch.tracks {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                log.detail(format!(
+                    "  {} {name}: '{before}' -> '{}'",
+                    "NORM".cyan(),
+                    mismatch.value
+                ));
+                changes.entry(path).or_default().push((
+                    mismatch.key.clone(),
+                    before.clone(),
+                    mismatch.value.clone(),
+                ));
+            }
+        }
+        if changes.is_empty() || self.dry_run {
+            return changes.len();
+        }
+        if self.prompter().is_enabled() {
+            log.flush();
+        }
+        if self.prompter().is_skipped(dir)
+            || self.prompter().confirm_normalize(dir) == Decision::Skip
+        {
+            return 0;
+        }
+
+        let mut normalized = 0;
+        for (path, changes) in changes {
+            if !self.may_modify(path, log) {
+                continue;
+            }
+            // Копия записанного в этом запуске файла уже есть, а полный бэкап сохранил
+            // исходный файл: новый бэкап затёр бы его
+            let written = tracks
+                .iter()
+                .any(|track| track.path == path && track.status == Status::Fixed);
+            let output = if written {
+                Ok(self.output_path(path))
+            } else {
+                self.output_copy(path)
+            };
+            let no_backup = BackupManager::new(true);
+            let backup_manager = if written && self.backup_manager.manifest().is_none() {
+                &no_backup
+            } else {
+                &self.backup_manager
+            };
+            let mtime = self.mtime(path);
+            let output = match output.and_then(|output| {
+                self.audio_fixer
+                    .set_fields(&output, &changes, backup_manager)
+                    .map(|()| output)
+            }) {
+                Ok(output) => output,
+                Err(e) => {
+                    fail(log, e);
+                    continue;
+                }
+            };
+            self.restore_mtime(&output, mtime, log);
+            self.run_hooks(path, log);
+            self.add_changed_dir(path);
+            normalized += 1;
+        }
+        normalized
+    }
+
+    /// Отложить исправления с низкой уверенностью: в отчёт и в итоговый список для проверки
+    fn defer(&self, path: &Path, log: &mut FileLog, fixes: Vec<ReportFix>) {
+        // В предпросмотре альбома файл ещё будет обработан по-настоящему
+        if fixes.is_empty() || self.prompter().is_previewing() {
+            return;
+        }
+        let mut review = self.review.lock().unwrap_or_else(|e| e.into_inner());
+        for fix in &fixes {
+            let path = fix
+                .member
+                .as_ref()
+                .map_or(path.to_path_buf(), |m| path.join(m));
+            let mut details: Vec<String> = fix
+                .encoding
+                .iter()
+                .zip(&fix.misread_as)
+                .map(|(source, misread)| format!("{source} {} {misread}", tr("как", "as")))
+                .collect();
+            details.extend(fix.tag.clone());
+            details.extend(fix.confidence.map(|c| format!("{c}%")));
+            review.push(format!(
+                "{}: {} '{}' -> '{}' ({})",
+                path.display(),
+                fix.field,
+                fix.before.trim(),
+                fix.after.trim(),
+                details.join(", ")
+            ));
+        }
+        log.report().fixes.extend(fixes);
+    }
+
+    /// Значения тегов, исходные байты которых уже потеряны: в отчёт и в итоговый список,
+    /// чтобы такие файлы заполнить заново вручную
+    fn add_unrecoverable(&self, path: &Path, values: Vec<String>, log: &mut FileLog) {
+        // В предпросмотре альбома файл ещё будет обработан по-настоящему
+        if values.is_empty() || self.prompter().is_previewing() {
+            return;
+        }
+        let mut unrecoverable = self.unrecoverable.lock().unwrap_or_else(|e| e.into_inner());
+        for value in &values {
+            log.detail(format!("  {} '{}'", "LOST".red(), value.trim()));
+            unrecoverable.push(format!("{}: '{}'", path.display(), value.trim()));
+        }
+        log.report().unrecoverable = values;
+    }
+
+    /// Где окажется исправленный файл: сам `path` или его место в зеркальном дереве --output-dir
+    fn output_path(&self, path: &Path) -> PathBuf {
+        match &self.output_dir {
+            Some(dir) => dir
+                .backup_path(path)
+                .unwrap_or_else(|| dir.dir.join(path.file_name().unwrap_or_default())),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Какой файл исправлять: сам `path` или, с --output-dir, его свежую копию в зеркальном
+    /// дереве (файл вне корня обхода копируется в корень --output-dir)
+    fn output_copy(&self, path: &Path) -> cyrtag_fix::Result<PathBuf> {
+        if self.output_dir.is_none() {
+            return Ok(path.to_path_buf());
+        }
+        let output = self.output_path(path);
+        output
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::copy(path, &output))
+            .map_err(|source| cyrtag_fix::Error::Write {
+                path: output.clone(),
+                source,
+            })?;
+        Ok(output)
+    }
+
+    /// Можно ли менять сам файл: файлы раздач не трогаются, а у файла с другими жёсткими
+    /// ссылками запись (через временный файл) разрывает ссылку — остальные ссылки
+    /// сохраняют прежнее содержимое. С --output-dir оригиналы не меняются вовсе
+    fn may_modify(&self, path: &Path, log: &mut FileLog) -> bool {
+        if self.output_dir.is_some() {
+            return true;
+        }
+        let warn = |log: &mut FileLog, message: &str| {
+            log.warn(format!(
+                "{}: {} {message}",
+                tr("Внимание", "Warning").yellow(),
+                path.display()
+            ));
+        };
+
+        if !self.seed_dirs.is_empty()
+            && let Ok(canonical) = fs::canonicalize(path)
+            && self.seed_dirs.iter().any(|dir| canonical.starts_with(dir))
+        {
+            warn(
+                log,
+                tr(
+                    "в каталоге раздачи (--seed-dir), пропущен",
+                    "is in a seeding directory (--seed-dir), skipped",
+                ),
+            );
+            return false;
+        }
+
+        let links = link_count(path);
+        if links > 1 {
+            match self.hardlinks {
+                HardlinkMode::Skip => {
+                    warn(
+                        log,
+                        &tr_format!(
+                            "— жёстких ссылок: {links}, пропущен (--hardlinks skip)",
+                            "has {links} hard links, skipped (--hardlinks skip)"
+                        ),
+                    );
+                    return false;
+                }
+                HardlinkMode::Break => warn(
+                    log,
+                    &tr_format!(
+                        "— жёстких ссылок: {links}; исправленный файл будет отдельной копией, \
+                         остальные ссылки не изменятся",
+                        "has {links} hard links; the fixed file will be a separate copy, \
+                         other links stay unchanged"
+                    ),
+                ),
+            }
+        }
+        true
+    }
+
+    /// Время изменения файла до записи, если его нужно сохранить (--preserve-mtime)
+    fn mtime(&self, path: &Path) -> Option<SystemTime> {
+        if !self.preserve_mtime {
+            return None;
+        }
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Вернуть записанному файлу прежнее время изменения; при неудаче — только предупреждение
+    fn restore_mtime(&self, path: &Path, mtime: Option<SystemTime>, log: &mut FileLog) {
+        let Some(mtime) = mtime else {
+            return;
+        };
+        if let Err(e) = File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(mtime))
+        {
+            log.warn(tr_format!(
+                "{}: не удалось сохранить время изменения {}: {e}",
+                "{}: could not preserve modification time of {}: {e}",
+                tr("Внимание", "Warning").yellow(),
+                path.display()
+            ));
+        }
+    }
+
+    /// Показывать ли изменённые строки текстовых файлов: их бывает много, поэтому — только
+    /// в пробном запуске и перед вопросом о подтверждении
+    fn shows_text_changes(&self) -> bool {
+        self.dry_run || self.prompter().is_enabled()
+    }
+
+    /// Спросить подтверждение, предварительно показав накопленный вывод
+    fn confirm(&self, path: &Path, log: &mut FileLog) -> Decision {
+        let mut prompter = self.prompter();
+        if prompter.is_enabled() {
+            log.flush();
+        }
+        prompter.confirm(path)
+    }
+
+    /// Обработка .cue или плейлиста: читаем cp1251 (или utf-8 с кракозябрами) -> пишем utf-8
+    fn process_text(
+        &self,
+        path: &Path,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> Status {
+        let fix = match changes {
+            Some(changes) => self.text_fixer.planned(path, changes).map(Some),
+            None => self.text_fixer.scan(path),
+        };
+        let mut fix = match fix {
+            Ok(Some(fix)) => fix,
+            Ok(None) => return Status::Clean,
+            Err(e) => return fail(log, e),
+        };
+
+        if fix.had_errors {
+            log.warn(tr_format!(
+                "{}: не удалось полностью декодировать {} как {}",
+                "{}: could not fully decode {} as {}",
+                tr("Внимание", "Warning").yellow(),
+                path.display(),
+                encoding_name(fix.encoding)
+            ));
+        }
+
+        let review = fix.retain_changes(|c| c.confidence.is_none_or(|c| c >= self.min_confidence));
+        let changes = fix.changed_lines();
+        if self.shows_text_changes() {
+            for change in changes {
+                log.detail(format!(
+                    "  {} {}",
+                    "FIX".cyan(),
+                    text_change_line(change, fix.encoding)
+                ));
+            }
+        }
+        let report = |change, review| text_report(change, fix.encoding, review);
+        log.report().fixes = changes.iter().map(|c| report(c, false)).collect();
+        self.defer(path, log, review.iter().map(|c| report(c, true)).collect());
+        if changes.is_empty() {
+            return Status::Review;
+        }
+
+        let status = self.decide(path, log);
+        if status != Status::Fixed {
+            return status;
+        }
+
+        let output = match self.output_copy(path) {
+            Ok(output) => output,
+            Err(e) => return fail(log, e),
+        };
+        if output != path
+            && let Some(name) = fix.target.file_name()
+        {
+            fix.target = output.with_file_name(name);
+            // .m3u8 от прошлого запуска с тем же --output-dir перезаписывается, как и копии
+            if fix.target != output {
+                let _ = fs::remove_file(&fix.target);
+            }
+        }
+        let mtime = self.mtime(path);
+        if let Err(e) = self.text_fixer.apply(&output, &fix, &self.backup_manager) {
+            return fail(log, e);
+        }
+        self.restore_mtime(&fix.target, mtime, log);
+
+        if fix.target == path {
+            log.detail(format!(
+                "  {}",
+                tr("→ сохранён в UTF-8", "→ saved as UTF-8").green()
+            ));
+        } else {
+            log.detail(format!(
+                "  {}",
+                tr_format!(
+                    "→ сохранён в UTF-8 как {}",
+                    "→ saved as UTF-8 to {}",
+                    fix.target.display()
+                )
+                .green()
+            ));
+        }
+        Status::Fixed
+    }
+
+    /// Обработка аудио-файла через lofty
+    fn process_audio(
+        &self,
+        path: &Path,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> Status {
+        let fix = match changes {
+            Some(changes) => self.audio_fixer.planned(path, changes).map(Some),
+            None => self.audio_fixer.scan(path),
+        };
+        let mut fix = match fix {
+            Ok(Some(fix)) => fix,
+            Ok(None) => return Status::Clean,
+            Err(e) => return fail(log, e),
+        };
+        if let Some(musicbrainz) = &self.musicbrainz
+            && changes.is_none()
+        {
+            self.validate_online(musicbrainz, &mut fix, log);
+        }
+        let review: Vec<_> = fix
+            .fixes
+            .extract_if(.., |f| f.confidence < self.min_confidence)
+            .collect();
+
+        for field in &fix.fixes {
+            log.detail(format!("  {} {}", "FIX".cyan(), audio_fix_line(field)));
+        }
+        if fix.reencode {
+            log.detail(format!("  {} {}", "FIX".cyan(), reencode_line()));
+        }
+        if let Some(id3v1) = &fix.id3v1 {
+            log.detail(format!(
+                "  {} {}",
+                "FIX".cyan(),
+                id3v1_line(id3v1, self.audio_fixer.id3v1)
+            ));
+        }
+        log.report().fixes = fix.fixes.iter().map(|f| audio_report(f, false)).collect();
+        self.defer(
+            path,
+            log,
+            review.iter().map(|f| audio_report(f, true)).collect(),
+        );
+        if fix.fixes.is_empty() && !fix.reencode && fix.id3v1.is_none() {
+            return Status::Review;
+        }
+
+        let status = self.decide(path, log);
+        if status != Status::Fixed {
+            return status;
+        }
+
+        let output = match self.output_copy(path) {
+            Ok(output) => output,
+            Err(e) => return fail(log, e),
+        };
+        let mtime = self.mtime(path);
+        if let Err(e) = self.audio_fixer.apply(&output, fix, &self.backup_manager) {
+            return fail(log, e);
+        }
+        self.restore_mtime(&output, mtime, log);
+
+        log_tags_updated(path, &output, log);
+        Status::Fixed
+    }
+
+    /// Проверка исправлений исполнителя, альбома и названия с низкой уверенностью по
+    /// MusicBrainz: если релиз, запись или исполнитель с исправленным названием нашёлся,
+    /// уверенность исправлений, участвовавших в поиске, поднимается
+    fn validate_online(&self, musicbrainz: &MusicBrainz, fix: &mut AudioFix, log: &mut FileLog) {
+        let threshold = self.min_confidence.max(ONLINE_VALIDATE_BELOW);
+        let low = |fix: &AudioFix, key: &ItemKey| {
+            fix.fixes
+                .iter()
+                .any(|f| f.key == *key && f.confidence < threshold)
+        };
+        let artist_key = [ItemKey::TrackArtist, ItemKey::AlbumArtist]
+            .into_iter()
+            .find(|key| fix.value(key).is_some())
+            .unwrap_or(ItemKey::TrackArtist);
+        let artist = fix.value(&artist_key);
+
+        for (entity, key) in [
+            (Entity::Release, ItemKey::AlbumTitle),
+            (Entity::Recording, ItemKey::TrackTitle),
+            (Entity::Artist, artist_key.clone()),
+        ] {
+            let artist_low = low(fix, &artist_key);
+            if !(low(fix, &key) || artist_low && entity != Entity::Artist) {
+                continue;
+            }
+            // Многострочное значение — не название
+            let Some(title) = fix.value(&key).filter(|title| !title.contains('\n')) else {
+                continue;
+            };
+            let artist = artist.as_deref().filter(|_| entity != Entity::Artist);
+            let found = match musicbrainz.exists(entity, &title, artist) {
+                Ok(Some(found)) => found,
+                Ok(None) => return,
+                Err(e) => {
+                    log.warn(tr_format!(
+                        "{}: MusicBrainz: {e}; онлайн-проверка отключена до конца запуска",
+                        "{}: MusicBrainz: {e}; online validation is off for the rest of the run",
+                        tr("Внимание", "Warning").yellow()
+                    ));
+                    return;
+                }
+            };
+            let found_text = match artist {
+                Some(artist) => format!("'{title}' — '{artist}'"),
+                None => format!("'{title}'"),
+            };
+            if !found {
+                log.verbose(
+                    Verbosity::Verbose,
+                    format!(
+                        "  {} {}",
+                        "MB".cyan(),
+                        tr_format!("не найдено: {found_text}", "not found: {found_text}").dimmed()
+                    ),
+                );
+                continue;
+            }
+            log.detail(format!(
+                "  {} {}",
+                "MB".cyan(),
+                tr_format!(
+                    "найдено в MusicBrainz: {found_text}",
+                    "found in MusicBrainz: {found_text}"
+                )
+            ));
+            for field in &mut fix.fixes {
+                let confirmed = field.key == key || (artist.is_some() && field.key == artist_key);
+                if confirmed && field.confidence < threshold {
+                    field.confidence = field.confidence.max(ONLINE_VALIDATED);
+                }
+            }
+        }
+    }
+
+    /// Обработка ZIP-архива (--zip): теги и тексты файлов внутри исправляются в памяти,
+    /// имена файлов с кракозябрами (cp866 от старых архиваторов) сохраняются в UTF-8,
+    /// и архив переписывается целиком. Файл архива, который не удалось прочитать, остаётся
+    /// как есть с предупреждением. С одним --zip-names исправляются только имена, данные
+    /// файлов копируются байт в байт
+    fn process_zip(
+        &self,
+        path: &Path,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> Status {
+        if let Some(change) = changes.and_then(<[_]>::first) {
+            return fail(
+                log,
+                cyrtag_fix::Error::PlanApply {
+                    path: path.to_path_buf(),
+                    field: change.field.clone(),
+                    reason: tr(
+                        "план не применяется к ZIP-архивам",
+                        "plans do not apply to ZIP archives",
+                    ),
+                },
+            );
+        }
+        let mut archive = match Archive::read(path) {
+            Ok(archive) => archive,
+            Err(e) => return fail(log, e),
+        };
+
+        let mut found = false;
+        let mut fixes = Vec::new();
+        let mut review = Vec::new();
+        let mut names = BTreeMap::new();
+        let mut members = Vec::new();
+        for (index, entry) in archive.entries.iter().enumerate() {
+            let original = entry.name();
+            let mut name = original.clone();
+            if let Some(detection) = self.audio_fixer.detector.detect(&original) {
+                found = true;
+                let report = ReportFix {
+                    field: "name".to_string(),
+                    key: None,
+                    line: None,
+                    before: original.clone(),
+                    after: detection.text.clone(),
+                    encoding: Some(encoding_name(detection.mojibake.source)),
+                    misread_as: Some(encoding_name(detection.mojibake.misread)),
+                    tag: None,
+                    confidence: Some(detection.confidence),
+                    review: detection.confidence < self.min_confidence,
+                    member: Some(detection.text.clone()),
+                };
+                if report.review {
+                    review.push(report);
+                } else {
+                    log.detail(format!(
+                        "  {} {}: '{original}' -> '{}' {}",
+                        "FIX".cyan(),
+                        tr("имя", "name"),
+                        detection.text,
+                        format!("({}, {}%)", detection.mojibake, detection.confidence).dimmed()
+                    ));
+                    fixes.push(report);
+                    name = detection.text;
+                    names.insert(index, name.clone());
+                }
+            }
+
+            if !self.filter.zip {
+                continue;
+            }
+            let Some(ext) = extension(Path::new(&name))
+                .filter(|ext| ext != zip::EXTENSION && self.filter.supports_extension(ext))
+            else {
+                continue;
+            };
+            if entry.is_dir() || !entry.is_supported() {
+                continue;
+            }
+            let member_path = path.join(&name);
+            let data = match entry.data() {
+                Ok(data) => data,
+                Err(source) => {
+                    let e = cyrtag_fix::Error::Read {
+                        path: member_path,
+                        source,
+                    };
+                    log.warn(format!("{}: {e}", tr("Внимание", "Warning").yellow()));
+                    continue;
+                }
+            };
+            let member = if TEXT_EXTENSIONS.contains(&ext) {
+                self.text_fixer
+                    .scan_bytes(&member_path, data)
+                    .map(|fix| fix.map(MemberFix::Text))
+            } else if ASF_EXTENSIONS.contains(&ext) {
+                self.asf_fixer
+                    .scan_bytes(&member_path, &data)
+                    .map(|fix| fix.map(MemberFix::Asf))
+            } else {
+                self.audio_fixer
+                    .scan_bytes(&member_path, &data)
+                    .map(|fix| fix.map(MemberFix::Audio))
+            };
+            let mut member = match member {
+                Ok(Some(member)) => member,
+                Ok(None) => continue,
+                Err(e) => {
+                    log.warn(format!("{}: {e}", tr("Внимание", "Warning").yellow()));
+                    continue;
+                }
+            };
+            found = true;
+
+            let mut renamed = None;
+            let (applied, deferred) = match &mut member {
+                MemberFix::Text(fix) => {
+                    let deferred = fix
+                        .retain_changes(|c| c.confidence.is_none_or(|c| c >= self.min_confidence));
+                    let encoding = fix.encoding;
+                    if self.shows_text_changes() {
+                        for change in fix.changed_lines() {
+                            log.detail(format!(
+                                "  {} {name}: {}",
+                                "FIX".cyan(),
+                                text_change_line(change, encoding)
+                            ));
+                        }
+                    }
+                    // .m3u в UTF-8 сохраняется как .m3u8, если такого файла ещё нет
+                    if fix.target != member_path
+                        && let Some((stem, _)) = name.rsplit_once('.')
+                    {
+                        let target = format!("{stem}.m3u8");
+                        if !archive.entries.iter().any(|e| e.name() == target) {
+                            renamed = Some(target);
+                        }
+                    }
+                    (
+                        fix.changed_lines()
+                            .iter()
+                            .map(|c| text_report(c, encoding, false))
+                            .collect::<Vec<_>>(),
+                        deferred
+                            .iter()
+                            .map(|c| text_report(c, encoding, true))
+                            .collect::<Vec<_>>(),
+                    )
+                }
+                MemberFix::Audio(fix) => {
+                    let deferred: Vec<_> = fix
+                        .fixes
+                        .extract_if(.., |f| f.confidence < self.min_confidence)
+                        .collect();
+                    for field in &fix.fixes {
+                        log.detail(format!(
+                            "  {} {name}: {}",
+                            "FIX".cyan(),
+                            audio_fix_line(field)
+                        ));
+                    }
+                    if fix.reencode {
+                        log.detail(format!("  {} {name}: {}", "FIX".cyan(), reencode_line()));
+                    }
+                    if let Some(id3v1) = &fix.id3v1 {
+                        log.detail(format!(
+                            "  {} {name}: {}",
+                            "FIX".cyan(),
+                            id3v1_line(id3v1, self.audio_fixer.id3v1)
+                        ));
+                    }
+                    (
+                        fix.fixes.iter().map(|f| audio_report(f, false)).collect(),
+                        deferred.iter().map(|f| audio_report(f, true)).collect(),
+                    )
+                }
+                MemberFix::Asf(fix) => {
+                    let deferred: Vec<_> = fix
+                        .fixes
+                        .extract_if(.., |f| f.confidence < self.min_confidence)
+                        .collect();
+                    for field in &fix.fixes {
+                        log.detail(format!(
+                            "  {} {name}: {}",
+                            "FIX".cyan(),
+                            asf_fix_line(field)
+                        ));
+                    }
+                    (
+                        fix.fixes.iter().map(|f| asf_report(f, false)).collect(),
+                        deferred.iter().map(|f| asf_report(f, true)).collect(),
+                    )
+                }
+            };
+            let with_member = |mut fix: ReportFix| {
+                fix.member = Some(name.clone());
+                fix
+            };
+            review.extend(deferred.into_iter().map(with_member));
+            let rewrites =
+                matches!(&member, MemberFix::Audio(fix) if fix.reencode || fix.id3v1.is_some());
+            if !applied.is_empty() || rewrites {
+                fixes.extend(applied.into_iter().map(with_member));
+                members.push((index, member));
+                names.extend(renamed.map(|name| (index, name)));
+            }
+        }
+        if !found {
+            return Status::Clean;
+        }
+
+        log.report().fixes = fixes;
+        self.defer(path, log, review);
+        if members.is_empty() && names.is_empty() {
+            return Status::Review;
+        }
+
+        let status = self.decide(path, log);
+        if status != Status::Fixed {
+            return status;
+        }
+
+        for (index, member) in members {
+            let entry = &mut archive.entries[index];
+            let member_path = path.join(names.get(&index).cloned().unwrap_or_else(|| entry.name()));
+            let write_error = |source| cyrtag_fix::Error::Write {
+                path: member_path.clone(),
+                source,
+            };
+            let data = match member {
+                MemberFix::Text(fix) => Ok(self.text_fixer.render(&fix).into_bytes()),
+                MemberFix::Asf(fix) => Ok(self.asf_fixer.apply_bytes(fix)),
+                MemberFix::Audio(fix) => entry.data().map_err(write_error).and_then(|mut data| {
+                    self.audio_fixer
+                        .apply_bytes(&member_path, &mut data, fix)
+                        .map(|()| data)
+                }),
+            };
+            if let Err(e) = data.and_then(|data| entry.set_data(&data).map_err(write_error)) {
+                return fail(log, e);
+            }
+        }
+        for (index, name) in names {
+            archive.entries[index].set_name(&name);
+        }
+        let bytes = match archive.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(message) => {
+                return fail(
+                    log,
+                    cyrtag_fix::Error::Write {
+                        path: path.to_path_buf(),
+                        source: io::Error::new(io::ErrorKind::FileTooLarge, message),
+                    },
+                );
+            }
+        };
+
+        let output = match self.output_copy(path) {
+            Ok(output) => output,
+            Err(e) => return fail(log, e),
+        };
+        let mtime = self.mtime(path);
+        if let Err(e) = self
+            .backup_manager
+            .backup_file(&output)
+            .and_then(|()| atomic::write(&output, &bytes))
+        {
+            return fail(log, e);
+        }
+        self.restore_mtime(&output, mtime, log);
+
+        if output == path {
+            log.detail(format!(
+                "  {}",
+                tr("→ архив перезаписан", "→ archive rewritten").green()
+            ));
+        } else {
+            log.detail(format!(
+                "  {}",
+                tr_format!(
+                    "→ архив записан в {}",
+                    "→ archive written to {}",
+                    output.display()
+                )
+                .green()
+            ));
+        }
+        Status::Fixed
+    }
+
+    /// Обработка WMA/ASF: lofty их не читает, теги разбираются модулем asf
+    fn process_asf(
+        &self,
+        path: &Path,
+        changes: Option<&[PlanChange]>,
+        log: &mut FileLog,
+    ) -> Status {
+        let fix = match changes {
+            Some(changes) => self.asf_fixer.planned(path, changes).map(Some),
+            None => self.asf_fixer.scan(path),
+        };
+        let mut fix = match fix {
+            Ok(Some(fix)) => fix,
+            Ok(None) => return Status::Clean,
+            Err(e) => return fail(log, e),
+        };
+        let review: Vec<_> = fix
+            .fixes
+            .extract_if(.., |f| f.confidence < self.min_confidence)
+            .collect();
+
+        for field in &fix.fixes {
+            log.detail(format!("  {} {}", "FIX".cyan(), asf_fix_line(field)));
+        }
+        log.report().fixes = fix.fixes.iter().map(|f| asf_report(f, false)).collect();
+        self.defer(
+            path,
+            log,
+            review.iter().map(|f| asf_report(f, true)).collect(),
+        );
+        if fix.fixes.is_empty() {
+            return Status::Review;
+        }
+
+        let status = self.decide(path, log);
+        if status != Status::Fixed {
+            return status;
+        }
+
+        let output = match self.output_copy(path) {
+            Ok(output) => output,
+            Err(e) => return fail(log, e),
+        };
+        let mtime = self.mtime(path);
+        if let Err(e) = self.asf_fixer.apply(&output, fix, &self.backup_manager) {
+            return fail(log, e);
+        }
+        self.restore_mtime(&output, mtime, log);
+
+        log_tags_updated(path, &output, log);
+        Status::Fixed
+    }
+}
+
+/// Исправление строки текстового файла в кодировке `encoding` для вывода
+fn text_change_line(change: &LineChange, encoding: &'static Encoding) -> String {
+    let details = match (change.mojibake, change.confidence) {
+        (Some(mojibake), Some(confidence)) => {
+            format!(" {}", format!("({mojibake}, {confidence}%)").dimmed())
+        }
+        // Перекодирование: показываем, из какой кодировки
+        (None, _) if encoding != UTF_8 => {
+            format!(" {}", format!("({})", encoding_name(encoding)).dimmed())
+        }
+        _ => String::new(),
+    };
+    format!(
+        "'{}' -> '{}'{details}",
+        change.before.trim(),
+        change.after.trim()
+    )
+}
+
+fn text_report(change: &LineChange, encoding: &'static Encoding, review: bool) -> ReportFix {
+    ReportFix {
+        field: format!("line {}", change.line),
+        key: None,
+        line: Some(change.line),
+        before: change.before.clone(),
+        after: change.after.clone(),
+        encoding: Some(encoding_name(
+            change.mojibake.map_or(encoding, |m| m.source),
+        )),
+        tag: None,
+        misread_as: change.mojibake.map(|m| encoding_name(m.misread)),
+        confidence: change.confidence,
+        review,
+        member: None,
+    }
+}
+
+/// Исправление поля тега для вывода
+fn audio_fix_line(field: &FieldFix) -> String {
+    format!(
+        "{}: '{}' -> '{}' {}",
+        field_name(&field.key, field.tag_type),
+        field.original,
+        field.fixed,
+        format!(
+            "({}{}, {}%)",
+            fix_source(field.mojibake, field.yo, field.transliteration),
+            tag_type_name(field.tag_type),
+            field.confidence
+        )
+        .dimmed()
+    )
+}
+
+/// Откуда взялось исправленное значение поля: кракозябры, замена `ё`, транслитерация
+fn fix_source(
+    mojibake: Option<Mojibake>,
+    yo: Option<Yo>,
+    transliteration: Option<Scheme>,
+) -> String {
+    let mut source = String::new();
+    if let Some(mojibake) = mojibake {
+        source.push_str(&format!("{mojibake}, "));
+    }
+    match yo {
+        Some(Yo::ToE) => source.push_str("ё → е, "),
+        Some(Yo::Restore) => source.push_str(tr("е → ё по словарю, ", "е → ё by dictionary, ")),
+        None => {}
+    }
+    if let Some(scheme) = transliteration {
+        source.push_str(&format!(
+            "{} {}, ",
+            tr("транслит", "transliteration"),
+            scheme.name()
+        ));
+    }
+    source
+}
+
+/// Перезапись фреймов ID3v2 из Latin-1 (--reencode-frames) для вывода
+fn reencode_line() -> String {
+    tr(
+        "ID3v2: фреймы в Latin-1 переписываются в UTF-8/UTF-16",
+        "ID3v2: Latin-1 frames are rewritten as UTF-8/UTF-16",
+    )
+    .to_string()
+}
+
+/// Пересборка или удаление ID3v1 (--id3v1) для вывода
+fn id3v1_line(fix: &Id3v1Fix, policy: Id3v1Policy) -> String {
+    match fix {
+        Id3v1Fix::Rebuild(tag) => {
+            let encoding = match policy {
+                Id3v1Policy::Translit => tr("транслит", "transliteration"),
+                _ => "cp1251",
+            };
+            tr_format!(
+                "ID3v1: пересобирается из {} ({encoding})",
+                "ID3v1: rebuilt from {} ({encoding})",
+                tag_type_name(tag.tag_type())
+            )
+        }
+        Id3v1Fix::Strip => tr("ID3v1: удаляется", "ID3v1: removed").to_string(),
+    }
+}
+
+fn audio_report(field: &FieldFix, review: bool) -> ReportFix {
+    ReportFix {
+        field: field_name(&field.key, field.tag_type),
+        key: field.key.map_key(field.tag_type, true).map(str::to_string),
+        line: None,
+        before: field.original.clone(),
+        after: field.fixed.clone(),
+        encoding: field.mojibake.map(|m| encoding_name(m.source)),
+        misread_as: field.mojibake.map(|m| encoding_name(m.misread)),
+        tag: Some(tag_type_name(field.tag_type).to_string()),
+        confidence: Some(field.confidence),
+        review,
+        member: None,
+    }
+}
+
+/// Исправление атрибута ASF для вывода
+fn asf_fix_line(field: &AsfFieldFix) -> String {
+    format!(
+        "{}: '{}' -> '{}' {}",
+        field.name,
+        field.original,
+        field.fixed,
+        format!(
+            "({}asf, {}%)",
+            fix_source(field.mojibake, field.yo, field.transliteration),
+            field.confidence
+        )
+        .dimmed()
+    )
+}
+
+fn asf_report(field: &AsfFieldFix, review: bool) -> ReportFix {
+    ReportFix {
+        field: field.name.clone(),
+        key: Some(field.name.clone()),
+        line: None,
+        before: field.original.clone(),
+        after: field.fixed.clone(),
+        encoding: field.mojibake.map(|m| encoding_name(m.source)),
+        misread_as: field.mojibake.map(|m| encoding_name(m.misread)),
+        tag: Some("asf".to_string()),
+        confidence: Some(field.confidence),
+        review,
+        member: None,
+    }
+}
+
+/// Исправление содержимого файла внутри ZIP-архива
+enum MemberFix {
+    Text(TextFix),
+    Audio(AudioFix),
+    Asf(AsfFix),
+}
+
+/// Число жёстких ссылок на файл; там, где его не узнать, — 1
+#[cfg(unix)]
+fn link_count(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).map_or(1, |m| m.nlink())
+}
+
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> u64 {
+    1
+}
+
+fn log_tags_updated(path: &Path, output: &Path, log: &mut FileLog) {
+    if output == path {
+        log.detail(format!(
+            "  {}",
+            tr("→ теги обновлены", "→ tags updated").green()
+        ));
+    } else {
+        log.detail(format!(
+            "  {}",
+            tr_format!(
+                "→ теги обновлены в {}",
+                "→ tags updated in {}",
+                output.display()
+            )
+            .green()
+        ));
+    }
+}
+
+/// Запись плана по отчёту о файле; путь — относительно корня обхода
+fn plan_file(root: &Path, report: &FileReport) -> PlanFile {
+    PlanFile {
+        path: report
+            .path
+            .strip_prefix(root)
+            .unwrap_or(&report.path)
+            .to_path_buf(),
+        format: report.format.clone(),
+        changes: report
+            .fixes
+            .iter()
+            // Файлы внутри архивов в план не попадают: он применяется к отдельным файлам
+            .filter(|fix| !fix.review && fix.member.is_none())
+            .map(|fix| PlanChange {
+                field: fix.field.clone(),
+                tag: fix.tag.clone(),
+                key: fix.key.clone(),
+                line: fix.line,
+                before: fix.before.clone(),
+                after: fix.after.clone(),
+                encoding: fix.encoding.clone(),
+                misread_as: fix.misread_as.clone(),
+                confidence: fix.confidence,
+            })
+            .collect(),
+    }
+}
+
+/// Разбор решения детектора (-vv): исходная оценка, порог и лучшие варианты
+fn log_trace(log: &mut FileLog, trace: &Trace) {
+    /// Сколько лучших вариантов показывать
+    const SHOWN: usize = 3;
+
+    let verdict = match &trace.fixed {
+        Some(fixed) => format!("→ '{fixed}'"),
+        None => tr("не исправлено", "not fixed").to_string(),
+    };
+    log.out(format!(
+        "  {} '{}': {verdict} {}",
+        "DETECT".magenta(),
+        trace.text,
+        tr_format!(
+            "(оценка исходного текста {:.2}, порог {:.2})",
+            "(original text score {:.2}, threshold {:.2})",
+            trace.original_score,
+            trace.threshold
+        )
+        .dimmed()
+    ));
+    for candidate in trace.candidates.iter().take(SHOWN) {
+        let chain: Vec<String> = candidate.chain.iter().map(|m| m.to_string()).collect();
+        log.out(format!(
+            "      {:.2} {}: '{}'",
+            candidate.score,
+            chain.join(tr(", затем ", ", then ")),
+            candidate.text
+        ));
+    }
+    if trace.candidates.len() > SHOWN {
+        log.out(format!(
+            "      {}",
+            tr_format!(
+                "… ещё вариантов: {}",
+                "… {} more candidates",
+                trace.candidates.len() - SHOWN
+            )
+            .dimmed()
+        ));
+    }
+}
+
+/// Строка `NEAR …` для --show-candidates, если вариант немного не дотянул до порога
+fn log_near_miss(log: &mut FileLog, trace: &Trace) {
+    let Some(candidate) = trace.near_miss() else {
+        return;
+    };
+    let chain: Vec<String> = candidate.chain.iter().map(|m| m.to_string()).collect();
+    log.out(format!(
+        "  {} '{}' -> '{}' {}",
+        "NEAR".cyan(),
+        trace.text,
+        candidate.text,
+        tr_format!(
+            "(оценка {:.2}, порог {:.2}, {})",
+            "(score {:.2}, threshold {:.2}, {})",
+            candidate.score,
+            trace.threshold,
+            chain.join(tr(", затем ", ", then "))
+        )
+        .dimmed()
+    ));
+}
+
+/// Файлы `paths`, сгруппированные по каталогам (альбомам), в порядке путей
+fn albums(paths: impl Iterator<Item = PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut albums: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        albums.entry(dir).or_default().push(path);
+    }
+    albums
+        .into_values()
+        .map(|mut files| {
+            files.sort();
+            files
+        })
+        .collect()
+}
+
+/// Таблица исправлений альбома `dir` (--by-album): файл, поле, было, стало и итог по
+/// кодировкам; `None` — исправлять нечего. Отложенных для проверки исправлений в ней нет:
+/// они показываются в конце общим списком
+fn album_table(dir: &Path, reports: &[FileReport]) -> Option<String> {
+    let mut rows: Vec<[String; 4]> = Vec::new();
+    let mut encodings: BTreeMap<String, usize> = BTreeMap::new();
+    let mut files = 0;
+    for report in reports {
+        let fixes: Vec<&ReportFix> = report.fixes.iter().filter(|f| !f.review).collect();
+        if fixes.is_empty() {
+            continue;
+        }
+        files += 1;
+        let name = report.path.file_name().map_or_else(
+            || report.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let mut previous = None;
+        for fix in fixes {
+            let file = match &fix.member {
+                Some(member) => format!("{name} → {member}"),
+                None => name.clone(),
+            };
+            // Имя файла — только в первой строке его исправлений
+            let shown = if previous.as_ref() == Some(&file) {
+                String::new()
+            } else {
+                file.clone()
+            };
+            previous = Some(file);
+            rows.push([
+                shown,
+                fix.field.clone(),
+                album_cell(&fix.before),
+                album_cell(&fix.after),
+            ]);
+            let encoding = match (&fix.encoding, &fix.misread_as) {
+                (Some(source), Some(misread)) => format!("{source} {} {misread}", tr("как", "as")),
+                (Some(source), None) => source.clone(),
+                _ => continue,
+            };
+            *encodings.entry(encoding).or_default() += 1;
+        }
+    }
+    if rows.is_empty() {
+        return None;
+    }
+
+    let header = [
+        tr("Файл", "File"),
+        tr("Поле", "Field"),
+        tr("Было", "Before"),
+        tr("Стало", "After"),
+    ]
+    .map(str::to_string);
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .chain([&header])
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |row: &[String; 4]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - cell.chars().count())))
+            .collect();
+        format!("  {}", cells.join("  ").trim_end())
+    };
+
+    let mut table = vec![format!(
+        "{} {} {}",
+        "[ALBUM]".bold(),
+        dir.display(),
+        tr_format!(
+            "(файлов с исправлениями: {files})",
+            "(files with fixes: {files})"
+        )
+        .dimmed()
+    )];
+    table.push(line(&header).dimmed().to_string());
+    table.extend(rows.iter().map(line));
+    if !encodings.is_empty() {
+        let encodings: Vec<String> = encodings
+            .iter()
+            .map(|(encoding, count)| format!("{encoding}: {count}"))
+            .collect();
+        table.push(format!("  {}", encodings.join(", ").dimmed()));
+    }
+    Some(table.join("\n"))
+}
+
+/// Значение для таблицы альбома: первая строка, длинное — с многоточием
+fn album_cell(text: &str) -> String {
+    let text = text.trim();
+    let first = text.lines().next().unwrap_or_default();
+    if first.len() == text.len() && first.chars().count() <= ALBUM_CELL_WIDTH {
+        return first.to_string();
+    }
+    let mut cell: String = first.chars().take(ALBUM_CELL_WIDTH - 1).collect();
+    cell.push('…');
+    cell
+}
+
+/// Итог записи альбома: сколько файлов исправлено и пропущено; `None` — ничего не записывалось
+fn album_result(reports: &[FileReport]) -> Option<String> {
+    let count = |status| reports.iter().filter(|r| r.status == status).count();
+    let (fixed, skipped) = (count(Status::Fixed), count(Status::Skipped));
+    if fixed == 0 && skipped == 0 {
+        return None;
+    }
+    let mut result = tr_format!("→ исправлено файлов: {fixed}", "→ files fixed: {fixed}");
+    if skipped > 0 {
+        result += &tr_format!(", пропущено: {skipped}", ", skipped: {skipped}");
+    }
+    Some(format!("  {}", result.green()))
+}
+
+/// Ошибка обработки файла: в stderr и в отчёт
+fn fail(log: &mut FileLog, e: cyrtag_fix::Error) -> Status {
+    log.err(format!("{} {e}", tr("Ошибка", "Error").red()));
+    log.report().fail(format!("{} {e}", tr("Ошибка", "Error")));
+    Status::Error
+}
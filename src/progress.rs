@@ -0,0 +1,121 @@
+//! Журнал хода запуска для --resume: пути обработанных файлов, по строке на файл.
+//!
+//! Журнал `.cyrtag-fix/progress` в корне обработки ведётся во время `fix` и удаляется, когда
+//! запуск доходит до конца. Если запуск прервали (Ctrl-C, перезагрузка), журнал остаётся,
+//! и `fix --resume` пропускает уже обработанные файлы.
+
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Журнал относительно корня обработки
+pub const PROGRESS_FILE: &str = ".cyrtag-fix/progress";
+
+/// Путь журнала для корня `root`
+pub fn progress_path(root: &Path) -> PathBuf {
+    root.join(PROGRESS_FILE)
+}
+
+/// Журнал текущего запуска
+pub struct Progress {
+    root: PathBuf,
+    path: PathBuf,
+    done: HashSet<String>,
+    file: Mutex<File>,
+}
+
+impl Progress {
+    /// Начать журнал в корне `root`; с `resume` — дописывать журнал прерванного запуска
+    /// и пропускать записанные в нём файлы, иначе начать заново
+    pub fn start(root: &Path, resume: bool) -> Result<Self> {
+        let path = progress_path(root);
+        let error = |source| Error::State {
+            path: path.clone(),
+            source,
+        };
+
+        let done = if resume {
+            match fs::read_to_string(&path) {
+                Ok(content) => content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+                Err(e) => return Err(error(e)),
+            }
+        } else {
+            HashSet::new()
+        };
+        let file = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(resume)
+                    .write(true)
+                    .truncate(!resume)
+                    .open(&path)
+            })
+            .map_err(error)?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            path,
+            done,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Сколько файлов обработано в прерванном запуске
+    pub fn resumed(&self) -> usize {
+        self.done.len()
+    }
+
+    fn key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// Обработан ли файл в прерванном запуске
+    pub fn is_done(&self, path: &Path) -> bool {
+        self.done.contains(&self.key(path))
+    }
+
+    /// Записать файл как обработанный
+    pub fn record(&self, path: &Path) -> Result<()> {
+        let line = format!("{}\n", self.key(path));
+        self.file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_all(line.as_bytes())
+            .map_err(|source| Error::State {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// Запуск дошёл до конца: журнал больше не нужен
+    pub fn finish(self) -> Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path).map_err(|source| Error::State {
+            path: self.path.clone(),
+            source,
+        })?;
+        // Каталог .cyrtag-fix, созданный только ради журнала, тоже не нужен
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Интерактивное подтверждение изменений перед записью файла
+
+use colored::*;
+use cyrtag_fix::locale::tr;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Ответ пользователя на предложенные изменения
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Записать изменения
+    Apply,
+    /// Пропустить файл
+    Skip,
+}
+
+/// Слой подтверждения между поиском исправлений и записью
+#[derive(Debug, Default)]
+pub struct Prompter {
+    enabled: bool,
+    apply_all: bool,
+    quit: bool,
+    skipped_dirs: Vec<PathBuf>,
+    /// Ответы на альбом целиком (--by-album): файлы каталога больше не спрашиваются
+    albums: Vec<(PathBuf, Decision)>,
+    /// Идёт предпросмотр альбома: исправления только ищутся, без вопросов и записи
+    preview: bool,
+}
+
+impl Prompter {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// Включено ли интерактивное подтверждение
+    pub fn is_enabled(&self) -> bool {
+        self.enabled && !self.apply_all
+    }
+
+    /// Пользователь попросил завершить работу
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Файл лежит в каталоге, который пользователь попросил пропустить
+    pub fn is_skipped(&self, path: &Path) -> bool {
+        self.skipped_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Идёт ли предпросмотр альбома
+    pub fn is_previewing(&self) -> bool {
+        self.preview
+    }
+
+    /// Начать или закончить предпросмотр альбома
+    pub fn set_preview(&mut self, preview: bool) {
+        self.preview = preview;
+    }
+
+    /// Спросить, применять ли изменения к `path`
+    pub fn confirm(&mut self, path: &Path) -> Decision {
+        if !self.enabled || self.apply_all {
+            return Decision::Apply;
+        }
+        if let Some((_, decision)) = self
+            .albums
+            .iter()
+            .find(|(dir, _)| path.parent() == Some(dir.as_path()))
+        {
+            return *decision;
+        }
+        self.ask(tr("Применить изменения?", "Apply changes?"), path.parent())
+    }
+
+    /// Спросить, применять ли изменения ко всем файлам альбома `dir`; ответ запоминается,
+    /// и файлы каталога потом подтверждаются без вопросов
+    pub fn confirm_album(&mut self, dir: &Path) -> Decision {
+        if !self.enabled || self.apply_all {
+            return Decision::Apply;
+        }
+        let decision = self.ask(
+            tr(
+                "Применить изменения к альбому?",
+                "Apply changes to the album?",
+            ),
+            Some(dir),
+        );
+        self.albums.push((dir.to_path_buf(), decision));
+        decision
+    }
+
+    /// Спросить, приводить ли поле альбома `dir` к значению большинства треков
+    pub fn confirm_normalize(&mut self, dir: &Path) -> Decision {
+        if !self.enabled || self.apply_all {
+            return Decision::Apply;
+        }
+        self.ask(
+            tr(
+                "Привести к значению большинства?",
+                "Apply the majority value?",
+            ),
+            Some(dir),
+        )
+    }
+
+    /// Задать вопрос и разобрать ответ; `dir` — каталог для ответа [s]kip dir
+    fn ask(&mut self, question: &str, dir: Option<&Path>) -> Decision {
+        let stdin = io::stdin();
+        loop {
+            print!(
+                "  {} {} [y]es/[n]o/[a]ll/[q]uit/[s]kip dir: ",
+                "?".yellow().bold(),
+                question.bold()
+            );
+            let _ = io::stdout().flush();
+
+            let mut answer = String::new();
+            match stdin.lock().read_line(&mut answer) {
+                // stdin закрыт — дальше спрашивать некого
+                Ok(0) | Err(_) => {
+                    println!();
+                    self.quit = true;
+                    return Decision::Skip;
+                }
+                Ok(_) => {}
+            }
+
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Decision::Apply,
+                "n" | "no" => return Decision::Skip,
+                "a" | "all" => {
+                    self.apply_all = true;
+                    return Decision::Apply;
+                }
+                "q" | "quit" => {
+                    self.quit = true;
+                    return Decision::Skip;
+                }
+                "s" | "skip" => {
+                    if let Some(dir) = dir {
+                        self.skipped_dirs.push(dir.to_path_buf());
+                    }
+                    return Decision::Skip;
+                }
+                _ => continue,
+            }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Простые регулярные выражения для словаря замен: `.`, `[...]`, `[^...]`, `\d`, `\w`, `\s`,
+//! `^`, `$`, группы `(...)` с `|`, `*`, `+`, `?` и `{n,m}`. В замене `$1`…`$9` — текст
+//! группы, `$0` — всё совпадение, `$$` — сам `$`.
+//!
+//! Поиск — перебором с возвратами по символам: для коротких значений тегов этого хватает
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Char(char),
+    /// `.`
+    Any,
+    /// `[...]`, `\d`, `\w`, `\s`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+        /// Любые буквы и цифры (`\w`): кириллица тоже «слово»
+        alphanumeric: bool,
+    },
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// `(...)`: варианты через `|` и номер группы
+    Group(Vec<Vec<Node>>, usize),
+    /// Повтор от `min` до `max` раз, жадный
+    Repeat(Box<Node>, usize, usize),
+}
+
+/// Скомпилированное выражение
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regex {
+    source: String,
+    /// Всё выражение — группа 0
+    root: Node,
+    groups: usize,
+}
+
+/// Ошибка разбора выражения
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    pub pattern: String,
+    pub message: &'static str,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pattern, self.message)
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let error = |message| RegexError {
+            pattern: pattern.to_string(),
+            message,
+        };
+        let alternatives = parser.alternatives().map_err(error)?;
+        if parser.pos < parser.chars.len() {
+            return Err(error("лишняя `)`"));
+        }
+        Ok(Self {
+            source: pattern.to_string(),
+            root: Node::Group(alternatives, 0),
+            groups: parser.groups + 1,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Заменить все совпадения на `replacement` (с `$1` и т.п.); `None` — совпадений нет
+    pub fn replace_all(&self, text: &str, replacement: &str) -> Option<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::new();
+        let mut found = false;
+        // Конец последнего совпадения: текст до следующего копируется как есть
+        let mut last = 0;
+        let mut pos = 0;
+        while pos <= chars.len() {
+            let Some(groups) = self.find_at(&chars, pos) else {
+                pos += 1;
+                continue;
+            };
+            found = true;
+            let end = groups[0].map_or(pos, |(_, end)| end);
+            out.extend(&chars[last..pos]);
+            expand(replacement, &chars, &groups, &mut out);
+            last = end;
+            // После пустого совпадения поиск идёт со следующего символа
+            pos = if end > pos { end } else { pos + 1 };
+        }
+        found.then(|| {
+            out.extend(&chars[last..]);
+            out
+        })
+    }
+
+    /// Совпадение, начинающееся ровно в `start`: границы всех групп
+    fn find_at(&self, chars: &[char], start: usize) -> Option<Vec<Option<(usize, usize)>>> {
+        let mut matcher = Matcher {
+            text: chars,
+            groups: vec![None; self.groups],
+        };
+        matcher
+            .node(&self.root, start, &mut |_, _| true)
+            .then_some(matcher.groups)
+    }
+}
+
+impl FromStr for Regex {
+    type Err = RegexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Подстановка групп в замену
+fn expand(replacement: &str, chars: &[char], groups: &[Option<(usize, usize)>], out: &mut String) {
+    let mut rest = replacement.chars().peekable();
+    while let Some(c) = rest.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match rest.peek().copied() {
+            Some('$') => {
+                rest.next();
+                out.push('$');
+            }
+            Some(digit @ '0'..='9') => {
+                rest.next();
+                let index = digit as usize - '0' as usize;
+                if let Some(Some((start, end))) = groups.get(index) {
+                    out.extend(&chars[*start..*end]);
+                }
+            }
+            _ => out.push('$'),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Варианты через `|` до `)` или конца выражения
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, &'static str> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, &'static str> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            self.pos += 1;
+            let node = match c {
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '(' => {
+                    self.groups += 1;
+                    let index = self.groups;
+                    let alternatives = self.alternatives()?;
+                    if self.next() != Some(')') {
+                        return Err("нет закрывающей `)`");
+                    }
+                    Node::Group(alternatives, index)
+                }
+                '[' => self.class()?,
+                '\\' => self.escape()?,
+                '*' | '+' | '?' | '{' => return Err("повтор без выражения"),
+                c => Node::Char(c),
+            };
+            let node = self.repeat(node)?;
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    /// Повтор после выражения: `*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`
+    fn repeat(&mut self, node: Node) -> Result<Node, &'static str> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.number().ok_or("ожидалось число в `{}`")?;
+                let max = match self.next() {
+                    Some('}') => min,
+                    Some(',') if self.peek() == Some('}') => {
+                        self.pos += 1;
+                        usize::MAX
+                    }
+                    Some(',') => {
+                        let max = self.number().ok_or("ожидалось число в `{}`")?;
+                        if self.next() != Some('}') {
+                            return Err("нет закрывающей `}`");
+                        }
+                        max
+                    }
+                    _ => return Err("нет закрывающей `}`"),
+                };
+                if max < min {
+                    return Err("в `{n,m}` n больше m");
+                }
+                return Ok(Node::Repeat(Box::new(node), min, max));
+            }
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+        if matches!(node, Node::Start | Node::End) {
+            return Err("повтор без выражения");
+        }
+        Ok(Node::Repeat(Box::new(node), min, max))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    /// После `\`: класс символов или сам символ
+    fn escape(&mut self) -> Result<Node, &'static str> {
+        let c = self.next().ok_or("`\\` в конце выражения")?;
+        Ok(match shorthand(c) {
+            Some(class) => class,
+            None => Node::Char(unescape(c)),
+        })
+    }
+
+    /// После `[`: до `]`, с диапазонами `а-я` и `^` в начале
+    fn class(&mut self) -> Result<Node, &'static str> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut alphanumeric = false;
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("нет закрывающей `]`")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match c {
+                '\\' => {
+                    let c = self.next().ok_or("нет закрывающей `]`")?;
+                    if let Some(Node::Class {
+                        negated: false,
+                        ranges: shorthand,
+                        alphanumeric: letters,
+                    }) = shorthand(c)
+                    {
+                        ranges.extend(shorthand);
+                        alphanumeric |= letters;
+                        continue;
+                    }
+                    unescape(c)
+                }
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let high = match self.next() {
+                    Some('\\') => unescape(self.next().ok_or("нет закрывающей `]`")?),
+                    Some(c) => c,
+                    None => return Err("нет закрывающей `]`"),
+                };
+                if high < low {
+                    return Err("диапазон в `[]` наоборот");
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Ok(Node::Class {
+            negated,
+            ranges,
+            alphanumeric,
+        })
+    }
+}
+
+/// `\d`, `\w`, `\s` и их отрицания `\D`, `\W`, `\S`
+fn shorthand(c: char) -> Option<Node> {
+    let (ranges, alphanumeric) = match c.to_ascii_lowercase() {
+        'd' => (vec![('0', '9')], false),
+        'w' => (vec![('_', '_')], true),
+        's' => (vec![(' ', ' '), ('\t', '\r')], false),
+        _ => return None,
+    };
+    Some(Node::Class {
+        negated: c.is_ascii_uppercase(),
+        ranges,
+        alphanumeric,
+    })
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        c => c,
+    }
+}
+
+/// Продолжение поиска после узла: получает позицию, на которой узел закончился
+type Next<'m, 'a> = dyn FnMut(&mut Matcher<'a>, usize) -> bool + 'm;
+
+struct Matcher<'a> {
+    text: &'a [char],
+    groups: Vec<Option<(usize, usize)>>,
+}
+
+impl<'a> Matcher<'a> {
+    /// Совпадает ли `nodes` с позиции `pos` так, что `next` принимает остаток
+    fn sequence(&mut self, nodes: &[Node], pos: usize, next: &mut Next<'_, 'a>) -> bool {
+        match nodes.split_first() {
+            None => next(self, pos),
+            Some((node, rest)) => self.node(node, pos, &mut |m, end| m.sequence(rest, end, next)),
+        }
+    }
+
+    fn node(&mut self, node: &Node, pos: usize, next: &mut Next<'_, 'a>) -> bool {
+        let current = self.text.get(pos).copied();
+        match node {
+            Node::Char(c) => current == Some(*c) && next(self, pos + 1),
+            Node::Any => current.is_some() && next(self, pos + 1),
+            Node::Class {
+                negated,
+                ranges,
+                alphanumeric,
+            } => {
+                current.is_some_and(|c| {
+                    let inside = (*alphanumeric && c.is_alphanumeric())
+                        || ranges.iter().any(|&(low, high)| (low..=high).contains(&c));
+                    inside != *negated
+                }) && next(self, pos + 1)
+            }
+            Node::Start => pos == 0 && next(self, pos),
+            Node::End => pos == self.text.len() && next(self, pos),
+            Node::Group(alternatives, index) => alternatives.iter().any(|alternative| {
+                self.sequence(alternative, pos, &mut |m, end| {
+                    let previous = m.groups[*index].replace((pos, end));
+                    if next(m, end) {
+                        return true;
+                    }
+                    m.groups[*index] = previous;
+                    false
+                })
+            }),
+            Node::Repeat(inner, min, max) => self.repeat(inner, *min, *max, 0, pos, next),
+        }
+    }
+
+    /// Жадный повтор: сначала ещё одно совпадение `inner`, потом остаток
+    fn repeat(
+        &mut self,
+        inner: &Node,
+        min: usize,
+        max: usize,
+        count: usize,
+        pos: usize,
+        next: &mut Next<'_, 'a>,
+    ) -> bool {
+        if count < max
+            && self.node(inner, pos, &mut |m, end| {
+                // Пустое совпадение повторять бессмысленно: иначе `(a*)*` зациклится
+                (end != pos || count < min) && m.repeat(inner, min, max, count + 1, end, next)
+            })
+        {
+            return true;
+        }
+        count >= min && next(self, pos)
+    }
+}
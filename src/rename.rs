@@ -0,0 +1,168 @@
+//! Исправление кракозябр в именах файлов
+
+use crate::backup;
+use crate::detect::Detector;
+use crate::error::{Error, Result};
+use crate::locale::tr;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Журнал переименований относительно корня обработанного каталога
+pub const JOURNAL_FILE: &str = ".cyrtag-fix/renames.tsv";
+/// Журнал прежних версий: его записи ещё читаются, а при перезаписи переносятся в новый
+const LEGACY_JOURNAL_FILE: &str = ".cyrtag-fix-renames.tsv";
+
+#[derive(Debug, Clone, Default)]
+pub struct Renamer {
+    pub detector: Detector,
+}
+
+impl Renamer {
+    pub fn new(detector: Detector) -> Self {
+        Self { detector }
+    }
+
+    /// Новый путь файла, если в его имени (без расширения) найдены кракозябры
+    pub fn fixed_path(&self, path: &Path) -> Option<PathBuf> {
+        let stem = path.file_stem()?.to_str()?;
+        let fixed = self.detector.fix(stem)?;
+
+        let file_name = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!("{fixed}.{ext}"),
+            None => fixed,
+        };
+        Some(path.with_file_name(file_name))
+    }
+
+    /// Новый путь каталога, если в его имени найдены кракозябры
+    pub fn fixed_dir_path(&self, path: &Path) -> Option<PathBuf> {
+        let name = path.file_name()?.to_str()?;
+        let fixed = self.detector.fix(name)?;
+        Some(path.with_file_name(fixed))
+    }
+
+    /// Переименование файла вместе с его .bak бэкапами (`.bak`, `.bak.1`…); существующие
+    /// файлы не перезаписываются
+    pub fn apply(&self, from: &Path, to: &Path) -> Result<()> {
+        rename_no_clobber(from, to)?;
+
+        if let (Some(from_bak), Some(to_bak)) = (backup::backup_path(from), backup::backup_path(to))
+        {
+            for (path, target) in backup::moved_backups(&from_bak, &to_bak) {
+                rename_no_clobber(&path, &target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rename_no_clobber(from: &Path, to: &Path) -> Result<()> {
+    let rename_error = |source| Error::Rename {
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+        source,
+    };
+
+    if to.exists() {
+        return Err(rename_error(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            tr("файл уже существует", "file already exists"),
+        )));
+    }
+    fs::rename(from, to).map_err(rename_error)
+}
+
+/// Журнал выполненных переименований (`старый путь<TAB>новый путь` на строку) для отката
+#[derive(Debug, Clone)]
+pub struct RenameJournal {
+    path: PathBuf,
+    legacy: PathBuf,
+}
+
+impl RenameJournal {
+    /// Журнал в каталоге `dir`
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            path: dir.join(JOURNAL_FILE),
+            legacy: dir.join(LEGACY_JOURNAL_FILE),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn error(&self, source: io::Error) -> Error {
+        self.error_at(&self.path, source)
+    }
+
+    fn error_at(&self, path: &Path, source: io::Error) -> Error {
+        Error::Journal {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Дописать переименование в журнал
+    pub fn record(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut file = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+            })
+            .map_err(|e| self.error(e))?;
+        // Абсолютные пути: по журналу обновляются пути в плейлистах
+        let from = std::path::absolute(from).map_err(|e| self.error(e))?;
+        let to = std::path::absolute(to).map_err(|e| self.error(e))?;
+        writeln!(file, "{}\t{}", from.display(), to.display()).map_err(|e| self.error(e))
+    }
+
+    /// Записи журнала в порядке выполнения: сначала из журнала прежних версий, затем
+    /// из нового; отсутствующий журнал — пустой список
+    pub fn entries(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut content = String::new();
+        for path in [&self.legacy, &self.path] {
+            match fs::read_to_string(path) {
+                Ok(text) => content.push_str(&text),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(self.error_at(path, e)),
+            }
+        }
+
+        Ok(content
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(from, to)| (PathBuf::from(from), PathBuf::from(to)))
+            .collect())
+    }
+
+    /// Перезаписать журнал оставшимися записями; пустой журнал удаляется, а журнал прежних
+    /// версий удаляется всегда: его записи уже среди `entries`
+    pub fn replace(&self, entries: &[(PathBuf, PathBuf)]) -> Result<()> {
+        let remove = |path: &Path| match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(self.error_at(path, e)),
+            _ => Ok(()),
+        };
+        if entries.is_empty() {
+            remove(&self.path)?;
+            return remove(&self.legacy);
+        }
+
+        let content: String = entries
+            .iter()
+            .map(|(from, to)| format!("{}\t{}\n", from.display(), to.display()))
+            .collect();
+        self.path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&self.path, content))
+            .map_err(|e| self.error(e))?;
+        remove(&self.legacy)
+    }
+}
@@ -0,0 +1,264 @@
+//! HTML-отчёт (--report): все исправления одной страницей, сгруппированные по каталогам
+//! альбомов, с фильтрами по формату и уверенности. Страница самодостаточна: стили и скрипт
+//! встроены, внешних файлов не нужно. Значения, которые уже не восстановить (`????`,
+//! U+FFFD), перечислены отдельным разделом в конце: такие теги нужно заполнить заново
+
+use crate::output::{FileReport, Status};
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::tr_format;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; }
+h2 { font-size: 1.1em; margin-top: 2em; border-bottom: 1px solid #ccc; }
+h3 { font-size: 1em; margin: 1em 0 0.3em; }
+.filters { position: sticky; top: 0; background: #fff; padding: 0.5em 0; }
+.filters label { margin-right: 1.5em; }
+table { border-collapse: collapse; width: 100%; }
+td, th { border: 1px solid #ddd; padding: 0.2em 0.5em; text-align: left; vertical-align: top; }
+td.before { color: #a00; }
+td.after { color: #070; }
+tr.review td { background: #fff8e0; }
+.status, .meta { color: #777; font-size: 0.9em; }
+.error { color: #a00; }
+"#;
+
+const SCRIPT: &str = r#"
+function applyFilters() {
+  const format = document.getElementById('format').value;
+  const min = Number(document.getElementById('confidence').value);
+  document.getElementById('confidence-value').textContent = min + '%';
+  document.querySelectorAll('section').forEach(section => {
+    let shown = 0;
+    section.querySelectorAll('.file').forEach(file => {
+      let rows = 0;
+      file.querySelectorAll('tr[data-confidence]').forEach(row => {
+        const c = row.dataset.confidence;
+        const visible = c === '' || Number(c) >= min;
+        row.hidden = !visible;
+        if (visible) rows++;
+      });
+      const visible = (format === '' || file.dataset.format === format)
+        && (rows > 0 || file.dataset.error === 'true');
+      file.hidden = !visible;
+      if (visible) shown++;
+    });
+    section.hidden = shown === 0;
+  });
+}
+document.getElementById('format').addEventListener('change', applyFilters);
+document.getElementById('confidence').addEventListener('input', applyFilters);
+applyFilters();
+"#;
+
+/// Сохранить отчёт о файлах `reports` (пути внутри `root`) в `path`
+pub fn save(
+    path: &Path,
+    root: &Path,
+    title: &str,
+    reports: &[FileReport],
+) -> cyrtag_fix::Result<()> {
+    fs::write(path, render(root, title, reports)).map_err(|source| cyrtag_fix::Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn render(root: &Path, title: &str, reports: &[FileReport]) -> String {
+    let mut albums: BTreeMap<String, Vec<&FileReport>> = BTreeMap::new();
+    for report in reports {
+        if report.fixes.is_empty() && report.error.is_none() {
+            continue;
+        }
+        let dir = report.path.parent().unwrap_or(Path::new(""));
+        let dir = dir.strip_prefix(root).unwrap_or(dir).display().to_string();
+        albums.entry(dir).or_default().push(report);
+    }
+    let formats: BTreeSet<&str> = reports.iter().map(|r| r.format.as_str()).collect();
+    let changes: usize = reports.iter().map(|r| r.fixes.len()).sum();
+    let files: usize = albums.values().map(Vec::len).sum();
+    let dirs = albums.len();
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}: {root}</h1>\n\
+         <p class=\"meta\">{meta}</p>\n",
+        lang = tr("ru", "en"),
+        meta = tr_format!(
+            "{changes} исправлений в {files} файлах, каталогов: {dirs}",
+            "{changes} fixes in {files} files, directories: {dirs}"
+        ),
+        title = escape(title),
+        root = escape(&root.display().to_string()),
+    );
+
+    let _ = write!(
+        html,
+        "<div class=\"filters\">\n<label>{}: <select id=\"format\"><option value=\"\">{}</option>",
+        tr("Формат", "Format"),
+        tr("все", "all")
+    );
+    for format in &formats {
+        let _ = write!(html, "<option>{}</option>", escape(format));
+    }
+    let _ = write!(
+        html,
+        "</select></label>\n<label>{}: \
+         <input id=\"confidence\" type=\"range\" min=\"0\" max=\"100\" value=\"0\"> \
+         <span id=\"confidence-value\">0%</span></label>\n</div>\n",
+        tr("Уверенность не ниже", "Minimum confidence")
+    );
+
+    for (dir, files) in &albums {
+        let name = if dir.is_empty() { "." } else { dir.as_str() };
+        let _ = writeln!(html, "<section>\n<h2>{}</h2>", escape(name));
+        for report in files {
+            render_file(&mut html, report);
+        }
+        html.push_str("</section>\n");
+    }
+
+    render_unrecoverable(&mut html, root, reports);
+    let _ = write!(html, "<script>{SCRIPT}</script>\n</body>\n</html>\n");
+    html
+}
+
+/// Раздел со значениями, которые не восстановить: вне `<section>`, чтобы фильтры его не скрывали
+fn render_unrecoverable(html: &mut String, root: &Path, reports: &[FileReport]) {
+    let mut lost: Vec<(String, &str)> = reports
+        .iter()
+        .flat_map(|report| {
+            let path = report.path.strip_prefix(root).unwrap_or(&report.path);
+            let path = path.display().to_string();
+            report
+                .unrecoverable
+                .iter()
+                .map(move |value| (path.clone(), value.as_str()))
+        })
+        .collect();
+    if lost.is_empty() {
+        return;
+    }
+    lost.sort();
+    let headers = tr(["Файл", "Значение"], ["File", "Value"]);
+    let _ = writeln!(
+        html,
+        "<div class=\"unrecoverable\">\n<h2>{}</h2>\n<p class=\"meta\">{}</p>\n\
+         <table>\n<tr><th>{}</th><th>{}</th></tr>",
+        tr("Не восстановить", "Unrecoverable"),
+        tr(
+            "Исходные байты этих значений заменены на «?» или U+FFFD — заполните теги заново",
+            "The original bytes of these values were replaced with '?' or U+FFFD — re-tag them"
+        ),
+        headers[0],
+        headers[1]
+    );
+    for (path, value) in lost {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"before\">{}</td></tr>",
+            escape(&path),
+            escape(value)
+        );
+    }
+    html.push_str("</table>\n</div>\n");
+}
+
+fn render_file(html: &mut String, report: &FileReport) {
+    let name = report
+        .path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let _ = writeln!(
+        html,
+        "<div class=\"file\" data-format=\"{}\" data-error=\"{}\">\n\
+         <h3>{} <span class=\"status\">{}</span></h3>",
+        escape(&report.format),
+        report.error.is_some(),
+        escape(&name),
+        status_label(report.status),
+    );
+    if let Some(error) = &report.error {
+        let _ = writeln!(html, "<p class=\"error\">{}</p>", escape(error));
+    }
+    if !report.fixes.is_empty() {
+        let headers = tr(
+            ["Поле", "Было", "Стало", "Кодировка", "Уверенность"],
+            ["Field", "Before", "After", "Encoding", "Confidence"],
+        );
+        html.push_str("<table>\n<tr>");
+        for header in headers {
+            let _ = write!(html, "<th>{header}</th>");
+        }
+        html.push_str("</tr>\n");
+        for fix in &report.fixes {
+            let encoding = match (&fix.encoding, &fix.misread_as) {
+                (Some(source), Some(misread)) => {
+                    format!("{source} {} {misread}", tr("как", "as"))
+                }
+                (Some(source), None) => source.clone(),
+                _ => String::new(),
+            };
+            let mut details = vec![encoding];
+            details.extend(fix.tag.clone());
+            details.retain(|d| !d.is_empty());
+            let _ = writeln!(
+                html,
+                "<tr data-confidence=\"{confidence}\"{class}><td>{field}</td>\
+                 <td class=\"before\">{before}</td><td class=\"after\">{after}</td>\
+                 <td>{details}</td><td>{percent}</td></tr>",
+                confidence = fix.confidence.map(|c| c.to_string()).unwrap_or_default(),
+                class = if fix.review { " class=\"review\"" } else { "" },
+                field = escape(&match &fix.member {
+                    Some(member) => format!("{member}: {}", fix.field),
+                    None => fix.field.clone(),
+                }),
+                before = escape(fix.before.trim()),
+                after = escape(fix.after.trim()),
+                details = escape(&details.join(", ")),
+                percent = match fix.confidence {
+                    Some(c) if fix.review => format!("{c}%, {}", tr("на проверку", "for review")),
+                    Some(c) => format!("{c}%"),
+                    None => String::new(),
+                },
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</div>\n");
+}
+
+fn status_label(status: Status) -> &'static str {
+    match status {
+        Status::Clean => tr("без изменений", "unchanged"),
+        Status::Fixed => tr("исправлен", "fixed"),
+        Status::WouldFix => tr("будет исправлен", "would be fixed"),
+        Status::Skipped => tr("пропущен", "skipped"),
+        Status::Review => tr("на проверку", "for review"),
+        Status::Error => tr("ошибка", "error"),
+    }
+}
+
+/// Экранирование текста для HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,106 @@
+//! Список RIFF INFO в WAV
+//!
+//! Кодировка значений INFO нигде не записана: старые программы Windows сохраняли их
+//! в кодировке системы, и lofty такие значения (не UTF-8) молча пропускает, а при записи
+//! тега теряет. Здесь значения читаются из байтов файла: UTF-8 — как есть, остальное —
+//! как cp1252, чтобы cp1251 выглядел так же, как кракозябры в других тегах. Записывается
+//! INFO через lofty, в UTF-8, на прежнее место; остальные чанки не меняются
+
+use encoding_rs::WINDOWS_1252;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Поля первого списка LIST/INFO файла: (код поля, значение) в порядке хранения;
+/// пусто, если это не WAV или списка нет
+pub fn read_info<R: Read + Seek>(file: &mut R) -> Vec<(String, String)> {
+    read_list(file).unwrap_or_default()
+}
+
+fn read_list<R: Read + Seek>(file: &mut R) -> Option<Vec<(String, String)>> {
+    let mut header = [0; 12];
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+        return None;
+    }
+    let end = 8 + u64::from(u32::from_le_bytes(header[4..8].try_into().unwrap()));
+    let mut offset = 12;
+    while offset + 8 <= end {
+        let (id, size) = chunk_header(file, offset)?;
+        let mut kind = [0; 4];
+        if &id == b"LIST" && size >= 4 && file.read_exact(&mut kind).is_ok() && &kind == b"INFO" {
+            // размер не проверен: не выделять под список больше, чем осталось в RIFF
+            if u64::from(size) > end - offset - 8 {
+                return None;
+            }
+            let mut body = vec![0; size as usize - 4];
+            file.read_exact(&mut body).ok()?;
+            return Some(parse_info(&body));
+        }
+        // чанки выравниваются по чётной границе
+        offset += 8 + u64::from(size) + u64::from(size % 2);
+    }
+    None
+}
+
+fn chunk_header<R: Read + Seek>(file: &mut R, offset: u64) -> Option<([u8; 4], u32)> {
+    let mut header = [0; 8];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut header).ok()?;
+    let size = u32::from_le_bytes(header[4..].try_into().unwrap());
+    Some((header[..4].try_into().unwrap(), size))
+}
+
+/// Поля из содержимого LIST после `INFO`; обрезанное поле в конце отбрасывается
+fn parse_info(body: &[u8]) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while let Some(header) = body.get(pos..pos + 8) {
+        let size = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        let Some(value) = body.get(pos + 8..pos + 8 + size) else {
+            break;
+        };
+        if let Ok(id) = std::str::from_utf8(&header[..4]) {
+            fields.push((id.to_string(), decode(value)));
+        }
+        pos += 8 + size + size % 2;
+    }
+    fields
+}
+
+/// Значение без завершающих нулей: UTF-8, а если это не UTF-8 — cp1252
+fn decode(bytes: &[u8]) -> String {
+    let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let bytes = &bytes[..end];
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn wav(list_size: u32, info: &[u8]) -> Vec<u8> {
+        let mut body = b"WAVELIST".to_vec();
+        body.extend(list_size.to_le_bytes());
+        body.extend(b"INFO");
+        body.extend(info);
+        let mut out = b"RIFF".to_vec();
+        out.extend((body.len() as u32).to_le_bytes());
+        out.extend(body);
+        out
+    }
+
+    #[test]
+    fn list_size_beyond_riff_is_ignored() {
+        let info = b"INAM\x04\0\0\0\xcb\xfc\xe2\0";
+        let fields = read_info(&mut Cursor::new(wav(4 + info.len() as u32, info)));
+        assert_eq!(fields, [("INAM".to_string(), "Ëüâ".to_string())]);
+        assert!(read_info(&mut Cursor::new(wav(u32::MAX - 8, info))).is_empty());
+    }
+}
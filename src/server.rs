@@ -0,0 +1,440 @@
+//! Оповещение медиасервера (--notify-server): после запуска, изменившего файлы, Jellyfin,
+//! Plex или Navidrome перечитывает каталоги с исправленными файлами, и новые названия видны
+//! без ручного пересканирования библиотеки.
+//!
+//! Запросы идут по HTTP/1.0 без сторонних библиотек, поэтому HTTPS не поддерживается: нужен
+//! http-адрес сервера в локальной сети (обычно http://host:8096 у Jellyfin,
+//! http://host:32400 у Plex, http://host:4533 у Navidrome)
+
+use clap::ValueEnum;
+use cyrtag_fix::json::Json;
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::tr_format;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Plex отвечает на запрос обновления сразу, но большой ответ со списком библиотек может
+/// идти долго
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Медиасервер, которому сообщить об исправленных файлах
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaServer {
+    /// Jellyfin или Emby: обновляются только каталоги с исправленными файлами
+    Jellyfin,
+    /// Plex: частичное сканирование каталогов в библиотеках, куда они входят
+    Plex,
+    /// Navidrome и другие серверы Subsonic API: сканирование всей библиотеки
+    Navidrome,
+}
+
+impl MediaServer {
+    pub fn name(self) -> &'static str {
+        match self {
+            MediaServer::Jellyfin => "Jellyfin",
+            MediaServer::Plex => "Plex",
+            MediaServer::Navidrome => "Navidrome",
+        }
+    }
+}
+
+/// Соответствие путей --notify-path-map: каталог `local` на этой машине сервер видит
+/// как `remote` (например, сервер в Docker с библиотекой в /music)
+#[derive(Debug, Clone)]
+pub struct PathMap {
+    local: PathBuf,
+    remote: String,
+}
+
+impl PathMap {
+    /// Путь каталога `dir` так, как его видит сервер
+    fn remote(&self, dir: &Path) -> String {
+        let Ok(rest) = dir.strip_prefix(&self.local) else {
+            return dir.display().to_string();
+        };
+        let mut remote = self.remote.trim_end_matches('/').to_string();
+        for component in rest.components() {
+            if let Component::Normal(name) = component {
+                remote.push('/');
+                remote.push_str(&name.to_string_lossy());
+            }
+        }
+        if remote.is_empty() {
+            remote.push('/');
+        }
+        remote
+    }
+}
+
+impl FromStr for PathMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((local, remote)) = s
+            .split_once('=')
+            .filter(|(l, r)| !l.is_empty() && !r.is_empty())
+        else {
+            return Err(tr(
+                "ожидалось ЛОКАЛЬНЫЙ=НА_СЕРВЕРЕ, например ~/Music=/music",
+                "expected LOCAL=REMOTE, for example ~/Music=/music",
+            )
+            .to_string());
+        };
+        let local = std::path::absolute(local).map_err(|e| e.to_string())?;
+        Ok(Self {
+            local,
+            remote: remote.to_string(),
+        })
+    }
+}
+
+/// Адрес сервера: http://host[:port][/путь], путь — для сервера за обратным прокси
+#[derive(Debug, Clone)]
+pub struct Url {
+    host: String,
+    port: u16,
+    base: String,
+}
+
+impl FromStr for Url {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("https://") {
+            return Err(tr(
+                "HTTPS не поддерживается, укажите http-адрес сервера в локальной сети",
+                "HTTPS is not supported, use the server's plain http address on the local network",
+            )
+            .to_string());
+        }
+        let Some(rest) = s.strip_prefix("http://") else {
+            return Err(tr_format!(
+                "адрес должен начинаться с http://: {s}",
+                "the address must start with http://: {s}"
+            ));
+        };
+        let (authority, base) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        // Порт после последнего двоеточия, кроме двоеточий адреса IPv6 в [скобках]
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => (
+                host,
+                port.parse()
+                    .map_err(|_| tr_format!("неверный порт: {port}", "invalid port: {port}"))?,
+            ),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(tr_format!(
+                "в адресе нет сервера: {s}",
+                "no host in the address: {s}"
+            ));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            base: base.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl Url {
+    /// Адрес этой же машины: запросы к нему не уходят в сеть открытым текстом
+    pub fn is_loopback(&self) -> bool {
+        self.host.eq_ignore_ascii_case("localhost")
+            || self.host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+    }
+}
+
+/// Настроенный медиасервер
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    pub server: MediaServer,
+    url: Url,
+    token: String,
+    user: Option<String>,
+    path_map: Option<PathMap>,
+}
+
+impl Notifier {
+    /// Проверить настройки до начала обработки, а не после неё
+    pub fn new(
+        server: MediaServer,
+        url: Option<&str>,
+        token: Option<&str>,
+        user: Option<&str>,
+        path_map: Option<PathMap>,
+    ) -> Result<Self, String> {
+        let url = url
+            .ok_or_else(|| {
+                tr(
+                    "для --notify-server нужен адрес сервера (--notify-url)",
+                    "--notify-server needs the server address (--notify-url)",
+                )
+                .to_string()
+            })?
+            .parse()?;
+        let token = token.ok_or_else(|| {
+            match server {
+                MediaServer::Jellyfin => tr(
+                    "для Jellyfin нужен API-ключ (--notify-token)",
+                    "Jellyfin needs an API key (--notify-token)",
+                ),
+                MediaServer::Plex => tr(
+                    "для Plex нужен X-Plex-Token (--notify-token)",
+                    "Plex needs an X-Plex-Token (--notify-token)",
+                ),
+                MediaServer::Navidrome => tr(
+                    "для Navidrome нужен пароль (--notify-token)",
+                    "Navidrome needs a password (--notify-token)",
+                ),
+            }
+            .to_string()
+        })?;
+        if server == MediaServer::Navidrome && user.is_none() {
+            return Err(tr(
+                "для Navidrome нужен пользователь (--notify-user)",
+                "Navidrome needs a user name (--notify-user)",
+            )
+            .to_string());
+        }
+        Ok(Self {
+            server,
+            url,
+            token: token.to_string(),
+            user: user.map(str::to_string),
+            path_map,
+        })
+    }
+
+    /// Попросить сервер перечитать каталоги `dirs` (абсолютные пути на этой машине)
+    pub fn notify(&self, dirs: &[PathBuf]) -> Result<(), String> {
+        let remote: Vec<String> = dirs
+            .iter()
+            .map(|dir| match &self.path_map {
+                Some(map) => map.remote(dir),
+                None => dir.display().to_string(),
+            })
+            .collect();
+        match self.server {
+            MediaServer::Jellyfin => self.notify_jellyfin(&remote),
+            MediaServer::Plex => self.notify_plex(&remote),
+            MediaServer::Navidrome => self.notify_navidrome(),
+        }
+    }
+
+    /// POST /Library/Media/Updated: Jellyfin перечитывает только перечисленные каталоги
+    fn notify_jellyfin(&self, dirs: &[String]) -> Result<(), String> {
+        let updates = dirs
+            .iter()
+            .map(|dir| {
+                Json::object([
+                    ("Path", Json::from(dir.as_str())),
+                    ("UpdateType", Json::from("Modified")),
+                ])
+            })
+            .collect();
+        let body = Json::object([("Updates", Json::Array(updates))]).to_string();
+        self.request(
+            "POST",
+            "/Library/Media/Updated",
+            &[("X-Emby-Token", &self.token)],
+            Some(("application/json", &body)),
+        )?;
+        Ok(())
+    }
+
+    /// Частичное сканирование Plex: для каждого каталога ищется библиотека, в папки которой
+    /// он входит, и запрашивается /library/sections/<id>/refresh?path=…
+    fn notify_plex(&self, dirs: &[String]) -> Result<(), String> {
+        let headers = [
+            ("X-Plex-Token", self.token.as_str()),
+            ("Accept", "application/json"),
+        ];
+        let listing = self.request("GET", "/library/sections", &headers, None)?;
+        let listing = Json::parse(&listing).map_err(|e| e.to_string())?;
+        let sections: Vec<(&str, Vec<&str>)> = listing
+            .get("MediaContainer")
+            .and_then(|c| c.get("Directory"))
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|section| {
+                let key = section.get("key")?.as_str()?;
+                let locations = section
+                    .get("Location")
+                    .and_then(Json::as_array)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|l| l.get("path")?.as_str())
+                    .collect();
+                Some((key, locations))
+            })
+            .collect();
+
+        let mut outside = Vec::new();
+        for dir in dirs {
+            let section = sections
+                .iter()
+                .find(|(_, locations)| locations.iter().any(|location| within(dir, location)));
+            let Some((key, _)) = section else {
+                outside.push(dir.as_str());
+                continue;
+            };
+            let path = format!("/library/sections/{key}/refresh?path={}", encode(dir));
+            self.request("GET", &path, &headers, None)?;
+        }
+        if !outside.is_empty() {
+            return Err(tr_format!(
+                "каталоги не входят ни в одну библиотеку Plex (нужен --notify-path-map?): {}",
+                "directories are not in any Plex library (missing --notify-path-map?): {}",
+                outside.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Subsonic API startScan: Navidrome не умеет сканировать отдельные каталоги, но при
+    /// обычном сканировании перечитывает только изменившиеся файлы
+    fn notify_navidrome(&self) -> Result<(), String> {
+        let password: String = self.token.bytes().map(|b| format!("{b:02x}")).collect();
+        let path = format!(
+            "/rest/startScan?u={}&p=enc:{password}&v=1.16.1&c=cyrtag-fix&f=json",
+            encode(self.user.as_deref().unwrap_or_default())
+        );
+        let answer = self.request("GET", &path, &[], None)?;
+        let answer = Json::parse(&answer).map_err(|e| e.to_string())?;
+        let answer = answer.get("subsonic-response");
+        if answer.and_then(|a| a.get("status")).and_then(Json::as_str) == Some("ok") {
+            return Ok(());
+        }
+        Err(answer
+            .and_then(|a| a.get("error"))
+            .and_then(|e| e.get("message"))
+            .and_then(Json::as_str)
+            .unwrap_or(tr(
+                "неожиданный ответ сервера",
+                "unexpected server response",
+            ))
+            .to_string())
+    }
+
+    /// Запрос HTTP/1.0 (без постоянных соединений и chunked-ответов); возвращает тело ответа
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<(&str, &str)>,
+    ) -> Result<String, String> {
+        http_request(&self.url, method, path, headers, body)
+    }
+}
+
+/// Запрос `method` к `url` с путём `path` (от базового пути адреса); тело ответа, если
+/// сервер ответил 2xx
+pub fn http_request(
+    url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<(&str, &str)>,
+) -> Result<String, String> {
+    let addrs = (url.host.as_str(), url.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("{}: {e}", url.host))?;
+    let mut last_error = None;
+    let mut stream = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(s) => {
+                stream = Some(s);
+                break;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let mut stream = stream.ok_or_else(|| {
+        let reason = last_error.map_or_else(String::new, |e| format!(": {e}"));
+        format!("{}:{}{reason}", url.host, url.port)
+    })?;
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+
+    let mut request = format!(
+        "{method} {}{path} HTTP/1.0\r\nHost: {}:{}\r\nUser-Agent: cyrtag-fix/{} ( https://github.com/NikkoFox/cyrtag-fixer )\r\n",
+        url.base,
+        url.host,
+        url.port,
+        env!("CARGO_PKG_VERSION")
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if let Some((content_type, body)) = body {
+        request.push_str(&format!(
+            "Content-Type: {content_type}\r\nContent-Length: {}\r\n",
+            body.len()
+        ));
+    }
+    request.push_str("\r\n");
+    if let Some((_, body)) = body {
+        request.push_str(body);
+    }
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).map_err(|e| e.to_string())?;
+    let raw = String::from_utf8_lossy(&raw);
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| tr("неожиданный ответ сервера", "unexpected server response"))?;
+    if !(200..300).contains(&status) {
+        let reason = match status {
+            401 | 403 => tr(" (неверный токен?)", " (wrong token?)"),
+            // Перенаправлений не следуем: обычно это переход на https, которого здесь нет
+            301 | 302 | 307 | 308 => tr(
+                " (перенаправление, скорее всего на https: нужен http-адрес или локальный прокси)",
+                " (redirect, most likely to https: a plain http address or a local proxy is needed)",
+            ),
+            _ => "",
+        };
+        return Err(tr_format!(
+            "сервер ответил {status} на {method} {path}{reason}",
+            "the server answered {status} to {method} {path}{reason}",
+            path = path.split('?').next().unwrap_or(path)
+        ));
+    }
+    Ok(body.to_string())
+}
+
+/// Входит ли каталог `dir` в каталог `location` (пути сервера)
+fn within(dir: &str, location: &str) -> bool {
+    let location = location.trim_end_matches(['/', '\\']);
+    dir.strip_prefix(location)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+}
+
+/// Процентное кодирование значения параметра запроса
+pub fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,263 @@
+//! Состояние повторных запусков (--incremental): отпечаток каждого проверенного файла
+//! (размер и время изменения) вместе с итогом проверки.
+//!
+//! Состояние хранится в `.cyrtag-fix/state/<ключ>.tsv` в корне обработки, по файлу на каждый
+//! набор настроек: после смены языков, порогов или полей файлы снова проверяются целиком.
+//! Строка файла — `размер<TAB>время в нс<TAB>итог<TAB>путь`; записи дописываются сразу после
+//! проверки файла, поэтому прерванный запуск тоже сохраняет сделанное, а в конце запуска
+//! файл переписывается без повторов и записей об удалённых файлах.
+//!
+//! С --mark тот же отпечаток с итогом пишется в расширенный атрибут самого файла: отметка
+//! переезжает вместе с ним, и после слияния библиотек уже проверенные файлы не читаются
+//! заново, хотя в состоянии нового корня их нет.
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::xattr;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Каталог состояний относительно корня обработки
+pub const STATE_DIR: &str = ".cyrtag-fix/state";
+const STATE_EXTENSION: &str = "tsv";
+
+/// Расширенный атрибут с отметкой о проверке (--mark)
+pub const MARK_ATTRIBUTE: &str = "user.cyrtag-fix";
+
+/// Отпечаток файла: если он не изменился, файл не изменился с прошлой проверки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub size: u64,
+    /// Время изменения в наносекундах от начала эпохи Unix
+    pub modified: u128,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// Запись о проверенном файле
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    fingerprint: Fingerprint,
+    result: String,
+}
+
+/// Состояние для корня `root` и одного набора настроек
+pub struct State {
+    root: PathBuf,
+    path: PathBuf,
+    entries: Mutex<HashMap<String, Entry>>,
+    file: Mutex<File>,
+}
+
+impl State {
+    /// Загрузить состояние для корня `root` и настроек `settings` (любая строка, которая
+    /// меняется вместе с настройками) и открыть его для записи
+    pub fn open(root: &Path, settings: &str) -> Result<Self> {
+        let dir = root.join(STATE_DIR);
+        let path = dir.join(format!("{:016x}.{STATE_EXTENSION}", fnv1a(settings)));
+        let error = |source| Error::State {
+            path: path.clone(),
+            source,
+        };
+
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().filter_map(parse_line).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(error(e)),
+        };
+        let file = fs::create_dir_all(&dir)
+            .and_then(|()| OpenOptions::new().create(true).append(true).open(&path))
+            .map_err(error)?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            path,
+            entries: Mutex::new(entries),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn error(&self, source: io::Error) -> Error {
+        Error::State {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    fn key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// Не изменился ли файл с прошлой проверки
+    pub fn is_unchanged(&self, path: &Path) -> bool {
+        let Ok(fingerprint) = Fingerprint::of(path) else {
+            return false;
+        };
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&self.key(path))
+            .is_some_and(|entry| entry.fingerprint == fingerprint)
+    }
+
+    /// Запомнить текущий отпечаток файла с итогом проверки `result`
+    pub fn record(&self, path: &Path, result: &str) -> Result<()> {
+        let fingerprint = Fingerprint::of(path).map_err(|e| self.error(e))?;
+        let key = self.key(path);
+        let line = format_line(&key, fingerprint, result);
+        self.file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_all(line.as_bytes())
+            .map_err(|e| self.error(e))?;
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                key,
+                Entry {
+                    fingerprint,
+                    result: result.to_string(),
+                },
+            );
+        Ok(())
+    }
+
+    /// Переписать файл состояния: по записи на файл, без записей об удалённых файлах
+    pub fn compact(&self) -> Result<()> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut keys: Vec<&String> = entries
+            .keys()
+            .filter(|key| self.root.join(key).exists())
+            .collect();
+        keys.sort();
+        let content: String = keys
+            .into_iter()
+            .map(|key| {
+                let entry = &entries[key];
+                format_line(key, entry.fingerprint, &entry.result)
+            })
+            .collect();
+        // Блокировка не даёт дописать запись во время замены; после замены дескриптор
+        // указывает на старый файл, поэтому файл открывается заново
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        atomic::write(&self.path, content.as_bytes())?;
+        *file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+}
+
+/// Отметки о проверке в атрибутах файлов для одного набора настроек. Значение атрибута —
+/// `ключ настроек размер время итог`: отметка с другими настройками или от изменённого
+/// файла не считается
+pub struct Marker {
+    key: String,
+    /// Сообщалось ли уже, что файловая система не поддерживает атрибуты
+    unsupported: AtomicBool,
+}
+
+impl Marker {
+    /// Отметки для настроек `settings` (как у [`State::open`])
+    pub fn new(settings: &str) -> Self {
+        Self {
+            key: format!("{:016x}", fnv1a(settings)),
+            unsupported: AtomicBool::new(false),
+        }
+    }
+
+    /// Есть ли у файла отметка с этими настройками и не изменился ли он с тех пор
+    pub fn is_unchanged(&self, path: &Path) -> bool {
+        let Some(value) = xattr::get(path, MARK_ATTRIBUTE) else {
+            return false;
+        };
+        let mut parts = value.splitn(4, ' ');
+        let (Some(key), Some(size), Some(modified)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        let (Ok(size), Ok(modified)) = (size.parse(), modified.parse()) else {
+            return false;
+        };
+        key == self.key && Fingerprint::of(path).is_ok_and(|f| f == Fingerprint { size, modified })
+    }
+
+    /// Отметить файл с итогом проверки `result`. Атрибут не меняет время изменения файла.
+    /// Если файловая система атрибутов не поддерживает, ошибка возвращается только в первый
+    /// раз за запуск, чтобы не повторять её для каждого файла
+    pub fn record(&self, path: &Path, result: &str) -> Result<()> {
+        let error = |source| Error::Mark {
+            path: path.to_path_buf(),
+            source,
+        };
+        let fingerprint = Fingerprint::of(path).map_err(error)?;
+        let value = format!(
+            "{} {} {} {result}",
+            self.key, fingerprint.size, fingerprint.modified
+        );
+        match xattr::set(path, MARK_ATTRIBUTE, &value) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                if self.unsupported.swap(true, Ordering::Relaxed) {
+                    Ok(())
+                } else {
+                    Err(error(e))
+                }
+            }
+            result => result.map_err(error),
+        }
+    }
+}
+
+fn format_line(key: &str, fingerprint: Fingerprint, result: &str) -> String {
+    format!(
+        "{}\t{}\t{result}\t{key}\n",
+        fingerprint.size, fingerprint.modified
+    )
+}
+
+fn parse_line(line: &str) -> Option<(String, Entry)> {
+    let mut parts = line.splitn(4, '\t');
+    let size = parts.next()?.parse().ok()?;
+    let modified = parts.next()?.parse().ok()?;
+    let result = parts.next()?.to_string();
+    let key = parts.next().filter(|key| !key.is_empty())?.to_string();
+    Some((
+        key,
+        Entry {
+            fingerprint: Fingerprint { size, modified },
+            result,
+        },
+    ))
+}
+
+/// FNV-1a: короткий ключ настроек, одинаковый во всех версиях компилятора
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
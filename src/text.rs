@@ -0,0 +1,912 @@
+//! Текстовые файлы рядом с музыкой (.cue, .m3u, логи рипа, .lrc): перекодирование из cp1251
+//! (KOI8-R, CP866 и других однобайтовых кодировок) в UTF-8 и исправление кракозябр в уже UTF-8 файлах
+
+use crate::atomic;
+use crate::backup::BackupManager;
+use crate::detect::{self, Detection, Detector, Mojibake};
+use crate::error::{Error, Result};
+use crate::locale::tr;
+use crate::plan::PlanChange;
+use encoding_rs::{
+    Encoding, IBM866, ISO_8859_5, KOI8_R, KOI8_U, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1251,
+    WINDOWS_1252, X_MAC_CYRILLIC,
+};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+/// Текстовые файлы больше этого не читаются: файл разбирается в памяти целиком, а .cue,
+/// плейлист или лог такого размера — скорее всего, чужой файл с неверным расширением
+pub const MAX_TEXT_SIZE: u64 = 64 * 1024 * 1024;
+/// Подписи EAC и XLD в конце лога: после перекодирования они перестанут сходиться
+const LOG_SIGNATURES: &[&[u8]] = &[b"==== Log checksum", b"-----BEGIN XLD SIGNATURE-----"];
+
+/// Однобайтовые кодировки, среди которых определяется кодировка файла не в UTF-8,
+/// в порядке приоритета: при равной оценке побеждает первая
+pub const LEGACY_ENCODINGS: &[&Encoding] = &[
+    WINDOWS_1251,
+    KOI8_R,
+    IBM866,
+    ISO_8859_5,
+    KOI8_U,
+    X_MAC_CYRILLIC,
+];
+
+/// Команды .cue, значения которых — текст для человека и могут содержать кракозябры
+const CUE_TEXT_COMMANDS: &[&str] = &["TITLE", "PERFORMER", "SONGWRITER", "REM"];
+/// Директивы расширенного M3U с текстом после двоеточия
+const PLAYLIST_TEXT_DIRECTIVES: &[&[u8]] =
+    &[b"#EXTALB:", b"#EXTART:", b"#EXTGENRE:", b"#PLAYLIST:"];
+
+/// Формат текстового файла: определяет, какие части строк можно исправлять
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    /// .cue: значения TITLE/PERFORMER/SONGWRITER/REM
+    Cue,
+    /// .m3u/.m3u8: названия в #EXTINF и подобных директивах, остальные строки — пути
+    Playlist,
+    /// .log (EAC/XLD) и .accurip: не-ASCII часть любой строки
+    Log,
+    /// .lrc: текст после меток времени `[mm:ss.xx]` и значения тегов вроде `[ar:…]`
+    Lyrics,
+}
+
+impl TextFormat {
+    /// Формат по расширению (в нижнем регистре)
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "cue" => Some(Self::Cue),
+            "m3u" | "m3u8" => Some(Self::Playlist),
+            "log" | "accurip" => Some(Self::Log),
+            "lrc" => Some(Self::Lyrics),
+            _ => None,
+        }
+    }
+
+    /// Положение текстового значения в строке (без перевода строки)
+    fn text_value(self, line: &[u8]) -> Option<Range<usize>> {
+        match self {
+            Self::Cue => cue_value(line),
+            Self::Playlist => playlist_value(line),
+            Self::Log => log_value(line),
+            Self::Lyrics => lyrics_value(line),
+        }
+    }
+
+    /// Строка — путь к файлу
+    fn is_path(self, line: &str) -> bool {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        self == Self::Playlist && !line.is_empty() && !line.starts_with('#')
+    }
+}
+
+/// Строка текстового файла: исходные байты и положение текстового значения
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLine {
+    /// Строка целиком, вместе с переводом строки
+    pub raw: Vec<u8>,
+    /// Длина строки без перевода строки
+    body_len: usize,
+    /// Байты значения, в котором ищутся кракозябры (например, TITLE без кавычек)
+    pub value: Option<Range<usize>>,
+}
+
+impl TextLine {
+    fn parse(raw: &[u8], format: TextFormat) -> Self {
+        let body_len = raw.len()
+            - raw
+                .iter()
+                .rev()
+                .take_while(|&&b| b == b'\r' || b == b'\n')
+                .count();
+        Self {
+            raw: raw.to_vec(),
+            body_len,
+            value: format.text_value(&raw[..body_len]),
+        }
+    }
+
+    /// Строка без перевода строки
+    pub fn body(&self) -> &[u8] {
+        &self.raw[..self.body_len]
+    }
+
+    /// Перевод строки (`\r\n`, `\n` или пусто в конце файла)
+    pub fn ending(&self) -> &[u8] {
+        &self.raw[self.body_len..]
+    }
+}
+
+/// Разбор текстового файла без потерь: склейка строк даёт исходные байты
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextDocument {
+    pub format: TextFormat,
+    pub lines: Vec<TextLine>,
+}
+
+impl TextDocument {
+    pub fn parse(raw: &[u8], format: TextFormat) -> Self {
+        Self {
+            format,
+            lines: raw
+                .split_inclusive(|&b| b == b'\n')
+                .map(|line| TextLine::parse(line, format))
+                .collect(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.lines
+            .iter()
+            .flat_map(|l| l.raw.iter().copied())
+            .collect()
+    }
+}
+
+/// Изменённая строка текстового файла
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    /// Номер строки, начиная с 1
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+    /// Как появились кракозябры в строке уже UTF-8 файла
+    pub mojibake: Option<Mojibake>,
+    /// Уверенность в исправлении кракозябр, 0–100; `None` — простое перекодирование
+    pub confidence: Option<u8>,
+}
+
+/// Строка результата: исходный и новый текст без перевода строки
+#[derive(Debug, Clone)]
+struct FixedLine {
+    before: String,
+    after: String,
+    ending: String,
+}
+
+/// Найденное исправление текстового файла
+pub struct TextFix {
+    changes: Vec<LineChange>,
+    lines: Vec<FixedLine>,
+    /// Исходная кодировка файла
+    pub encoding: &'static Encoding,
+    /// При декодировании встретились недопустимые последовательности
+    pub had_errors: bool,
+    /// Куда сохранить результат: .m3u в UTF-8 сохраняется как .m3u8
+    pub target: PathBuf,
+}
+
+impl TextFix {
+    /// Изменённые строки (было, стало)
+    pub fn changed_lines(&self) -> &[LineChange] {
+        &self.changes
+    }
+
+    /// Содержимое файла после перекодирования
+    pub fn content(&self) -> String {
+        self.lines
+            .iter()
+            .flat_map(|line| [line.after.as_str(), line.ending.as_str()])
+            .collect()
+    }
+
+    /// Отказаться от исправлений кракозябр, для которых `keep` вернул `false`: их строки
+    /// останутся как были. Перекодирование из cp1251 не отменяется — без него файл
+    /// не станет UTF-8. Возвращает убранные изменения
+    pub fn retain_changes(&mut self, mut keep: impl FnMut(&LineChange) -> bool) -> Vec<LineChange> {
+        let (kept, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.changes)
+            .into_iter()
+            .partition(|change| change.mojibake.is_none() || keep(change));
+        for change in &removed {
+            let line = &mut self.lines[change.line - 1];
+            line.after = line.before.clone();
+        }
+        self.changes = kept;
+        removed
+    }
+}
+
+/// Что делать с BOM UTF-8 в начале записываемых файлов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BomPolicy {
+    /// Оставить как в исходном файле (BOM UTF-16 становится BOM UTF-8)
+    #[default]
+    Keep,
+    /// Добавить: без него .cue в UTF-8 не читают некоторые старые плееры для Windows
+    Add,
+    /// Убрать: BOM мешает утилитам Linux
+    Strip,
+}
+
+/// Переводы строк в записываемых файлах
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Как в исходном файле, в каждой строке свой
+    #[default]
+    Keep,
+    /// `\r\n`
+    Crlf,
+    /// `\n`
+    Lf,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextFixer {
+    /// Принудительно считать все текстовые файлы в однобайтовой кодировке `legacy`
+    /// (без неё — в cp1251)
+    pub force_cp1251: bool,
+    /// Кодировка файлов не в UTF-8; `None` — определить по содержимому среди [`LEGACY_ENCODINGS`]
+    pub legacy: Option<&'static Encoding>,
+    /// Поиск кракозябр в строках файлов, уже сохранённых в UTF-8
+    pub detector: Detector,
+    /// BOM в записываемых файлах
+    pub bom: BomPolicy,
+    /// Переводы строк в записываемых файлах
+    pub line_endings: LineEndings,
+    /// Переименования (старый путь, новый путь) для обновления путей в плейлистах
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Default for TextFixer {
+    fn default() -> Self {
+        Self::new(false, Detector::default())
+    }
+}
+
+impl TextFixer {
+    pub fn new(force_cp1251: bool, detector: Detector) -> Self {
+        Self {
+            force_cp1251,
+            legacy: None,
+            detector,
+            bom: BomPolicy::Keep,
+            line_endings: LineEndings::Keep,
+            renames: Vec::new(),
+        }
+    }
+
+    /// Перекодировать файлы не в UTF-8 из `encoding`, не определяя кодировку
+    pub fn with_legacy_encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.legacy = Some(encoding);
+        self
+    }
+
+    pub fn with_bom(mut self, bom: BomPolicy) -> Self {
+        self.bom = bom;
+        self
+    }
+
+    pub fn with_line_endings(mut self, line_endings: LineEndings) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Обновлять пути в плейлистах по журналу переименований (в порядке выполнения)
+    pub fn with_renames(mut self, renames: Vec<(PathBuf, PathBuf)>) -> Self {
+        self.renames = renames
+            .into_iter()
+            .map(|(from, to)| (absolute(&from), absolute(&to)))
+            .collect();
+        self
+    }
+
+    /// Обработка текстового файла без записи: готовим utf-8.
+    ///
+    /// Меняются только текстовые значения (у .cue — TITLE/PERFORMER/SONGWRITER/REM);
+    /// FILE, INDEX и остальные строки остаются байт в байт. Исключение — не-UTF-8 байты
+    /// вне этих значений в cp1251 файле (например, имя в FILE или путь в плейлисте):
+    /// их приходится перекодировать, иначе результат не будет валидным UTF-8
+    pub fn scan(&self, path: &Path) -> Result<Option<TextFix>> {
+        let (raw, format) = read_text(path)?;
+        self.scan_raw(path, raw, format)
+    }
+
+    /// Поиск исправлений в файле, уже прочитанном в память (например, из ZIP-архива);
+    /// формат — по расширению `path`
+    pub fn scan_bytes(&self, path: &Path, raw: Vec<u8>) -> Result<Option<TextFix>> {
+        if raw.len() as u64 > MAX_TEXT_SIZE {
+            return Err(Error::TooLarge {
+                path: path.to_path_buf(),
+                size: raw.len() as u64,
+            });
+        }
+        self.scan_raw(path, raw, text_format(path))
+    }
+
+    fn scan_raw(&self, path: &Path, raw: Vec<u8>, format: TextFormat) -> Result<Option<TextFix>> {
+        let utf16 = utf16_encoding(&raw);
+        // Логи в UTF-16 (так сохраняет EAC) уже хранят кириллицу как есть, а подписанный лог
+        // править нельзя
+        if format == TextFormat::Log
+            && (utf16.is_some()
+                || LOG_SIGNATURES
+                    .iter()
+                    .any(|sig| raw.windows(sig.len()).any(|w| w == *sig)))
+        {
+            return Ok(None);
+        }
+        let mut had_errors = false;
+        // Остальные файлы в UTF-16 перекодируются в UTF-8 целиком и дальше обрабатываются как UTF-8
+        let raw = match utf16 {
+            Some(encoding) => {
+                let (text, errors) = encoding.decode_without_bom_handling(&raw);
+                had_errors |= errors;
+                text.into_owned().into_bytes()
+            }
+            None => raw,
+        };
+        let document = TextDocument::parse(&raw, format);
+
+        // Пробуем определить кодировку:
+        // если UTF-16 — она уже известна;
+        // если force_cp1251 — просто cp1251;
+        // иначе: если файл уже в utf-8 — ищем кракозябры в значениях, иначе выбираем
+        // однобайтовую кодировку, в которой текст больше всего похож на слова.
+        let utf8 = utf16.is_some() || (!self.force_cp1251 && std::str::from_utf8(&raw).is_ok());
+        let legacy = match self.legacy {
+            Some(encoding) => encoding,
+            None if self.force_cp1251 || utf8 => WINDOWS_1251,
+            None => self
+                .detector
+                .detect_encoding(&raw, LEGACY_ENCODINGS)
+                .unwrap_or(WINDOWS_1251),
+        };
+        let mut lines: Vec<(String, String, Option<Detection>)> = document
+            .lines
+            .iter()
+            .map(|line| {
+                if utf8 {
+                    fix_utf8_line(&self.detector, line)
+                } else {
+                    let (before, after, errors) = decode_legacy_line(line, legacy);
+                    had_errors |= errors;
+                    (before, after, None)
+                }
+            })
+            .collect();
+
+        if !self.renames.is_empty() {
+            let base = absolute(path.parent().unwrap_or(Path::new("")));
+            for (_, after, _) in lines.iter_mut().filter(|(_, a, _)| format.is_path(a)) {
+                if let Some(renamed) = remap_entry(after, &base, &self.renames) {
+                    *after = renamed;
+                }
+            }
+        }
+
+        let changes: Vec<_> = lines
+            .iter()
+            .enumerate()
+            // у UTF-16 файла перекодируется каждая строка с не-ASCII текстом
+            .filter(|(_, (before, after, _))| {
+                before != after || (utf16.is_some() && !before.is_ascii())
+            })
+            .map(|(i, (before, after, detection))| LineChange {
+                line: i + 1,
+                before: before.trim_start_matches('\u{feff}').to_string(),
+                after: after.trim_start_matches('\u{feff}').to_string(),
+                mojibake: detection.as_ref().map(|d| d.mojibake),
+                confidence: detection.as_ref().map(|d| d.confidence),
+            })
+            .collect();
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let lines = lines
+            .into_iter()
+            .zip(&document.lines)
+            .map(|((before, after, _), line)| FixedLine {
+                before,
+                after,
+                // перевод строки — ASCII
+                ending: String::from_utf8_lossy(line.ending()).into_owned(),
+            })
+            .collect();
+        Ok(Some(TextFix {
+            changes,
+            lines,
+            encoding: match utf16 {
+                Some(encoding) => encoding,
+                None if utf8 => UTF_8,
+                None => legacy,
+            },
+            had_errors,
+            target: target_path(path),
+        }))
+    }
+
+    /// Исправления из плана. Если в плане есть перекодирование из однобайтовой кодировки,
+    /// файл перекодируется целиком, как при scan; иначе меняются только строки из плана.
+    /// Исходный текст каждой строки сверяется с `before`
+    pub fn planned(&self, path: &Path, changes: &[PlanChange]) -> Result<TextFix> {
+        let (raw, format) = read_text(path)?;
+        let mut had_errors = false;
+        let utf16 = utf16_encoding(&raw);
+        let raw = match utf16 {
+            Some(encoding) => {
+                let (text, errors) = encoding.decode_without_bom_handling(&raw);
+                had_errors |= errors;
+                text.into_owned().into_bytes()
+            }
+            None => raw,
+        };
+        let document = TextDocument::parse(&raw, format);
+        let error = |change: &PlanChange, reason| Error::PlanApply {
+            path: path.to_path_buf(),
+            field: change.field.clone(),
+            reason,
+        };
+
+        // Перекодирование записано в плане как изменение без misread_as;
+        // UTF-16 определяется по самому файлу
+        let legacy = changes
+            .iter()
+            .filter(|c| c.misread_as.is_none())
+            .find_map(|c| Encoding::for_label(c.encoding.as_deref()?.as_bytes()))
+            .filter(|&encoding| utf16.is_none() && encoding != UTF_8);
+        if legacy.is_none()
+            && std::str::from_utf8(&raw).is_err()
+            && let Some(change) = changes.first()
+        {
+            return Err(error(
+                change,
+                tr(
+                    "значение изменилось после составления плана",
+                    "value changed since the plan was made",
+                ),
+            ));
+        }
+
+        let mut lines: Vec<FixedLine> = document
+            .lines
+            .iter()
+            .map(|line| {
+                let (before, after) = match legacy {
+                    Some(encoding) => {
+                        let (before, after, errors) = decode_legacy_line(line, encoding);
+                        had_errors |= errors;
+                        (before, after)
+                    }
+                    None => {
+                        let body = String::from_utf8_lossy(line.body()).into_owned();
+                        (body.clone(), body)
+                    }
+                };
+                FixedLine {
+                    before,
+                    after,
+                    ending: String::from_utf8_lossy(line.ending()).into_owned(),
+                }
+            })
+            .collect();
+
+        let mut line_changes = Vec::new();
+        for change in changes {
+            let Some((number, line)) = change
+                .line
+                .filter(|&n| n > 0)
+                .and_then(|n| Some((n, lines.get_mut(n - 1)?)))
+            else {
+                return Err(error(
+                    change,
+                    tr("неполная запись плана", "incomplete plan record"),
+                ));
+            };
+            let bom = if line.before.starts_with('\u{feff}') {
+                "\u{feff}"
+            } else {
+                ""
+            };
+            if line.before[bom.len()..] != change.before {
+                return Err(error(
+                    change,
+                    tr(
+                        "значение изменилось после составления плана",
+                        "value changed since the plan was made",
+                    ),
+                ));
+            }
+            line.after = format!("{bom}{}", change.after);
+            line_changes.push(LineChange {
+                line: number,
+                before: change.before.clone(),
+                after: change.after.clone(),
+                mojibake: change.mojibake(),
+                confidence: change.confidence,
+            });
+        }
+
+        Ok(TextFix {
+            changes: line_changes,
+            lines,
+            encoding: utf16.or(legacy).unwrap_or(UTF_8),
+            had_errors,
+            target: target_path(path),
+        })
+    }
+
+    /// Запись файла в UTF-8 с предварительным бэкапом; .m3u сохраняется как .m3u8,
+    /// а исходный файл удаляется (его копия остаётся в бэкапе)
+    pub fn apply(&self, path: &Path, fix: &TextFix, backup_manager: &BackupManager) -> Result<()> {
+        let write_error = |path: &Path, source| Error::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+        if fix.target != path && fix.target.exists() {
+            return Err(write_error(
+                &fix.target,
+                io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    tr("файл уже существует", "file already exists"),
+                ),
+            ));
+        }
+
+        backup_manager.backup_file(path)?;
+
+        atomic::write(&fix.target, self.render(fix).as_bytes())?;
+        if fix.target != path {
+            fs::remove_file(path).map_err(|e| write_error(path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Содержимое файла для записи: BOM и переводы строк — по [`TextFixer::bom`]
+    /// и [`TextFixer::line_endings`]
+    pub fn render(&self, fix: &TextFix) -> String {
+        let content: String = fix
+            .lines
+            .iter()
+            .flat_map(|line| {
+                let ending = match self.line_endings {
+                    // последняя строка без перевода строки так и остаётся
+                    _ if line.ending.is_empty() => "",
+                    LineEndings::Keep => line.ending.as_str(),
+                    LineEndings::Crlf => "\r\n",
+                    LineEndings::Lf => "\n",
+                };
+                [line.after.as_str(), ending]
+            })
+            .collect();
+
+        let body = content.strip_prefix('\u{feff}').unwrap_or(&content);
+        match self.bom {
+            BomPolicy::Keep => content.clone(),
+            BomPolicy::Add => format!("\u{feff}{body}"),
+            BomPolicy::Strip => body.to_string(),
+        }
+    }
+}
+
+/// Кодировка файла в UTF-16: по BOM, а без него — по нулевым байтам через один
+/// (старшие байты ASCII-символов), как у .cue, записанных программами для Windows.
+/// BOM при перекодировании сохраняется как BOM UTF-8
+fn utf16_encoding(raw: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(raw) {
+        return (encoding != UTF_8).then_some(encoding);
+    }
+    if raw.len() < 4 || !raw.len().is_multiple_of(2) {
+        return None;
+    }
+    let half = raw.len() / 2;
+    let zeros = |parity: usize| {
+        raw.iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if even.max(odd) * 2 <= half || even.min(odd) * 20 >= half {
+        return None;
+    }
+    // ASCII в UTF-16LE: сначала код символа, потом ноль
+    Some(if odd > even { UTF_16LE } else { UTF_16BE })
+}
+
+/// Содержимое текстового файла и его формат по расширению
+fn read_text(path: &Path) -> Result<(Vec<u8>, TextFormat)> {
+    let read_error = |source| Error::Read {
+        path: path.to_path_buf(),
+        source,
+    };
+    let too_large = |size| Error::TooLarge {
+        path: path.to_path_buf(),
+        size,
+    };
+    let file = File::open(path).map_err(read_error)?;
+    let size = file.metadata().map_err(read_error)?.len();
+    if size > MAX_TEXT_SIZE {
+        return Err(too_large(size));
+    }
+    // Файл мог вырасти после проверки размера: читается не больше предела
+    let mut raw = Vec::with_capacity(size as usize);
+    file.take(MAX_TEXT_SIZE + 1)
+        .read_to_end(&mut raw)
+        .map_err(read_error)?;
+    if raw.len() as u64 > MAX_TEXT_SIZE {
+        return Err(too_large(raw.len() as u64));
+    }
+    Ok((raw, text_format(path)))
+}
+
+fn text_format(path: &Path) -> TextFormat {
+    TextFormat::from_extension(&extension(path)).unwrap_or(TextFormat::Cue)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default()
+}
+
+/// Куда сохранить исправленный файл: .m3u в UTF-8 сохраняется как .m3u8
+fn target_path(path: &Path) -> PathBuf {
+    if extension(path) == "m3u" {
+        path.with_extension("m3u8")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Перекодирование строки из однобайтовой кодировки в utf-8: (было, стало, были ли ошибки).
+/// ASCII одинаков в обеих кодировках, а строки без текстового значения не трогаем, если можно
+fn decode_legacy_line(line: &TextLine, encoding: &'static Encoding) -> (String, String, bool) {
+    let body = line.body();
+    if let Ok(text) = std::str::from_utf8(body)
+        && (line.value.is_none() || body.is_ascii())
+    {
+        return (text.to_string(), text.to_string(), false);
+    }
+
+    let (decoded, errors) = encoding.decode_without_bom_handling(body);
+    // Исходные байты показываем так, как их увидел бы «западный» плеер — с кракозябрами
+    let (before, _) = WINDOWS_1252.decode_without_bom_handling(body);
+    (before.into_owned(), decoded.into_owned(), errors)
+}
+
+/// Исправление кракозябр в текстовом значении строки уже UTF-8 файла:
+/// (было, стало, найденные кракозябры). Остальные байты (включая BOM) не меняются
+fn fix_utf8_line(detector: &Detector, line: &TextLine) -> (String, String, Option<Detection>) {
+    // Файл валиден целиком, а границы значения — ASCII, так что срезы — тоже UTF-8
+    let body = String::from_utf8_lossy(line.body()).into_owned();
+    let Some(range) = line.value.clone() else {
+        return (body.clone(), body, None);
+    };
+    let Some(detection) = detector.detect(&body[range.clone()]) else {
+        return (body.clone(), body, None);
+    };
+
+    let after = format!(
+        "{}{}{}",
+        &body[..range.start],
+        detection.text,
+        &body[range.end..]
+    );
+    (body, after, Some(detection))
+}
+
+/// Исправление .cue, встроенного в тег (CUESHEET у FLAC и APE): как и в файле .cue, меняются
+/// только значения TITLE/PERFORMER/SONGWRITER/REM, а FILE и INDEX остаются как были.
+/// Оценка и уверенность — худшие среди исправленных строк
+pub fn fix_cue_text(detector: &Detector, text: &str) -> Option<Detection> {
+    let document = TextDocument::parse(text.as_bytes(), TextFormat::Cue);
+    let mut fixed = String::with_capacity(text.len());
+    let mut result: Option<Detection> = None;
+    for line in &document.lines {
+        let (_, after, detection) = fix_utf8_line(detector, line);
+        fixed.push_str(&after);
+        // перевод строки — ASCII
+        fixed.push_str(&String::from_utf8_lossy(line.ending()));
+        if let Some(detection) = detection {
+            result = Some(detect::worst(result, detection));
+        }
+    }
+
+    result.map(|detection| Detection {
+        text: fixed,
+        ..detection
+    })
+}
+
+/// Положение текстового значения в строке .cue:
+/// текст в кавычках, а без кавычек — всё после команды (у REM — после имени поля)
+fn cue_value(line: &[u8]) -> Option<Range<usize>> {
+    // BOM в начале файла и отступ
+    let mut pos = if line.starts_with(BOM) { BOM.len() } else { 0 };
+    pos += skip_spaces(&line[pos..]);
+
+    let command_len = line[pos..]
+        .iter()
+        .take_while(|b| !b.is_ascii_whitespace())
+        .count();
+    let command = &line[pos..pos + command_len];
+    if !CUE_TEXT_COMMANDS
+        .iter()
+        .any(|c| c.as_bytes().eq_ignore_ascii_case(command))
+    {
+        return None;
+    }
+    pos += command_len;
+    pos += skip_spaces(&line[pos..]);
+
+    if command.eq_ignore_ascii_case(b"REM") {
+        // REM GENRE "Rock", REM COMMENT "…"
+        pos += line[pos..]
+            .iter()
+            .take_while(|b| !b.is_ascii_whitespace())
+            .count();
+        pos += skip_spaces(&line[pos..]);
+    }
+
+    let rest = &line[pos..];
+    if rest.first() == Some(&b'"')
+        && let Some(end) = rest.iter().rposition(|&b| b == b'"')
+        && end > 0
+    {
+        return Some(pos + 1..pos + end);
+    }
+
+    trimmed(line, pos)
+}
+
+/// Положение названия в строке M3U: после запятой в `#EXTINF:длительность,название`
+/// и после двоеточия в #EXTALB/#EXTART/#EXTGENRE/#PLAYLIST
+fn playlist_value(line: &[u8]) -> Option<Range<usize>> {
+    let start = if line.starts_with(BOM) { BOM.len() } else { 0 };
+    let rest = &line[start..];
+
+    let value_start = if rest.starts_with(b"#EXTINF:") {
+        rest.iter().position(|&b| b == b',')? + 1
+    } else if PLAYLIST_TEXT_DIRECTIVES.iter().any(|d| rest.starts_with(d)) {
+        rest.iter().position(|&b| b == b':')? + 1
+    } else {
+        return None;
+    };
+
+    let pos = start + value_start;
+    trimmed(line, pos + skip_spaces(&line[pos..]))
+}
+
+/// Положение текста в строке лога: от первого до последнего не-ASCII символа.
+/// Английские подписи вокруг названия («Track 1: … accurately ripped») не мешают поиску кракозябр
+fn log_value(line: &[u8]) -> Option<Range<usize>> {
+    let start = if line.starts_with(BOM) { BOM.len() } else { 0 };
+    let first = start + line[start..].iter().position(|b| !b.is_ascii())?;
+    let last = line.iter().rposition(|b| !b.is_ascii())?;
+    Some(first..last + 1)
+}
+
+/// Положение текста в строке .lrc: после всех меток времени (`[01:23.45][02:10.00]текст`),
+/// а у тегов (`[ar:Исполнитель]`, `[ti:…]`) — значение до закрывающей скобки
+fn lyrics_value(line: &[u8]) -> Option<Range<usize>> {
+    let mut pos = if line.starts_with(BOM) { BOM.len() } else { 0 };
+    pos += skip_spaces(&line[pos..]);
+
+    while line[pos..].first() == Some(&b'[') {
+        let len = line[pos..].iter().position(|&b| b == b']')?;
+        let inner = &line[pos + 1..pos + len];
+        if inner.contains(&b':')
+            && inner
+                .iter()
+                .all(|&b| b.is_ascii_digit() || b == b':' || b == b'.')
+        {
+            pos += len + 1;
+            continue;
+        }
+
+        // Тег `[ключ:значение]`; в самом значении тоже может встретиться `]`
+        let key_len = inner.iter().take_while(|b| b.is_ascii_alphabetic()).count();
+        if key_len == 0 || inner.get(key_len) != Some(&b':') {
+            break;
+        }
+        let start = pos + 1 + key_len + 1;
+        let end = line.iter().rposition(|&b| b == b']')?;
+        let start = start + skip_spaces(&line[start..end]);
+        return trimmed(&line[..end], start);
+    }
+
+    trimmed(line, pos + skip_spaces(&line[pos..]))
+}
+
+/// Остаток строки с `pos` без пробелов в конце; `None`, если он пуст
+fn trimmed(line: &[u8], pos: usize) -> Option<Range<usize>> {
+    let rest = &line[pos..];
+    let len = rest.len()
+        - rest
+            .iter()
+            .rev()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+    (len > 0).then_some(pos..pos + len)
+}
+
+fn skip_spaces(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take_while(|&&b| b == b' ' || b == b'\t')
+        .count()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Новая запись плейлиста, если файл, на который она указывает, был переименован.
+///
+/// Переименования применяются по порядку (сначала файлы, потом каталоги), меняются только
+/// имена, поэтому заменяются последние компоненты записи, а её вид (относительный путь,
+/// разделитель `\` у плейлистов из Windows) сохраняется
+fn remap_entry(entry: &str, base: &Path, renames: &[(PathBuf, PathBuf)]) -> Option<String> {
+    let bom = if entry.starts_with('\u{feff}') {
+        "\u{feff}"
+    } else {
+        ""
+    };
+    let entry = &entry[bom.len()..];
+    if entry.contains("://") {
+        return None;
+    }
+    let separator = if !entry.contains('/') && entry.contains('\\') {
+        '\\'
+    } else {
+        '/'
+    };
+
+    let parts: Vec<&str> = entry.split(separator).collect();
+    // Ведущие "", "." и ".." (корень и переходы вверх), дальше — только имена
+    let prefix_len = parts
+        .iter()
+        .take_while(|p| matches!(**p, "" | "." | ".."))
+        .count();
+    let names = &parts[prefix_len..];
+    if names.is_empty() || names.iter().any(|n| matches!(*n, "" | "." | "..")) {
+        return None;
+    }
+
+    let mut old = if entry.starts_with(separator) {
+        PathBuf::from("/")
+    } else {
+        base.to_path_buf()
+    };
+    for part in &parts {
+        match *part {
+            "" | "." => {}
+            ".." => {
+                old.pop();
+            }
+            name => old.push(name),
+        }
+    }
+
+    let mut new = old.clone();
+    for (from, to) in renames {
+        if let Ok(rest) = new.strip_prefix(from) {
+            new = if rest.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(rest)
+            };
+        }
+    }
+    if new == old {
+        return None;
+    }
+
+    let new_names: Vec<_> = new
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect();
+    let new_names = new_names.get(new_names.len().checked_sub(names.len())?..)?;
+
+    let mut out: Vec<&str> = parts[..prefix_len].to_vec();
+    out.extend_from_slice(new_names);
+    Some(format!("{bom}{}", out.join(&separator.to_string())))
+}
@@ -0,0 +1,212 @@
+//! Транслитерация кириллицы латиницей: для магнитол и плееров, которые кириллицу не
+//! показывают вовсе (--transliterate), и для ID3v1 в Latin-1 (--id3v1 translit).
+//!
+//! Системы:
+//! - ГОСТ 7.79-2000, система Б — только ASCII и обратима: `щ` → `shh`, `ы` → `y'`,
+//!   `ъ` → ``` `` ```; `ц` перед `e`, `i`, `y`, `j` пишется `c`, в остальных случаях `cz`;
+//! - ISO 9:1995 (ГОСТ 7.79, система А) — буква в букву, с диакритикой: `ж` → `ž`, `щ` → `ŝ`;
+//! - простая — привычная по загранпаспортам и навигаторам латиница без диакритики и знаков:
+//!   `ж` → `zh`, `х` → `kh`, твёрдый и мягкий знаки опускаются
+
+/// Система транслитерации
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scheme {
+    /// ГОСТ 7.79-2000, система Б
+    Gost,
+    /// ISO 9:1995
+    Iso9,
+    /// Простая латиница без диакритики
+    #[default]
+    Simple,
+}
+
+impl Scheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Gost => "gost",
+            Scheme::Iso9 => "iso9",
+            Scheme::Simple => "simple",
+        }
+    }
+}
+
+/// Есть ли в `text` буквы, которые меняет транслитерация
+pub fn has_cyrillic(text: &str) -> bool {
+    text.chars()
+        .any(|c| letter(c, None, Scheme::Simple).is_some())
+}
+
+/// `text` латиницей; символы не из кириллицы остаются как были. Заглавная буква внутри
+/// слова из заглавных (`ЖУКИ`) транслитерируется заглавными целиком, в остальных
+/// случаях заглавной становится только первая буква (`Жуки` → `Zhuki`)
+pub fn transliterate(text: &str, scheme: Scheme) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+        let Some(latin) = letter(c, next, scheme) else {
+            out.push(c);
+            continue;
+        };
+        if !c.is_uppercase() {
+            out.push_str(latin);
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let upper = |c: Option<char>| c.is_some_and(|c| c.is_alphabetic() && c.is_uppercase());
+        let alphabetic = |c: Option<char>| c.is_some_and(char::is_alphabetic);
+        if upper(next) || (upper(previous) && !alphabetic(next)) {
+            out.extend(latin.chars().flat_map(char::to_uppercase));
+        } else {
+            out.push_str(&capitalize(latin));
+        }
+    }
+    out
+}
+
+/// Латиница для одной кириллической буквы без учёта соседних (для ID3v1): заглавной
+/// становится первая буква
+pub fn transliterate_char(c: char, scheme: Scheme) -> Option<String> {
+    let latin = letter(c, None, scheme)?;
+    Some(if c.is_uppercase() {
+        capitalize(latin)
+    } else {
+        latin.to_string()
+    })
+}
+
+fn capitalize(latin: &str) -> String {
+    let mut chars = latin.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().collect::<String>() + chars.as_str()
+    })
+}
+
+/// Латиница строчной буквы `c`; `next` — следующий символ (нужен для `ц` по ГОСТ)
+fn letter(c: char, next: Option<char>, scheme: Scheme) -> Option<&'static str> {
+    let lower = c.to_lowercase().next()?;
+    let latin = match scheme {
+        Scheme::Gost => match lower {
+            'а' => "a",
+            'б' => "b",
+            'в' => "v",
+            'г' => "g",
+            'ґ' => "g`",
+            'д' => "d",
+            'е' => "e",
+            'ё' => "yo",
+            'є' => "ye",
+            'ж' => "zh",
+            'з' => "z",
+            'и' => "i",
+            'і' => "i",
+            'ї' => "yi",
+            'й' => "j",
+            'к' => "k",
+            'л' => "l",
+            'м' => "m",
+            'н' => "n",
+            'о' => "o",
+            'п' => "p",
+            'р' => "r",
+            'с' => "s",
+            'т' => "t",
+            'у' => "u",
+            'ў' => "u`",
+            'ф' => "f",
+            'х' => "x",
+            'ц' => {
+                let soft = next
+                    .and_then(|c| c.to_lowercase().next())
+                    .is_some_and(|c| "еиіыйєїэюяё".contains(c));
+                if soft { "c" } else { "cz" }
+            }
+            'ч' => "ch",
+            'ш' => "sh",
+            'щ' => "shh",
+            'ъ' => "``",
+            'ы' => "y'",
+            'ь' => "`",
+            'э' => "e`",
+            'ю' => "yu",
+            'я' => "ya",
+            _ => return None,
+        },
+        Scheme::Iso9 => match lower {
+            'а' => "a",
+            'б' => "b",
+            'в' => "v",
+            'г' => "g",
+            'ґ' => "g\u{300}",
+            'д' => "d",
+            'е' => "e",
+            'ё' => "ë",
+            'є' => "ê",
+            'ж' => "ž",
+            'з' => "z",
+            'и' => "i",
+            'і' => "ì",
+            'ї' => "ï",
+            'й' => "j",
+            'к' => "k",
+            'л' => "l",
+            'м' => "m",
+            'н' => "n",
+            'о' => "o",
+            'п' => "p",
+            'р' => "r",
+            'с' => "s",
+            'т' => "t",
+            'у' => "u",
+            'ў' => "ǔ",
+            'ф' => "f",
+            'х' => "h",
+            'ц' => "c",
+            'ч' => "č",
+            'ш' => "š",
+            'щ' => "ŝ",
+            'ъ' => "ʺ",
+            'ы' => "y",
+            'ь' => "ʹ",
+            'э' => "è",
+            'ю' => "û",
+            'я' => "â",
+            _ => return None,
+        },
+        Scheme::Simple => match lower {
+            'а' => "a",
+            'б' => "b",
+            'в' => "v",
+            'г' | 'ґ' => "g",
+            'д' => "d",
+            'е' | 'ё' | 'э' => "e",
+            'є' => "ye",
+            'ж' => "zh",
+            'з' => "z",
+            'и' | 'і' => "i",
+            'ї' => "yi",
+            'й' | 'ы' => "y",
+            'к' => "k",
+            'л' => "l",
+            'м' => "m",
+            'н' => "n",
+            'о' => "o",
+            'п' => "p",
+            'р' => "r",
+            'с' => "s",
+            'т' => "t",
+            'у' | 'ў' => "u",
+            'ф' => "f",
+            'х' => "kh",
+            'ц' => "ts",
+            'ч' => "ch",
+            'ш' => "sh",
+            'щ' => "shch",
+            'ъ' | 'ь' => "",
+            'ю' => "yu",
+            'я' => "ya",
+            _ => return None,
+        },
+    };
+    Some(latin)
+}
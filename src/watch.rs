@@ -0,0 +1,144 @@
+//! Наблюдение за каталогами (fix --watch): новые и изменённые файлы исправляются, как только
+//! их перестали записывать.
+//!
+//! Каталоги перечитываются раз в интервал. Файл берётся в работу, когда его размер и время
+//! изменения не поменялись между двумя проверками подряд, а файлы каталога — только когда
+//! в нём не осталось файлов, которые ещё дописываются: альбом из торрент-клиента или
+//! рипера исправляется целиком, а не по одному треку
+
+use crate::interrupt;
+use crate::log::{self, LogLevel};
+use crate::output::OutputFormat;
+use crate::process::{Processor, Summary};
+use crate::{FilterArgs, take_walk_errors, walk_files};
+use colored::*;
+use cyrtag_fix::locale::tr;
+use cyrtag_fix::state::Fingerprint;
+use cyrtag_fix::tr_format;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Как часто проверять, не пришёл ли Ctrl-C, пока ждём следующей проверки
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+
+/// Следить за путями `roots` до Ctrl-C или SIGTERM, исправляя новые и изменённые файлы
+/// каждые `interval`; `summary` — итоги первого прохода, к ним добавляются новые
+pub fn watch(
+    processor: &Processor,
+    roots: &[PathBuf],
+    root: &Path,
+    interval: Duration,
+    threads: usize,
+    mut summary: Summary,
+) -> Summary {
+    let human = processor.output == OutputFormat::Human;
+    if human {
+        println!(
+            "{}",
+            tr_format!(
+                "Наблюдение за изменениями каждые {} с; остановить — Ctrl-C",
+                "Watching for changes every {} s; press Ctrl-C to stop",
+                interval.as_secs_f64()
+            )
+            .bold()
+        );
+    }
+    log::write(
+        LogLevel::Info,
+        tr("Наблюдение за изменениями", "Watching for changes"),
+    );
+
+    let mut known = snapshot(roots, &processor.filter);
+    let mut pending: HashMap<PathBuf, Fingerprint> = HashMap::new();
+    while sleep(interval) {
+        let current = snapshot(roots, &processor.filter);
+        let mut settled = Vec::new();
+        let mut busy_dirs = HashSet::new();
+        for (path, fingerprint) in &current {
+            if known.get(path) == Some(fingerprint) {
+                continue;
+            }
+            if pending.get(path) == Some(fingerprint) {
+                settled.push(path.clone());
+            } else {
+                busy_dirs.insert(path.parent().unwrap_or(path).to_path_buf());
+            }
+        }
+        pending = current
+            .iter()
+            .filter(|(path, fingerprint)| known.get(*path) != Some(*fingerprint))
+            .map(|(path, fingerprint)| (path.clone(), *fingerprint))
+            .collect();
+        known.retain(|path, _| current.contains_key(path));
+
+        // Файлы каталога, где что-то ещё записывается, подождут следующей проверки
+        settled.retain(|path| !busy_dirs.contains(path.parent().unwrap_or(path)));
+        if settled.is_empty() {
+            continue;
+        }
+        settled.sort();
+        for path in &settled {
+            pending.remove(path);
+        }
+
+        let batch = processor.process_paths(settled.iter().cloned(), root, threads);
+        let message = tr_format!(
+            "Проверено новых и изменённых файлов: {}, исправлено: {}",
+            "New and changed files checked: {}, fixed: {}",
+            settled.len(),
+            batch.fixed
+        );
+        log::write(LogLevel::Info, &message);
+        if human {
+            println!("{message}");
+        }
+        processor.notify_changes();
+        // После записи у исправленных файлов новый отпечаток: повторно их брать не нужно
+        for path in settled {
+            if let Ok(fingerprint) = Fingerprint::of(&path) {
+                known.insert(path, fingerprint);
+            }
+        }
+        let stopped = batch.interrupted || batch.failed_fast;
+        summary.merge(batch);
+        if stopped {
+            break;
+        }
+    }
+
+    // Остановка по Ctrl-C — обычный способ завершить наблюдение, а не прерванная работа
+    summary.interrupted = false;
+    summary
+}
+
+/// Отпечатки всех обрабатываемых файлов в путях `roots`. Ошибки обхода уже выведены,
+/// а в итоги они не идут: недоступный каталог иначе считался бы при каждой проверке
+fn snapshot(roots: &[PathBuf], filter: &FilterArgs) -> HashMap<PathBuf, Fingerprint> {
+    let snapshot = roots
+        .iter()
+        .flat_map(|root| walk_files(root, filter))
+        // С --sniff формат определяется при обработке: открывать каждый файл при каждой
+        // проверке слишком дорого
+        .filter(|entry| filter.sniff || filter.supports(entry.path()))
+        .filter_map(|entry| {
+            let fingerprint = Fingerprint::of(entry.path()).ok()?;
+            Some((entry.into_path(), fingerprint))
+        })
+        .collect();
+    take_walk_errors();
+    snapshot
+}
+
+/// Подождать `interval`; `false` — пришёл сигнал остановки
+fn sleep(interval: Duration) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < interval {
+        if interrupt::interrupted() {
+            return false;
+        }
+        thread::sleep(INTERRUPT_POLL.min(interval.saturating_sub(started.elapsed())));
+    }
+    !interrupt::interrupted()
+}
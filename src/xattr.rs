@@ -0,0 +1,154 @@
+//! Расширенные атрибуты файлов (xattr) в Linux и macOS: в отличие от записей в каталоге
+//! состояния они переезжают вместе с файлом при `mv` и `cp -a`/`rsync -X`. На других
+//! системах атрибутов нет: чтение ничего не находит, запись возвращает `Unsupported`
+
+use std::io;
+use std::path::Path;
+
+/// Значение атрибута `name` или `None`, если его нет или прочитать не удалось
+pub fn get(path: &Path, name: &str) -> Option<String> {
+    let value = sys::get(path, name)?;
+    String::from_utf8(value).ok()
+}
+
+/// Записать атрибут `name`; у файловых систем без атрибутов (FAT, многие сетевые папки)
+/// ошибка `Unsupported`
+pub fn set(path: &Path, name: &str, value: &str) -> io::Result<()> {
+    sys::set(path, name, value.as_bytes())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::ffi::{CString, c_char, c_void};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Значения отметок короткие; длиннее — не наш атрибут
+    const MAX_VALUE: usize = 256;
+
+    /// Файловая система не поддерживает атрибуты
+    #[cfg(target_os = "linux")]
+    const ENOTSUP: i32 = 95;
+    #[cfg(target_os = "macos")]
+    const ENOTSUP: i32 = 45;
+
+    #[cfg(target_os = "linux")]
+    unsafe extern "C" {
+        fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize;
+        fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            flags: i32,
+        ) -> i32;
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe extern "C" {
+        fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+            position: u32,
+            options: i32,
+        ) -> isize;
+        fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            position: u32,
+            options: i32,
+        ) -> i32;
+    }
+
+    fn c_strings(path: &Path, name: &str) -> io::Result<(CString, CString)> {
+        let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+        Ok((
+            CString::new(path.as_os_str().as_bytes()).map_err(invalid)?,
+            CString::new(name).map_err(invalid)?,
+        ))
+    }
+
+    pub fn get(path: &Path, name: &str) -> Option<Vec<u8>> {
+        let (path, name) = c_strings(path, name).ok()?;
+        let mut value = vec![0u8; MAX_VALUE];
+        // SAFETY: строки завершены нулём, буфер длиной `value.len()` живёт до конца вызова
+        let read = unsafe {
+            #[cfg(target_os = "linux")]
+            let read = getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            );
+            #[cfg(target_os = "macos")]
+            let read = getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            );
+            read
+        };
+        let read = usize::try_from(read).ok()?;
+        value.truncate(read);
+        Some(value)
+    }
+
+    pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+        let (path, name) = c_strings(path, name)?;
+        // SAFETY: строки завершены нулём, `value` живёт до конца вызова
+        let result = unsafe {
+            #[cfg(target_os = "linux")]
+            let result = setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            );
+            #[cfg(target_os = "macos")]
+            let result = setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            );
+            result
+        };
+        if result == 0 {
+            return Ok(());
+        }
+        match io::Error::last_os_error() {
+            e if e.raw_os_error() == Some(ENOTSUP) => Err(io::ErrorKind::Unsupported.into()),
+            e => Err(e),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::io;
+    use std::path::Path;
+
+    pub fn get(_path: &Path, _name: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    pub fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
@@ -0,0 +1,148 @@
+//! Единообразная буква `ё` (--yo): в одних тегах `Ёлка` и `ещё`, в других `Елка` и `еще`,
+//! и плееры раскладывают такие альбомы и исполнителей по разным местам.
+//!
+//! `ё` можно заменить на `е` везде или, наоборот, вернуть по словарю. В словаре только
+//! слова, где `е` на месте `ё` не бывает: `все`, `берет` или `звезды` пишутся и так,
+//! и так, и остаются как есть
+
+use phf::{Map, phf_map};
+use std::borrow::Cow;
+
+/// Что делать с `ё`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Yo {
+    /// Заменять `ё` на `е`
+    ToE,
+    /// Возвращать `ё` в словах из словаря
+    Restore,
+}
+
+/// Слова с `е` вместо `ё` (строчными) → правильное написание
+static YO_WORDS: Map<&'static str, &'static str> = phf_map! {
+    "еще" => "ещё",
+    "ее" => "её",
+    "нее" => "неё",
+    "мое" => "моё",
+    "твое" => "твоё",
+    "свое" => "своё",
+    "елка" => "ёлка",
+    "елки" => "ёлки",
+    "еж" => "ёж",
+    "ежик" => "ёжик",
+    "лед" => "лёд",
+    "мед" => "мёд",
+    "пес" => "пёс",
+    "черт" => "чёрт",
+    "шел" => "шёл",
+    "пришел" => "пришёл",
+    "ушел" => "ушёл",
+    "нашел" => "нашёл",
+    "вперед" => "вперёд",
+    "полет" => "полёт",
+    "самолет" => "самолёт",
+    "вертолет" => "вертолёт",
+    "ребенок" => "ребёнок",
+    "котенок" => "котёнок",
+    "береза" => "берёза",
+    "березка" => "берёзка",
+    "идет" => "идёт",
+    "поет" => "поёт",
+    "живет" => "живёт",
+    "ждет" => "ждёт",
+    "зовет" => "зовёт",
+    "придет" => "придёт",
+    "пойдет" => "пойдёт",
+    "уйдет" => "уйдёт",
+    "найдет" => "найдёт",
+    "несет" => "несёт",
+    "растет" => "растёт",
+    "течет" => "течёт",
+    "плывет" => "плывёт",
+    "цветет" => "цветёт",
+    "серьезно" => "серьёзно",
+    "черный" => "чёрный",
+    "черная" => "чёрная",
+    "черное" => "чёрное",
+    "черные" => "чёрные",
+    "темный" => "тёмный",
+    "темная" => "тёмная",
+    "темное" => "тёмное",
+    "темные" => "тёмные",
+    "зеленый" => "зелёный",
+    "зеленая" => "зелёная",
+    "зеленое" => "зелёное",
+    "зеленые" => "зелёные",
+    "желтый" => "жёлтый",
+    "желтая" => "жёлтая",
+    "желтое" => "жёлтое",
+    "желтые" => "жёлтые",
+    "веселый" => "весёлый",
+    "веселая" => "весёлая",
+    "веселое" => "весёлое",
+    "веселые" => "весёлые",
+    "тяжелый" => "тяжёлый",
+    "тяжелая" => "тяжёлая",
+    "тяжелое" => "тяжёлое",
+    "тяжелые" => "тяжёлые",
+    "далекий" => "далёкий",
+    "далекая" => "далёкая",
+    "далекое" => "далёкое",
+    "далекие" => "далёкие",
+    "звездный" => "звёздный",
+    "звездная" => "звёздная",
+    "семен" => "семён",
+    "федор" => "фёдор",
+    "артем" => "артём",
+    "алена" => "алёна",
+    "петр" => "пётр",
+};
+
+impl Yo {
+    /// `text` с заменённой или восстановленной `ё`
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Yo::ToE if text.contains(['ё', 'Ё']) => {
+                Cow::Owned(text.replace('ё', "е").replace('Ё', "Е"))
+            }
+            Yo::ToE => Cow::Borrowed(text),
+            Yo::Restore => restore(text),
+        }
+    }
+}
+
+/// Слова из словаря с `ё`; регистр слова сохраняется: `Еще` → `Ещё`, `ЕЩЕ` → `ЕЩЁ`
+fn restore(text: &str) -> Cow<'_, str> {
+    if !text.contains(['е', 'Е']) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let (word, tail) = rest.split_at(end);
+        match YO_WORDS.get(word.to_lowercase().as_str()) {
+            Some(yo) => {
+                out.extend(word.chars().zip(yo.chars()).map(|(original, yo)| {
+                    if original.is_uppercase() {
+                        yo.to_uppercase().next().unwrap_or(yo)
+                    } else {
+                        yo
+                    }
+                }));
+                changed = true;
+            }
+            None => out.push_str(word),
+        }
+        let separator = tail.find(char::is_alphabetic).unwrap_or(tail.len());
+        out.push_str(&tail[..separator]);
+        rest = &tail[separator..];
+    }
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
@@ -0,0 +1,442 @@
+//! ZIP-архивы альбомов: чтение файлов архива в память и запись архива обратно
+//!
+//! Сжатие поддерживается только для методов stored и deflate, остальные записи (и
+//! зашифрованные) переписываются байт в байт. Изменённая запись сжимается тем же методом
+//! и с той же степенью сжатия, что указана в её флагах. Атрибуты, время, комментарии
+//! и дополнительные поля записей сохраняются; ZIP64 и многотомные архивы не поддерживаются.
+
+use crate::error::{Error, Result};
+use crate::locale::tr;
+use encoding_rs::WINDOWS_1252;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Расширение ZIP-архивов
+pub const EXTENSION: &str = "zip";
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+/// Размер конца центрального каталога без комментария
+const END_LEN: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Флаги записи
+const FLAG_ENCRYPTED: u16 = 1 << 0;
+/// Степень сжатия deflate: биты 1–2
+const FLAG_LEVEL: u16 = 0b110;
+/// CRC и размеры — в дескрипторе после данных
+const FLAG_DESCRIPTOR: u16 = 1 << 3;
+/// Имя и комментарий в UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+/// Дополнительное поле Info-ZIP с UTF-8 именем: после переименования оно бы устарело
+const UNICODE_PATH_EXTRA: u16 = 0x7075;
+/// Дополнительное поле ZIP64
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Запись (файл или каталог) архива
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    version_made_by: u16,
+    version_needed: u16,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    /// CRC и размеры из локального заголовка: при записи через дескриптор там нули
+    local_sizes: [u8; 12],
+    name: Vec<u8>,
+    local_extra: Vec<u8>,
+    central_extra: Vec<u8>,
+    comment: Vec<u8>,
+    internal_attributes: u16,
+    external_attributes: u32,
+    /// Сжатые данные
+    data: Vec<u8>,
+    /// Дескриптор данных (флаг 3) как есть
+    descriptor: Vec<u8>,
+}
+
+impl Entry {
+    /// Имя записи (путь внутри архива через `/`). Имя не в UTF-8 показывается так, как его
+    /// видит «западная» программа, в cp1252 — с кракозябрами, если оно в cp866 или cp1251
+    pub fn name(&self) -> String {
+        match std::str::from_utf8(&self.name) {
+            Ok(name) => name.to_string(),
+            Err(_) if self.flags & FLAG_UTF8 != 0 => String::from_utf8_lossy(&self.name).into(),
+            Err(_) => WINDOWS_1252
+                .decode_without_bom_handling(&self.name)
+                .0
+                .into(),
+        }
+    }
+
+    /// Сменить имя; новое имя записывается в UTF-8 с флагом UTF-8
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.as_bytes().to_vec();
+        self.flags |= FLAG_UTF8;
+        self.local_extra = without_extra(&self.local_extra, UNICODE_PATH_EXTRA);
+        self.central_extra = without_extra(&self.central_extra, UNICODE_PATH_EXTRA);
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+
+    /// Содержимое можно распаковать и записать заново
+    pub fn is_supported(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED == 0 && matches!(self.method, STORED | DEFLATED)
+    }
+
+    /// Размер распакованных данных
+    pub fn size(&self) -> u64 {
+        u64::from(self.size)
+    }
+
+    /// Распакованное содержимое; CRC сверяется
+    pub fn data(&self) -> io::Result<Vec<u8>> {
+        if !self.is_supported() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                tr(
+                    "зашифрованная запись или неподдерживаемый метод сжатия",
+                    "encrypted entry or unsupported compression method",
+                ),
+            ));
+        }
+        let mut data = Vec::with_capacity(self.size as usize);
+        if self.method == STORED {
+            data.extend_from_slice(&self.data);
+        } else {
+            DeflateDecoder::new(self.data.as_slice()).read_to_end(&mut data)?;
+        }
+        if crc32fast::hash(&data) != self.crc {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr("не сходится CRC", "CRC mismatch"),
+            ));
+        }
+        Ok(data)
+    }
+
+    /// Заменить содержимое: сжимается тем же методом и с той же степенью сжатия
+    pub fn set_data(&mut self, data: &[u8]) -> io::Result<()> {
+        let compressed = match self.method {
+            DEFLATED => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), self.compression());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            _ => data.to_vec(),
+        };
+        let too_large = || {
+            io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                tr(
+                    "запись больше 4 ГБ (ZIP64)",
+                    "entry larger than 4 GB (ZIP64)",
+                ),
+            )
+        };
+        self.size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        self.compressed_size = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+        self.crc = crc32fast::hash(data);
+        self.data = compressed;
+        // Размеры теперь известны заранее: дескриптор не нужен
+        self.flags &= !FLAG_DESCRIPTOR;
+        self.descriptor.clear();
+        self.local_sizes = sizes(self.crc, self.compressed_size, self.size);
+        Ok(())
+    }
+
+    /// Степень сжатия deflate по флагам записи
+    fn compression(&self) -> Compression {
+        match (self.flags & FLAG_LEVEL) >> 1 {
+            1 => Compression::best(),
+            2 => Compression::new(2),
+            3 => Compression::fast(),
+            _ => Compression::default(),
+        }
+    }
+}
+
+/// ZIP-архив целиком в памяти
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    /// Данные до первой записи (например, самораспаковщик)
+    prefix: Vec<u8>,
+    pub entries: Vec<Entry>,
+    comment: Vec<u8>,
+}
+
+impl Archive {
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&bytes).map_err(|message| Error::Read {
+            path: path.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidData, message),
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> std::result::Result<Self, &'static str> {
+        let end = find_end(bytes).ok_or(tr("не ZIP-архив", "not a ZIP archive"))?;
+        let mut reader = Reader::at(bytes, end + 4);
+        let disk = reader.u16()?;
+        let central_disk = reader.u16()?;
+        let disk_entries = reader.u16()?;
+        let count = reader.u16()?;
+        let _central_size = reader.u32()?;
+        let central_offset = reader.u32()?;
+        let comment_len = reader.u16()?;
+        let comment = reader.take(comment_len as usize)?.to_vec();
+        if disk != 0 || central_disk != 0 || disk_entries != count {
+            return Err(tr(
+                "многотомные архивы не поддерживаются",
+                "multi-volume archives are not supported",
+            ));
+        }
+        if count == u16::MAX || central_offset == u32::MAX {
+            return Err(tr("ZIP64 не поддерживается", "ZIP64 is not supported"));
+        }
+
+        let mut reader = Reader::at(bytes, central_offset as usize);
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut first_offset = usize::MAX;
+        for _ in 0..count {
+            if reader.u32()? != CENTRAL_HEADER {
+                return Err(tr(
+                    "повреждён центральный каталог",
+                    "corrupt central directory",
+                ));
+            }
+            let version_made_by = reader.u16()?;
+            let version_needed = reader.u16()?;
+            let flags = reader.u16()?;
+            let method = reader.u16()?;
+            let time = reader.u16()?;
+            let date = reader.u16()?;
+            let crc = reader.u32()?;
+            let compressed_size = reader.u32()?;
+            let size = reader.u32()?;
+            let name_len = reader.u16()? as usize;
+            let extra_len = reader.u16()? as usize;
+            let comment_len = reader.u16()? as usize;
+            let _disk_start = reader.u16()?;
+            let internal_attributes = reader.u16()?;
+            let external_attributes = reader.u32()?;
+            let offset = reader.u32()? as usize;
+            let name = reader.take(name_len)?.to_vec();
+            let central_extra = reader.take(extra_len)?.to_vec();
+            let comment = reader.take(comment_len)?.to_vec();
+            if has_extra(&central_extra, ZIP64_EXTRA) {
+                return Err(tr("ZIP64 не поддерживается", "ZIP64 is not supported"));
+            }
+            first_offset = first_offset.min(offset);
+
+            let mut local = Reader::at(bytes, offset);
+            if local.u32()? != LOCAL_HEADER {
+                return Err(tr("повреждена запись архива", "corrupt archive entry"));
+            }
+            local.take(22)?;
+            let local_sizes: [u8; 12] = bytes[offset + 14..offset + 26].try_into().unwrap();
+            let local_name_len = local.u16()? as usize;
+            let local_extra_len = local.u16()? as usize;
+            local.take(local_name_len)?;
+            let local_extra = local.take(local_extra_len)?.to_vec();
+            let data = local.take(compressed_size as usize)?.to_vec();
+            let descriptor = if flags & FLAG_DESCRIPTOR != 0 {
+                // Подпись дескриптора необязательна
+                let len = if local.clone().u32() == Ok(DATA_DESCRIPTOR) {
+                    16
+                } else {
+                    12
+                };
+                local.take(len)?.to_vec()
+            } else {
+                Vec::new()
+            };
+
+            entries.push(Entry {
+                version_made_by,
+                version_needed,
+                flags,
+                method,
+                time,
+                date,
+                crc,
+                compressed_size,
+                size,
+                local_sizes,
+                name,
+                local_extra,
+                central_extra,
+                comment,
+                internal_attributes,
+                external_attributes,
+                data,
+                descriptor,
+            });
+        }
+
+        let prefix_end = first_offset.min(central_offset as usize).min(bytes.len());
+        Ok(Self {
+            prefix: bytes[..prefix_end].to_vec(),
+            entries,
+            comment,
+        })
+    }
+
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, &'static str> {
+        let too_large = tr(
+            "архив больше 4 ГБ (ZIP64)",
+            "archive larger than 4 GB (ZIP64)",
+        );
+        let mut out = self.prefix.clone();
+        let mut offsets = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            offsets.push(u32::try_from(out.len()).map_err(|_| too_large)?);
+            put_u32(&mut out, LOCAL_HEADER);
+            put_u16(&mut out, entry.version_needed);
+            put_u16(&mut out, entry.flags);
+            put_u16(&mut out, entry.method);
+            put_u16(&mut out, entry.time);
+            put_u16(&mut out, entry.date);
+            out.extend_from_slice(&entry.local_sizes);
+            put_u16(&mut out, entry.name.len() as u16);
+            put_u16(&mut out, entry.local_extra.len() as u16);
+            out.extend_from_slice(&entry.name);
+            out.extend_from_slice(&entry.local_extra);
+            out.extend_from_slice(&entry.data);
+            out.extend_from_slice(&entry.descriptor);
+        }
+
+        let central_offset = u32::try_from(out.len()).map_err(|_| too_large)?;
+        for (entry, offset) in self.entries.iter().zip(offsets) {
+            put_u32(&mut out, CENTRAL_HEADER);
+            put_u16(&mut out, entry.version_made_by);
+            put_u16(&mut out, entry.version_needed);
+            put_u16(&mut out, entry.flags);
+            put_u16(&mut out, entry.method);
+            put_u16(&mut out, entry.time);
+            put_u16(&mut out, entry.date);
+            out.extend_from_slice(&sizes(entry.crc, entry.compressed_size, entry.size));
+            put_u16(&mut out, entry.name.len() as u16);
+            put_u16(&mut out, entry.central_extra.len() as u16);
+            put_u16(&mut out, entry.comment.len() as u16);
+            put_u16(&mut out, 0);
+            put_u16(&mut out, entry.internal_attributes);
+            put_u32(&mut out, entry.external_attributes);
+            put_u32(&mut out, offset);
+            out.extend_from_slice(&entry.name);
+            out.extend_from_slice(&entry.central_extra);
+            out.extend_from_slice(&entry.comment);
+        }
+        let central_size = u32::try_from(out.len()).map_err(|_| too_large)? - central_offset;
+
+        put_u32(&mut out, END_OF_CENTRAL_DIRECTORY);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, self.entries.len() as u16);
+        put_u16(&mut out, self.entries.len() as u16);
+        put_u32(&mut out, central_size);
+        put_u32(&mut out, central_offset);
+        put_u16(&mut out, self.comment.len() as u16);
+        out.extend_from_slice(&self.comment);
+        Ok(out)
+    }
+}
+
+/// Начало записи о конце центрального каталога: ищется с конца, за ней может быть
+/// только комментарий архива
+fn find_end(bytes: &[u8]) -> Option<usize> {
+    let last = bytes.len().checked_sub(END_LEN)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last).rev().find(|&pos| {
+        bytes[pos..pos + 4] == END_OF_CENTRAL_DIRECTORY.to_le_bytes()
+            && pos + END_LEN + u16::from_le_bytes([bytes[pos + 20], bytes[pos + 21]]) as usize
+                == bytes.len()
+    })
+}
+
+/// CRC и размеры в порядке заголовков
+fn sizes(crc: u32, compressed_size: u32, size: u32) -> [u8; 12] {
+    let mut out = [0; 12];
+    out[..4].copy_from_slice(&crc.to_le_bytes());
+    out[4..8].copy_from_slice(&compressed_size.to_le_bytes());
+    out[8..].copy_from_slice(&size.to_le_bytes());
+    out
+}
+
+/// Дополнительные поля записи: (идентификатор, данные)
+fn extra_fields(extra: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut rest = extra;
+    std::iter::from_fn(move || {
+        let header = rest.get(..4)?;
+        let id = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let field = rest.get(..4 + len)?;
+        rest = &rest[4 + len..];
+        Some((id, field))
+    })
+}
+
+fn has_extra(extra: &[u8], id: u16) -> bool {
+    extra_fields(extra).any(|(field, _)| field == id)
+}
+
+fn without_extra(extra: &[u8], id: u16) -> Vec<u8> {
+    extra_fields(extra)
+        .filter(|(field, _)| *field != id)
+        .flat_map(|(_, bytes)| bytes.iter().copied())
+        .collect()
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[derive(Clone)]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at(bytes: &'a [u8], pos: usize) -> Self {
+        Self { bytes, pos }
+    }
+
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], &'static str> {
+        let truncated = tr("архив обрезан", "archive truncated");
+        let end = self.pos.checked_add(len).ok_or(truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> std::result::Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}